@@ -405,6 +405,11 @@ static int init(bt_callbacks_t* callbacks, bool start_restricted,
 
   bluetooth::common::InitFlags::Load(init_flags);
 
+  std::string config_file = bluetooth::common::InitFlags::GetConfigFile();
+  if (!config_file.empty()) {
+    bluetooth::os::ParameterProvider::OverrideConfigFilePath(config_file);
+  }
+
   if (interface_ready()) return BT_STATUS_DONE;
 
   set_hal_cbacks(callbacks);
@@ -43,6 +43,7 @@
 #include "btif/include/btif_metrics_logging.h"
 #include "btif/include/btif_profile_queue.h"
 #include "btif/include/btif_util.h"
+#include "common/init_flags.h"
 #include "common/metrics.h"
 #include "device/include/device_iot_config.h"
 #include "include/hardware/bluetooth_headset_callbacks.h"
@@ -162,7 +163,15 @@ static uint32_t get_hf_features() {
    BTA_AG_FEAT_UNAT)
 #endif
 
-  return GET_SYSPROP(Hfp, hf_features, DEFAULT_BTIF_HF_FEATURES);
+  uint32_t features = GET_SYSPROP(Hfp, hf_features, DEFAULT_BTIF_HF_FEATURES);
+#if TARGET_FLOSS
+  /* Voice recognition is only advertised when a host consumes the BVRA key */
+  if (bluetooth::common::init_flags::hfp_voice_recognition_is_enabled()) {
+    features |= BTA_AG_FEAT_VREC;
+  }
+#endif
+
+  return features;
 }
 
 /*******************************************************************************
@@ -66,6 +66,14 @@ class InitFlags final {
     return init_flags::get_hci_adapter();
   }
 
+  inline static std::string GetConfigFile() {
+    return std::string(init_flags::get_config_file());
+  }
+
+  inline static std::string GetSyspropOverride(const std::string& key) {
+    return std::string(init_flags::get_sysprop_override(key));
+  }
+
   inline static void SetAllForTesting() {
     init_flags::set_all_for_testing();
   }
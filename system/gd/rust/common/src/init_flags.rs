@@ -255,6 +255,17 @@ macro_rules! init_flags_getters {
     }
 }
 
+#[derive(Default)]
+struct SyspropOverrides {
+    map: HashMap<String, String>,
+}
+
+impl fmt::Display for SyspropOverrides {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.map)
+    }
+}
+
 #[derive(Default)]
 struct ExplicitTagSettings {
     map: HashMap<String, i32>,
@@ -325,12 +336,44 @@ fn parse_hci_adapter(flags: &mut InitFlags, values: Vec<&str>) {
     flags.hci_adapter = values[1].parse().unwrap_or(0);
 }
 
+fn parse_config_file(flags: &mut InitFlags, values: Vec<&str>) {
+    flags.config_file = values[1].to_string();
+}
+
+/// Returns the path of the config file to keep the devices in, empty for the default one.
+pub fn get_config_file() -> String {
+    FLAGS.lock().unwrap().config_file.clone()
+}
+
+fn parse_sysprop_overrides(flags: &mut InitFlags, values: Vec<&str>) {
+    for sysprop in values[1].split(',') {
+        if let Some((key, value)) = sysprop.split_once(':') {
+            flags.sysprop_overrides.map.insert(key.to_string(), value.to_string());
+        }
+    }
+}
+
+/// Returns the value |key| is overridden to for this adapter, empty if it isn't.
+pub fn get_sysprop_override(key: &str) -> String {
+    FLAGS.lock().unwrap().sysprop_overrides.map.get(key).cloned().unwrap_or_default()
+}
+
 /// Returns the log level for given flag.
 pub fn get_log_level_for_tag(tag: &str) -> i32 {
     let guard = FLAGS.lock().unwrap();
     *guard.logging_explicit_tag_settings.map.get(tag).unwrap_or(&guard.default_log_level)
 }
 
+/// Sets the log level for given tag at runtime.
+pub fn update_log_level_for_tag(tag: &str, level: i32) {
+    FLAGS.lock().unwrap().logging_explicit_tag_settings.map.insert(tag.to_string(), level);
+}
+
+/// Removes the log level for given tag so it follows the default log level again.
+pub fn clear_log_level_for_tag(tag: &str) {
+    FLAGS.lock().unwrap().logging_explicit_tag_settings.map.remove(tag);
+}
+
 /// Sets all bool flags to true
 /// Set all other flags and extra fields to their default type value
 pub fn set_all_for_testing() {
@@ -375,6 +418,7 @@ init_flags!(
         gatt_robust_caching_server,
         hci_adapter: i32,
         hfp_dynamic_version = true,
+        hfp_voice_recognition = false,
         irk_rotation,
         leaudio_targeted_announcement_reconnection_mode = true,
         pbap_pse_dynamic_version_upgrade = false,
@@ -404,6 +448,8 @@ init_flags!(
     // extra_fields are not a 1 to 1 match with "INIT_*" flags
     extra_fields: {
         logging_explicit_tag_settings: ExplicitTagSettings,
+        config_file: String,
+        sysprop_overrides: SyspropOverrides,
     }
     extra_parsed_flags: {
         "INIT_default_log_level_str" => parse_log_level(_, _),
@@ -412,6 +458,8 @@ init_flags!(
         "INIT_logging_debug_enabled_for_tags" => parse_debug_logging_tag(_, _, true),
         "INIT_logging_debug_disabled_for_tags" => parse_debug_logging_tag(_, _, false),
         "--hci" => parse_hci_adapter(_, _),
+        "--config-file" => parse_config_file(_, _),
+        "--sysprops" => parse_sysprop_overrides(_, _),
     }
     dependencies: {
         always_use_private_gatt_for_debugging => private_gatt,
@@ -488,6 +536,21 @@ mod tests {
         assert_eq!(get_hci_adapter(), 2);
     }
     #[test]
+    fn config_file_flag() {
+        let _guard = ASYNC_LOCK.lock().unwrap();
+        test_load(vec![]);
+        assert_eq!(get_config_file(), "");
+        test_load(vec!["--config-file=/var/lib/bluetooth/bt_config.001122334455.conf"]);
+        assert_eq!(get_config_file(), "/var/lib/bluetooth/bt_config.001122334455.conf");
+    }
+    #[test]
+    fn sysprop_overrides_flag() {
+        let _guard = ASYNC_LOCK.lock().unwrap();
+        test_load(vec!["--sysprops=bluetooth.core.gap.le.privacy.enabled:false,bad"]);
+        assert_eq!(get_sysprop_override("bluetooth.core.gap.le.privacy.enabled"), "false");
+        assert_eq!(get_sysprop_override("bluetooth.device.default_name"), "");
+    }
+    #[test]
     fn explicit_flag() {
         let _guard = ASYNC_LOCK.lock().unwrap();
         test_load(vec![
@@ -534,6 +597,20 @@ mod tests {
         assert!(get_default_log_level() == LOG_TAG_ERROR);
     }
     #[test]
+    fn test_runtime_update_tag() {
+        let _guard = ASYNC_LOCK.lock().unwrap();
+        test_load(vec![
+            "INIT_default_log_level_str=LOG_INFO",
+            "INIT_log_level_for_tags=foo:LOG_WARN",
+        ]);
+        update_log_level_for_tag("bar", LOG_TAG_VERBOSE);
+        assert!(get_log_level_for_tag("bar") == LOG_TAG_VERBOSE);
+        update_log_level_for_tag("foo", LOG_TAG_DEBUG);
+        assert!(get_log_level_for_tag("foo") == LOG_TAG_DEBUG);
+        clear_log_level_for_tag("foo");
+        assert!(get_log_level_for_tag("foo") == LOG_TAG_INFO);
+    }
+    #[test]
     fn test_default_log_level() {
         // Default log level can be provided via int value or string.
         // The string version is just for ease-of-use.
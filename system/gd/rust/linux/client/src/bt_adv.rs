@@ -6,8 +6,22 @@ use crate::{console_yellow, print_info};
 
 use bt_topshim::btif::Uuid;
 use bt_topshim::profiles::gatt::LePhy;
-use btstack::bluetooth_adv::{AdvertiseData, AdvertiserId, AdvertisingSetParameters};
+use btstack::bluetooth_adv::{
+    AdvertiseData, AdvertiserId, AdvertisingSetParameters, PeriodicAdvertisingParameters,
+};
 use btstack::bluetooth_gatt::IBluetoothGatt;
+use num_traits::ToPrimitive;
+
+/// A change pushed to a started advertiser by |AdvSet::update|.
+#[derive(Debug)]
+pub(crate) enum AdvSetUpdate {
+    Parameters(AdvertisingSetParameters),
+    Data(AdvertiseData),
+    ScanResponse(AdvertiseData),
+    PeriodicParameters(PeriodicAdvertisingParameters),
+    PeriodicData(AdvertiseData),
+    PeriodicEnable(bool),
+}
 
 /// Avertisement parameter and data for a BLE advertising set.
 #[derive(Debug, Clone)]
@@ -23,6 +37,15 @@ pub(crate) struct AdvSet {
 
     /// Scan response data.
     pub(crate) scan_rsp: AdvertiseData,
+
+    /// Periodic advertising parameters. None if periodic advertising is disabled.
+    pub(crate) periodic_params: Option<PeriodicAdvertisingParameters>,
+
+    /// Periodic advertising data.
+    pub(crate) periodic_data: AdvertiseData,
+
+    /// Registration ID of the started set. Only tracked for sets composed by set ID.
+    pub(crate) reg_id: Option<i32>,
 }
 
 impl AdvSet {
@@ -66,24 +89,167 @@ impl AdvSet {
             include_device_name: false,
         };
 
-        AdvSet { adv_id: None, params, data, scan_rsp }
+        AdvSet {
+            adv_id: None,
+            params,
+            data,
+            scan_rsp,
+            periodic_params: None,
+            periodic_data: AdvertiseData::default(),
+            reg_id: None,
+        }
+    }
+
+    /// Creates a set with default parameters and no data, to be composed field by field.
+    pub(crate) fn new_empty(is_legacy: bool) -> Self {
+        let mut s = AdvSet::new(is_legacy);
+        s.params.include_tx_power = false;
+        s.data = AdvertiseData::default();
+        s.scan_rsp = AdvertiseData::default();
+        s
+    }
+
+    /// Returns the advertising data of the given kind: "adv", "scan-rsp" or "periodic".
+    pub(crate) fn data_mut(&mut self, kind: &str) -> Option<&mut AdvertiseData> {
+        match kind {
+            "adv" => Some(&mut self.data),
+            "scan-rsp" => Some(&mut self.scan_rsp),
+            "periodic" => Some(&mut self.periodic_data),
+            _ => None,
+        }
+    }
+
+    /// JSON fields of the parameters and data of the set.
+    pub(crate) fn to_json(&self) -> serde_json::Value {
+        let data_json = |data: &AdvertiseData| {
+            serde_json::json!({
+                "service_uuids":
+                    data.service_uuids.iter().map(|uuid| uuid.to_string()).collect::<Vec<_>>(),
+                "solicit_uuids":
+                    data.solicit_uuids.iter().map(|uuid| uuid.to_string()).collect::<Vec<_>>(),
+                "transport_discovery_data":
+                    data.transport_discovery_data.iter().map(hex::encode).collect::<Vec<_>>(),
+                "manufacturer_data": data
+                    .manufacturer_data
+                    .iter()
+                    .map(|(id, value)| (id.to_string(), hex::encode(value).into()))
+                    .collect::<serde_json::Map<String, serde_json::Value>>(),
+                "service_data": data
+                    .service_data
+                    .iter()
+                    .map(|(uuid, value)| (uuid.clone(), hex::encode(value).into()))
+                    .collect::<serde_json::Map<String, serde_json::Value>>(),
+                "include_tx_power_level": data.include_tx_power_level,
+                "include_device_name": data.include_device_name,
+            })
+        };
+        serde_json::json!({
+            "params": {
+                "connectable": self.params.connectable,
+                "scannable": self.params.scannable,
+                "is_legacy": self.params.is_legacy,
+                "is_anonymous": self.params.is_anonymous,
+                "include_tx_power": self.params.include_tx_power,
+                "primary_phy": self.params.primary_phy.to_u8(),
+                "secondary_phy": self.params.secondary_phy.to_u8(),
+                "interval": self.params.interval,
+                "tx_power_level": self.params.tx_power_level,
+                "own_address_type": self.params.own_address_type,
+            },
+            "data": data_json(&self.data),
+            "scan_rsp": data_json(&self.scan_rsp),
+            "periodic_params": self.periodic_params.as_ref().map(|params| {
+                serde_json::json!({
+                    "include_tx_power": params.include_tx_power,
+                    "interval": params.interval,
+                })
+            }),
+            "periodic_data": data_json(&self.periodic_data),
+        })
     }
 
     pub(crate) fn start(context: Arc<Mutex<ClientContext>>, s: AdvSet, callback_id: u32) {
+        AdvSet::start_with_limits(context, s, callback_id, 0, 0);
+    }
+
+    /// Starts the set. A |duration| (in 10ms) or |max_ext_adv_events| of 0 means no limit.
+    pub(crate) fn start_with_limits(
+        context: Arc<Mutex<ClientContext>>,
+        s: AdvSet,
+        callback_id: u32,
+        duration: i32,
+        max_ext_adv_events: i32,
+    ) -> i32 {
         let mut context = context.lock().unwrap();
 
+        let scan_rsp = if s.params.scannable { Some(s.scan_rsp.clone()) } else { None };
+        let periodic_data = s.periodic_params.as_ref().map(|_| s.periodic_data.clone());
         let reg_id = context.gatt_dbus.as_mut().unwrap().start_advertising_set(
             s.params.clone(),
             s.data.clone(),
-            None,
-            None,
-            None,
-            0,
-            0,
+            scan_rsp,
+            s.periodic_params.clone(),
+            periodic_data,
+            duration,
+            max_ext_adv_events,
             callback_id,
         );
         print_info!("Starting advertising set for reg_id = {}", reg_id);
         context.adv_sets.insert(reg_id, s);
+        reg_id
+    }
+
+    /// Returns the changes that bring a started advertiser in line with this set, in order.
+    /// Periodic advertising parameters can't be changed while it's enabled, so if the advertiser
+    /// has it enabled (|periodic_enabled|) it's disabled first and enabled again once the new
+    /// parameters and data are set.
+    pub(crate) fn updates(&self, periodic_enabled: bool) -> Vec<AdvSetUpdate> {
+        let mut updates = vec![
+            AdvSetUpdate::Parameters(self.params.clone()),
+            AdvSetUpdate::Data(self.data.clone()),
+        ];
+        if self.params.scannable {
+            updates.push(AdvSetUpdate::ScanResponse(self.scan_rsp.clone()));
+        }
+        if periodic_enabled {
+            updates.push(AdvSetUpdate::PeriodicEnable(false));
+        }
+        if let Some(periodic_params) = &self.periodic_params {
+            updates.push(AdvSetUpdate::PeriodicParameters(periodic_params.clone()));
+            updates.push(AdvSetUpdate::PeriodicData(self.periodic_data.clone()));
+            updates.push(AdvSetUpdate::PeriodicEnable(true));
+        }
+        updates
+    }
+
+    /// Pushes the parameters and data of |s| to the started advertiser |adv_id|, which has periodic
+    /// advertising enabled if |periodic_enabled|.
+    pub(crate) fn update(
+        context: Arc<Mutex<ClientContext>>,
+        adv_id: AdvertiserId,
+        s: &AdvSet,
+        periodic_enabled: bool,
+    ) {
+        let mut context = context.lock().unwrap();
+        let gatt = context.gatt_dbus.as_mut().unwrap();
+
+        print_info!("Updating advertising set {}", adv_id);
+        for update in s.updates(periodic_enabled) {
+            match update {
+                AdvSetUpdate::Parameters(params) => gatt.set_advertising_parameters(adv_id, params),
+                AdvSetUpdate::Data(data) => gatt.set_advertising_data(adv_id, data),
+                AdvSetUpdate::ScanResponse(data) => gatt.set_scan_response_data(adv_id, data),
+                AdvSetUpdate::PeriodicParameters(params) => {
+                    gatt.set_periodic_advertising_parameters(adv_id, params)
+                }
+                AdvSetUpdate::PeriodicData(data) => {
+                    gatt.set_periodic_advertising_data(adv_id, data)
+                }
+                AdvSetUpdate::PeriodicEnable(enable) => {
+                    gatt.set_periodic_advertising_enable(adv_id, enable, false)
+                }
+            }
+        }
     }
 
     pub(crate) fn stop_all(context: Arc<Mutex<ClientContext>>) {
@@ -97,3 +263,81 @@ impl AdvSet {
         context.adv_sets.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        let s = AdvSet::new(true);
+        assert!(s.params.is_legacy);
+        assert!(s.params.include_tx_power);
+        assert_eq!(s.data.manufacturer_data.get(&0), Some(&vec![0, 1, 2]));
+        assert_eq!(s.scan_rsp.service_uuids.len(), 1);
+        assert!(s.periodic_params.is_none());
+        assert!(s.adv_id.is_none() && s.reg_id.is_none());
+
+        let s = AdvSet::new_empty(false);
+        assert!(!s.params.is_legacy);
+        assert!(!s.params.include_tx_power);
+        assert!(s.data.manufacturer_data.is_empty());
+        assert!(s.scan_rsp.service_uuids.is_empty());
+    }
+
+    #[test]
+    fn test_data_mut() {
+        let mut s = AdvSet::new_empty(false);
+        s.data_mut("adv").unwrap().include_device_name = true;
+        s.data_mut("scan-rsp").unwrap().include_tx_power_level = true;
+        s.data_mut("periodic").unwrap().manufacturer_data.insert(1, vec![2]);
+        assert!(s.data.include_device_name);
+        assert!(s.scan_rsp.include_tx_power_level);
+        assert_eq!(s.periodic_data.manufacturer_data.get(&1), Some(&vec![2]));
+        assert!(s.data_mut("other").is_none());
+    }
+
+    #[test]
+    fn test_updates() {
+        let mut s = AdvSet::new_empty(false);
+        assert!(matches!(
+            s.updates(true)[..],
+            [
+                AdvSetUpdate::Parameters(_),
+                AdvSetUpdate::Data(_),
+                AdvSetUpdate::PeriodicEnable(false)
+            ]
+        ));
+
+        s.params.scannable = true;
+        s.periodic_params = Some(PeriodicAdvertisingParameters::default());
+        assert!(matches!(
+            s.updates(true)[..],
+            [
+                AdvSetUpdate::Parameters(_),
+                AdvSetUpdate::Data(_),
+                AdvSetUpdate::ScanResponse(_),
+                AdvSetUpdate::PeriodicEnable(false),
+                AdvSetUpdate::PeriodicParameters(_),
+                AdvSetUpdate::PeriodicData(_),
+                AdvSetUpdate::PeriodicEnable(true)
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_updates_legacy() {
+        // Legacy sets never have periodic advertising, so it's left alone.
+        let mut s = AdvSet::new_empty(true);
+        assert!(matches!(
+            s.updates(false)[..],
+            [AdvSetUpdate::Parameters(_), AdvSetUpdate::Data(_)]
+        ));
+
+        s.params.scannable = true;
+        assert!(matches!(
+            s.updates(false)[..],
+            [AdvSetUpdate::Parameters(_), AdvSetUpdate::Data(_), AdvSetUpdate::ScanResponse(_)]
+        ));
+    }
+}
@@ -1,5 +1,10 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
 use bt_topshim::btif::BtTransport;
-use bt_topshim::profiles::gatt::LePhy;
+use bt_topshim::profiles::gatt::{GattStatus, LePhy};
+use btstack::bluetooth_gatt::{
+    BluetoothGattCharacteristic, BluetoothGattDescriptor, BluetoothGattService,
+};
 
 #[repr(i32)]
 #[derive(Debug, Copy, Clone)]
@@ -50,3 +55,325 @@ impl GattClientContext {
         self.auth_req
     }
 }
+
+/// Parses a comma separated list of names into the bitmask of their flags.
+fn parse_flags(names: &str, flags: &[(&str, i32)]) -> Option<i32> {
+    names.split(',').try_fold(0, |mask, name| {
+        flags.iter().find(|(n, _)| *n == name).map(|(_, flag)| mask | flag)
+    })
+}
+
+/// Names of the characteristic properties accepted by |parse_properties|.
+pub(crate) const PROPERTY_NAMES: &[(&str, i32)] = &[
+    ("broadcast", BluetoothGattCharacteristic::PROPERTY_BROADCAST),
+    ("read", BluetoothGattCharacteristic::PROPERTY_READ),
+    ("write-no-rsp", BluetoothGattCharacteristic::PROPERTY_WRITE_NO_RESPONSE),
+    ("write", BluetoothGattCharacteristic::PROPERTY_WRITE),
+    ("notify", BluetoothGattCharacteristic::PROPERTY_NOTIFY),
+    ("indicate", BluetoothGattCharacteristic::PROPERTY_INDICATE),
+    ("signed-write", BluetoothGattCharacteristic::PROPERTY_SIGNED_WRITE),
+    ("extended-props", BluetoothGattCharacteristic::PROPERTY_EXTENDED_PROPS),
+];
+
+/// Names of the attribute permissions accepted by |parse_permissions|.
+pub(crate) const PERMISSION_NAMES: &[(&str, i32)] = &[
+    ("read", BluetoothGattCharacteristic::PERMISSION_READ),
+    ("read-encrypted", BluetoothGattCharacteristic::PERMISSION_READ_ENCRYPTED),
+    ("read-encrypted-mitm", BluetoothGattCharacteristic::PERMISSION_READ_ENCRYPED_MITM),
+    ("write", BluetoothGattCharacteristic::PERMISSION_WRITE),
+    ("write-encrypted", BluetoothGattCharacteristic::PERMISSION_WRITE_ENCRYPTED),
+    ("write-encrypted-mitm", BluetoothGattCharacteristic::PERMISSION_WRITE_ENCRYPTED_MITM),
+    ("write-signed", BluetoothGattCharacteristic::PERMISSION_WRITE_SIGNED),
+    ("write-signed-mitm", BluetoothGattCharacteristic::PERMISSION_WRITE_SIGNED_MITM),
+];
+
+/// Parses characteristic properties, e.g. "read,notify".
+pub(crate) fn parse_properties(names: &str) -> Option<i32> {
+    parse_flags(names, PROPERTY_NAMES)
+}
+
+/// Parses attribute permissions, e.g. "read,write-encrypted".
+pub(crate) fn parse_permissions(names: &str) -> Option<i32> {
+    parse_flags(names, PERMISSION_NAMES)
+}
+
+/// State of the GATT server built from the command line.
+pub(crate) struct GattServerContext {
+    /// If set, the registered GATT server id. None otherwise.
+    pub(crate) server_id: Option<i32>,
+    /// Service being built, not yet added to the server.
+    pub(crate) pending_service: Option<BluetoothGattService>,
+    /// Initial values of the characteristics and descriptors of the pending service, in the order
+    /// they were added.
+    pending_values: Vec<Vec<u8>>,
+    /// Initial values of the services added to the server but not yet acknowledged.
+    adding_values: VecDeque<Vec<Vec<u8>>>,
+    /// Values of the characteristics and descriptors of the added services, by handle.
+    values: HashMap<i32, Vec<u8>>,
+    /// Handles of the characteristics and descriptors of the added services, by service handle.
+    service_handles: HashMap<i32, Vec<i32>>,
+    /// Writes prepared by each client, applied when the client executes them.
+    prepared_writes: HashMap<String, Vec<(i32, i32, Vec<u8>)>>,
+    /// Addresses of the connected clients.
+    pub(crate) connected_clients: HashSet<String>,
+}
+
+impl GattServerContext {
+    pub(crate) fn new() -> Self {
+        GattServerContext {
+            server_id: None,
+            pending_service: None,
+            pending_values: vec![],
+            adding_values: VecDeque::new(),
+            values: HashMap::new(),
+            service_handles: HashMap::new(),
+            prepared_writes: HashMap::new(),
+            connected_clients: HashSet::new(),
+        }
+    }
+
+    /// Starts building a new service, dropping the previously pending one.
+    pub(crate) fn create_service(&mut self, service: BluetoothGattService) {
+        self.pending_service = Some(service);
+        self.pending_values.clear();
+    }
+
+    /// Adds a characteristic to the pending service.
+    pub(crate) fn add_characteristic(
+        &mut self,
+        characteristic: BluetoothGattCharacteristic,
+        value: Vec<u8>,
+    ) -> Result<(), String> {
+        let service = self.pending_service.as_mut().ok_or("No service is being created")?;
+        service.characteristics.push(characteristic);
+        self.pending_values.push(value);
+        Ok(())
+    }
+
+    /// Adds a descriptor to the last characteristic of the pending service.
+    pub(crate) fn add_descriptor(
+        &mut self,
+        descriptor: BluetoothGattDescriptor,
+        value: Vec<u8>,
+    ) -> Result<(), String> {
+        let service = self.pending_service.as_mut().ok_or("No service is being created")?;
+        let characteristic =
+            service.characteristics.last_mut().ok_or("The service has no characteristic")?;
+        characteristic.descriptors.push(descriptor);
+        self.pending_values.push(value);
+        Ok(())
+    }
+
+    /// Takes the pending service to add it to the server. Its values are kept until the server
+    /// reports the handles of the service.
+    pub(crate) fn take_pending_service(&mut self) -> Option<BluetoothGattService> {
+        let service = self.pending_service.take()?;
+        self.adding_values.push_back(std::mem::take(&mut self.pending_values));
+        Some(service)
+    }
+
+    /// Stores the initial values of a service the server added, now that its handles are known.
+    /// Services are added in order, so the oldest values are the ones of this service.
+    pub(crate) fn on_service_added(&mut self, status: GattStatus, service: &BluetoothGattService) {
+        let values = match self.adding_values.pop_front() {
+            Some(values) => values,
+            None => return,
+        };
+        if status != GattStatus::Success {
+            return;
+        }
+
+        let handles: Vec<i32> = service
+            .characteristics
+            .iter()
+            .flat_map(|c| {
+                std::iter::once(c.instance_id).chain(c.descriptors.iter().map(|d| d.instance_id))
+            })
+            .collect();
+        self.values.extend(handles.iter().cloned().zip(values));
+        self.service_handles.insert(service.instance_id, handles);
+    }
+
+    /// Drops the values of a service the server removed.
+    pub(crate) fn on_service_removed(&mut self, status: GattStatus, handle: i32) {
+        if status != GattStatus::Success {
+            return;
+        }
+
+        for handle in self.service_handles.remove(&handle).unwrap_or_default() {
+            self.values.remove(&handle);
+        }
+    }
+
+    /// Returns the value of |handle| from |offset|.
+    pub(crate) fn read(&self, handle: i32, offset: i32) -> Result<Vec<u8>, GattStatus> {
+        let value = self.values.get(&handle).ok_or(GattStatus::InvalidHandle)?;
+        value.get(offset as usize..).map(|v| v.to_vec()).ok_or(GattStatus::InvalidOffset)
+    }
+
+    /// Writes |value| at |offset| of the value of |handle|.
+    pub(crate) fn write(&mut self, handle: i32, offset: i32, value: &[u8]) -> GattStatus {
+        let stored = match self.values.get_mut(&handle) {
+            Some(stored) => stored,
+            None => return GattStatus::InvalidHandle,
+        };
+        let offset = offset as usize;
+        if offset > stored.len() {
+            return GattStatus::InvalidOffset;
+        }
+        stored.truncate(offset);
+        stored.extend_from_slice(value);
+        GattStatus::Success
+    }
+
+    /// Sets the whole value of |handle|.
+    pub(crate) fn set_value(&mut self, handle: i32, value: Vec<u8>) -> Result<(), String> {
+        let stored = self
+            .values
+            .get_mut(&handle)
+            .ok_or(format!("No characteristic or descriptor with handle {}", handle))?;
+        *stored = value;
+        Ok(())
+    }
+
+    /// Queues a prepared write from |addr|.
+    pub(crate) fn prepare_write(&mut self, addr: String, handle: i32, offset: i32, value: Vec<u8>) {
+        self.prepared_writes.entry(addr).or_default().push((handle, offset, value));
+    }
+
+    /// Applies or drops the writes prepared by |addr|.
+    pub(crate) fn execute_write(&mut self, addr: &str, execute: bool) -> GattStatus {
+        let writes = self.prepared_writes.remove(addr).unwrap_or_default();
+        if !execute {
+            return GattStatus::Success;
+        }
+        writes
+            .iter()
+            .map(|(handle, offset, value)| self.write(*handle, *offset, value))
+            .find(|status| *status != GattStatus::Success)
+            .unwrap_or(GattStatus::Success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use btstack::bluetooth_gatt::GattWriteType;
+
+    const ADDR: &str = "00:11:22:33:44:55";
+
+    fn make_characteristic(instance_id: i32) -> BluetoothGattCharacteristic {
+        BluetoothGattCharacteristic {
+            uuid: [0; 16],
+            instance_id,
+            properties: BluetoothGattCharacteristic::PROPERTY_READ
+                | BluetoothGattCharacteristic::PROPERTY_WRITE,
+            permissions: 0,
+            key_size: 16,
+            write_type: GattWriteType::Write,
+            descriptors: vec![],
+        }
+    }
+
+    /// Adds a service with a characteristic at handle 2 and its descriptor at handle 3.
+    fn make_context() -> GattServerContext {
+        let mut context = GattServerContext::new();
+        context.create_service(BluetoothGattService {
+            uuid: [0; 16],
+            instance_id: 0,
+            service_type: 0,
+            characteristics: vec![],
+            included_services: vec![],
+        });
+        context.add_characteristic(make_characteristic(0), vec![1, 2, 3]).unwrap();
+        context
+            .add_descriptor(
+                BluetoothGattDescriptor { uuid: [0; 16], instance_id: 0, permissions: 0 },
+                vec![4],
+            )
+            .unwrap();
+
+        // The server assigns the handles.
+        let mut service = context.take_pending_service().unwrap();
+        service.instance_id = 1;
+        service.characteristics[0].instance_id = 2;
+        service.characteristics[0].descriptors[0].instance_id = 3;
+        context.on_service_added(GattStatus::Success, &service);
+        context
+    }
+
+    #[test]
+    fn test_on_service_added() {
+        let context = make_context();
+        assert_eq!(context.read(2, 0), Ok(vec![1, 2, 3]));
+        assert_eq!(context.read(3, 0), Ok(vec![4]));
+        assert_eq!(context.read(1, 0), Err(GattStatus::InvalidHandle));
+
+        // Values of services the server failed to add are dropped.
+        let mut context = GattServerContext::new();
+        assert!(context.add_characteristic(make_characteristic(0), vec![1]).is_err());
+        context.create_service(BluetoothGattService {
+            uuid: [0; 16],
+            instance_id: 0,
+            service_type: 0,
+            characteristics: vec![],
+            included_services: vec![],
+        });
+        context.add_characteristic(make_characteristic(0), vec![1]).unwrap();
+        let mut service = context.take_pending_service().unwrap();
+        service.characteristics[0].instance_id = 2;
+        context.on_service_added(GattStatus::Error, &service);
+        assert_eq!(context.read(2, 0), Err(GattStatus::InvalidHandle));
+    }
+
+    #[test]
+    fn test_read() {
+        let context = make_context();
+        assert_eq!(context.read(2, 1), Ok(vec![2, 3]));
+        assert_eq!(context.read(2, 3), Ok(vec![]));
+        assert_eq!(context.read(2, 4), Err(GattStatus::InvalidOffset));
+        assert_eq!(context.read(4, 0), Err(GattStatus::InvalidHandle));
+    }
+
+    #[test]
+    fn test_write() {
+        let mut context = make_context();
+        assert_eq!(context.write(2, 1, &[5, 6, 7]), GattStatus::Success);
+        assert_eq!(context.read(2, 0), Ok(vec![1, 5, 6, 7]));
+        assert_eq!(context.write(2, 5, &[8]), GattStatus::InvalidOffset);
+        assert_eq!(context.write(4, 0, &[8]), GattStatus::InvalidHandle);
+        assert_eq!(context.set_value(3, vec![9, 9]), Ok(()));
+        assert_eq!(context.read(3, 0), Ok(vec![9, 9]));
+        assert!(context.set_value(4, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_prepare_execute_write() {
+        let mut context = make_context();
+        context.prepare_write(ADDR.into(), 2, 0, vec![7]);
+        context.prepare_write(ADDR.into(), 2, 1, vec![8, 9]);
+        assert_eq!(context.read(2, 0), Ok(vec![1, 2, 3]));
+        assert_eq!(context.execute_write(ADDR, true), GattStatus::Success);
+        assert_eq!(context.read(2, 0), Ok(vec![7, 8, 9]));
+
+        // Cancelled writes are dropped.
+        context.prepare_write(ADDR.into(), 2, 0, vec![0]);
+        assert_eq!(context.execute_write(ADDR, false), GattStatus::Success);
+        assert_eq!(context.execute_write(ADDR, true), GattStatus::Success);
+        assert_eq!(context.read(2, 0), Ok(vec![7, 8, 9]));
+
+        context.prepare_write(ADDR.into(), 4, 0, vec![0]);
+        assert_eq!(context.execute_write(ADDR, true), GattStatus::InvalidHandle);
+    }
+
+    #[test]
+    fn test_on_service_removed() {
+        let mut context = make_context();
+        context.on_service_removed(GattStatus::Error, 1);
+        assert_eq!(context.read(2, 0), Ok(vec![1, 2, 3]));
+
+        context.on_service_removed(GattStatus::Success, 1);
+        assert_eq!(context.read(2, 0), Err(GattStatus::InvalidHandle));
+        assert_eq!(context.read(3, 0), Err(GattStatus::InvalidHandle));
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use bt_utils::adv_parser::{self, AdParseError};
+use btstack::bluetooth_gatt::{
+    ScanFilter, ScanFilterCondition, ScanFilterPattern, ScanResult, ScanSettings,
+};
+use num_traits::ToPrimitive;
+
+/// Minimum time between two redraws of the live table.
+const LIVE_TABLE_REDRAW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Returns the AD types found in advertising data, in order of appearance.
+pub(crate) fn ad_types(adv_data: &[u8]) -> Vec<u8> {
+    adv_parser::parse_ad_structures(adv_data)
+        .into_iter()
+        .filter_map(|structure| match structure {
+            Ok((ad_type, _)) => Some(ad_type),
+            Err(AdParseError::InvalidLength { ad_type, .. }) => Some(ad_type),
+            Err(AdParseError::Truncated { .. }) => None,
+        })
+        .collect()
+}
+
+/// Whether a device is currently reported as found or lost by the controller filter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ScanEntryState {
+    Seen,
+    Found,
+    Lost,
+}
+
+/// A row of the scan results table.
+#[derive(Clone, Debug)]
+pub(crate) struct ScanEntry {
+    pub(crate) name: String,
+    pub(crate) rssi: i8,
+    pub(crate) last_seen: Instant,
+    pub(crate) ad_types: Vec<u8>,
+    pub(crate) state: ScanEntryState,
+}
+
+/// User preference of LE scans and the results of the ongoing scans.
+pub(crate) struct ScanContext {
+    /// Settings of the next scans. None lets the stack pick.
+    pub(crate) settings: Option<ScanSettings>,
+    /// Patterns of the filter of the next scans. Empty matches all advertisements.
+    pub(crate) patterns: Vec<ScanFilterPattern>,
+    /// RSSI thresholds, timeout and sampling period of the filter of the next scans.
+    pub(crate) rssi_high_threshold: u8,
+    pub(crate) rssi_low_threshold: u8,
+    pub(crate) rssi_low_timeout: u8,
+    pub(crate) rssi_sampling_period: u8,
+    /// Whether the table is redrawn as results arrive.
+    pub(crate) live_table: bool,
+    /// Scan results by address.
+    entries: HashMap<String, ScanEntry>,
+    last_redraw: Option<Instant>,
+}
+
+impl ScanContext {
+    pub(crate) fn new() -> Self {
+        ScanContext {
+            settings: None,
+            patterns: vec![],
+            rssi_high_threshold: 0,
+            rssi_low_threshold: 0,
+            rssi_low_timeout: 0,
+            rssi_sampling_period: 0,
+            live_table: false,
+            entries: HashMap::new(),
+            last_redraw: None,
+        }
+    }
+
+    /// Builds the filter of the next scans.
+    pub(crate) fn filter(&self) -> ScanFilter {
+        ScanFilter {
+            rssi_high_threshold: self.rssi_high_threshold,
+            rssi_low_threshold: self.rssi_low_threshold,
+            rssi_low_timeout: self.rssi_low_timeout,
+            rssi_sampling_period: self.rssi_sampling_period,
+            condition: ScanFilterCondition::Patterns(self.patterns.clone()),
+        }
+    }
+
+    /// Resets the filter so all advertisements are matched.
+    pub(crate) fn clear_filter(&mut self) {
+        self.patterns.clear();
+        self.rssi_high_threshold = 0;
+        self.rssi_low_threshold = 0;
+        self.rssi_low_timeout = 0;
+        self.rssi_sampling_period = 0;
+    }
+
+    /// Records a scan result. Found or lost devices keep their state until the next found or
+    /// lost event.
+    pub(crate) fn update(&mut self, result: &ScanResult, state: Option<ScanEntryState>) {
+        let entry = self.entries.entry(result.address.clone()).or_insert_with(|| ScanEntry {
+            name: String::new(),
+            rssi: result.rssi,
+            last_seen: Instant::now(),
+            ad_types: vec![],
+            state: ScanEntryState::Seen,
+        });
+        if !result.name.is_empty() {
+            entry.name = result.name.clone();
+        }
+        entry.rssi = result.rssi;
+        entry.last_seen = Instant::now();
+        entry.ad_types = ad_types(&result.adv_data);
+        if let Some(state) = state {
+            entry.state = state;
+        }
+    }
+
+    pub(crate) fn clear_entries(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Returns the table if it is live and wasn't redrawn recently.
+    pub(crate) fn take_live_redraw(&mut self) -> Option<String> {
+        if !self.live_table {
+            return None;
+        }
+        let now = Instant::now();
+        if self.last_redraw.map_or(false, |last| now - last < LIVE_TABLE_REDRAW_INTERVAL) {
+            return None;
+        }
+        self.last_redraw = Some(now);
+        Some(self.render())
+    }
+
+    /// Returns the results, most recently seen first.
+    fn sorted_entries(&self) -> Vec<(&String, &ScanEntry)> {
+        let mut entries: Vec<(&String, &ScanEntry)> = self.entries.iter().collect();
+        entries.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.last_seen));
+        entries
+    }
+
+    /// JSON fields of the settings and filter of the next scans.
+    pub(crate) fn filter_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "settings": self.settings.as_ref().map(|settings| {
+                serde_json::json!({
+                    "interval": settings.interval,
+                    "window": settings.window,
+                    "scan_type": settings.scan_type.to_u32(),
+                })
+            }),
+            "rssi_high_threshold": self.rssi_high_threshold,
+            "rssi_low_threshold": self.rssi_low_threshold,
+            "rssi_low_timeout": self.rssi_low_timeout,
+            "rssi_sampling_period": self.rssi_sampling_period,
+            "patterns": self
+                .patterns
+                .iter()
+                .map(|pattern| {
+                    serde_json::json!({
+                        "start_position": pattern.start_position,
+                        "ad_type": pattern.ad_type,
+                        "content": hex::encode(&pattern.content),
+                    })
+                })
+                .collect::<Vec<serde_json::Value>>(),
+        })
+    }
+
+    /// JSON fields of the results, most recently seen first.
+    pub(crate) fn entries_json(&self) -> Vec<serde_json::Value> {
+        let now = Instant::now();
+        self.sorted_entries()
+            .into_iter()
+            .map(|(address, entry)| {
+                serde_json::json!({
+                    "address": address,
+                    "name": entry.name,
+                    "rssi": entry.rssi,
+                    "last_seen_sec": (now - entry.last_seen).as_secs(),
+                    "state": match entry.state {
+                        ScanEntryState::Seen => "seen",
+                        ScanEntryState::Found => "found",
+                        ScanEntryState::Lost => "lost",
+                    },
+                    "ad_types": entry.ad_types,
+                })
+            })
+            .collect()
+    }
+
+    /// Renders the results as a table, most recently seen first.
+    pub(crate) fn render(&self) -> String {
+        let entries = self.sorted_entries();
+        let now = Instant::now();
+        let mut table = format!(
+            "{:<17}  {:<20}  {:>4}  {:>9}  {:<5}  {}\n",
+            "Address", "Name", "RSSI", "Last seen", "State", "AD types"
+        );
+        for (address, entry) in entries {
+            let state = match entry.state {
+                ScanEntryState::Seen => "",
+                ScanEntryState::Found => "found",
+                ScanEntryState::Lost => "lost",
+            };
+            table += &format!(
+                "{:<17}  {:<20.20}  {:>4}  {:>8}s  {:<5}  {}\n",
+                address,
+                entry.name,
+                entry.rssi,
+                (now - entry.last_seen).as_secs(),
+                state,
+                entry.ad_types.iter().map(|t| format!("{:02x}", t)).collect::<Vec<_>>().join(" ")
+            );
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ad_types() {
+        // Flags, complete 16-bit UUIDs, then a truncated structure.
+        let adv_data = [0x02, 0x01, 0x06, 0x03, 0x03, 0x0d, 0x18, 0x05, 0xff, 0x00];
+        assert_eq!(ad_types(&adv_data), vec![0x01, 0x03]);
+        assert_eq!(ad_types(&[]), Vec::<u8>::new());
+        assert_eq!(ad_types(&[0x00, 0x02, 0x01, 0x06]), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_update_keeps_state() {
+        let mut context = ScanContext::new();
+        let mut result = ScanResult {
+            name: "Keyboard".into(),
+            address: "AA:BB:CC:DD:EE:FF".into(),
+            addr_type: 0,
+            event_type: 0,
+            primary_phy: 1,
+            secondary_phy: 0,
+            advertising_sid: 0,
+            tx_power: 0,
+            rssi: -40,
+            periodic_adv_int: 0,
+            flags: 0,
+            service_uuids: vec![],
+            service_data: HashMap::new(),
+            manufacturer_data: HashMap::new(),
+            adv_data: vec![0x02, 0x01, 0x06],
+        };
+        context.update(&result, Some(ScanEntryState::Found));
+        result.name = String::new();
+        result.rssi = -60;
+        context.update(&result, None);
+
+        let entry = &context.entries["AA:BB:CC:DD:EE:FF"];
+        assert_eq!(entry.name, "Keyboard");
+        assert_eq!(entry.rssi, -60);
+        assert_eq!(entry.state, ScanEntryState::Found);
+        assert_eq!(entry.ad_types, vec![0x01]);
+    }
+}
@@ -1,23 +1,28 @@
+use crate::bt_scan::ScanEntryState;
 use crate::command_handler::SocketSchedule;
 use crate::dbus_iface::{
     export_admin_policy_callback_dbus_intf, export_advertising_set_callback_dbus_intf,
-    export_bluetooth_callback_dbus_intf, export_bluetooth_connection_callback_dbus_intf,
-    export_bluetooth_gatt_callback_dbus_intf, export_bluetooth_manager_callback_dbus_intf,
-    export_bluetooth_media_callback_dbus_intf, export_bluetooth_telephony_callback_dbus_intf,
-    export_gatt_server_callback_dbus_intf, export_qa_callback_dbus_intf,
-    export_scanner_callback_dbus_intf, export_socket_callback_dbus_intf,
-    export_suspend_callback_dbus_intf,
+    export_battery_manager_callback_dbus_intf, export_bluetooth_callback_dbus_intf,
+    export_bluetooth_connection_callback_dbus_intf, export_bluetooth_gatt_callback_dbus_intf,
+    export_bluetooth_manager_callback_dbus_intf, export_bluetooth_media_callback_dbus_intf,
+    export_bluetooth_telephony_callback_dbus_intf, export_gatt_server_callback_dbus_intf,
+    export_qa_callback_dbus_intf, export_scanner_callback_dbus_intf,
+    export_socket_callback_dbus_intf, export_suspend_callback_dbus_intf,
 };
+use crate::script::ScriptEvent;
 use crate::ClientContext;
-use crate::{console_red, console_yellow, print_error, print_info};
+use crate::{console_red, console_yellow, print_error, print_event, print_info};
 use bt_topshim::btif::{BtBondState, BtPropertyType, BtSspVariant, BtStatus, Uuid128Bit};
 use bt_topshim::profiles::gatt::{AdvertisingStatus, GattStatus, LePhy};
 use bt_topshim::profiles::hfp::HfpCodecId;
 use bt_topshim::profiles::sdp::BtSdpRecord;
+use btstack::battery_manager::{BatterySet, IBatteryManagerCallback};
 use btstack::bluetooth::{
     BluetoothDevice, IBluetooth, IBluetoothCallback, IBluetoothConnectionCallback,
 };
-use btstack::bluetooth_admin::{IBluetoothAdminPolicyCallback, PolicyEffect};
+use btstack::bluetooth_admin::{
+    DevicePolicy, IBluetoothAdminPolicyCallback, PolicyBlockReason, PolicyEffect,
+};
 use btstack::bluetooth_adv::IAdvertisingSetCallback;
 use btstack::bluetooth_gatt::{
     BluetoothGattService, IBluetoothGattCallback, IBluetoothGattServerCallback, IScannerCallback,
@@ -27,6 +32,7 @@ use btstack::bluetooth_media::{
     BluetoothAudioDevice, IBluetoothMediaCallback, IBluetoothTelephonyCallback,
 };
 use btstack::bluetooth_qa::IBluetoothQACallback;
+use btstack::dis::DeviceInformationValues;
 use btstack::socket_manager::{
     BluetoothServerSocket, BluetoothSocket, IBluetoothSocketManager,
     IBluetoothSocketManagerCallbacks, SocketId,
@@ -38,7 +44,8 @@ use chrono::{TimeZone, Utc};
 use dbus::nonblock::SyncConnection;
 use dbus_crossroads::Crossroads;
 use dbus_projection::DisconnectWatcher;
-use manager_service::iface_bluetooth_manager::IBluetoothManagerCallback;
+use manager_service::iface_bluetooth_manager::{AdapterHealth, IBluetoothManagerCallback};
+use num_traits::ToPrimitive;
 use std::convert::TryFrom;
 use std::io::{Read, Write};
 use std::sync::{Arc, Mutex};
@@ -51,6 +58,174 @@ const SOCKET_TEST_WRITE: &[u8] =
 // doesn't trim our dump.
 const BINARY_PACKET_STATUS_WRAP: usize = 50;
 
+/// JSON fields of a remote device in events.
+fn device_json(device: &BluetoothDevice) -> serde_json::Value {
+    serde_json::json!({ "address": device.address, "name": device.name })
+}
+
+/// JSON fields of a battery set in events and command results.
+pub(crate) fn battery_set_json(battery_set: &BatterySet) -> serde_json::Value {
+    serde_json::json!({
+        "source_uuid": battery_set.source_uuid,
+        "source_info": battery_set.source_info,
+        "batteries": battery_set
+            .batteries
+            .iter()
+            .map(|b| serde_json::json!({ "percentage": b.percentage, "variant": b.variant }))
+            .collect::<Vec<serde_json::Value>>(),
+    })
+}
+
+/// Formats a battery set for humans, e.g. "Left 80%, Right 75% (BAS)".
+pub(crate) fn format_battery_set(battery_set: &BatterySet) -> String {
+    let batteries = battery_set
+        .batteries
+        .iter()
+        .map(|b| match b.variant.is_empty() {
+            true => format!("{}%", b.percentage),
+            false => format!("{} {}%", b.variant, b.percentage),
+        })
+        .collect::<Vec<String>>();
+    format!("{} ({})", batteries.join(", "), battery_set.source_info)
+}
+
+/// JSON fields of a scan result in events. Binary data is hex encoded.
+fn scan_result_json(result: &ScanResult) -> serde_json::Value {
+    let service_data: serde_json::Map<String, serde_json::Value> = result
+        .service_data
+        .iter()
+        .map(|(uuid, data)| (uuid.clone(), hex::encode(data).into()))
+        .collect();
+    let manufacturer_data: serde_json::Map<String, serde_json::Value> = result
+        .manufacturer_data
+        .iter()
+        .map(|(id, data)| (format!("{:04x}", id), hex::encode(data).into()))
+        .collect();
+    serde_json::json!({
+        "name": result.name,
+        "address": result.address,
+        "addr_type": result.addr_type,
+        "event_type": result.event_type,
+        "primary_phy": result.primary_phy,
+        "secondary_phy": result.secondary_phy,
+        "advertising_sid": result.advertising_sid,
+        "tx_power": result.tx_power,
+        "rssi": result.rssi,
+        "periodic_adv_int": result.periodic_adv_int,
+        "flags": result.flags,
+        "service_uuids": result
+            .service_uuids
+            .iter()
+            .map(|uuid| UuidWrapper(uuid).to_string())
+            .collect::<Vec<String>>(),
+        "service_data": service_data,
+        "manufacturer_data": manufacturer_data,
+        "adv_data": hex::encode(&result.adv_data),
+    })
+}
+
+/// JSON fields of an SDP record. Only the header common to all record types is included.
+fn sdp_record_json(record: &BtSdpRecord) -> serde_json::Value {
+    let hdr = match record {
+        BtSdpRecord::HeaderOverlay(header) => header,
+        BtSdpRecord::MapMas(record) => &record.hdr,
+        BtSdpRecord::MapMns(record) => &record.hdr,
+        BtSdpRecord::PbapPse(record) => &record.hdr,
+        BtSdpRecord::PbapPce(record) => &record.hdr,
+        BtSdpRecord::OppServer(record) => &record.hdr,
+        BtSdpRecord::SapServer(record) => &record.hdr,
+        BtSdpRecord::Dip(record) => &record.hdr,
+        BtSdpRecord::Mps(record) => &record.hdr,
+    };
+    serde_json::json!({
+        "type": hdr.sdp_type.to_u32(),
+        "uuid": hdr.uuid.to_string(),
+        "service_name": hdr.service_name,
+        "rfcomm_channel_number": hdr.rfcomm_channel_number,
+        "l2cap_psm": hdr.l2cap_psm,
+        "profile_version": hdr.profile_version,
+    })
+}
+
+/// JSON fields of a device policy.
+pub(crate) fn device_policy_json(policy: &DevicePolicy) -> serde_json::Value {
+    serde_json::json!({
+        "denied_addresses": policy.denied_addresses,
+        "allowed_bonding_ids": policy
+            .allowed_bonding_ids
+            .iter()
+            .map(|id| serde_json::json!({ "vendor_id": id.vendor_id, "product_id": id.product_id }))
+            .collect::<Vec<serde_json::Value>>(),
+        "allowed_transport": policy.allowed_transport.to_u32(),
+    })
+}
+
+/// JSON fields of the effect of the admin policy on a device.
+pub(crate) fn policy_effect_json(effect: &PolicyEffect) -> serde_json::Value {
+    serde_json::json!({
+        "service_blocked": effect
+            .service_blocked
+            .iter()
+            .map(|uuid| UuidWrapper(uuid).to_string())
+            .collect::<Vec<String>>(),
+        "device_blocked": effect.device_blocked.to_u32(),
+        "affected": effect.affected,
+    })
+}
+
+/// JSON fields of a GATT service, including its characteristics and included services.
+fn gatt_service_json(service: &BluetoothGattService) -> serde_json::Value {
+    serde_json::json!({
+        "uuid": UuidWrapper(&service.uuid).to_string(),
+        "instance_id": service.instance_id,
+        "service_type": service.service_type,
+        "characteristics": service
+            .characteristics
+            .iter()
+            .map(|characteristic| {
+                serde_json::json!({
+                    "uuid": UuidWrapper(&characteristic.uuid).to_string(),
+                    "instance_id": characteristic.instance_id,
+                    "properties": characteristic.properties,
+                    "permissions": characteristic.permissions,
+                    "key_size": characteristic.key_size,
+                    "write_type": characteristic.write_type.to_u32(),
+                    "descriptors": characteristic
+                        .descriptors
+                        .iter()
+                        .map(|descriptor| {
+                            serde_json::json!({
+                                "uuid": UuidWrapper(&descriptor.uuid).to_string(),
+                                "instance_id": descriptor.instance_id,
+                                "permissions": descriptor.permissions,
+                            })
+                        })
+                        .collect::<Vec<serde_json::Value>>(),
+                })
+            })
+            .collect::<Vec<serde_json::Value>>(),
+        "included_services": service
+            .included_services
+            .iter()
+            .map(gatt_service_json)
+            .collect::<Vec<serde_json::Value>>(),
+    })
+}
+
+/// JSON fields of a connected socket.
+fn socket_json(socket: &BluetoothSocket) -> serde_json::Value {
+    serde_json::json!({
+        "id": socket.id,
+        "device": device_json(&socket.remote_device),
+        "sock_type": socket.sock_type.to_u32(),
+        "flags": socket.flags,
+        "port": socket.port,
+        "uuid": socket.uuid.as_ref().map(|uuid| uuid.to_string()),
+        "max_rx_size": socket.max_rx_size,
+        "max_tx_size": socket.max_tx_size,
+    })
+}
+
 /// Callback context for manager interface callbacks.
 pub(crate) struct BtManagerCallback {
     objpath: String,
@@ -73,7 +248,13 @@ impl BtManagerCallback {
 
 impl IBluetoothManagerCallback for BtManagerCallback {
     fn on_hci_device_changed(&mut self, hci_interface: i32, present: bool) {
-        print_info!("hci{} present = {}", hci_interface, present);
+        print_event!(
+            "hci_device_changed",
+            { "hci": hci_interface, "present": present },
+            "hci{} present = {}",
+            hci_interface,
+            present
+        );
 
         if present {
             self.context.lock().unwrap().adapters.entry(hci_interface).or_insert(false);
@@ -87,7 +268,57 @@ impl IBluetoothManagerCallback for BtManagerCallback {
     }
 
     fn on_default_adapter_changed(&mut self, hci_interface: i32) {
-        print_info!("hci{} is now the default", hci_interface);
+        print_event!(
+            "default_adapter_changed",
+            { "hci": hci_interface },
+            "hci{} is now the default",
+            hci_interface
+        );
+    }
+
+    fn on_adapter_health_changed(&mut self, health: AdapterHealth) {
+        print_event!(
+            "adapter_health_changed",
+            data: serde_json::json!({
+                "hci": health.hci_interface,
+                "failed": health.failed,
+                "restarts": health.restart_history.len(),
+                "restart_delay_ms": health.restart_delay_ms,
+                "reset_count": health.reset_count,
+            }),
+            {
+                if health.failed {
+                    print_error!(
+                        "hci{} failed after {} restarts, start it again to retry",
+                        health.hci_interface,
+                        health.restart_history.len()
+                    );
+                } else if health.restart_delay_ms > 0 {
+                    print_info!(
+                        "hci{} will restart in {} ms (resets = {})",
+                        health.hci_interface,
+                        health.restart_delay_ms,
+                        health.reset_count
+                    );
+                }
+            }
+        );
+    }
+
+    fn on_hci_rfkill_changed(
+        &mut self,
+        hci_interface: i32,
+        soft_blocked: bool,
+        hard_blocked: bool,
+    ) {
+        print_event!(
+            "hci_rfkill_changed",
+            { "hci": hci_interface, "soft_blocked": soft_blocked, "hard_blocked": hard_blocked },
+            "hci{} rfkill soft blocked = {}, hard blocked = {}",
+            hci_interface,
+            soft_blocked,
+            hard_blocked
+        );
     }
 }
 
@@ -135,20 +366,60 @@ impl IBluetoothCallback for BtCallback {
         remote_device: BluetoothDevice,
         props: Vec<BtPropertyType>,
     ) {
-        print_info!("Bluetooth properties {:?} changed for {:?}", props, remote_device);
+        print_event!(
+            "device_properties_changed",
+            {
+                "device": device_json(&remote_device),
+                "properties": props.iter().map(|p| p.to_u32()).collect::<Vec<Option<u32>>>(),
+            },
+            "Bluetooth properties {:?} changed for {:?}",
+            props,
+            remote_device
+        );
+    }
+
+    fn on_device_information_changed(
+        &mut self,
+        remote_device: BluetoothDevice,
+        values: DeviceInformationValues,
+    ) {
+        // Shown most significant octet first, like in the DIS config.
+        let system_id: Vec<u8> = values.system_id.iter().rev().copied().collect();
+        print_event!(
+            "device_information_changed",
+            {
+                "device": device_json(&remote_device),
+                "manufacturer_name": values.manufacturer_name,
+                "model_number": values.model_number,
+                "serial_number": values.serial_number,
+                "hardware_revision": values.hardware_revision,
+                "firmware_revision": values.firmware_revision,
+                "software_revision": values.software_revision,
+                "system_id": hex::encode(&system_id),
+            },
+            "Device information of {} changed: manufacturer {:?}, model {:?}",
+            remote_device.address,
+            values.manufacturer_name,
+            values.model_number
+        );
     }
 
     fn on_address_changed(&mut self, addr: String) {
-        print_info!("Address changed to {}", &addr);
+        print_event!("address_changed", { "address": addr }, "Address changed to {}", &addr);
         self.context.lock().unwrap().adapter_address = Some(addr);
     }
 
     fn on_name_changed(&mut self, name: String) {
-        print_info!("Name changed to {}", &name);
+        print_event!("name_changed", { "name": name }, "Name changed to {}", &name);
     }
 
     fn on_discoverable_changed(&mut self, discoverable: bool) {
-        print_info!("Discoverable changed to {}", &discoverable);
+        print_event!(
+            "discoverable_changed",
+            { "discoverable": discoverable },
+            "Discoverable changed to {}",
+            &discoverable
+        );
     }
 
     fn on_device_found(&mut self, remote_device: BluetoothDevice) {
@@ -158,13 +429,27 @@ impl IBluetoothCallback for BtCallback {
             .found_devices
             .entry(remote_device.address.clone())
             .or_insert(remote_device.clone());
+        self.context
+            .lock()
+            .unwrap()
+            .notify_script_event(ScriptEvent::DeviceFound(remote_device.address.clone()));
 
-        print_info!("Found device: {:?}", remote_device);
+        print_event!(
+            "device_found",
+            { "device": device_json(&remote_device) },
+            "Found device: {:?}",
+            remote_device
+        );
     }
 
     fn on_device_cleared(&mut self, remote_device: BluetoothDevice) {
         match self.context.lock().unwrap().found_devices.remove(&remote_device.address) {
-            Some(_) => print_info!("Removed device: {:?}", remote_device),
+            Some(_) => print_event!(
+                "device_cleared",
+                { "device": device_json(&remote_device) },
+                "Removed device: {:?}",
+                remote_device
+            ),
             None => (),
         };
 
@@ -173,8 +458,14 @@ impl IBluetoothCallback for BtCallback {
 
     fn on_discovering_changed(&mut self, discovering: bool) {
         self.context.lock().unwrap().discovering_state = discovering;
+        self.context.lock().unwrap().notify_script_event(ScriptEvent::Discovering(discovering));
 
-        print_info!("Discovering: {}", discovering);
+        print_event!(
+            "discovering_changed",
+            { "discovering": discovering },
+            "Discovering: {}",
+            discovering
+        );
     }
 
     fn on_ssp_request(
@@ -186,7 +477,13 @@ impl IBluetoothCallback for BtCallback {
     ) {
         match variant {
             BtSspVariant::PasskeyNotification | BtSspVariant::PasskeyConfirmation => {
-                print_info!(
+                print_event!(
+                    "ssp_request",
+                    {
+                        "device": device_json(&remote_device),
+                        "variant": variant.to_u32(),
+                        "passkey": passkey,
+                    },
                     "Device [{}: {}] would like to pair, enter passkey on remote device: {:06}",
                     &remote_device.address,
                     &remote_device.name,
@@ -216,13 +513,15 @@ impl IBluetoothCallback for BtCallback {
                 }));
             }
             BtSspVariant::PasskeyEntry => {
-                println!("Got PasskeyEntry but it is not supported...");
+                print_error!("Got PasskeyEntry but it is not supported...");
             }
         }
     }
 
     fn on_pin_request(&mut self, remote_device: BluetoothDevice, _cod: u32, min_16_digit: bool) {
-        print_info!(
+        print_event!(
+            "pin_request",
+            { "device": device_json(&remote_device), "min_16_digit": min_16_digit },
             "Device [{}: {}] would like to pair, enter pin code {}",
             &remote_device.address,
             &remote_device.name,
@@ -234,7 +533,9 @@ impl IBluetoothCallback for BtCallback {
     }
 
     fn on_pin_display(&mut self, remote_device: BluetoothDevice, pincode: String) {
-        print_info!(
+        print_event!(
+            "pin_display",
+            { "device": device_json(&remote_device), "pin": pincode },
             "Device [{}: {}] would like to pair, enter pin code {} on the remote",
             &remote_device.address,
             &remote_device.name,
@@ -243,7 +544,18 @@ impl IBluetoothCallback for BtCallback {
     }
 
     fn on_bond_state_changed(&mut self, status: u32, address: String, state: u32) {
-        print_info!("Bonding state changed: [{}] state: {}, Status = {}", address, state, status);
+        print_event!(
+            "bond_state_changed",
+            {
+                "address": address,
+                "state": state,
+                "status": status,
+            },
+            "Bonding state changed: [{}] state: {}, Status = {}",
+            address,
+            state,
+            status
+        );
 
         // Clear bonding attempt if bonding fails or succeeds
         match BtBondState::from(state) {
@@ -274,6 +586,11 @@ impl IBluetoothCallback for BtCallback {
         if BtBondState::NotBonded == state.into() {
             self.context.lock().unwrap().bonded_devices.remove(&address);
         }
+
+        self.context
+            .lock()
+            .unwrap()
+            .notify_script_event(ScriptEvent::BondState(BtBondState::from(state), address));
     }
 
     fn on_sdp_search_complete(
@@ -282,19 +599,33 @@ impl IBluetoothCallback for BtCallback {
         searched_uuid: Uuid128Bit,
         sdp_records: Vec<BtSdpRecord>,
     ) {
-        print_info!(
+        print_event!(
+            "sdp_search_complete",
+            {
+                "device": device_json(&remote_device),
+                "uuid": UuidWrapper(&searched_uuid).to_string(),
+                "records": sdp_records
+                    .iter()
+                    .map(sdp_record_json)
+                    .collect::<Vec<serde_json::Value>>(),
+            },
             "SDP search of {} for UUID {} returned {} results",
             remote_device.address,
             UuidWrapper(&searched_uuid),
             sdp_records.len()
         );
-        if !sdp_records.is_empty() {
+        if !sdp_records.is_empty() && !crate::console::is_json_output() {
             print_info!("{:?}", sdp_records);
         }
     }
 
     fn on_sdp_record_created(&mut self, record: BtSdpRecord, handle: i32) {
-        print_info!("SDP record handle={} created", handle);
+        print_event!(
+            "sdp_record_created",
+            { "handle": handle },
+            "SDP record handle={} created",
+            handle
+        );
         if let BtSdpRecord::Mps(_) = record {
             let context = self.context.clone();
             // Callbacks first lock the DBus resource and then lock the context,
@@ -326,7 +657,7 @@ impl RPCProxy for BtCallback {
 
 pub(crate) struct BtConnectionCallback {
     objpath: String,
-    _context: Arc<Mutex<ClientContext>>,
+    context: Arc<Mutex<ClientContext>>,
 
     dbus_connection: Arc<SyncConnection>,
     dbus_crossroads: Arc<Mutex<Crossroads>>,
@@ -335,21 +666,41 @@ pub(crate) struct BtConnectionCallback {
 impl BtConnectionCallback {
     pub(crate) fn new(
         objpath: String,
-        _context: Arc<Mutex<ClientContext>>,
+        context: Arc<Mutex<ClientContext>>,
         dbus_connection: Arc<SyncConnection>,
         dbus_crossroads: Arc<Mutex<Crossroads>>,
     ) -> Self {
-        Self { objpath, _context, dbus_connection, dbus_crossroads }
+        Self { objpath, context, dbus_connection, dbus_crossroads }
     }
 }
 
 impl IBluetoothConnectionCallback for BtConnectionCallback {
     fn on_device_connected(&mut self, remote_device: BluetoothDevice) {
-        print_info!("Connected: [{}]: {}", remote_device.address, remote_device.name);
+        print_event!(
+            "device_connected",
+            { "device": device_json(&remote_device) },
+            "Connected: [{}]: {}",
+            remote_device.address,
+            remote_device.name
+        );
+        self.context
+            .lock()
+            .unwrap()
+            .notify_script_event(ScriptEvent::DeviceConnected(remote_device.address));
     }
 
     fn on_device_disconnected(&mut self, remote_device: BluetoothDevice) {
-        print_info!("Disconnected: [{}]: {}", remote_device.address, remote_device.name);
+        print_event!(
+            "device_disconnected",
+            { "device": device_json(&remote_device) },
+            "Disconnected: [{}]: {}",
+            remote_device.address,
+            remote_device.name
+        );
+        self.context
+            .lock()
+            .unwrap()
+            .notify_script_event(ScriptEvent::DeviceDisconnected(remote_device.address));
     }
 }
 
@@ -386,6 +737,21 @@ impl ScannerCallback {
     ) -> Self {
         Self { objpath, context, dbus_connection, dbus_crossroads }
     }
+
+    /// Records the result in the scan table. Returns true if the live table is shown, in which
+    /// case the result is not printed on its own.
+    fn update_scan_table(&self, scan_result: &ScanResult, state: Option<ScanEntryState>) -> bool {
+        let mut context = self.context.lock().unwrap();
+        context.scan_context.update(scan_result, state);
+        if !context.scan_context.live_table || crate::console::is_json_output() {
+            return false;
+        }
+        if let Some(table) = context.scan_context.take_live_redraw() {
+            // Clear the screen and move the cursor to the top left corner.
+            print!("\x1b[2J\x1b[H{}", table);
+        }
+        true
+    }
 }
 
 impl IScannerCallback for ScannerCallback {
@@ -395,7 +761,9 @@ impl IScannerCallback for ScannerCallback {
             return;
         }
 
-        print_info!(
+        print_event!(
+            "scanner_registered",
+            { "uuid": UuidWrapper(&uuid).to_string(), "scanner_id": scanner_id },
             "Scanner callback registered, uuid = {}, id = {}",
             UuidWrapper(&uuid),
             scanner_id
@@ -404,25 +772,56 @@ impl IScannerCallback for ScannerCallback {
 
     fn on_scan_result(&mut self, scan_result: ScanResult) {
         if self.context.lock().unwrap().active_scanner_ids.len() > 0 {
-            print_info!("Scan result: {:#?}", scan_result);
+            if self.update_scan_table(&scan_result, None) {
+                return;
+            }
+            print_event!(
+                "scan_result",
+                { "result": scan_result_json(&scan_result) },
+                "Scan result: {:#?}",
+                scan_result
+            );
         }
     }
 
     fn on_advertisement_found(&mut self, scanner_id: u8, scan_result: ScanResult) {
         if self.context.lock().unwrap().active_scanner_ids.len() > 0 {
-            print_info!("Advertisement found for scanner_id {} : {:#?}", scanner_id, scan_result);
+            if self.update_scan_table(&scan_result, Some(ScanEntryState::Found)) {
+                return;
+            }
+            print_event!(
+                "advertisement_found",
+                { "scanner_id": scanner_id, "result": scan_result_json(&scan_result) },
+                "Advertisement found for scanner_id {} : {:#?}",
+                scanner_id,
+                scan_result
+            );
         }
     }
 
     fn on_advertisement_lost(&mut self, scanner_id: u8, scan_result: ScanResult) {
         if self.context.lock().unwrap().active_scanner_ids.len() > 0 {
-            print_info!("Advertisement lost for scanner_id {} : {:#?}", scanner_id, scan_result);
+            if self.update_scan_table(&scan_result, Some(ScanEntryState::Lost)) {
+                return;
+            }
+            print_event!(
+                "advertisement_lost",
+                { "scanner_id": scanner_id, "result": scan_result_json(&scan_result) },
+                "Advertisement lost for scanner_id {} : {:#?}",
+                scanner_id,
+                scan_result
+            );
         }
     }
 
     fn on_suspend_mode_change(&mut self, suspend_mode: SuspendMode) {
         if self.context.lock().unwrap().active_scanner_ids.len() > 0 {
-            print_info!("Scan suspend mode change: {:#?}", suspend_mode);
+            print_event!(
+                "scan_suspend_mode_changed",
+                { "suspend_mode": suspend_mode.to_u32() },
+                "Scan suspend mode change: {:#?}",
+                suspend_mode
+            );
         }
     }
 }
@@ -462,7 +861,17 @@ impl AdminCallback {
 
 impl IBluetoothAdminPolicyCallback for AdminCallback {
     fn on_service_allowlist_changed(&mut self, allowlist: Vec<Uuid128Bit>) {
-        print_info!("new allowlist: {:?}", allowlist);
+        print_event!(
+            "admin_service_allowlist_changed",
+            {
+                "allowlist": allowlist
+                    .iter()
+                    .map(|uuid| UuidWrapper(uuid).to_string())
+                    .collect::<Vec<String>>(),
+            },
+            "new allowlist: {:?}",
+            allowlist
+        );
     }
 
     fn on_device_policy_effect_changed(
@@ -470,12 +879,36 @@ impl IBluetoothAdminPolicyCallback for AdminCallback {
         device: BluetoothDevice,
         new_policy_effect: Option<PolicyEffect>,
     ) {
-        print_info!(
+        print_event!(
+            "admin_device_policy_effect_changed",
+            {
+                "device": device_json(&device),
+                "new_policy_effect": new_policy_effect.as_ref().map(policy_effect_json),
+            },
             "new device policy effect. Device: {:?}. New Effect: {:?}",
             device,
             new_policy_effect
         );
     }
+
+    fn on_device_policy_changed(&mut self, policy: DevicePolicy) {
+        print_event!(
+            "admin_device_policy_changed",
+            { "policy": device_policy_json(&policy) },
+            "new device policy: {:?}",
+            policy
+        );
+    }
+
+    fn on_device_policy_violation(&mut self, device: BluetoothDevice, reason: PolicyBlockReason) {
+        print_event!(
+            "admin_device_policy_violation",
+            { "device": device_json(&device), "reason": reason.to_u32() },
+            "device blocked by policy. Device: {:?}. Reason: {:?}",
+            device,
+            reason
+        );
+    }
 }
 
 impl RPCProxy for AdminCallback {
@@ -521,7 +954,14 @@ impl IAdvertisingSetCallback for AdvertisingSetCallback {
         tx_power: i32,
         status: AdvertisingStatus,
     ) {
-        print_info!(
+        print_event!(
+            "advertising_set_started",
+            {
+                "reg_id": reg_id,
+                "advertiser_id": advertiser_id,
+                "tx_power": tx_power,
+                "status": status.to_u32(),
+            },
             "on_advertising_set_started: reg_id = {}, advertiser_id = {}, tx_power = {}, status = {:?}",
             reg_id,
             advertiser_id,
@@ -546,7 +986,9 @@ impl IAdvertisingSetCallback for AdvertisingSetCallback {
     }
 
     fn on_own_address_read(&mut self, advertiser_id: i32, address_type: i32, address: String) {
-        print_info!(
+        print_event!(
+            "advertising_own_address_read",
+            { "advertiser_id": advertiser_id, "address_type": address_type, "address": address },
             "on_own_address_read: advertiser_id = {}, address_type = {}, address = {}",
             advertiser_id,
             address_type,
@@ -555,7 +997,12 @@ impl IAdvertisingSetCallback for AdvertisingSetCallback {
     }
 
     fn on_advertising_set_stopped(&mut self, advertiser_id: i32) {
-        print_info!("on_advertising_set_stopped: advertiser_id = {}", advertiser_id);
+        print_event!(
+            "advertising_set_stopped",
+            { "advertiser_id": advertiser_id },
+            "on_advertising_set_stopped: advertiser_id = {}",
+            advertiser_id
+        );
     }
 
     fn on_advertising_enabled(
@@ -564,7 +1011,9 @@ impl IAdvertisingSetCallback for AdvertisingSetCallback {
         enable: bool,
         status: AdvertisingStatus,
     ) {
-        print_info!(
+        print_event!(
+            "advertising_enabled",
+            { "advertiser_id": advertiser_id, "enable": enable, "status": status.to_u32() },
             "on_advertising_enabled: advertiser_id = {}, enable = {}, status = {:?}",
             advertiser_id,
             enable,
@@ -573,7 +1022,9 @@ impl IAdvertisingSetCallback for AdvertisingSetCallback {
     }
 
     fn on_advertising_data_set(&mut self, advertiser_id: i32, status: AdvertisingStatus) {
-        print_info!(
+        print_event!(
+            "advertising_data_set",
+            { "advertiser_id": advertiser_id, "status": status.to_u32() },
             "on_advertising_data_set: advertiser_id = {}, status = {:?}",
             advertiser_id,
             status
@@ -581,7 +1032,9 @@ impl IAdvertisingSetCallback for AdvertisingSetCallback {
     }
 
     fn on_scan_response_data_set(&mut self, advertiser_id: i32, status: AdvertisingStatus) {
-        print_info!(
+        print_event!(
+            "advertising_scan_response_data_set",
+            { "advertiser_id": advertiser_id, "status": status.to_u32() },
             "on_scan_response_data_set: advertiser_id = {}, status = {:?}",
             advertiser_id,
             status
@@ -594,7 +1047,13 @@ impl IAdvertisingSetCallback for AdvertisingSetCallback {
         tx_power: i32,
         status: AdvertisingStatus,
     ) {
-        print_info!(
+        print_event!(
+            "advertising_parameters_updated",
+            {
+                "advertiser_id": advertiser_id,
+                "tx_power": tx_power,
+                "status": status.to_u32(),
+            },
             "on_advertising_parameters_updated: advertiser_id = {}, tx_power: {}, status = {:?}",
             advertiser_id,
             tx_power,
@@ -607,7 +1066,9 @@ impl IAdvertisingSetCallback for AdvertisingSetCallback {
         advertiser_id: i32,
         status: AdvertisingStatus,
     ) {
-        print_info!(
+        print_event!(
+            "advertising_periodic_advertising_parameters_updated",
+            { "advertiser_id": advertiser_id, "status": status.to_u32() },
             "on_periodic_advertising_parameters_updated: advertiser_id = {}, status = {:?}",
             advertiser_id,
             status
@@ -615,7 +1076,9 @@ impl IAdvertisingSetCallback for AdvertisingSetCallback {
     }
 
     fn on_periodic_advertising_data_set(&mut self, advertiser_id: i32, status: AdvertisingStatus) {
-        print_info!(
+        print_event!(
+            "advertising_periodic_advertising_data_set",
+            { "advertiser_id": advertiser_id, "status": status.to_u32() },
             "on_periodic_advertising_data_set: advertiser_id = {}, status = {:?}",
             advertiser_id,
             status
@@ -628,7 +1091,9 @@ impl IAdvertisingSetCallback for AdvertisingSetCallback {
         enable: bool,
         status: AdvertisingStatus,
     ) {
-        print_info!(
+        print_event!(
+            "advertising_periodic_advertising_enabled",
+            { "advertiser_id": advertiser_id, "enable": enable, "status": status.to_u32() },
             "on_periodic_advertising_enabled: advertiser_id = {}, enable = {}, status = {:?}",
             advertiser_id,
             enable,
@@ -637,7 +1102,12 @@ impl IAdvertisingSetCallback for AdvertisingSetCallback {
     }
 
     fn on_suspend_mode_change(&mut self, suspend_mode: SuspendMode) {
-        print_info!("on_suspend_mode_change: advertising suspend_mode = {:?}", suspend_mode);
+        print_event!(
+            "advertising_suspend_mode_changed",
+            { "suspend_mode": suspend_mode.to_u32() },
+            "on_suspend_mode_change: advertising suspend_mode = {:?}",
+            suspend_mode
+        );
     }
 }
 
@@ -678,7 +1148,13 @@ impl BtGattCallback {
 
 impl IBluetoothGattCallback for BtGattCallback {
     fn on_client_registered(&mut self, status: GattStatus, client_id: i32) {
-        print_info!("GATT Client registered status = {}, client_id = {}", status, client_id);
+        print_event!(
+            "gatt_client_registered",
+            { "status": status.to_u32(), "client_id": client_id },
+            "GATT Client registered status = {}, client_id = {}",
+            status,
+            client_id
+        );
         self.context.lock().unwrap().gatt_client_context.client_id = Some(client_id);
     }
 
@@ -689,7 +1165,14 @@ impl IBluetoothGattCallback for BtGattCallback {
         connected: bool,
         addr: String,
     ) {
-        print_info!(
+        print_event!(
+            "gatt_client_connection_state",
+            {
+                "status": status.to_u32(),
+                "client_id": client_id,
+                "connected": connected,
+                "address": addr,
+            },
             "GATT Client connection state = {}, client_id = {}, connected = {}, addr = {}",
             status,
             client_id,
@@ -699,7 +1182,14 @@ impl IBluetoothGattCallback for BtGattCallback {
     }
 
     fn on_phy_update(&mut self, addr: String, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus) {
-        print_info!(
+        print_event!(
+            "gatt_client_phy_update",
+            {
+                "address": addr,
+                "tx_phy": tx_phy.to_u32(),
+                "rx_phy": rx_phy.to_u32(),
+                "status": status.to_u32(),
+            },
             "Phy updated: addr = {}, tx_phy = {:?}, rx_phy = {:?}, status = {:?}",
             addr,
             tx_phy,
@@ -709,7 +1199,14 @@ impl IBluetoothGattCallback for BtGattCallback {
     }
 
     fn on_phy_read(&mut self, addr: String, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus) {
-        print_info!(
+        print_event!(
+            "gatt_client_phy_read",
+            {
+                "address": addr,
+                "tx_phy": tx_phy.to_u32(),
+                "rx_phy": rx_phy.to_u32(),
+                "status": status.to_u32(),
+            },
             "Phy read: addr = {}, tx_phy = {:?}, rx_phy = {:?}, status = {:?}",
             addr,
             tx_phy,
@@ -724,7 +1221,16 @@ impl IBluetoothGattCallback for BtGattCallback {
         services: Vec<BluetoothGattService>,
         status: GattStatus,
     ) {
-        print_info!(
+        print_event!(
+            "gatt_client_search_complete",
+            {
+                "address": addr,
+                "services": services
+                    .iter()
+                    .map(gatt_service_json)
+                    .collect::<Vec<serde_json::Value>>(),
+                "status": status.to_u32(),
+            },
             "GATT DB Search complete: addr = {}, services = {:?}, status = {}",
             addr,
             services,
@@ -739,7 +1245,14 @@ impl IBluetoothGattCallback for BtGattCallback {
         handle: i32,
         value: Vec<u8>,
     ) {
-        print_info!(
+        print_event!(
+            "gatt_client_characteristic_read",
+            {
+                "address": addr,
+                "status": status.to_u32(),
+                "handle": handle,
+                "value": hex::encode(&value),
+            },
             "GATT Characteristic read: addr = {}, status = {}, handle = {}, value = {:?}",
             addr,
             status,
@@ -749,7 +1262,9 @@ impl IBluetoothGattCallback for BtGattCallback {
     }
 
     fn on_characteristic_write(&mut self, addr: String, status: GattStatus, handle: i32) {
-        print_info!(
+        print_event!(
+            "gatt_client_characteristic_write",
+            { "address": addr, "status": status.to_u32(), "handle": handle },
             "GATT Characteristic write: addr = {}, status = {}, handle = {}",
             addr,
             status,
@@ -758,7 +1273,13 @@ impl IBluetoothGattCallback for BtGattCallback {
     }
 
     fn on_execute_write(&mut self, addr: String, status: GattStatus) {
-        print_info!("GATT execute write addr = {}, status = {}", addr, status);
+        print_event!(
+            "gatt_client_execute_write",
+            { "address": addr, "status": status.to_u32() },
+            "GATT execute write addr = {}, status = {}",
+            addr,
+            status
+        );
     }
 
     fn on_descriptor_read(
@@ -768,7 +1289,14 @@ impl IBluetoothGattCallback for BtGattCallback {
         handle: i32,
         value: Vec<u8>,
     ) {
-        print_info!(
+        print_event!(
+            "gatt_client_descriptor_read",
+            {
+                "address": addr,
+                "status": status.to_u32(),
+                "handle": handle,
+                "value": hex::encode(&value),
+            },
             "GATT Descriptor read: addr = {}, status = {}, handle = {}, value = {:?}",
             addr,
             status,
@@ -778,7 +1306,9 @@ impl IBluetoothGattCallback for BtGattCallback {
     }
 
     fn on_descriptor_write(&mut self, addr: String, status: GattStatus, handle: i32) {
-        print_info!(
+        print_event!(
+            "gatt_client_descriptor_write",
+            { "address": addr, "status": status.to_u32(), "handle": handle },
             "GATT Descriptor write: addr = {}, status = {}, handle = {}",
             addr,
             status,
@@ -787,15 +1317,36 @@ impl IBluetoothGattCallback for BtGattCallback {
     }
 
     fn on_notify(&mut self, addr: String, handle: i32, value: Vec<u8>) {
-        print_info!("GATT Notification: addr = {}, handle = {}, value = {:?}", addr, handle, value);
+        print_event!(
+            "gatt_client_notify",
+            { "address": addr, "handle": handle, "value": hex::encode(&value) },
+            "GATT Notification: addr = {}, handle = {}, value = {:?}",
+            addr,
+            handle,
+            value
+        );
     }
 
     fn on_read_remote_rssi(&mut self, addr: String, rssi: i32, status: GattStatus) {
-        print_info!("Remote RSSI read: addr = {}, rssi = {}, status = {}", addr, rssi, status);
+        print_event!(
+            "gatt_client_read_remote_rssi",
+            { "address": addr, "rssi": rssi, "status": status.to_u32() },
+            "Remote RSSI read: addr = {}, rssi = {}, status = {}",
+            addr,
+            rssi,
+            status
+        );
     }
 
     fn on_configure_mtu(&mut self, addr: String, mtu: i32, status: GattStatus) {
-        print_info!("MTU configured: addr = {}, mtu = {}, status = {}", addr, mtu, status);
+        print_event!(
+            "gatt_client_configure_mtu",
+            { "address": addr, "mtu": mtu, "status": status.to_u32() },
+            "MTU configured: addr = {}, mtu = {}, status = {}",
+            addr,
+            mtu,
+            status
+        );
     }
 
     fn on_connection_updated(
@@ -806,7 +1357,15 @@ impl IBluetoothGattCallback for BtGattCallback {
         timeout: i32,
         status: GattStatus,
     ) {
-        print_info!(
+        print_event!(
+            "gatt_client_connection_updated",
+            {
+                "address": addr,
+                "interval": interval,
+                "latency": latency,
+                "timeout": timeout,
+                "status": status.to_u32(),
+            },
             "Connection updated: addr = {}, interval = {}, latency = {}, timeout = {}, status = {}",
             addr,
             interval,
@@ -817,7 +1376,12 @@ impl IBluetoothGattCallback for BtGattCallback {
     }
 
     fn on_service_changed(&mut self, addr: String) {
-        print_info!("Service changed for {}", addr,);
+        print_event!(
+            "gatt_client_service_changed",
+            { "address": addr },
+            "Service changed for {}",
+            addr
+        );
     }
 }
 
@@ -840,7 +1404,7 @@ impl RPCProxy for BtGattCallback {
 
 pub(crate) struct BtGattServerCallback {
     objpath: String,
-    _context: Arc<Mutex<ClientContext>>,
+    context: Arc<Mutex<ClientContext>>,
 
     dbus_connection: Arc<SyncConnection>,
     dbus_crossroads: Arc<Mutex<Crossroads>>,
@@ -849,34 +1413,102 @@ pub(crate) struct BtGattServerCallback {
 impl BtGattServerCallback {
     pub(crate) fn new(
         objpath: String,
-        _context: Arc<Mutex<ClientContext>>,
+        context: Arc<Mutex<ClientContext>>,
         dbus_connection: Arc<SyncConnection>,
         dbus_crossroads: Arc<Mutex<Crossroads>>,
     ) -> Self {
-        Self { objpath, _context, dbus_connection, dbus_crossroads }
+        Self { objpath, context, dbus_connection, dbus_crossroads }
+    }
+
+    /// Sends the response of a request. The D-Bus call is made from the foreground so it doesn't
+    /// block this callback.
+    fn respond(
+        &self,
+        addr: String,
+        trans_id: i32,
+        status: GattStatus,
+        offset: i32,
+        value: Vec<u8>,
+    ) {
+        self.context.lock().unwrap().run_callback(Box::new(move |context| {
+            let server_id = match context.lock().unwrap().gatt_server_context.server_id {
+                Some(server_id) => server_id,
+                None => return,
+            };
+            context.lock().unwrap().gatt_dbus.as_ref().unwrap().send_response(
+                server_id,
+                addr.clone(),
+                trans_id,
+                status,
+                offset,
+                value.clone(),
+            );
+        }));
+    }
+
+    /// Responds to a read request with the stored value.
+    fn respond_with_value(&self, addr: String, trans_id: i32, handle: i32, offset: i32) {
+        let read = self.context.lock().unwrap().gatt_server_context.read(handle, offset);
+        match read {
+            Ok(value) => self.respond(addr, trans_id, GattStatus::Success, offset, value),
+            Err(status) => self.respond(addr, trans_id, status, offset, vec![]),
+        }
     }
 }
 
 impl IBluetoothGattServerCallback for BtGattServerCallback {
     fn on_server_registered(&mut self, status: GattStatus, server_id: i32) {
-        print_info!("GATT Server registered status = {}, server_id = {}", status, server_id);
+        print_event!(
+            "gatt_server_registered",
+            { "status": status.to_u32(), "server_id": server_id },
+            "GATT Server registered status = {}, server_id = {}",
+            status,
+            server_id
+        );
+
+        if status == GattStatus::Success {
+            self.context.lock().unwrap().gatt_server_context.server_id = Some(server_id);
+        }
     }
 
     fn on_server_connection_state(&mut self, server_id: i32, connected: bool, addr: String) {
-        print_info!(
+        print_event!(
+            "gatt_server_connection_state",
+            { "server_id": server_id, "connected": connected, "address": addr },
             "GATT server connection with server_id = {}, connected = {}, addr = {}",
             server_id,
             connected,
             addr
         );
+
+        let mut context = self.context.lock().unwrap();
+        if connected {
+            context.gatt_server_context.connected_clients.insert(addr);
+        } else {
+            context.gatt_server_context.connected_clients.remove(&addr);
+        }
     }
 
     fn on_service_added(&mut self, status: GattStatus, service: BluetoothGattService) {
-        print_info!("GATT service added with status = {}, service = {:?}", status, service)
+        print_event!(
+            "gatt_server_service_added",
+            { "status": status.to_u32(), "service": gatt_service_json(&service) },
+            "GATT service added with status = {}, service = {:?}",
+            status,
+            service
+        );
+        self.context.lock().unwrap().gatt_server_context.on_service_added(status, &service);
     }
 
     fn on_service_removed(&mut self, status: GattStatus, handle: i32) {
-        print_info!("GATT service removed with status = {}, handle = {:?}", status, handle);
+        self.context.lock().unwrap().gatt_server_context.on_service_removed(status, handle);
+        print_event!(
+            "gatt_server_service_removed",
+            { "status": status.to_u32(), "handle": handle },
+            "GATT service removed with status = {}, handle = {:?}",
+            status,
+            handle
+        );
     }
 
     fn on_characteristic_read_request(
@@ -887,7 +1519,15 @@ impl IBluetoothGattServerCallback for BtGattServerCallback {
         is_long: bool,
         handle: i32,
     ) {
-        print_info!(
+        print_event!(
+            "gatt_server_characteristic_read_request",
+            {
+                "address": addr,
+                "trans_id": trans_id,
+                "offset": offset,
+                "is_long": is_long,
+                "handle": handle,
+            },
             "GATT characteristic read request for addr = {}, trans_id = {}, offset = {}, is_long = {}, handle = {}",
             addr,
             trans_id,
@@ -895,6 +1535,8 @@ impl IBluetoothGattServerCallback for BtGattServerCallback {
             is_long,
             handle
         );
+
+        self.respond_with_value(addr, trans_id, handle, offset);
     }
 
     fn on_descriptor_read_request(
@@ -905,7 +1547,15 @@ impl IBluetoothGattServerCallback for BtGattServerCallback {
         is_long: bool,
         handle: i32,
     ) {
-        print_info!(
+        print_event!(
+            "gatt_server_descriptor_read_request",
+            {
+                "address": addr,
+                "trans_id": trans_id,
+                "offset": offset,
+                "is_long": is_long,
+                "handle": handle,
+            },
             "GATT descriptor read request for addr = {}, trans_id = {}, offset = {}, is_long = {}, handle = {}",
             addr,
             trans_id,
@@ -913,6 +1563,8 @@ impl IBluetoothGattServerCallback for BtGattServerCallback {
             is_long,
             handle
         );
+
+        self.respond_with_value(addr, trans_id, handle, offset);
     }
 
     fn on_characteristic_write_request(
@@ -926,7 +1578,18 @@ impl IBluetoothGattServerCallback for BtGattServerCallback {
         handle: i32,
         value: Vec<u8>,
     ) {
-        print_info!(
+        print_event!(
+            "gatt_server_characteristic_write_request",
+            {
+                "address": addr,
+                "trans_id": trans_id,
+                "offset": offset,
+                "len": len,
+                "is_prep": is_prep,
+                "need_rsp": need_rsp,
+                "handle": handle,
+                "value": hex::encode(&value),
+            },
             "GATT characteristic write request for \
                 addr = {}, trans_id = {}, offset = {}, len = {}, is_prep = {}, need_rsp = {}, handle = {}, value = {:?}",
             addr,
@@ -938,6 +1601,21 @@ impl IBluetoothGattServerCallback for BtGattServerCallback {
             handle,
             value
         );
+
+        let status = if is_prep {
+            self.context.lock().unwrap().gatt_server_context.prepare_write(
+                addr.clone(),
+                handle,
+                offset,
+                value.clone(),
+            );
+            GattStatus::Success
+        } else {
+            self.context.lock().unwrap().gatt_server_context.write(handle, offset, &value)
+        };
+        if need_rsp {
+            self.respond(addr, trans_id, status, offset, value);
+        }
     }
 
     fn on_descriptor_write_request(
@@ -951,7 +1629,18 @@ impl IBluetoothGattServerCallback for BtGattServerCallback {
         handle: i32,
         value: Vec<u8>,
     ) {
-        print_info!(
+        print_event!(
+            "gatt_server_descriptor_write_request",
+            {
+                "address": addr,
+                "trans_id": trans_id,
+                "offset": offset,
+                "len": len,
+                "is_prep": is_prep,
+                "need_rsp": need_rsp,
+                "handle": handle,
+                "value": hex::encode(&value),
+            },
             "GATT descriptor write request for \
                 addr = {}, trans_id = {}, offset = {}, len = {}, is_prep = {}, need_rsp = {}, handle = {}, value = {:?}",
             addr,
@@ -963,19 +1652,42 @@ impl IBluetoothGattServerCallback for BtGattServerCallback {
             handle,
             value
         );
+
+        let status = if is_prep {
+            self.context.lock().unwrap().gatt_server_context.prepare_write(
+                addr.clone(),
+                handle,
+                offset,
+                value.clone(),
+            );
+            GattStatus::Success
+        } else {
+            self.context.lock().unwrap().gatt_server_context.write(handle, offset, &value)
+        };
+        if need_rsp {
+            self.respond(addr, trans_id, status, offset, value);
+        }
     }
 
     fn on_execute_write(&mut self, addr: String, trans_id: i32, exec_write: bool) {
-        print_info!(
+        print_event!(
+            "gatt_server_execute_write",
+            { "address": addr, "trans_id": trans_id, "exec_write": exec_write },
             "GATT executed write for addr = {}, trans_id = {}, exec_write = {}",
             addr,
             trans_id,
             exec_write
         );
+
+        let status =
+            self.context.lock().unwrap().gatt_server_context.execute_write(&addr, exec_write);
+        self.respond(addr, trans_id, status, 0, vec![]);
     }
 
     fn on_notification_sent(&mut self, addr: String, status: GattStatus) {
-        print_info!(
+        print_event!(
+            "gatt_server_notification_sent",
+            { "address": addr, "status": status.to_u32() },
             "GATT notification/indication sent for addr = {} with status = {}",
             addr,
             status
@@ -983,11 +1695,24 @@ impl IBluetoothGattServerCallback for BtGattServerCallback {
     }
 
     fn on_mtu_changed(&mut self, addr: String, mtu: i32) {
-        print_info!("GATT server MTU changed for addr = {}, mtu = {}", addr, mtu);
+        print_event!(
+            "gatt_server_mtu_changed",
+            { "address": addr, "mtu": mtu },
+            "GATT server MTU changed for addr = {}, mtu = {}",
+            addr,
+            mtu
+        );
     }
 
     fn on_phy_update(&mut self, addr: String, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus) {
-        print_info!(
+        print_event!(
+            "gatt_server_phy_update",
+            {
+                "address": addr,
+                "tx_phy": tx_phy.to_u32(),
+                "rx_phy": rx_phy.to_u32(),
+                "status": status.to_u32(),
+            },
             "GATT server phy updated for addr = {}: tx_phy = {:?}, rx_phy = {:?}, status = {}",
             addr,
             tx_phy,
@@ -997,7 +1722,14 @@ impl IBluetoothGattServerCallback for BtGattServerCallback {
     }
 
     fn on_phy_read(&mut self, addr: String, tx_phy: LePhy, rx_phy: LePhy, status: GattStatus) {
-        print_info!(
+        print_event!(
+            "gatt_server_phy_read",
+            {
+                "address": addr,
+                "tx_phy": tx_phy.to_u32(),
+                "rx_phy": rx_phy.to_u32(),
+                "status": status.to_u32(),
+            },
             "GATT server phy read for addr = {}: tx_phy = {:?}, rx_phy = {:?}, status = {}",
             addr,
             tx_phy,
@@ -1014,7 +1746,15 @@ impl IBluetoothGattServerCallback for BtGattServerCallback {
         timeout: i32,
         status: GattStatus,
     ) {
-        print_info!(
+        print_event!(
+            "gatt_server_connection_updated",
+            {
+                "address": addr,
+                "interval": interval,
+                "latency": latency,
+                "timeout": timeout,
+                "status": status.to_u32(),
+            },
             "GATT server connection updated for addr = {}, interval = {}, latency = {}, timeout = {}, status = {}",
             addr,
             interval,
@@ -1033,7 +1773,16 @@ impl IBluetoothGattServerCallback for BtGattServerCallback {
         timeout: i32,
         status: GattStatus,
     ) {
-        print_info!(
+        print_event!(
+            "gatt_server_subrate_change",
+            {
+                "address": addr,
+                "subrate_factor": subrate_factor,
+                "latency": latency,
+                "cont_num": cont_num,
+                "timeout": timeout,
+                "status": status.to_u32(),
+            },
             "GATT server subrate changed for addr = {}, subrate_factor = {}, latency = {}, cont_num = {}, timeout = {}, status = {}",
             addr,
             subrate_factor,
@@ -1097,7 +1846,7 @@ impl BtSocketManagerCallback {
         tokio::spawn(async move {
             for i in 0..num_frame {
                 fd.write_all(SOCKET_TEST_WRITE).ok();
-                print_info!("data sent: {}", i + 1);
+                print_event!("socket_data_sent", { "frame": i + 1 }, "data sent: {}", i + 1);
                 tokio::time::sleep(send_interval).await;
             }
 
@@ -1108,7 +1857,13 @@ impl BtSocketManagerCallback {
                 let sz = fd.read(&mut buf).unwrap();
                 let data = buf[..sz].to_vec();
                 if sz > 0 {
-                    print_info!("received {} bytes: {:?}", sz, data);
+                    print_event!(
+                        "socket_data_received",
+                        { "size": sz, "data": data },
+                        "received {} bytes: {:?}",
+                        sz,
+                        data
+                    );
                 }
                 tokio::time::sleep(Duration::from_millis(interval as u64)).await;
             }
@@ -1131,7 +1886,17 @@ impl IBluetoothSocketManagerCallbacks for BtSocketManagerCallback {
             return;
         }
 
-        print_info!(
+        print_event!(
+            "socket_incoming_socket_ready",
+            {
+                "id": socket.id,
+                "sock_type": socket.sock_type.to_u32(),
+                "flags": socket.flags,
+                "psm": socket.psm,
+                "channel": socket.channel,
+                "name": socket.name,
+                "uuid": socket.uuid.as_ref().map(|uuid| uuid.to_string()),
+            },
             "Socket {} ready, details: {:?}, flags = {}, psm = {:?}, channel = {:?}, name = {:?}, uuid = {:?}",
             socket.id,
             socket.sock_type,
@@ -1159,7 +1924,13 @@ impl IBluetoothSocketManagerCallbacks for BtSocketManagerCallback {
     }
 
     fn on_incoming_socket_closed(&mut self, listener_id: SocketId, reason: BtStatus) {
-        print_info!("Socket {} closed, reason = {:?}", listener_id, reason);
+        print_event!(
+            "socket_incoming_socket_closed",
+            { "listener_id": listener_id, "reason": reason.to_u32() },
+            "Socket {} closed, reason = {:?}",
+            listener_id,
+            reason
+        );
     }
 
     fn on_handle_incoming_connection(
@@ -1167,7 +1938,12 @@ impl IBluetoothSocketManagerCallbacks for BtSocketManagerCallback {
         listener_id: SocketId,
         connection: BluetoothSocket,
     ) {
-        print_info!("Socket {} connected", listener_id);
+        print_event!(
+            "socket_handle_incoming_connection",
+            { "listener_id": listener_id },
+            "Socket {} connected",
+            listener_id
+        );
         self.start_socket_schedule(connection);
     }
 
@@ -1178,10 +1954,31 @@ impl IBluetoothSocketManagerCallbacks for BtSocketManagerCallback {
         socket: Option<BluetoothSocket>,
     ) {
         if let Some(s) = socket {
-            print_info!("Connection success on {}: {:?} for {}", connecting_id, result, s);
+            print_event!(
+                "socket_outgoing_connection_result",
+                {
+                    "connecting_id": connecting_id,
+                    "status": result.to_u32(),
+                    "socket": socket_json(&s),
+                },
+                "Connection success on {}: {:?} for {}",
+                connecting_id,
+                result,
+                s
+            );
             self.start_socket_schedule(s);
         } else {
-            print_info!("Connection failed on {}: {:?}", connecting_id, result);
+            print_event!(
+                "socket_outgoing_connection_result",
+                {
+                    "connecting_id": connecting_id,
+                    "status": result.to_u32(),
+                    "socket": null,
+                },
+                "Connection failed on {}: {:?}",
+                connecting_id,
+                result
+            );
         }
     }
 }
@@ -1221,10 +2018,25 @@ impl SuspendCallback {
 }
 
 impl ISuspendCallback for SuspendCallback {
-    // TODO(b/224606285): Implement suspend utils in btclient.
     fn on_callback_registered(&mut self, _callback_id: u32) {}
-    fn on_suspend_ready(&mut self, _suspend_id: i32) {}
-    fn on_resumed(&mut self, _suspend_id: i32) {}
+
+    fn on_suspend_ready(&mut self, suspend_id: i32) {
+        print_event!(
+            "suspend_ready",
+            { "suspend_id": suspend_id },
+            "Ready for suspend, suspend_id = {}",
+            suspend_id
+        );
+    }
+
+    fn on_resumed(&mut self, suspend_id: i32) {
+        print_event!(
+            "resumed",
+            { "suspend_id": suspend_id },
+            "Resumed from suspend, suspend_id = {}",
+            suspend_id
+        );
+    }
 }
 
 impl RPCProxy for SuspendCallback {
@@ -1243,6 +2055,52 @@ impl RPCProxy for SuspendCallback {
     }
 }
 
+/// Callback container for battery manager interface callbacks.
+pub(crate) struct BatteryManagerCallback {
+    objpath: String,
+
+    dbus_connection: Arc<SyncConnection>,
+    dbus_crossroads: Arc<Mutex<Crossroads>>,
+}
+
+impl BatteryManagerCallback {
+    pub(crate) fn new(
+        objpath: String,
+        dbus_connection: Arc<SyncConnection>,
+        dbus_crossroads: Arc<Mutex<Crossroads>>,
+    ) -> Self {
+        Self { objpath, dbus_connection, dbus_crossroads }
+    }
+}
+
+impl IBatteryManagerCallback for BatteryManagerCallback {
+    fn on_battery_info_updated(&mut self, remote_address: String, battery_set: BatterySet) {
+        print_event!(
+            "battery_info_updated",
+            { "address": remote_address, "battery_set": battery_set_json(&battery_set) },
+            "Battery of {} updated: {}",
+            remote_address,
+            format_battery_set(&battery_set)
+        );
+    }
+}
+
+impl RPCProxy for BatteryManagerCallback {
+    fn get_object_id(&self) -> String {
+        self.objpath.clone()
+    }
+
+    fn export_for_rpc(self: Box<Self>) {
+        let cr = self.dbus_crossroads.clone();
+        let iface = export_battery_manager_callback_dbus_intf(
+            self.dbus_connection.clone(),
+            &mut cr.lock().unwrap(),
+            Arc::new(Mutex::new(DisconnectWatcher::new())),
+        );
+        cr.lock().unwrap().insert(self.get_object_id(), &[iface], Arc::new(Mutex::new(self)));
+    }
+}
+
 /// Callback container for suspend interface callbacks.
 pub(crate) struct QACallback {
     objpath: String,
@@ -1264,15 +2122,27 @@ impl QACallback {
 
 impl IBluetoothQACallback for QACallback {
     fn on_fetch_discoverable_mode_completed(&mut self, mode: bt_topshim::btif::BtDiscMode) {
-        print_info!("Discoverable mode: {:?}", mode);
+        print_event!(
+            "qa_fetch_discoverable_mode_completed",
+            { "mode": mode.to_u32() },
+            "Discoverable mode: {:?}",
+            mode
+        );
     }
 
     fn on_fetch_connectable_completed(&mut self, connectable: bool) {
-        print_info!("Connectable mode: {:?}", connectable);
+        print_event!(
+            "qa_fetch_connectable_completed",
+            { "connectable": connectable },
+            "Connectable mode: {:?}",
+            connectable
+        );
     }
 
     fn on_set_connectable_completed(&mut self, succeed: bool) {
-        print_info!(
+        print_event!(
+            "qa_set_connectable_completed",
+            { "succeed": succeed },
             "Set connectable mode: {}",
             match succeed {
                 true => "succeeded",
@@ -1282,19 +2152,39 @@ impl IBluetoothQACallback for QACallback {
     }
 
     fn on_fetch_alias_completed(&mut self, alias: String) {
-        print_info!("Alias: {}", alias);
+        print_event!(
+            "qa_fetch_alias_completed",
+            { "alias": alias },
+            "Alias: {}",
+            alias
+        );
     }
 
     fn on_get_hid_report_completed(&mut self, status: BtStatus) {
-        print_info!("Get HID report: {:?}", status);
+        print_event!(
+            "qa_get_hid_report_completed",
+            { "status": status.to_u32() },
+            "Get HID report: {:?}",
+            status
+        );
     }
 
     fn on_set_hid_report_completed(&mut self, status: BtStatus) {
-        print_info!("Set HID report: {:?}", status);
+        print_event!(
+            "qa_set_hid_report_completed",
+            { "status": status.to_u32() },
+            "Set HID report: {:?}",
+            status
+        );
     }
 
     fn on_send_hid_data_completed(&mut self, status: BtStatus) {
-        print_info!("Send HID data: {:?}", status);
+        print_event!(
+            "qa_send_hid_data_completed",
+            { "status": status.to_u32() },
+            "Send HID data: {:?}",
+            status
+        );
     }
 }
 
@@ -1340,12 +2230,77 @@ fn timestamp_to_string(ts_in_us: u64) -> String {
 }
 
 impl IBluetoothMediaCallback for MediaCallback {
-    fn on_bluetooth_audio_device_added(&mut self, _device: BluetoothAudioDevice) {}
-    fn on_bluetooth_audio_device_removed(&mut self, _addr: String) {}
-    fn on_absolute_volume_supported_changed(&mut self, _supported: bool) {}
-    fn on_absolute_volume_changed(&mut self, _volume: u8) {}
-    fn on_hfp_volume_changed(&mut self, _volume: u8, _addr: String) {}
-    fn on_hfp_audio_disconnected(&mut self, _addr: String) {}
+    fn on_bluetooth_audio_device_added(&mut self, device: BluetoothAudioDevice) {
+        print_event!(
+            "media_device_added",
+            {
+                "address": device.address,
+                "name": device.name,
+                "a2dp_caps": device
+                    .a2dp_caps
+                    .iter()
+                    .map(|cap| {
+                        serde_json::json!({
+                            "codec_type": cap.codec_type,
+                            "sample_rate": cap.sample_rate,
+                            "bits_per_sample": cap.bits_per_sample,
+                            "channel_mode": cap.channel_mode,
+                        })
+                    })
+                    .collect::<Vec<_>>(),
+                "hfp_cap": device.hfp_cap.bits(),
+                "absolute_volume": device.absolute_volume,
+            },
+            "Audio device added: [{}] {}, A2DP caps = {:?}, HFP caps = {:?}, absolute volume = {}",
+            device.address,
+            device.name,
+            device.a2dp_caps,
+            device.hfp_cap,
+            device.absolute_volume
+        );
+    }
+
+    fn on_bluetooth_audio_device_removed(&mut self, addr: String) {
+        print_event!("media_device_removed", { "address": addr }, "Audio device removed: {}", addr);
+    }
+
+    fn on_absolute_volume_supported_changed(&mut self, supported: bool) {
+        print_event!(
+            "media_absolute_volume_supported_changed",
+            { "supported": supported },
+            "Absolute volume supported: {}",
+            supported
+        );
+    }
+
+    fn on_absolute_volume_changed(&mut self, volume: u8) {
+        print_event!(
+            "media_absolute_volume_changed",
+            { "volume": volume },
+            "Absolute volume changed: {}",
+            volume
+        );
+    }
+
+    fn on_hfp_volume_changed(&mut self, volume: u8, addr: String) {
+        print_event!(
+            "media_hfp_volume_changed",
+            { "volume": volume, "address": addr },
+            "HFP volume changed: {} for {}",
+            volume,
+            addr
+        );
+    }
+
+    fn on_hfp_audio_disconnected(&mut self, addr: String) {
+        print_event!(
+            "media_hfp_audio_disconnected",
+            { "address": addr },
+            "HFP audio disconnected: {}",
+            addr
+        );
+    }
+
     fn on_hfp_debug_dump(
         &mut self,
         active: bool,
@@ -1362,6 +2317,13 @@ impl IBluetoothMediaCallback for MediaCallback {
         self.context.lock().unwrap().run_callback(Box::new(move |_context| {
             let is_wbs = codec_id == HfpCodecId::MSBC as u16;
             let is_swb = codec_id == HfpCodecId::LC3 as u16;
+            let codec = if is_wbs {
+                "mSBC"
+            } else if is_swb {
+                "LC3"
+            } else {
+                "CVSD"
+            };
             let dump = if active && (is_wbs || is_swb) {
                 let mut to_split_binary = pkt_status_in_binary.clone();
                 let mut wrapped_binary = String::new();
@@ -1393,19 +2355,25 @@ impl IBluetoothMediaCallback for MediaCallback {
                 "".to_string()
             };
 
-            print_info!(
+            print_event!(
+                "hfp_debug_dump",
+                {
+                    "active": active,
+                    "codec_id": codec_id,
+                    "codec": codec,
+                    "total_num_decoded_frames": total_num_decoded_frames,
+                    "pkt_loss_ratio": pkt_loss_ratio,
+                    "begin_ts": begin_ts,
+                    "end_ts": end_ts,
+                    "pkt_status_in_hex": pkt_status_in_hex,
+                    "pkt_status_in_binary": pkt_status_in_binary,
+                },
                 "\n--------HFP debug dump---------\n\
                      HFP SCO: {}, Codec: {}\
                      {}
                      ",
                 if active { "active" } else { "inactive" },
-                if is_wbs {
-                    "mSBC"
-                } else if is_swb {
-                    "LC3"
-                } else {
-                    "CVSD"
-                },
+                codec,
                 dump
             );
         }));
@@ -1449,7 +2417,13 @@ impl TelephonyCallback {
 
 impl IBluetoothTelephonyCallback for TelephonyCallback {
     fn on_telephony_use(&mut self, addr: String, state: bool) {
-        print_info!("Telephony use changed: [{}] state: {}", addr, state);
+        print_event!(
+            "telephony_use",
+            { "address": addr, "state": state },
+            "Telephony use changed: [{}] state: {}",
+            addr,
+            state
+        );
     }
 }
 
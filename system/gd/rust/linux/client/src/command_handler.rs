@@ -5,21 +5,38 @@ use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use crate::bt_adv::AdvSet;
-use crate::bt_gatt::AuthReq;
-use crate::callbacks::{BtGattCallback, BtGattServerCallback};
+use crate::bt_gatt::{self, AuthReq};
+use crate::callbacks::{self, BtGattCallback, BtGattServerCallback};
+use crate::console;
 use crate::ClientContext;
-use crate::{console_red, console_yellow, print_error, print_info};
-use bt_topshim::btif::{BtConnectionState, BtDiscMode, BtStatus, BtTransport, INVALID_RSSI};
+use crate::{console_red, console_yellow, print_error, print_info, print_output, print_result};
+use bt_topshim::btif::{
+    BtConnectionState, BtDiscMode, BtStatus, BtTransport, Uuid128Bit, INVALID_RSSI,
+};
+use bt_topshim::profiles::a2dp::{
+    A2dpCodecBitsPerSample, A2dpCodecChannelMode, A2dpCodecIndex, A2dpCodecSampleRate,
+};
+use bt_topshim::profiles::hfp::HfpCodecCapability;
 use bt_topshim::profiles::hid_host::BthhReportType;
 use bt_topshim::profiles::sdp::{BtSdpMpsRecord, BtSdpRecord};
 use bt_topshim::profiles::{gatt::LePhy, ProfileConnectionState};
+use bt_utils::socket::mgmt_settings_to_names;
+use btstack::battery_manager::IBatteryManager;
 use btstack::bluetooth::{BluetoothDevice, IBluetooth};
-use btstack::bluetooth_gatt::{GattWriteType, IBluetoothGatt};
+use btstack::bluetooth_admin::IBluetoothAdmin;
+use btstack::bluetooth_adv::PeriodicAdvertisingParameters;
+use btstack::bluetooth_gatt::{
+    BluetoothGattCharacteristic, BluetoothGattDescriptor, BluetoothGattService, GattWriteType,
+    IBluetoothGatt, ScanFilterPattern, ScanSettings, ScanType,
+};
+use btstack::bluetooth_logging::{IBluetoothLogging, Level, LogTargetKind};
 use btstack::bluetooth_media::{IBluetoothMedia, IBluetoothTelephony};
 use btstack::bluetooth_qa::IBluetoothQA;
 use btstack::socket_manager::{IBluetoothSocketManager, SocketResult};
+use btstack::suspend::{ISuspend, SuspendType};
 use btstack::uuid::{Profile, UuidHelper, UuidWrapper};
-use manager_service::iface_bluetooth_manager::IBluetoothManager;
+use manager_service::iface_bluetooth_manager::{BondBackupStatus, IBluetoothManager};
+use num_traits::ToPrimitive;
 
 const INDENT_CHAR: &str = " ";
 const BAR1_CHAR: &str = "=";
@@ -129,11 +146,14 @@ fn build_commands() -> HashMap<String, CommandOption> {
                 String::from("adapter discoverable <on|limited|off> <duration>"),
                 String::from("adapter connectable <on|off>"),
                 String::from("adapter set-name <name>"),
+                String::from("adapter list"),
+                String::from("adapter select <hci>"),
             ],
             description: String::from(
                 "Enable/Disable/Show default bluetooth adapter. (e.g. adapter enable)\n
                  Discoverable On/Limited/Off (e.g. adapter discoverable on 60)\n
-                 Connectable On/Off (e.g. adapter connectable on)",
+                 Connectable On/Off (e.g. adapter connectable on)\n
+                 List adapters and select the one commands are sent to (e.g. adapter select 1)",
             ),
             function_pointer: CommandHandler::cmd_adapter,
         },
@@ -172,8 +192,17 @@ fn build_commands() -> HashMap<String, CommandOption> {
     command_options.insert(
         String::from("floss"),
         CommandOption {
-            rules: vec![String::from("floss <enable|disable>")],
-            description: String::from("Enable or disable Floss for dogfood."),
+            rules: vec![
+                String::from("floss <enable|disable>"),
+                String::from("floss migrate-report <to-floss|to-bluez>"),
+                String::from("floss bonds <export|import> <adapter address> <file> <hex key>"),
+            ],
+            description: String::from(
+                "Enable or disable Floss for dogfood. migrate-report shows what switching stacks \
+                would do to each bonded device without changing anything. bonds exports or \
+                imports the bonded devices of an adapter to a backup encrypted with a 32 bytes \
+                key.",
+            ),
             function_pointer: CommandHandler::cmd_floss,
         },
     );
@@ -202,8 +231,25 @@ fn build_commands() -> HashMap<String, CommandOption> {
                 ),
                 String::from("gatt register-notification <address> <handle> <enable|disable>"),
                 String::from("gatt register-server"),
+                String::from("gatt server-create-service <uuid> [primary|secondary]"),
+                String::from(
+                    "gatt server-add-characteristic <uuid> <properties> <permissions> [value]",
+                ),
+                String::from("gatt server-add-descriptor <uuid> <permissions> [value]"),
+                String::from("gatt server-add-service"),
+                String::from("gatt server-remove-service <handle>"),
+                String::from("gatt server-clear-services"),
+                String::from("gatt server-set-value <handle> <value>"),
+                String::from("gatt server-notify <handle> <notify|indicate> <value>"),
             ],
-            description: String::from("GATT tools"),
+            description: String::from(
+                "GATT tools. Server properties are comma separated among broadcast, read, \
+                write-no-rsp, write, notify, indicate, signed-write and extended-props. \
+                Permissions are comma separated among read, read-encrypted, read-encrypted-mitm, \
+                write, write-encrypted, write-encrypted-mitm, write-signed and write-signed-mitm. \
+                Values are in hex. Reads from clients are answered with the stored values and \
+                writes update them.",
+            ),
             function_pointer: CommandHandler::cmd_gatt,
         },
     );
@@ -215,8 +261,24 @@ fn build_commands() -> HashMap<String, CommandOption> {
                 String::from("le-scan unregister-scanner <scanner-id>"),
                 String::from("le-scan start-scan <scanner-id>"),
                 String::from("le-scan stop-scan <scanner-id>"),
+                String::from("le-scan set-settings <interval> <window> <active|passive>"),
+                String::from("le-scan clear-settings"),
+                String::from("le-scan add-pattern <hex-ad-type> <start-position> <hex-content>"),
+                String::from(
+                    "le-scan set-rssi <high-threshold> <low-threshold> <low-timeout> \
+                    <sampling-period>",
+                ),
+                String::from("le-scan clear-filter"),
+                String::from("le-scan show-filter"),
+                String::from("le-scan table [on|off|clear]"),
             ],
-            description: String::from("LE scanning utilities."),
+            description: String::from(
+                "LE scanning utilities. Settings and filter are used by the following \
+                start-scan. Interval and window are in units of 0.625ms. RSSI thresholds are in \
+                dBm, the low timeout in seconds and the sampling period in units of 100ms. \
+                Without pattern, all advertisements are matched. table shows the scan results, \
+                on keeps it updated as results arrive.",
+            ),
             function_pointer: CommandHandler::cmd_le_scan,
         },
     );
@@ -229,8 +291,53 @@ fn build_commands() -> HashMap<String, CommandOption> {
                 String::from("advertise set-scan-rsp <enable|disable>"),
                 String::from("advertise set-raw-data <raw-adv-data> <adv-id>"),
                 String::from("advertise set-connectable <on|off> <adv-id>"),
+                String::from("advertise create <set-id> <legacy|ext>"),
+                String::from("advertise delete <set-id>"),
+                String::from("advertise show [set-id]"),
+                String::from(
+                    "advertise param <set-id> \
+                    <connectable|scannable|anonymous|include-tx-power> <on|off>",
+                ),
+                String::from(
+                    "advertise param <set-id> <primary-phy|secondary-phy> \
+                    <Phy1m|Phy2m|PhyCoded>",
+                ),
+                String::from("advertise param <set-id> tx-power <dBm>"),
+                String::from("advertise param <set-id> interval <ms>"),
+                String::from("advertise param <set-id> own-address <default|public|random>"),
+                String::from(
+                    "advertise data <set-id> <adv|scan-rsp|periodic> \
+                    <add-service-uuid|add-solicit-uuid> <uuid>",
+                ),
+                String::from(
+                    "advertise data <set-id> <adv|scan-rsp|periodic> add-service-data \
+                    <uuid> <hex-data>",
+                ),
+                String::from(
+                    "advertise data <set-id> <adv|scan-rsp|periodic> add-manufacturer-data \
+                    <hex-company-id> <hex-data>",
+                ),
+                String::from(
+                    "advertise data <set-id> <adv|scan-rsp|periodic> add-transport-discovery \
+                    <hex-data>",
+                ),
+                String::from(
+                    "advertise data <set-id> <adv|scan-rsp|periodic> \
+                    <include-tx-power|include-name> <on|off>",
+                ),
+                String::from("advertise data <set-id> <adv|scan-rsp|periodic> clear"),
+                String::from("advertise periodic <set-id> on <interval-ms> [include-tx-power]"),
+                String::from("advertise periodic <set-id> off"),
+                String::from("advertise start <set-id> [duration-ms] [max-ext-adv-events]"),
+                String::from("advertise stop <set-id>"),
+                String::from("advertise update <set-id>"),
             ],
-            description: String::from("Advertising utilities."),
+            description: String::from(
+                "Advertising utilities. Besides the quick on/off/ext sets, advertising sets can \
+                be composed under a set ID with create, param, data and periodic, then started. \
+                Several sets can run at once. Changes to a started set are applied with update. \
+                The advertising SID is picked by the controller.",
+            ),
             function_pointer: CommandHandler::cmd_advertise,
         },
     );
@@ -321,6 +428,7 @@ fn build_commands() -> HashMap<String, CommandOption> {
                     "telephony <release-held|release-active-accept-held|hold-active-accept-held>",
                 ),
                 String::from("telephony <audio-connect|audio-disconnect> <address>"),
+                String::from("telephony stop-voice-recognition <address>"),
             ],
             description: String::from("Set device telephony status."),
             function_pointer: CommandHandler::cmd_telephony,
@@ -329,11 +437,90 @@ fn build_commands() -> HashMap<String, CommandOption> {
     command_options.insert(
         String::from("media"),
         CommandOption {
-            rules: vec![String::from("media log")],
-            description: String::from("Audio tools."),
+            rules: vec![
+                String::from("media log"),
+                String::from("media <connect|disconnect> <address>"),
+                String::from("media <set-active|set-hfp-active> <address>"),
+                String::from("media reset-active"),
+                String::from(
+                    "media set-audio-config <address> <sbc|aac|aptx|aptx-hd|ldac> \
+                    <sample-rate> <16|24|32> <mono|stereo>",
+                ),
+                String::from("media set-volume <0-127>"),
+                String::from("media set-hfp-volume <0-15> <address>"),
+                String::from("media <start-audio|stop-audio>"),
+                String::from("media audio-status <address>"),
+                String::from("media position"),
+                String::from("media start-sco <address> <offload|no-offload> [<cvsd|msbc|lc3>...]"),
+                String::from("media stop-sco <address>"),
+            ],
+            description: String::from(
+                "Audio tools. set-active picks the A2DP device and set-hfp-active the HFP one. \
+                start-audio and stop-audio request the A2DP stream of the active device. \
+                start-sco connects HFP audio, without the listed codecs.",
+            ),
             function_pointer: CommandHandler::cmd_media,
         },
     );
+    command_options.insert(
+        String::from("admin"),
+        CommandOption {
+            rules: vec![
+                String::from("admin allowlist [set [<uuid>...]|add <uuid>|remove <uuid>]"),
+                String::from("admin is-allowed <uuid>"),
+                String::from("admin policy"),
+                String::from("admin <deny|undeny> <address|oui>"),
+                String::from("admin policy-effect <address>"),
+            ],
+            description: String::from(
+                "Admin policy tools. An empty allowlist allows all services.",
+            ),
+            function_pointer: CommandHandler::cmd_admin,
+        },
+    );
+    command_options.insert(
+        String::from("battery"),
+        CommandOption {
+            rules: vec![String::from("battery status <address>")],
+            description: String::from(
+                "Battery information of remote devices. Updates are printed as they arrive.",
+            ),
+            function_pointer: CommandHandler::cmd_battery,
+        },
+    );
+    command_options.insert(
+        String::from("log"),
+        CommandOption {
+            rules: vec![
+                String::from("log status"),
+                String::from("log debug <on|off>"),
+                String::from(
+                    "log set-level <module|tag> <target> <off|error|warn|info|debug|trace>",
+                ),
+                String::from("log get-level <module|tag> <target>"),
+                String::from("log clear-level <module|tag> <target>"),
+                String::from("log dump"),
+                String::from("log clear"),
+            ],
+            description: String::from(
+                "Logging of the Bluetooth daemon. Targets are module paths of the Linux stack, \
+                e.g. module bt_topshim::profiles::gatt, or log tags of libbluetooth, e.g. tag \
+                bt_btm.",
+            ),
+            function_pointer: CommandHandler::cmd_log,
+        },
+    );
+    command_options.insert(
+        String::from("suspend"),
+        CommandOption {
+            rules: vec![
+                String::from("suspend start <no-wakes|hid-wakes|other> [suspend_id]"),
+                String::from("suspend resume"),
+            ],
+            description: String::from("Prepares the stack for system suspend and resumes it."),
+            function_pointer: CommandHandler::cmd_suspend,
+        },
+    );
     command_options.insert(
         String::from("quit"),
         CommandOption {
@@ -360,6 +547,52 @@ where
     args.get(index).ok_or(CommandError::InvalidArgs)
 }
 
+// Helper to parse a hex string such as "0a1b" into bytes.
+fn hex_to_bytes(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect()
+}
+
+fn parse_on_off(value: &str) -> Result<bool, CommandError> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("Expected on or off, got {}", value).into()),
+    }
+}
+
+fn parse_log_target_kind(value: &str) -> Result<LogTargetKind, CommandError> {
+    match value {
+        "module" => Ok(LogTargetKind::Module),
+        "tag" => Ok(LogTargetKind::Tag),
+        _ => Err(format!("Expected module or tag, got {}", value).into()),
+    }
+}
+
+fn parse_le_phy(value: &str) -> Result<LePhy, CommandError> {
+    match value {
+        "Phy1m" => Ok(LePhy::Phy1m),
+        "Phy2m" => Ok(LePhy::Phy2m),
+        "PhyCoded" => Ok(LePhy::PhyCoded),
+        _ => Err(format!("Expected Phy1m, Phy2m or PhyCoded, got {}", value).into()),
+    }
+}
+
+/// Prints the result of a command in JSON mode.
+fn print_command_result(command: &str, args: &Vec<String>, result: &CommandResult) {
+    match result {
+        Ok(()) => print_result!(command, { "args": args, "status": "ok" }),
+        Err(CommandError::InvalidArgs) => {
+            print_result!(command, { "args": args, "status": "invalid_args" })
+        }
+        Err(CommandError::Failed(msg)) => {
+            print_result!(command, { "args": args, "status": "failed", "error": msg })
+        }
+    }
+}
+
 impl CommandHandler {
     /// Creates a new CommandHandler.
     pub fn new(context: Arc<Mutex<ClientContext>>) -> CommandHandler {
@@ -374,7 +607,12 @@ impl CommandHandler {
             _ => match self.command_options.get(command) {
                 Some(cmd) => {
                     let rules = cmd.rules.clone();
-                    match (cmd.function_pointer)(self, &args) {
+                    let result = (cmd.function_pointer)(self, &args);
+                    if console::is_json_output() {
+                        print_command_result(command, args, &result);
+                        return result.is_ok();
+                    }
+                    match result {
                         Ok(()) => true,
                         Err(CommandError::InvalidArgs) => {
                             print_error!("Invalid arguments. Usage:\n{}", rules.join("\n"));
@@ -387,6 +625,10 @@ impl CommandHandler {
                     }
                 }
                 None => {
+                    if console::is_json_output() {
+                        print_result!(command, { "args": args, "status": "invalid_command" });
+                        return false;
+                    }
                     println!("'{}' is an invalid command!", command);
                     self.cmd_help(&args).ok();
                     false
@@ -409,6 +651,27 @@ impl CommandHandler {
     }
 
     fn cmd_help(&mut self, args: &Vec<String>) -> CommandResult {
+        print_output!("help", data: self.help_json(args), { self.print_help_menu(args) });
+        Ok(())
+    }
+
+    /// Describes the commands in |args|, or all of them, for JSON mode.
+    fn help_json(&self, args: &Vec<String>) -> serde_json::Value {
+        let commands: serde_json::Map<String, serde_json::Value> = self
+            .command_options
+            .iter()
+            .filter(|(key, _)| args.is_empty() || args.contains(key))
+            .map(|(key, val)| {
+                (
+                    key.clone(),
+                    serde_json::json!({ "description": val.description, "rules": val.rules }),
+                )
+            })
+            .collect();
+        serde_json::json!({ "commands": commands })
+    }
+
+    fn print_help_menu(&self, args: &Vec<String>) {
         if let Some(command) = args.get(0) {
             match self.command_options.get(command) {
                 Some(cmd) => {
@@ -422,7 +685,7 @@ impl CommandHandler {
                 }
                 None => {
                     println!("'{}' is an invalid command!", command);
-                    self.cmd_help(&vec![]).ok();
+                    self.print_help_menu(&vec![]);
                 }
             }
         } else {
@@ -455,8 +718,6 @@ impl CommandHandler {
             // Footer
             println!("{}\n{}", empty_bar, equal_bar);
         }
-
-        Ok(())
     }
 
     fn cmd_adapter(&mut self, args: &Vec<String>) -> CommandResult {
@@ -475,6 +736,96 @@ impl CommandHandler {
                 }
                 self.lock_context().manager_dbus.start(default_adapter);
             }
+            "list" => {
+                let adapters = self.lock_context().manager_dbus.get_available_adapters();
+                print_output!(
+                    "adapter_list",
+                    data: serde_json::json!({
+                        "adapters": adapters
+                            .iter()
+                            .map(|adapter| {
+                                serde_json::json!({
+                                    "hci": adapter.hci_interface,
+                                    "default": adapter.hci_interface == default_adapter,
+                                    "enabled": adapter.enabled,
+                                    "launch_profile": {
+                                        "init_flags": adapter.launch_profile.init_flags,
+                                        "debug_level": adapter.launch_profile.debug_level.to_u32(),
+                                        "ll_privacy": adapter.launch_profile.ll_privacy.to_option(),
+                                        "devcoredump":
+                                            adapter.launch_profile.devcoredump.to_option(),
+                                    },
+                                    "controller": adapter.controller.as_ref().map(|c| {
+                                        serde_json::json!({
+                                            "address": c.address,
+                                            "manufacturer": c.manufacturer,
+                                            "bus": c.bus.map(|bus| format!("{:?}", bus)),
+                                            "supported_settings":
+                                                mgmt_settings_to_names(c.supported_settings),
+                                            "current_settings":
+                                                mgmt_settings_to_names(c.current_settings),
+                                            "last_error": c.last_error,
+                                        })
+                                    }),
+                                })
+                            })
+                            .collect::<Vec<serde_json::Value>>(),
+                    }),
+                    {
+                        for adapter in adapters {
+                            print_info!(
+                                "{} hci{}: {}, launch profile {:?}",
+                                if adapter.hci_interface == default_adapter { "*" } else { " " },
+                                adapter.hci_interface,
+                                if adapter.enabled { "enabled" } else { "disabled" },
+                                adapter.launch_profile
+                            );
+                            if let Some(c) = adapter.controller {
+                                print_info!(
+                                    "    {}, manufacturer {:#06x}, bus {}",
+                                    c.address,
+                                    c.manufacturer,
+                                    c.bus.map_or("unknown".to_string(), |bus| format!("{:?}", bus))
+                                );
+                                print_info!(
+                                    "    settings: {}",
+                                    mgmt_settings_to_names(c.current_settings).join(" ")
+                                );
+                                print_info!(
+                                    "    supported: {}",
+                                    mgmt_settings_to_names(c.supported_settings).join(" ")
+                                );
+                                if let Some(error) = c.last_error {
+                                    print_info!("    last controller error: {:#04x}", error);
+                                }
+                            }
+                        }
+                    }
+                );
+            }
+            "select" => {
+                let hci_interface = String::from(get_arg(args, 1)?)
+                    .trim_start_matches("hci")
+                    .parse::<i32>()
+                    .or(Err("Failed parsing hci interface"))?;
+                if hci_interface == default_adapter {
+                    return Ok(());
+                }
+
+                let mut context = self.lock_context();
+                let adapter = context
+                    .manager_dbus
+                    .get_available_adapters()
+                    .into_iter()
+                    .find(|adapter| adapter.hci_interface == hci_interface)
+                    .ok_or(format!("hci{} is not available", hci_interface))?;
+                context.select_adapter(hci_interface, adapter.enabled);
+                print_info!(
+                    "Selected hci{}{}",
+                    hci_interface,
+                    if adapter.enabled { "" } else { ", enable it with `adapter enable`" }
+                );
+            }
             "disable" => {
                 if self.lock_context().is_restricted {
                     return Err("You are not allowed to toggle adapter power".into());
@@ -518,25 +869,55 @@ impl CommandHandler {
                 qa_dbus.fetch_connectable();
                 qa_dbus.fetch_alias();
                 qa_dbus.fetch_discoverable_mode();
-                print_info!("Address: {}", address);
-                print_info!("Name: {}", name);
-                print_info!("Modalias: {}", modalias);
-                print_info!("State: {}", if enabled { "enabled" } else { "disabled" });
-                print_info!("Discoverable: {}", is_discoverable);
-                print_info!("DiscoverableTimeout: {}s", discoverable_timeout);
-                print_info!("Class: {:#06x}", cod);
-                print_info!("IsMultiAdvertisementSupported: {}", multi_adv_supported);
-                print_info!("IsLeExtendedAdvertisingSupported: {}", le_ext_adv_supported);
-                print_info!("Connected profiles: {:?}", connected_profiles);
-                print_info!("IsWbsSupported: {}", wbs_supported);
-                print_info!(
-                    "Uuids: {}",
-                    DisplayList(
-                        uuids
+                print_output!(
+                    "adapter_show",
+                    data: serde_json::json!({
+                        "address": address,
+                        "name": name,
+                        "modalias": modalias,
+                        "enabled": enabled,
+                        "discoverable": is_discoverable,
+                        "discoverable_timeout": discoverable_timeout,
+                        "class": cod,
+                        "multi_adv_supported": multi_adv_supported,
+                        "le_ext_adv_supported": le_ext_adv_supported,
+                        "connected_profiles": connected_profiles
                             .iter()
-                            .map(|&x| UuidHelper::known_uuid_to_string(&x))
-                            .collect::<Vec<String>>()
-                    )
+                            .map(|(prof, state)| {
+                                serde_json::json!({
+                                    "profile": prof.to_string().to_lowercase(),
+                                    "state": state.to_u32(),
+                                })
+                            })
+                            .collect::<Vec<serde_json::Value>>(),
+                        "wbs_supported": wbs_supported,
+                        "uuids": uuids
+                            .iter()
+                            .map(|x| UuidWrapper(x).to_string())
+                            .collect::<Vec<String>>(),
+                    }),
+                    {
+                        print_info!("Address: {}", address);
+                        print_info!("Name: {}", name);
+                        print_info!("Modalias: {}", modalias);
+                        print_info!("State: {}", if enabled { "enabled" } else { "disabled" });
+                        print_info!("Discoverable: {}", is_discoverable);
+                        print_info!("DiscoverableTimeout: {}s", discoverable_timeout);
+                        print_info!("Class: {:#06x}", cod);
+                        print_info!("IsMultiAdvertisementSupported: {}", multi_adv_supported);
+                        print_info!("IsLeExtendedAdvertisingSupported: {}", le_ext_adv_supported);
+                        print_info!("Connected profiles: {:?}", connected_profiles);
+                        print_info!("IsWbsSupported: {}", wbs_supported);
+                        print_info!(
+                            "Uuids: {}",
+                            DisplayList(
+                                uuids
+                                    .iter()
+                                    .map(|&x| UuidHelper::known_uuid_to_string(&x))
+                                    .collect::<Vec<String>>()
+                            )
+                        );
+                    }
                 );
             }
             "discoverable" => match &get_arg(args, 1)?[..] {
@@ -551,7 +932,13 @@ impl CommandHandler {
                         .as_mut()
                         .unwrap()
                         .set_discoverable(BtDiscMode::GeneralDiscoverable, duration);
-                    print_info!(
+                    print_output!(
+                        "set_discoverable",
+                        {
+                            "mode": BtDiscMode::GeneralDiscoverable.to_u32(),
+                            "duration": duration,
+                            "success": discoverable,
+                        },
                         "Set discoverable for {} seconds: {}",
                         duration,
                         if discoverable { "succeeded" } else { "failed" }
@@ -568,7 +955,13 @@ impl CommandHandler {
                         .as_mut()
                         .unwrap()
                         .set_discoverable(BtDiscMode::LimitedDiscoverable, duration);
-                    print_info!(
+                    print_output!(
+                        "set_discoverable",
+                        {
+                            "mode": BtDiscMode::LimitedDiscoverable.to_u32(),
+                            "duration": duration,
+                            "success": discoverable,
+                        },
                         "Set limited discoverable for {} seconds: {}",
                         duration,
                         if discoverable { "succeeded" } else { "failed" }
@@ -581,12 +974,18 @@ impl CommandHandler {
                         .as_mut()
                         .unwrap()
                         .set_discoverable(BtDiscMode::NonDiscoverable, 0 /*not used*/);
-                    print_info!(
+                    print_output!(
+                        "set_discoverable",
+                        {
+                            "mode": BtDiscMode::NonDiscoverable.to_u32(),
+                            "duration": 0,
+                            "success": discoverable,
+                        },
                         "Turn discoverable off: {}",
                         if discoverable { "succeeded" } else { "failed" }
                     );
                 }
-                other => println!("Invalid argument for adapter discoverable '{}'", other),
+                other => print_error!("Invalid argument for adapter discoverable '{}'", other),
             },
             "connectable" => match &get_arg(args, 1)?[..] {
                 "on" => {
@@ -595,13 +994,13 @@ impl CommandHandler {
                 "off" => {
                     self.lock_context().qa_dbus.as_mut().unwrap().set_connectable(false);
                 }
-                other => println!("Invalid argument for adapter connectable '{}'", other),
+                other => print_error!("Invalid argument for adapter connectable '{}'", other),
             },
             "set-name" => {
                 if let Some(name) = args.get(1) {
                     self.lock_context().adapter_dbus.as_ref().unwrap().set_name(name.to_string());
                 } else {
-                    println!("usage: adapter set-name <name>");
+                    print_error!("usage: adapter set-name <name>");
                 }
             }
 
@@ -617,7 +1016,7 @@ impl CommandHandler {
         }
 
         let address = self.lock_context().update_adapter_address();
-        print_info!("Local address = {}", &address);
+        print_output!("local_address", { "address": address }, "Local address = {}", &address);
         Ok(())
     }
 
@@ -693,7 +1092,7 @@ impl CommandHandler {
                 self.lock_context().adapter_dbus.as_mut().unwrap().cancel_bond_process(device);
             }
             other => {
-                println!("Invalid argument '{}'", other);
+                print_error!("Invalid argument '{}'", other);
             }
         }
 
@@ -722,9 +1121,9 @@ impl CommandHandler {
                     .connect_all_enabled_profiles(device.clone());
 
                 if success {
-                    println!("Connecting to {}", &device.address);
+                    print_info!("Connecting to {}", &device.address);
                 } else {
-                    println!("Can't connect to {}", &device.address);
+                    print_error!("Can't connect to {}", &device.address);
                 }
             }
             "disconnect" => {
@@ -741,9 +1140,9 @@ impl CommandHandler {
                     .disconnect_all_enabled_profiles(device.clone());
 
                 if success {
-                    println!("Disconnecting from {}", &device.address);
+                    print_info!("Disconnecting from {}", &device.address);
                 } else {
-                    println!("Can't disconnect from {}", &device.address);
+                    print_error!("Can't disconnect from {}", &device.address);
                 }
             }
             "info" => {
@@ -774,11 +1173,7 @@ impl CommandHandler {
                     let class = adapter.get_remote_class(device.clone());
                     let appearance = adapter.get_remote_appearance(device.clone());
                     let bonded = adapter.get_bond_state(device.clone());
-                    let connection_state = match adapter.get_connection_state(device.clone()) {
-                        BtConnectionState::NotConnected => "Not Connected",
-                        BtConnectionState::ConnectedOnly => "Connected",
-                        _ => "Connected and Paired",
-                    };
+                    let connection_state = adapter.get_connection_state(device.clone());
                     let uuids = adapter.get_remote_uuids(device.clone());
                     let wake_allowed = adapter.get_remote_wake_allowed(device.clone());
 
@@ -796,24 +1191,52 @@ impl CommandHandler {
                     )
                 };
 
-                print_info!("Address: {}", &device.address);
-                print_info!("Name: {}", name);
-                print_info!("Alias: {}", alias);
-                print_info!("Device Type: {:?}", device_type);
-                print_info!("Address Type: {:?}", addr_type);
-                print_info!("Class: {}", class);
-                print_info!("Appearance: {}", appearance);
-                print_info!("Wake Allowed: {}", wake_allowed);
-                print_info!("Bond State: {:?}", bonded);
-                print_info!("Connection State: {}", connection_state);
-                print_info!(
-                    "Uuids: {}",
-                    DisplayList(
-                        uuids
+                print_output!(
+                    "device_info",
+                    data: serde_json::json!({
+                        "address": device.address,
+                        "name": name,
+                        "alias": alias,
+                        "device_type": device_type.to_u32(),
+                        "address_type": addr_type.to_u32(),
+                        "class": class,
+                        "appearance": appearance,
+                        "wake_allowed": wake_allowed,
+                        "bond_state": bonded.to_u32(),
+                        "connection_state": connection_state.to_u32(),
+                        "uuids": uuids
                             .iter()
-                            .map(|&x| UuidHelper::known_uuid_to_string(&x))
-                            .collect::<Vec<String>>()
-                    )
+                            .map(|x| UuidWrapper(x).to_string())
+                            .collect::<Vec<String>>(),
+                    }),
+                    {
+                        print_info!("Address: {}", &device.address);
+                        print_info!("Name: {}", name);
+                        print_info!("Alias: {}", alias);
+                        print_info!("Device Type: {:?}", device_type);
+                        print_info!("Address Type: {:?}", addr_type);
+                        print_info!("Class: {}", class);
+                        print_info!("Appearance: {}", appearance);
+                        print_info!("Wake Allowed: {}", wake_allowed);
+                        print_info!("Bond State: {:?}", bonded);
+                        print_info!(
+                            "Connection State: {}",
+                            match connection_state {
+                                BtConnectionState::NotConnected => "Not Connected",
+                                BtConnectionState::ConnectedOnly => "Connected",
+                                _ => "Connected and Paired",
+                            }
+                        );
+                        print_info!(
+                            "Uuids: {}",
+                            DisplayList(
+                                uuids
+                                    .iter()
+                                    .map(|&x| UuidHelper::known_uuid_to_string(&x))
+                                    .collect::<Vec<String>>()
+                            )
+                        );
+                    }
                 );
             }
             "set-alias" => {
@@ -828,9 +1251,11 @@ impl CommandHandler {
                     .as_ref()
                     .unwrap()
                     .get_remote_alias(device.clone());
-                println!(
+                print_output!(
+                    "alias_update",
+                    { "address": device.address, "old_alias": old_alias, "new_alias": new_alias },
                     "Updating alias for {}: {} -> {}",
-                    get_arg(args, 1)?,
+                    device.address,
                     old_alias,
                     new_alias
                 );
@@ -911,15 +1336,20 @@ impl CommandHandler {
                     .get_remote_rssi(device.clone())
                 {
                     INVALID_RSSI => {
-                        println!("Invalid RSSI");
+                        print_error!("Invalid RSSI");
                     }
                     rssi => {
-                        println!("RSSI: {}", rssi);
+                        print_output!(
+                            "device_rssi",
+                            { "address": device.address, "rssi": rssi },
+                            "RSSI: {}",
+                            rssi
+                        );
                     }
                 };
             }
             other => {
-                println!("Invalid argument '{}'", other);
+                print_error!("Invalid argument '{}'", other);
             }
         }
 
@@ -936,12 +1366,126 @@ impl CommandHandler {
             "disable" => {
                 self.lock_context().manager_dbus.set_floss_enabled(false);
             }
+            "migrate-report" => {
+                let floss_enabled = match &get_arg(args, 1)?[..] {
+                    "to-floss" => true,
+                    "to-bluez" => false,
+                    _ => return Err(CommandError::InvalidArgs),
+                };
+                let report = self.lock_context().manager_dbus.get_migration_report(floss_enabled);
+                print_output!(
+                    "migration_report",
+                    data: serde_json::json!({
+                        "devices": report
+                            .iter()
+                            .map(|device| {
+                                serde_json::json!({
+                                    "address": device.address,
+                                    "adapter": device.adapter,
+                                    "action": device.action.to_u32(),
+                                    "converted_keys": device.converted_keys,
+                                    "errors": device.errors,
+                                })
+                            })
+                            .collect::<Vec<serde_json::Value>>(),
+                    }),
+                    {
+                        if report.is_empty() {
+                            print_info!("No devices to migrate");
+                        }
+                        for device in report {
+                            print_info!(
+                                "{} on adapter {}: {:?}",
+                                device.address,
+                                device.adapter,
+                                device.action
+                            );
+                            if !device.converted_keys.is_empty() {
+                                print_info!("  Converted: {}", device.converted_keys.join(", "));
+                            }
+                            for err in device.errors {
+                                print_info!("  Error: {}", err);
+                            }
+                        }
+                    }
+                );
+            }
+            "bonds" => {
+                let action = get_arg(args, 1)?;
+                let adapter = String::from(get_arg(args, 2)?);
+                let file = get_arg(args, 3)?;
+                let key = hex_to_bytes(get_arg(args, 4)?)
+                    .ok_or(CommandError::Failed("Key must be in hex".into()))?;
+
+                match &action[..] {
+                    "export" => {
+                        let export = self
+                            .lock_context()
+                            .manager_dbus
+                            .export_bonded_devices(adapter.clone(), key);
+                        if export.status != BondBackupStatus::Success {
+                            return Err(format!(
+                                "Failed to export bonded devices: {:?}",
+                                export.status
+                            )
+                            .into());
+                        }
+                        std::fs::write(file, export.backup).map_err(|e| e.to_string())?;
+                        print_output!(
+                            "bonds_export",
+                            { "adapter": adapter, "file": file },
+                            "Exported bonded devices to {}",
+                            file
+                        );
+                    }
+                    "import" => {
+                        let backup = std::fs::read(file).map_err(|e| e.to_string())?;
+                        match self.lock_context().manager_dbus.import_bonded_devices(
+                            adapter.clone(),
+                            backup,
+                            key,
+                        ) {
+                            BondBackupStatus::Success => {
+                                print_output!(
+                                    "bonds_import",
+                                    { "adapter": adapter, "file": file, "partial": false },
+                                    "Imported bonded devices from {}",
+                                    file
+                                );
+                            }
+                            BondBackupStatus::PartiallyImported => {
+                                print_output!(
+                                    "bonds_import",
+                                    { "adapter": adapter, "file": file, "partial": true },
+                                    "Imported bonded devices from {}, some only partially",
+                                    file
+                                );
+                            }
+                            status => {
+                                return Err(format!(
+                                    "Failed to import bonded devices: {:?}",
+                                    status
+                                )
+                                .into());
+                            }
+                        }
+                    }
+                    _ => return Err(CommandError::InvalidArgs),
+                }
+            }
             "show" => {
                 let (major, minor) = self.lock_context().get_floss_api_version();
-                print_info!("Floss API version: {}.{}", major, minor);
-                print_info!(
-                    "Floss enabled: {}",
-                    self.lock_context().manager_dbus.get_floss_enabled()
+                let floss_enabled = self.lock_context().manager_dbus.get_floss_enabled();
+                print_output!(
+                    "floss_show",
+                    data: serde_json::json!({
+                        "api_version": format!("{}.{}", major, minor),
+                        "enabled": floss_enabled,
+                    }),
+                    {
+                        print_info!("Floss API version: {}.{}", major, minor);
+                        print_info!("Floss enabled: {}", floss_enabled);
+                    }
                 );
             }
             _ => return Err(CommandError::InvalidArgs),
@@ -986,7 +1530,7 @@ impl CommandHandler {
                 let oppurtunistic = self.lock_context().gatt_client_context.connect_opportunistic;
                 let phy = self.lock_context().gatt_client_context.connect_phy;
 
-                println!("Initiating GATT client connect. client_id: {}, addr: {}, is_direct: {}, transport: {:?}, oppurtunistic: {}, phy: {:?}", client_id, addr, is_direct, transport, oppurtunistic, phy);
+                print_info!("Initiating GATT client connect. client_id: {}, addr: {}, is_direct: {}, transport: {:?}, oppurtunistic: {}, phy: {:?}", client_id, addr, is_direct, transport, oppurtunistic, phy);
                 self.lock_context().gatt_dbus.as_ref().unwrap().client_connect(
                     client_id,
                     addr,
@@ -1102,7 +1646,13 @@ impl CommandHandler {
                 };
 
                 self.lock_context().gatt_client_context.auth_req = flag;
-                println!("AuthReq: {:?}", self.lock_context().gatt_client_context.get_auth_req());
+                let auth_req = self.lock_context().gatt_client_context.get_auth_req();
+                print_output!(
+                    "auth_req",
+                    { "auth_req": auth_req as i32 },
+                    "AuthReq: {:?}",
+                    auth_req
+                );
             }
             "write-characteristic" => {
                 let addr = String::from(get_arg(args, 1)?);
@@ -1223,6 +1773,136 @@ impl CommandHandler {
                     false,
                 );
             }
+            "server-create-service" => {
+                let uuid = UuidHelper::parse_string(get_arg(args, 1)?).ok_or("Invalid UUID")?;
+                let service_type = match args.get(2).map(String::as_str) {
+                    None | Some("primary") => 0,
+                    Some("secondary") => 1,
+                    Some(_) => return Err("Failed to parse service type".into()),
+                };
+                self.lock_context().gatt_server_context.create_service(BluetoothGattService {
+                    uuid: uuid.uu,
+                    instance_id: 0,
+                    service_type,
+                    characteristics: vec![],
+                    included_services: vec![],
+                });
+            }
+            "server-add-characteristic" => {
+                let uuid = UuidHelper::parse_string(get_arg(args, 1)?).ok_or("Invalid UUID")?;
+                let properties = bt_gatt::parse_properties(get_arg(args, 2)?)
+                    .ok_or("Failed to parse properties")?;
+                let permissions = bt_gatt::parse_permissions(get_arg(args, 3)?)
+                    .ok_or("Failed to parse permissions")?;
+                let value = match args.get(4) {
+                    Some(value) => hex::decode(value).or(Err("Failed to parse value"))?,
+                    None => vec![],
+                };
+                let characteristic = BluetoothGattCharacteristic {
+                    uuid: uuid.uu,
+                    instance_id: 0,
+                    properties,
+                    permissions,
+                    key_size: 16,
+                    write_type: if properties
+                        & BluetoothGattCharacteristic::PROPERTY_WRITE_NO_RESPONSE
+                        != 0
+                    {
+                        GattWriteType::WriteNoRsp
+                    } else {
+                        GattWriteType::Write
+                    },
+                    descriptors: vec![],
+                };
+                self.lock_context()
+                    .gatt_server_context
+                    .add_characteristic(characteristic, value)?;
+            }
+            "server-add-descriptor" => {
+                let uuid = UuidHelper::parse_string(get_arg(args, 1)?).ok_or("Invalid UUID")?;
+                let permissions = bt_gatt::parse_permissions(get_arg(args, 2)?)
+                    .ok_or("Failed to parse permissions")?;
+                let value = match args.get(3) {
+                    Some(value) => hex::decode(value).or(Err("Failed to parse value"))?,
+                    None => vec![],
+                };
+                let descriptor =
+                    BluetoothGattDescriptor { uuid: uuid.uu, instance_id: 0, permissions };
+                self.lock_context().gatt_server_context.add_descriptor(descriptor, value)?;
+            }
+            "server-add-service" => {
+                let server_id = self
+                    .lock_context()
+                    .gatt_server_context
+                    .server_id
+                    .ok_or("GATT server is not yet registered.")?;
+                let service = self
+                    .lock_context()
+                    .gatt_server_context
+                    .take_pending_service()
+                    .ok_or("No service is being created")?;
+                self.lock_context().gatt_dbus.as_ref().unwrap().add_service(server_id, service);
+            }
+            "server-remove-service" => {
+                let handle = String::from(get_arg(args, 1)?)
+                    .parse::<i32>()
+                    .or(Err("Failed to parse handle"))?;
+                let server_id = self
+                    .lock_context()
+                    .gatt_server_context
+                    .server_id
+                    .ok_or("GATT server is not yet registered.")?;
+                self.lock_context().gatt_dbus.as_ref().unwrap().remove_service(server_id, handle);
+            }
+            "server-clear-services" => {
+                let server_id = self
+                    .lock_context()
+                    .gatt_server_context
+                    .server_id
+                    .ok_or("GATT server is not yet registered.")?;
+                self.lock_context().gatt_dbus.as_ref().unwrap().clear_services(server_id);
+            }
+            "server-set-value" => {
+                let handle = String::from(get_arg(args, 1)?)
+                    .parse::<i32>()
+                    .or(Err("Failed to parse handle"))?;
+                let value = hex::decode(get_arg(args, 2)?).or(Err("Failed to parse value"))?;
+                self.lock_context().gatt_server_context.set_value(handle, value)?;
+            }
+            "server-notify" => {
+                let handle = String::from(get_arg(args, 1)?)
+                    .parse::<i32>()
+                    .or(Err("Failed to parse handle"))?;
+                let confirm = match &get_arg(args, 2)?[..] {
+                    "notify" => false,
+                    "indicate" => true,
+                    _ => return Err(CommandError::InvalidArgs),
+                };
+                let value = hex::decode(get_arg(args, 3)?).or(Err("Failed to parse value"))?;
+                let server_id = self
+                    .lock_context()
+                    .gatt_server_context
+                    .server_id
+                    .ok_or("GATT server is not yet registered.")?;
+                self.lock_context().gatt_server_context.set_value(handle, value.clone())?;
+
+                let clients = self.lock_context().gatt_server_context.connected_clients.clone();
+                if clients.is_empty() {
+                    return Err("No client is connected".into());
+                }
+                for addr in clients {
+                    let sent = self.lock_context().gatt_dbus.as_ref().unwrap().send_notification(
+                        server_id,
+                        addr.clone(),
+                        handle,
+                        confirm,
+                        value.clone(),
+                    );
+                    if !sent {
+                        print_error!("Failed to send to {}", addr);
+                    }
+                }
+            }
             _ => return Err(CommandError::InvalidArgs),
         }
         Ok(())
@@ -1249,7 +1929,12 @@ impl CommandHandler {
                     .unwrap()
                     .register_scanner(scanner_callback_id);
 
-                print_info!("Scanner to be registered with UUID = {}", UuidWrapper(&uuid));
+                print_output!(
+                    "register_scanner",
+                    { "uuid": UuidWrapper(&uuid).to_string() },
+                    "Scanner to be registered with UUID = {}",
+                    UuidWrapper(&uuid)
+                );
             }
             "unregister-scanner" => {
                 let scanner_id = String::from(get_arg(args, 1)?)
@@ -1263,18 +1948,14 @@ impl CommandHandler {
                     .parse::<u8>()
                     .or(Err("Failed parsing scanner id"))?;
 
+                let (settings, filter) = {
+                    let context = self.lock_context();
+                    (context.scan_context.settings.clone(), context.scan_context.filter())
+                };
                 self.lock_context().gatt_dbus.as_mut().unwrap().start_scan(
                     scanner_id,
-                    // TODO(b/254870159): Construct real settings and filters depending on
-                    // command line options.
-                    None,
-                    Some(btstack::bluetooth_gatt::ScanFilter {
-                        rssi_high_threshold: 0,
-                        rssi_low_threshold: 0,
-                        rssi_low_timeout: 0,
-                        rssi_sampling_period: 0,
-                        condition: btstack::bluetooth_gatt::ScanFilterCondition::Patterns(vec![]),
-                    }),
+                    settings,
+                    Some(filter),
                 );
 
                 self.lock_context().active_scanner_ids.insert(scanner_id);
@@ -1287,6 +1968,92 @@ impl CommandHandler {
                 self.lock_context().gatt_dbus.as_mut().unwrap().stop_scan(scanner_id);
                 self.lock_context().active_scanner_ids.remove(&scanner_id);
             }
+            "set-settings" => {
+                let interval = String::from(get_arg(args, 1)?)
+                    .parse::<i32>()
+                    .or(Err("Failed parsing interval"))?;
+                let window = String::from(get_arg(args, 2)?)
+                    .parse::<i32>()
+                    .or(Err("Failed parsing window"))?;
+                let scan_type = match &get_arg(args, 3)?[..] {
+                    "active" => ScanType::Active,
+                    "passive" => ScanType::Passive,
+                    _ => return Err("Scan type must be active or passive".into()),
+                };
+                if window > interval {
+                    return Err("Scan window must not be larger than the interval".into());
+                }
+
+                self.lock_context().scan_context.settings =
+                    Some(ScanSettings { interval, window, scan_type });
+            }
+            "clear-settings" => {
+                self.lock_context().scan_context.settings = None;
+            }
+            "add-pattern" => {
+                let ad_type = u8::from_str_radix(get_arg(args, 1)?.trim_start_matches("0x"), 16)
+                    .or(Err("Failed parsing AD type"))?;
+                let start_position = String::from(get_arg(args, 2)?)
+                    .parse::<u8>()
+                    .or(Err("Failed parsing start position"))?;
+                let content = hex_to_bytes(get_arg(args, 3)?).ok_or("Failed parsing content")?;
+
+                self.lock_context().scan_context.patterns.push(ScanFilterPattern {
+                    start_position,
+                    ad_type,
+                    content,
+                });
+            }
+            "set-rssi" => {
+                // Thresholds are in dBm and passed to the stack as their two's complement.
+                let high_threshold = String::from(get_arg(args, 1)?)
+                    .parse::<i8>()
+                    .or(Err("Failed parsing high threshold"))?;
+                let low_threshold = String::from(get_arg(args, 2)?)
+                    .parse::<i8>()
+                    .or(Err("Failed parsing low threshold"))?;
+                let low_timeout = String::from(get_arg(args, 3)?)
+                    .parse::<u8>()
+                    .or(Err("Failed parsing low timeout"))?;
+                let sampling_period = String::from(get_arg(args, 4)?)
+                    .parse::<u8>()
+                    .or(Err("Failed parsing sampling period"))?;
+
+                let mut context = self.lock_context();
+                context.scan_context.rssi_high_threshold = high_threshold as u8;
+                context.scan_context.rssi_low_threshold = low_threshold as u8;
+                context.scan_context.rssi_low_timeout = low_timeout;
+                context.scan_context.rssi_sampling_period = sampling_period;
+            }
+            "clear-filter" => {
+                self.lock_context().scan_context.clear_filter();
+            }
+            "show-filter" => {
+                let context = self.lock_context();
+                print_output!(
+                    "scan_filter",
+                    data: context.scan_context.filter_json(),
+                    {
+                        print_info!("Settings: {:?}", context.scan_context.settings);
+                        print_info!("Filter: {:?}", context.scan_context.filter());
+                    }
+                );
+            }
+            "table" => match args.get(1).map(|s| &s[..]) {
+                None => {
+                    let context = self.lock_context();
+                    print_output!(
+                        "scan_table",
+                        { "entries": context.scan_context.entries_json() },
+                        "Scan results:\n{}",
+                        context.scan_context.render()
+                    );
+                }
+                Some("on") => self.lock_context().scan_context.live_table = true,
+                Some("off") => self.lock_context().scan_context.live_table = false,
+                Some("clear") => self.lock_context().scan_context.clear_entries(),
+                _ => return Err(CommandError::InvalidArgs),
+            },
             _ => return Err(CommandError::InvalidArgs),
         }
 
@@ -1424,6 +2191,290 @@ impl CommandHandler {
                 print_info!("Setting advertising data for {}", adv_id);
                 context.gatt_dbus.as_mut().unwrap().set_raw_adv_data(adv_id, data);
             }
+            "create" => {
+                let set_id = get_arg(args, 1)?;
+                let is_legacy = match &get_arg(args, 2)?[..] {
+                    "legacy" => true,
+                    "ext" => false,
+                    _ => return Err("Advertising type must be legacy or ext".into()),
+                };
+
+                let mut context = self.lock_context();
+                if context.adv_set_builders.contains_key(set_id) {
+                    return Err(format!("Advertising set {} already exists", set_id).into());
+                }
+                context.adv_set_builders.insert(set_id.clone(), AdvSet::new_empty(is_legacy));
+            }
+            "delete" => {
+                let set_id = get_arg(args, 1)?;
+
+                let mut context = self.lock_context();
+                let s = context
+                    .adv_set_builders
+                    .remove(set_id)
+                    .ok_or(format!("No advertising set {}", set_id))?;
+                if let Some(adv_id) = s
+                    .reg_id
+                    .and_then(|reg_id| context.adv_sets.remove(&reg_id))
+                    .and_then(|s| s.adv_id)
+                {
+                    print_info!("Stopping advertising set {}", adv_id);
+                    context.gatt_dbus.as_mut().unwrap().stop_advertising_set(adv_id);
+                }
+            }
+            "show" => {
+                let context = self.lock_context();
+                let mut set_ids: Vec<&String> = match args.get(1) {
+                    Some(set_id) => vec![set_id],
+                    None => context.adv_set_builders.keys().collect(),
+                };
+                set_ids.sort();
+                let mut sets = vec![];
+                for set_id in set_ids {
+                    let s = context
+                        .adv_set_builders
+                        .get(set_id)
+                        .ok_or(format!("No advertising set {}", set_id))?;
+                    let adv_id = s
+                        .reg_id
+                        .and_then(|reg_id| context.adv_sets.get(&reg_id))
+                        .and_then(|s| s.adv_id);
+                    sets.push((set_id, adv_id, s));
+                }
+                print_output!(
+                    "adv_sets",
+                    data: serde_json::json!({
+                        "sets": sets
+                            .iter()
+                            .map(|(set_id, adv_id, s)| {
+                                serde_json::json!({
+                                    "set_id": set_id,
+                                    "advertiser_id": adv_id,
+                                    "set": s.to_json(),
+                                })
+                            })
+                            .collect::<Vec<serde_json::Value>>(),
+                    }),
+                    {
+                        for (set_id, adv_id, s) in sets {
+                            print_info!(
+                                "Advertising set {} (advertiser id {:?}): {:#?}",
+                                set_id,
+                                adv_id,
+                                s
+                            );
+                        }
+                    }
+                );
+            }
+            "param" => {
+                let set_id = get_arg(args, 1)?;
+                let param = get_arg(args, 2)?;
+                let value = get_arg(args, 3)?;
+
+                let mut context = self.lock_context();
+                let s = context
+                    .adv_set_builders
+                    .get_mut(set_id)
+                    .ok_or(format!("No advertising set {}", set_id))?;
+                match &param[..] {
+                    "connectable" => s.params.connectable = parse_on_off(value)?,
+                    "scannable" => s.params.scannable = parse_on_off(value)?,
+                    "anonymous" => s.params.is_anonymous = parse_on_off(value)?,
+                    "include-tx-power" => s.params.include_tx_power = parse_on_off(value)?,
+                    "primary-phy" => s.params.primary_phy = parse_le_phy(value)?,
+                    "secondary-phy" => s.params.secondary_phy = parse_le_phy(value)?,
+                    "tx-power" => {
+                        let tx_power = value.parse::<i32>().or(Err("Failed parsing TX power"))?;
+                        if !(-127..=1).contains(&tx_power) {
+                            return Err("TX power must be between -127 and 1 dBm".into());
+                        }
+                        s.params.tx_power_level = tx_power;
+                    }
+                    "interval" => {
+                        let ms = value.parse::<i32>().or(Err("Failed parsing interval"))?;
+                        s.params.interval = ms * 8 / 5; // in 0.625 ms.
+                    }
+                    "own-address" => {
+                        s.params.own_address_type = match &value[..] {
+                            "default" => -1,
+                            "public" => 0,
+                            "random" => 1,
+                            _ => {
+                                return Err("Address type must be default, public or random".into())
+                            }
+                        };
+                    }
+                    _ => return Err(CommandError::InvalidArgs),
+                }
+            }
+            "data" => {
+                let set_id = get_arg(args, 1)?;
+                let kind = get_arg(args, 2)?;
+                let field = get_arg(args, 3)?;
+
+                let mut context = self.lock_context();
+                let s = context
+                    .adv_set_builders
+                    .get_mut(set_id)
+                    .ok_or(format!("No advertising set {}", set_id))?;
+                let data = s.data_mut(kind).ok_or("Data must be adv, scan-rsp or periodic")?;
+                match &field[..] {
+                    "add-service-uuid" => {
+                        let uuid =
+                            UuidHelper::parse_string(get_arg(args, 4)?).ok_or("Invalid UUID")?;
+                        data.service_uuids.push(uuid);
+                    }
+                    "add-solicit-uuid" => {
+                        let uuid =
+                            UuidHelper::parse_string(get_arg(args, 4)?).ok_or("Invalid UUID")?;
+                        data.solicit_uuids.push(uuid);
+                    }
+                    "add-service-data" => {
+                        let uuid = get_arg(args, 4)?;
+                        UuidHelper::parse_string(uuid).ok_or("Invalid UUID")?;
+                        let value = hex_to_bytes(get_arg(args, 5)?).ok_or("Failed parsing data")?;
+                        data.service_data.insert(uuid.clone(), value);
+                    }
+                    "add-manufacturer-data" => {
+                        let company_id =
+                            u16::from_str_radix(get_arg(args, 4)?.trim_start_matches("0x"), 16)
+                                .or(Err("Failed parsing company id"))?;
+                        let value = hex_to_bytes(get_arg(args, 5)?).ok_or("Failed parsing data")?;
+                        data.manufacturer_data.insert(company_id, value);
+                    }
+                    "add-transport-discovery" => {
+                        let value = hex_to_bytes(get_arg(args, 4)?).ok_or("Failed parsing data")?;
+                        data.transport_discovery_data.push(value);
+                    }
+                    "include-tx-power" => {
+                        data.include_tx_power_level = parse_on_off(get_arg(args, 4)?)?;
+                    }
+                    "include-name" => {
+                        data.include_device_name = parse_on_off(get_arg(args, 4)?)?;
+                    }
+                    "clear" => *data = Default::default(),
+                    _ => return Err(CommandError::InvalidArgs),
+                }
+            }
+            "periodic" => {
+                let set_id = get_arg(args, 1)?;
+                let enable = parse_on_off(get_arg(args, 2)?)?;
+                let periodic_params = if enable {
+                    let ms = String::from(get_arg(args, 3)?)
+                        .parse::<i32>()
+                        .or(Err("Failed parsing interval"))?;
+                    let include_tx_power = match args.get(4) {
+                        Some(value) => parse_on_off(value)?,
+                        None => false,
+                    };
+                    Some(PeriodicAdvertisingParameters {
+                        include_tx_power,
+                        interval: ms * 4 / 5, // in 1.25 ms.
+                    })
+                } else {
+                    None
+                };
+
+                let mut context = self.lock_context();
+                let s = context
+                    .adv_set_builders
+                    .get_mut(set_id)
+                    .ok_or(format!("No advertising set {}", set_id))?;
+                if s.params.is_legacy && enable {
+                    return Err("Periodic advertising requires an ext advertising set".into());
+                }
+                s.periodic_params = periodic_params;
+
+                // Periodic advertising can be turned off right away, while new parameters are
+                // applied with update.
+                let reg_id = s.reg_id;
+                if let Some(adv_id) =
+                    reg_id.and_then(|reg_id| context.adv_sets.get(&reg_id)).and_then(|s| s.adv_id)
+                {
+                    if !enable {
+                        print_info!("Disabling periodic advertising for {}", adv_id);
+                        context
+                            .gatt_dbus
+                            .as_mut()
+                            .unwrap()
+                            .set_periodic_advertising_enable(adv_id, false, false);
+                    }
+                }
+            }
+            "start" => {
+                let set_id = get_arg(args, 1)?;
+                let duration = match args.get(2) {
+                    Some(ms) => ms.parse::<i32>().or(Err("Failed parsing duration"))? / 10,
+                    None => 0,
+                };
+                let max_ext_adv_events = match args.get(3) {
+                    Some(events) => events.parse::<i32>().or(Err("Failed parsing max events"))?,
+                    None => 0,
+                };
+
+                let s = {
+                    let context = self.lock_context();
+                    let s = context
+                        .adv_set_builders
+                        .get(set_id)
+                        .ok_or(format!("No advertising set {}", set_id))?;
+                    if s.reg_id.map_or(false, |reg_id| context.adv_sets.contains_key(&reg_id)) {
+                        return Err(format!("Advertising set {} already started", set_id).into());
+                    }
+                    s.clone()
+                };
+
+                let reg_id = AdvSet::start_with_limits(
+                    self.context.clone(),
+                    s,
+                    callback_id,
+                    duration,
+                    max_ext_adv_events,
+                );
+                if let Some(s) = self.lock_context().adv_set_builders.get_mut(set_id) {
+                    s.reg_id = Some(reg_id);
+                }
+            }
+            "stop" => {
+                let set_id = get_arg(args, 1)?;
+
+                let mut context = self.lock_context();
+                let reg_id = context
+                    .adv_set_builders
+                    .get_mut(set_id)
+                    .ok_or(format!("No advertising set {}", set_id))?
+                    .reg_id
+                    .take()
+                    .ok_or(format!("Advertising set {} not started", set_id))?;
+                if let Some(adv_id) = context.adv_sets.remove(&reg_id).and_then(|s| s.adv_id) {
+                    print_info!("Stopping advertising set {}", adv_id);
+                    context.gatt_dbus.as_mut().unwrap().stop_advertising_set(adv_id);
+                }
+            }
+            "update" => {
+                let set_id = get_arg(args, 1)?;
+
+                let (adv_id, s, periodic_enabled) = {
+                    let mut context = self.lock_context();
+                    let s = context
+                        .adv_set_builders
+                        .get(set_id)
+                        .ok_or(format!("No advertising set {}", set_id))?
+                        .clone();
+                    let started = s.reg_id.and_then(|reg_id| context.adv_sets.get_mut(&reg_id));
+                    let started =
+                        started.ok_or(format!("Advertising set {} not started", set_id))?;
+                    let adv_id = started
+                        .adv_id
+                        .ok_or(format!("Advertising set {} is still starting", set_id))?;
+                    let periodic_enabled = started.periodic_params.is_some();
+                    *started = AdvSet { adv_id: Some(adv_id), ..s.clone() };
+                    (adv_id, s, periodic_enabled)
+                };
+
+                AdvSet::update(self.context.clone(), adv_id, &s, periodic_enabled);
+            }
             _ => return Err(CommandError::InvalidArgs),
         }
 
@@ -1522,7 +2573,12 @@ impl CommandHandler {
                     )
                     .into());
                 }
-                print_info!("Requested for listening using rfcomm on socket {}", id);
+                print_output!(
+                    "socket_listen",
+                    { "type": "rfcomm", "socket_id": id },
+                    "Requested for listening using rfcomm on socket {}",
+                    id
+                );
             }
             "listen" => {
                 let auth_required = String::from(get_arg(args, 1)?)
@@ -1561,7 +2617,12 @@ impl CommandHandler {
                     )
                     .into());
                 }
-                print_info!("Requested for listening using l2cap channel on socket {}", id);
+                print_output!(
+                    "socket_listen",
+                    { "type": "l2cap", "le": is_le, "socket_id": id },
+                    "Requested for listening using l2cap channel on socket {}",
+                    id
+                );
             }
             "connect" => {
                 let (addr, sock_type, psm_or_uuid) =
@@ -1650,8 +2711,22 @@ impl CommandHandler {
                     return Err(CommandError::Failed(format!("Failed to create socket with status={:?} against {}, type {}, with psm/uuid {}",
                         status, addr, sock_type, psm_or_uuid)));
                 } else {
-                    print_info!("Called create socket with result ({:?}, {}) against {}, type {}, with psm/uuid {}",
-                    status, id, addr, sock_type, psm_or_uuid);
+                    print_output!(
+                        "socket_connect",
+                        {
+                            "address": addr,
+                            "type": sock_type,
+                            "psm_or_uuid": psm_or_uuid,
+                            "socket_id": id,
+                        },
+                        "Called create socket with result ({:?}, {}) against {}, type {}, with \
+                         psm/uuid {}",
+                        status,
+                        id,
+                        addr,
+                        sock_type,
+                        psm_or_uuid
+                    );
                 }
             }
             "close" => {
@@ -1751,33 +2826,39 @@ impl CommandHandler {
 
         let command = get_arg(args, 0)?;
 
-        match &command[..] {
-            "bonded" => {
-                print_info!("Known bonded devices:");
-                let devices =
-                    self.lock_context().adapter_dbus.as_ref().unwrap().get_bonded_devices();
-                for device in devices.iter() {
-                    print_info!("[{:17}] {}", device.address, device.name);
-                }
-            }
-            "found" => {
-                print_info!("Devices found in most recent discovery session:");
-                for (key, val) in self.lock_context().found_devices.iter() {
-                    print_info!("[{:17}] {}", key, val.name);
-                }
-            }
-            "connected" => {
-                print_info!("Connected devices:");
-                let devices =
-                    self.lock_context().adapter_dbus.as_ref().unwrap().get_connected_devices();
+        let (header, devices): (&str, Vec<BluetoothDevice>) = match &command[..] {
+            "bonded" => (
+                "Known bonded devices:",
+                self.lock_context().adapter_dbus.as_ref().unwrap().get_bonded_devices(),
+            ),
+            "found" => (
+                "Devices found in most recent discovery session:",
+                self.lock_context().found_devices.values().cloned().collect(),
+            ),
+            "connected" => (
+                "Connected devices:",
+                self.lock_context().adapter_dbus.as_ref().unwrap().get_connected_devices(),
+            ),
+            other => return Err(format!("Invalid argument '{}'", other).into()),
+        };
+        print_output!(
+            "device_list",
+            data: serde_json::json!({
+                "kind": command,
+                "devices": devices
+                    .iter()
+                    .map(|device| {
+                        serde_json::json!({ "address": device.address, "name": device.name })
+                    })
+                    .collect::<Vec<serde_json::Value>>(),
+            }),
+            {
+                print_info!("{}", header);
                 for device in devices.iter() {
                     print_info!("[{:17}] {}", device.address, device.name);
                 }
             }
-            other => {
-                println!("Invalid argument '{}'", other);
-            }
-        }
+        );
 
         Ok(())
     }
@@ -1991,6 +3072,19 @@ impl CommandHandler {
                     .unwrap()
                     .audio_disconnect(String::from(get_arg(args, 1)?));
             }
+            "stop-voice-recognition" => {
+                let success = self
+                    .context
+                    .lock()
+                    .unwrap()
+                    .telephony_dbus
+                    .as_mut()
+                    .unwrap()
+                    .stop_voice_recognition(String::from(get_arg(args, 1)?));
+                if !success {
+                    return Err("StopVoiceRecognition failed".into());
+                }
+            }
             other => {
                 return Err(format!("Invalid argument '{}'", other).into());
             }
@@ -2025,6 +3119,310 @@ impl CommandHandler {
         Ok(())
     }
 
+    fn cmd_admin(&mut self, args: &Vec<String>) -> CommandResult {
+        if !self.lock_context().adapter_ready {
+            return Err(self.adapter_not_ready());
+        }
+
+        let parse_uuid = |arg: &String| -> Result<Uuid128Bit, CommandError> {
+            UuidHelper::parse_string(arg).map(|uuid| uuid.uu).ok_or("Invalid UUID".into())
+        };
+
+        match &get_arg(args, 0)?[..] {
+            "allowlist" => {
+                let mut allowlist =
+                    self.lock_context().admin_dbus.as_ref().unwrap().get_allowed_services();
+                let new_allowlist = match args.get(1).map(String::as_str) {
+                    None => {
+                        print_output!(
+                            "allowed_services",
+                            {
+                                "uuids": allowlist
+                                    .iter()
+                                    .map(|uuid| UuidWrapper(uuid).to_string())
+                                    .collect::<Vec<String>>(),
+                            },
+                            "Allowed services: {}",
+                            DisplayList(
+                                allowlist
+                                    .iter()
+                                    .map(|uuid| UuidHelper::known_uuid_to_string(uuid))
+                                    .collect::<Vec<String>>()
+                            )
+                        );
+                        return Ok(());
+                    }
+                    Some("set") => {
+                        args[2..].iter().map(parse_uuid).collect::<Result<Vec<_>, _>>()?
+                    }
+                    Some("add") => {
+                        let uuid = parse_uuid(get_arg(args, 2)?)?;
+                        if !allowlist.contains(&uuid) {
+                            allowlist.push(uuid);
+                        }
+                        allowlist
+                    }
+                    Some("remove") => {
+                        let uuid = parse_uuid(get_arg(args, 2)?)?;
+                        allowlist.retain(|u| *u != uuid);
+                        allowlist
+                    }
+                    Some(_) => return Err(CommandError::InvalidArgs),
+                };
+                if !self
+                    .lock_context()
+                    .admin_dbus
+                    .as_mut()
+                    .unwrap()
+                    .set_allowed_services(new_allowlist)
+                {
+                    return Err("Failed to set the allowlist".into());
+                }
+            }
+            "is-allowed" => {
+                let uuid = parse_uuid(get_arg(args, 1)?)?;
+                let allowed =
+                    self.lock_context().admin_dbus.as_ref().unwrap().is_service_allowed(uuid);
+                print_output!(
+                    "service_allowed",
+                    { "uuid": UuidWrapper(&uuid).to_string(), "allowed": allowed },
+                    "Service {} is {}",
+                    UuidHelper::known_uuid_to_string(&uuid),
+                    if allowed { "allowed" } else { "blocked" }
+                );
+            }
+            "policy" => {
+                let policy = self.lock_context().admin_dbus.as_ref().unwrap().get_device_policy();
+                print_output!(
+                    "device_policy",
+                    { "policy": callbacks::device_policy_json(&policy) },
+                    "Device policy: {:?}",
+                    policy
+                );
+            }
+            "deny" | "undeny" => {
+                let address = get_arg(args, 1)?.to_uppercase();
+                let mut policy =
+                    self.lock_context().admin_dbus.as_ref().unwrap().get_device_policy();
+                policy.denied_addresses.retain(|a| *a != address);
+                if args[0] == "deny" {
+                    policy.denied_addresses.push(address);
+                }
+                if !self.lock_context().admin_dbus.as_mut().unwrap().set_device_policy(policy) {
+                    return Err("Failed to set the device policy".into());
+                }
+            }
+            "policy-effect" => {
+                let device = BluetoothDevice {
+                    address: String::from(get_arg(args, 1)?),
+                    name: String::from(""),
+                };
+                let effect = self
+                    .lock_context()
+                    .admin_dbus
+                    .as_ref()
+                    .unwrap()
+                    .get_device_policy_effect(device.clone());
+                print_output!(
+                    "policy_effect",
+                    {
+                        "address": device.address,
+                        "effect": effect.as_ref().map(callbacks::policy_effect_json),
+                    },
+                    "Policy effect of {}: {:?}",
+                    device.address,
+                    effect
+                );
+            }
+            other => {
+                return Err(format!("Invalid argument '{}'", other).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn cmd_battery(&mut self, args: &Vec<String>) -> CommandResult {
+        if !self.lock_context().adapter_ready {
+            return Err(self.adapter_not_ready());
+        }
+
+        match &get_arg(args, 0)?[..] {
+            "status" => {
+                let address = String::from(get_arg(args, 1)?);
+                let battery_set = self
+                    .lock_context()
+                    .battery_manager_dbus
+                    .as_ref()
+                    .unwrap()
+                    .get_battery_information(address.clone());
+                match battery_set {
+                    Some(battery_set) => print_output!(
+                        "battery_status",
+                        {
+                            "address": address,
+                            "battery_set": callbacks::battery_set_json(&battery_set),
+                        },
+                        "Battery of {}: {}",
+                        address,
+                        callbacks::format_battery_set(&battery_set)
+                    ),
+                    None => print_output!(
+                        "battery_status",
+                        { "address": address, "battery_set": null },
+                        "No battery information for {}",
+                        address
+                    ),
+                }
+            }
+            other => {
+                return Err(format!("Invalid argument '{}'", other).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn cmd_log(&mut self, args: &Vec<String>) -> CommandResult {
+        if !self.lock_context().adapter_ready {
+            return Err(self.adapter_not_ready());
+        }
+
+        match &get_arg(args, 0)?[..] {
+            "status" => {
+                let enabled = self.lock_context().logging_dbus.as_ref().unwrap().is_debug_enabled();
+                print_output!(
+                    "debug_logging",
+                    { "enabled": enabled },
+                    "Debug logging is {}",
+                    if enabled { "enabled" } else { "disabled" }
+                );
+            }
+            "debug" => {
+                let enabled = match &get_arg(args, 1)?[..] {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(CommandError::InvalidArgs),
+                };
+                self.lock_context().logging_dbus.as_mut().unwrap().set_debug_logging(enabled);
+            }
+            "set-level" => {
+                let kind = parse_log_target_kind(get_arg(args, 1)?)?;
+                let target = String::from(get_arg(args, 2)?);
+                let level = match &get_arg(args, 3)?[..] {
+                    "off" => Level::Off,
+                    "error" => Level::Error,
+                    "warn" => Level::Warn,
+                    "info" => Level::Info,
+                    "debug" => Level::Debug,
+                    "trace" => Level::Trace,
+                    _ => return Err(CommandError::InvalidArgs),
+                };
+                if !self
+                    .lock_context()
+                    .logging_dbus
+                    .as_mut()
+                    .unwrap()
+                    .set_log_level(kind, target, level)
+                {
+                    return Err("Failed to set the log level".into());
+                }
+            }
+            "get-level" => {
+                let kind = parse_log_target_kind(get_arg(args, 1)?)?;
+                let target = String::from(get_arg(args, 2)?);
+                let level = self
+                    .lock_context()
+                    .logging_dbus
+                    .as_ref()
+                    .unwrap()
+                    .get_log_level(kind, target.clone());
+                print_output!(
+                    "log_level",
+                    {
+                        "kind": match kind {
+                            LogTargetKind::Module => "module",
+                            LogTargetKind::Tag => "tag",
+                        },
+                        "target": target,
+                        "level": match level {
+                            Level::Off => "off",
+                            Level::Error => "error",
+                            Level::Warn => "warn",
+                            Level::Info => "info",
+                            Level::Debug => "debug",
+                            Level::Trace => "trace",
+                        },
+                    },
+                    "Log level of {}: {:?}",
+                    target,
+                    level
+                );
+            }
+            "clear-level" => {
+                let kind = parse_log_target_kind(get_arg(args, 1)?)?;
+                let target = String::from(get_arg(args, 2)?);
+                self.lock_context().logging_dbus.as_mut().unwrap().clear_log_level(kind, target);
+            }
+            "dump" => {
+                let lines = self.lock_context().logging_dbus.as_ref().unwrap().get_log_buffer();
+                print_output!(
+                    "log_buffer",
+                    data: serde_json::json!({ "lines": lines }),
+                    {
+                        for line in lines {
+                            print_info!("{}", line);
+                        }
+                    }
+                );
+            }
+            "clear" => {
+                self.lock_context().logging_dbus.as_mut().unwrap().clear_log_buffer();
+            }
+            other => {
+                return Err(format!("Invalid argument '{}'", other).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn cmd_suspend(&mut self, args: &Vec<String>) -> CommandResult {
+        if !self.lock_context().adapter_ready {
+            return Err(self.adapter_not_ready());
+        }
+
+        match &get_arg(args, 0)?[..] {
+            "start" => {
+                let suspend_type = match &get_arg(args, 1)?[..] {
+                    "no-wakes" => SuspendType::NoWakesAllowed,
+                    "hid-wakes" => SuspendType::AllowWakeFromHid,
+                    "other" => SuspendType::Other,
+                    _ => return Err(CommandError::InvalidArgs),
+                };
+                let suspend_id = match args.get(2) {
+                    Some(id) => id.parse::<i32>().or(Err("Failed to parse suspend_id"))?,
+                    None => 1,
+                };
+                self.lock_context()
+                    .suspend_dbus
+                    .as_mut()
+                    .unwrap()
+                    .suspend(suspend_type, suspend_id);
+            }
+            "resume" => {
+                if !self.lock_context().suspend_dbus.as_mut().unwrap().resume() {
+                    return Err("There is no suspend to resume".into());
+                }
+            }
+            other => {
+                return Err(format!("Invalid argument '{}'", other).into());
+            }
+        }
+
+        Ok(())
+    }
+
     fn cmd_media(&mut self, args: &Vec<String>) -> CommandResult {
         if !self.context.lock().unwrap().adapter_ready {
             return Err(self.adapter_not_ready());
@@ -2034,6 +3432,167 @@ impl CommandHandler {
             "log" => {
                 self.context.lock().unwrap().media_dbus.as_mut().unwrap().trigger_debug_dump();
             }
+            "connect" => {
+                let address = get_arg(args, 1)?.clone();
+                self.lock_context().media_dbus.as_mut().unwrap().connect(address);
+            }
+            "disconnect" => {
+                let address = get_arg(args, 1)?.clone();
+                self.lock_context().media_dbus.as_mut().unwrap().disconnect(address);
+            }
+            "set-active" => {
+                let address = get_arg(args, 1)?.clone();
+                self.lock_context().media_dbus.as_mut().unwrap().set_active_device(address);
+            }
+            "set-hfp-active" => {
+                let address = get_arg(args, 1)?.clone();
+                self.lock_context().media_dbus.as_mut().unwrap().set_hfp_active_device(address);
+            }
+            "reset-active" => {
+                self.lock_context().media_dbus.as_mut().unwrap().reset_active_device();
+            }
+            "set-audio-config" => {
+                let address = get_arg(args, 1)?.clone();
+                let codec_type = match &get_arg(args, 2)?[..] {
+                    "sbc" => A2dpCodecIndex::SrcSbc,
+                    "aac" => A2dpCodecIndex::SrcAac,
+                    "aptx" => A2dpCodecIndex::SrcAptx,
+                    "aptx-hd" => A2dpCodecIndex::SrcAptxHD,
+                    "ldac" => A2dpCodecIndex::SrcLdac,
+                    other => return Err(format!("Invalid codec '{}'", other).into()),
+                };
+                let sample_rate = match &get_arg(args, 3)?[..] {
+                    "16000" => A2dpCodecSampleRate::RATE_16000,
+                    "24000" => A2dpCodecSampleRate::RATE_24000,
+                    "44100" => A2dpCodecSampleRate::RATE_44100,
+                    "48000" => A2dpCodecSampleRate::RATE_48000,
+                    "88200" => A2dpCodecSampleRate::RATE_88200,
+                    "96000" => A2dpCodecSampleRate::RATE_96000,
+                    "176400" => A2dpCodecSampleRate::RATE_176400,
+                    "192000" => A2dpCodecSampleRate::RATE_192000,
+                    other => return Err(format!("Invalid sample rate '{}'", other).into()),
+                };
+                let bits_per_sample = match &get_arg(args, 4)?[..] {
+                    "16" => A2dpCodecBitsPerSample::SAMPLE_16,
+                    "24" => A2dpCodecBitsPerSample::SAMPLE_24,
+                    "32" => A2dpCodecBitsPerSample::SAMPLE_32,
+                    other => return Err(format!("Invalid bits per sample '{}'", other).into()),
+                };
+                let channel_mode = match &get_arg(args, 5)?[..] {
+                    "mono" => A2dpCodecChannelMode::MODE_MONO,
+                    "stereo" => A2dpCodecChannelMode::MODE_STEREO,
+                    other => return Err(format!("Invalid channel mode '{}'", other).into()),
+                };
+
+                let success = self.lock_context().media_dbus.as_mut().unwrap().set_audio_config(
+                    address,
+                    codec_type,
+                    sample_rate,
+                    bits_per_sample,
+                    channel_mode,
+                );
+                if !success {
+                    return Err("Failed to set audio config".into());
+                }
+            }
+            "set-volume" => {
+                let volume = String::from(get_arg(args, 1)?)
+                    .parse::<u8>()
+                    .or(Err("Failed parsing volume"))?;
+                if volume > 127 {
+                    return Err(format!("Invalid volume, got {}, want 0 to 127", volume).into());
+                }
+                self.lock_context().media_dbus.as_mut().unwrap().set_volume(volume);
+            }
+            "set-hfp-volume" => {
+                let volume = String::from(get_arg(args, 1)?)
+                    .parse::<u8>()
+                    .or(Err("Failed parsing volume"))?;
+                if volume > 15 {
+                    return Err(format!("Invalid volume, got {}, want 0 to 15", volume).into());
+                }
+                let address = get_arg(args, 2)?.clone();
+                self.lock_context().media_dbus.as_mut().unwrap().set_hfp_volume(volume, address);
+            }
+            "start-audio" => {
+                if !self.lock_context().media_dbus.as_mut().unwrap().start_audio_request() {
+                    return Err("Failed to start audio".into());
+                }
+            }
+            "stop-audio" => {
+                self.lock_context().media_dbus.as_mut().unwrap().stop_audio_request();
+            }
+            "audio-status" => {
+                let address = get_arg(args, 1)?;
+                let mut context = self.lock_context();
+                let media = context.media_dbus.as_mut().unwrap();
+                let a2dp_started = media.get_a2dp_audio_started(address.clone());
+                let hfp_codecs = media.get_hfp_audio_final_codecs(address.clone());
+                print_output!(
+                    "audio_status",
+                    data: serde_json::json!({
+                        "address": address,
+                        "a2dp_started": a2dp_started,
+                        "hfp_codecs": hfp_codecs,
+                    }),
+                    {
+                        let hfp_codec = match hfp_codecs {
+                            0 => "not started",
+                            1 => "CVSD",
+                            2 => "mSBC",
+                            4 => "LC3",
+                            _ => "unknown",
+                        };
+                        print_info!("A2DP audio started: {}", a2dp_started);
+                        print_info!("HFP audio: {}", hfp_codec);
+                    }
+                );
+            }
+            "position" => {
+                let position =
+                    self.lock_context().media_dbus.as_mut().unwrap().get_presentation_position();
+                print_output!(
+                    "presentation_position",
+                    {
+                        "remote_delay_report_ns": position.remote_delay_report_ns,
+                        "total_bytes_read": position.total_bytes_read,
+                        "data_position_sec": position.data_position_sec,
+                        "data_position_nsec": position.data_position_nsec,
+                    },
+                    "Presentation position: {:?}",
+                    position
+                );
+            }
+            "start-sco" => {
+                let address = get_arg(args, 1)?.clone();
+                let sco_offload = match &get_arg(args, 2)?[..] {
+                    "offload" => true,
+                    "no-offload" => false,
+                    other => return Err(format!("Invalid argument '{}'", other).into()),
+                };
+                let mut disabled_codecs = HfpCodecCapability::NONE;
+                for codec in args.iter().skip(3) {
+                    disabled_codecs |= match &codec[..] {
+                        "cvsd" => HfpCodecCapability::CVSD,
+                        "msbc" => HfpCodecCapability::MSBC,
+                        "lc3" => HfpCodecCapability::LC3,
+                        other => return Err(format!("Invalid codec '{}'", other).into()),
+                    };
+                }
+
+                let success = self.lock_context().media_dbus.as_mut().unwrap().start_sco_call(
+                    address,
+                    sco_offload,
+                    disabled_codecs,
+                );
+                if !success {
+                    return Err("Failed to start SCO call".into());
+                }
+            }
+            "stop-sco" => {
+                let address = get_arg(args, 1)?.clone();
+                self.lock_context().media_dbus.as_mut().unwrap().stop_sco_call(address);
+            }
             other => {
                 return Err(format!("Invalid argument '{}'", other).into());
             }
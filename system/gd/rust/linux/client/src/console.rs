@@ -1,4 +1,33 @@
 //! Convenient functions to print messages to console.
+//!
+//! In JSON mode, every message is printed as a single line JSON object of the form
+//! `{"type": <type>, "name": <name>, "data": <object>}`, where type is one of:
+//!
+//! * `info` and `error` - Free-form messages, with an empty name and a `message` string. Data
+//!   printed by commands and callbacks is never sent this way.
+//! * `event` - An asynchronous callback, named after the callback.
+//! * `output` - Data printed by a command, e.g. `adapter_show`. It comes before the result.
+//! * `result` - The result of a command, named after the command.
+//!
+//! Fields hold typed values: enums are printed as their integer values, structs as nested objects
+//! and binary data as hex strings.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_json_output(enabled: bool) {
+    JSON_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+pub(crate) fn is_json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Prints a JSON message on a single line.
+pub(crate) fn print_json(msg_type: &str, name: &str, data: serde_json::Value) {
+    println!("{}", serde_json::json!({ "type": msg_type, "name": name, "data": data }));
+}
 
 #[macro_export]
 macro_rules! console_blue {
@@ -24,7 +53,10 @@ macro_rules! console_red {
 #[macro_export]
 macro_rules! print_info {
     ( $($arg:tt)* ) => {
-        {
+        if $crate::console::is_json_output() {
+            $crate::console::print_json(
+                "info", "", serde_json::json!({ "message": format!($($arg)*) }));
+        } else {
             print!("{}: ", console_yellow!("btclient:info"));
             println!($($arg)*);
         }
@@ -34,9 +66,62 @@ macro_rules! print_info {
 #[macro_export]
 macro_rules! print_error {
     ( $($arg:tt)* ) => {
-        {
+        if $crate::console::is_json_output() {
+            $crate::console::print_json(
+                "error", "", serde_json::json!({ "message": format!($($arg)*) }));
+        } else {
             print!("{}: ", console_red!("btclient:error"));
             println!($($arg)*);
         }
     };
 }
+
+/// Prints a callback event. JSON mode prints the event |name| with the given fields, otherwise the
+/// message is printed like |print_info|.
+///
+/// With `data:` instead of fields, JSON mode prints the given object and text mode runs the block.
+#[macro_export]
+macro_rules! print_event {
+    ( $name:expr, { $($json:tt)* }, $($arg:tt)* ) => {
+        if $crate::console::is_json_output() {
+            $crate::console::print_json("event", $name, serde_json::json!({ $($json)* }));
+        } else {
+            print!("{}: ", console_yellow!("btclient:info"));
+            println!($($arg)*);
+        }
+    };
+    ( $name:expr, data: $data:expr, $text:block ) => {
+        if $crate::console::is_json_output() {
+            $crate::console::print_json("event", $name, $data);
+        } else $text
+    };
+}
+
+/// Prints the output of a command. JSON mode prints the output |name| with the given fields,
+/// otherwise the message is printed like |print_info|.
+///
+/// With `data:` instead of fields, JSON mode prints the given object and text mode runs the block.
+#[macro_export]
+macro_rules! print_output {
+    ( $name:expr, { $($json:tt)* }, $($arg:tt)* ) => {
+        if $crate::console::is_json_output() {
+            $crate::console::print_json("output", $name, serde_json::json!({ $($json)* }));
+        } else {
+            print!("{}: ", console_yellow!("btclient:info"));
+            println!($($arg)*);
+        }
+    };
+    ( $name:expr, data: $data:expr, $text:block ) => {
+        if $crate::console::is_json_output() {
+            $crate::console::print_json("output", $name, $data);
+        } else $text
+    };
+}
+
+/// Prints the result of the command |name| with the given fields. Only used in JSON mode.
+#[macro_export]
+macro_rules! print_result {
+    ( $name:expr, { $($json:tt)* } ) => {
+        $crate::console::print_json("result", $name, serde_json::json!({ $($json)* }))
+    };
+}
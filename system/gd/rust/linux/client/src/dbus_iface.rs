@@ -20,11 +20,17 @@ use bt_topshim::profiles::sdp::{
 use bt_topshim::profiles::socket::SocketType;
 use bt_topshim::profiles::ProfileConnectionState;
 
+use bt_utils::socket::MgmtControllerBus;
+
+use btstack::battery_manager::{Battery, BatterySet, IBatteryManager, IBatteryManagerCallback};
 use btstack::bluetooth::{
     BluetoothDevice, IBluetooth, IBluetoothCallback, IBluetoothConnectionCallback,
     IBluetoothQALegacy,
 };
-use btstack::bluetooth_admin::{IBluetoothAdmin, IBluetoothAdminPolicyCallback, PolicyEffect};
+use btstack::bluetooth_admin::{
+    DevicePolicy, IBluetoothAdmin, IBluetoothAdminPolicyCallback, PolicyBlockReason, PolicyEffect,
+    VendorProductId,
+};
 use btstack::bluetooth_adv::{
     AdvertiseData, AdvertisingSetParameters, IAdvertisingSetCallback, ManfId,
     PeriodicAdvertisingParameters,
@@ -35,11 +41,13 @@ use btstack::bluetooth_gatt::{
     IBluetoothGattServerCallback, IScannerCallback, ScanFilter, ScanFilterCondition,
     ScanFilterPattern, ScanResult, ScanSettings, ScanType,
 };
+use btstack::bluetooth_logging::{IBluetoothLogging, Level, LogTargetKind};
 use btstack::bluetooth_media::{
     BluetoothAudioDevice, IBluetoothMedia, IBluetoothMediaCallback, IBluetoothTelephony,
     IBluetoothTelephonyCallback,
 };
 use btstack::bluetooth_qa::IBluetoothQA;
+use btstack::dis::DeviceInformationValues;
 use btstack::socket_manager::{
     BluetoothServerSocket, BluetoothSocket, CallbackId, IBluetoothSocketManager,
     IBluetoothSocketManagerCallbacks, SocketId, SocketResult,
@@ -58,7 +66,10 @@ use dbus_macros::{
 };
 
 use manager_service::iface_bluetooth_manager::{
-    AdapterWithEnabled, IBluetoothManager, IBluetoothManagerCallback,
+    AdapterControllerInfo, AdapterDebugLevel, AdapterHealth, AdapterLaunchProfile,
+    AdapterWithEnabled, BondBackupExport, BondBackupStatus, DeviceMigrationAction,
+    DeviceMigrationReport, FeatureOverride, IBluetoothManager, IBluetoothManagerCallback,
+    RestartEvent, RestartReason,
 };
 
 use num_traits::{FromPrimitive, ToPrimitive};
@@ -88,6 +99,8 @@ impl_dbus_arg_enum!(GattStatus);
 impl_dbus_arg_enum!(GattWriteRequestStatus);
 impl_dbus_arg_enum!(GattWriteType);
 impl_dbus_arg_enum!(LePhy);
+impl_dbus_arg_enum!(Level);
+impl_dbus_arg_enum!(LogTargetKind);
 impl_dbus_arg_enum!(ProfileConnectionState);
 impl_dbus_arg_enum!(ScanType);
 impl_dbus_arg_enum!(SocketType);
@@ -218,6 +231,17 @@ pub struct BtVendorProductInfoDBus {
     version: u16,
 }
 
+#[dbus_propmap(DeviceInformationValues)]
+pub struct DeviceInformationValuesDBus {
+    manufacturer_name: String,
+    model_number: String,
+    serial_number: String,
+    hardware_revision: String,
+    firmware_revision: String,
+    software_revision: String,
+    system_id: Vec<u8>,
+}
+
 fn read_propmap_value<T: 'static + DirectDBus>(
     propmap: &dbus::arg::PropMap,
     key: &str,
@@ -589,6 +613,14 @@ impl IBluetoothCallback for IBluetoothCallbackDBus {
     ) {
     }
 
+    #[dbus_method("OnDeviceInformationChanged", DBusLog::Disable)]
+    fn on_device_information_changed(
+        &mut self,
+        remote_device: BluetoothDevice,
+        values: DeviceInformationValues,
+    ) {
+    }
+
     #[dbus_method("OnAddressChanged", DBusLog::Disable)]
     fn on_address_changed(&mut self, addr: String) {}
 
@@ -930,6 +962,11 @@ impl IBluetooth for BluetoothDBus {
         dbus_generated!()
     }
 
+    #[dbus_method("GetRemoteDeviceInformation")]
+    fn get_remote_device_information(&self, device: BluetoothDevice) -> DeviceInformationValues {
+        dbus_generated!()
+    }
+
     #[dbus_method("GetRemoteRSSI")]
     fn get_remote_rssi(&self, device: BluetoothDevice) -> i8 {
         dbus_generated!()
@@ -1059,10 +1096,71 @@ impl IBluetoothQALegacy for BluetoothQALegacyDBus {
     fn send_hid_data(&mut self, addr: String, data: String) -> BtStatus;
 }
 
+impl_dbus_arg_enum!(AdapterDebugLevel);
+impl_dbus_arg_enum!(FeatureOverride);
+
+#[dbus_propmap(AdapterLaunchProfile)]
+pub struct AdapterLaunchProfileDbus {
+    init_flags: Vec<String>,
+    debug_level: AdapterDebugLevel,
+    ll_privacy: FeatureOverride,
+    devcoredump: FeatureOverride,
+}
+
+impl_dbus_arg_enum!(MgmtControllerBus);
+
+#[dbus_propmap(AdapterControllerInfo)]
+pub struct AdapterControllerInfoDbus {
+    address: String,
+    manufacturer: u16,
+    bus: Option<MgmtControllerBus>,
+    supported_settings: u32,
+    current_settings: u32,
+    last_error: Option<u8>,
+}
+
 #[dbus_propmap(AdapterWithEnabled)]
 pub struct AdapterWithEnabledDbus {
     hci_interface: i32,
     enabled: bool,
+    launch_profile: AdapterLaunchProfile,
+    controller: Option<AdapterControllerInfo>,
+}
+
+impl_dbus_arg_enum!(RestartReason);
+
+#[dbus_propmap(RestartEvent)]
+pub struct RestartEventDbus {
+    timestamp_ms: u64,
+    reason: RestartReason,
+}
+
+#[dbus_propmap(AdapterHealth)]
+pub struct AdapterHealthDbus {
+    hci_interface: i32,
+    failed: bool,
+    reset_count: u32,
+    restart_delay_ms: u64,
+    restart_history: Vec<RestartEvent>,
+}
+
+impl_dbus_arg_enum!(DeviceMigrationAction);
+
+#[dbus_propmap(DeviceMigrationReport)]
+pub struct DeviceMigrationReportDbus {
+    adapter: String,
+    address: String,
+    action: DeviceMigrationAction,
+    converted_keys: Vec<String>,
+    errors: Vec<String>,
+}
+
+impl_dbus_arg_enum!(BondBackupStatus);
+
+#[dbus_propmap(BondBackupExport)]
+pub struct BondBackupExportDbus {
+    status: BondBackupStatus,
+    backup: Vec<u8>,
 }
 
 // Implements RPC-friendly wrapper methods for calling IBluetoothManager, generated by
@@ -1155,6 +1253,31 @@ impl IBluetoothManager for BluetoothManagerDBus {
     fn set_tablet_mode(&mut self, tablet_mode: bool) {
         dbus_generated!()
     }
+
+    #[dbus_method("GetAdapterHealth")]
+    fn get_adapter_health(&mut self, hci_interface: i32) -> AdapterHealth {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetMigrationReport")]
+    fn get_migration_report(&mut self, floss_enabled: bool) -> Vec<DeviceMigrationReport> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("ExportBondedDevices", DBusLog::Disable)]
+    fn export_bonded_devices(&mut self, adapter_address: String, key: Vec<u8>) -> BondBackupExport {
+        dbus_generated!()
+    }
+
+    #[dbus_method("ImportBondedDevices", DBusLog::Disable)]
+    fn import_bonded_devices(
+        &mut self,
+        adapter_address: String,
+        backup: Vec<u8>,
+        key: Vec<u8>,
+    ) -> BondBackupStatus {
+        dbus_generated!()
+    }
 }
 
 struct IBluetoothManagerCallbackDBus {}
@@ -1174,6 +1297,18 @@ impl IBluetoothManagerCallback for IBluetoothManagerCallbackDBus {
 
     #[dbus_method("OnDefaultAdapterChanged", DBusLog::Disable)]
     fn on_default_adapter_changed(&mut self, hci_interface: i32) {}
+
+    #[dbus_method("OnAdapterHealthChanged", DBusLog::Disable)]
+    fn on_adapter_health_changed(&mut self, health: AdapterHealth) {}
+
+    #[dbus_method("OnHciRfkillChanged", DBusLog::Disable)]
+    fn on_hci_rfkill_changed(
+        &mut self,
+        hci_interface: i32,
+        soft_blocked: bool,
+        hard_blocked: bool,
+    ) {
+    }
 }
 
 #[allow(dead_code)]
@@ -1327,6 +1462,16 @@ impl IBluetoothAdmin for BluetoothAdminDBus {
         dbus_generated!()
     }
 
+    #[dbus_method("SetDevicePolicy")]
+    fn set_device_policy(&mut self, policy: DevicePolicy) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetDevicePolicy")]
+    fn get_device_policy(&self) -> DevicePolicy {
+        dbus_generated!()
+    }
+
     #[dbus_method("GetDevicePolicyEffect")]
     fn get_device_policy_effect(&self, device: BluetoothDevice) -> Option<PolicyEffect> {
         dbus_generated!()
@@ -1346,12 +1491,28 @@ impl IBluetoothAdmin for BluetoothAdminDBus {
     }
 }
 
+impl_dbus_arg_enum!(PolicyBlockReason);
+
 #[dbus_propmap(PolicyEffect)]
 pub struct PolicyEffectDBus {
     pub service_blocked: Vec<Uuid128Bit>,
+    pub device_blocked: PolicyBlockReason,
     pub affected: bool,
 }
 
+#[dbus_propmap(VendorProductId)]
+pub struct VendorProductIdDBus {
+    pub vendor_id: u16,
+    pub product_id: u16,
+}
+
+#[dbus_propmap(DevicePolicy)]
+pub struct DevicePolicyDBus {
+    pub denied_addresses: Vec<String>,
+    pub allowed_bonding_ids: Vec<VendorProductId>,
+    pub allowed_transport: BtTransport,
+}
+
 struct IBluetoothAdminPolicyCallbackDBus {}
 
 impl RPCProxy for IBluetoothAdminPolicyCallbackDBus {}
@@ -1374,6 +1535,16 @@ impl IBluetoothAdminPolicyCallback for IBluetoothAdminPolicyCallbackDBus {
     ) {
         dbus_generated!()
     }
+
+    #[dbus_method("OnDevicePolicyChanged", DBusLog::Disable)]
+    fn on_device_policy_changed(&mut self, policy: DevicePolicy) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnDevicePolicyViolation", DBusLog::Disable)]
+    fn on_device_policy_violation(&mut self, device: BluetoothDevice, reason: PolicyBlockReason) {
+        dbus_generated!()
+    }
 }
 
 pub(crate) struct BluetoothGattDBusRPC {
@@ -2293,6 +2464,140 @@ impl ISuspendCallback for ISuspendCallbackDBus {
     fn on_resumed(&mut self, suspend_id: i32) {}
 }
 
+#[dbus_propmap(BatterySet)]
+pub struct BatterySetDBus {
+    address: String,
+    source_uuid: String,
+    source_info: String,
+    batteries: Vec<Battery>,
+}
+
+#[dbus_propmap(Battery)]
+pub struct BatteryDBus {
+    percentage: u32,
+    variant: String,
+}
+
+pub(crate) struct BatteryManagerDBusRPC {
+    client_proxy: ClientDBusProxy,
+}
+
+pub(crate) struct BatteryManagerDBus {
+    client_proxy: ClientDBusProxy,
+    pub rpc: BatteryManagerDBusRPC,
+}
+
+impl BatteryManagerDBus {
+    fn make_client_proxy(conn: Arc<SyncConnection>, index: i32) -> ClientDBusProxy {
+        ClientDBusProxy::new(
+            conn,
+            String::from("org.chromium.bluetooth"),
+            make_object_path(index, "battery_manager"),
+            String::from("org.chromium.bluetooth.BatteryManager"),
+        )
+    }
+
+    pub(crate) fn new(conn: Arc<SyncConnection>, index: i32) -> BatteryManagerDBus {
+        BatteryManagerDBus {
+            client_proxy: Self::make_client_proxy(conn.clone(), index),
+            rpc: BatteryManagerDBusRPC {
+                client_proxy: Self::make_client_proxy(conn.clone(), index),
+            },
+        }
+    }
+}
+
+#[generate_dbus_interface_client(BatteryManagerDBusRPC)]
+impl IBatteryManager for BatteryManagerDBus {
+    #[dbus_method("RegisterBatteryCallback")]
+    fn register_battery_callback(
+        &mut self,
+        battery_manager_callback: Box<dyn IBatteryManagerCallback + Send>,
+    ) -> u32 {
+        dbus_generated!()
+    }
+
+    #[dbus_method("UnregisterBatteryCallback")]
+    fn unregister_battery_callback(&mut self, callback_id: u32) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetBatteryInformation")]
+    fn get_battery_information(&self, remote_address: String) -> Option<BatterySet> {
+        dbus_generated!()
+    }
+}
+
+struct IBatteryManagerCallbackDBus {}
+
+impl RPCProxy for IBatteryManagerCallbackDBus {}
+
+#[generate_dbus_exporter(
+    export_battery_manager_callback_dbus_intf,
+    "org.chromium.bluetooth.BatteryManagerCallback"
+)]
+impl IBatteryManagerCallback for IBatteryManagerCallbackDBus {
+    #[dbus_method("OnBatteryInfoUpdated", DBusLog::Disable)]
+    fn on_battery_info_updated(&mut self, remote_address: String, battery_set: BatterySet) {
+        dbus_generated!()
+    }
+}
+
+pub(crate) struct BluetoothLoggingDBus {
+    client_proxy: ClientDBusProxy,
+}
+
+impl BluetoothLoggingDBus {
+    pub(crate) fn new(conn: Arc<SyncConnection>, index: i32) -> BluetoothLoggingDBus {
+        BluetoothLoggingDBus {
+            client_proxy: ClientDBusProxy::new(
+                conn.clone(),
+                String::from("org.chromium.bluetooth"),
+                make_object_path(index, "logging"),
+                String::from("org.chromium.bluetooth.Logging"),
+            ),
+        }
+    }
+}
+
+#[generate_dbus_interface_client]
+impl IBluetoothLogging for BluetoothLoggingDBus {
+    #[dbus_method("IsDebugEnabled")]
+    fn is_debug_enabled(&self) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetDebugLogging")]
+    fn set_debug_logging(&mut self, enabled: bool) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetLogLevel")]
+    fn set_log_level(&mut self, kind: LogTargetKind, target: String, level: Level) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetLogLevel")]
+    fn get_log_level(&self, kind: LogTargetKind, target: String) -> Level {
+        dbus_generated!()
+    }
+
+    #[dbus_method("ClearLogLevel")]
+    fn clear_log_level(&mut self, kind: LogTargetKind, target: String) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetLogBuffer", DBusLog::Disable)]
+    fn get_log_buffer(&self) -> Vec<String> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("ClearLogBuffer")]
+    fn clear_log_buffer(&mut self) {
+        dbus_generated!()
+    }
+}
+
 pub(crate) struct BluetoothTelephonyDBusRPC {
     client_proxy: ClientDBusProxy,
 }
@@ -2400,6 +2705,10 @@ impl IBluetoothTelephony for BluetoothTelephonyDBus {
     fn audio_disconnect(&mut self, address: String) {
         dbus_generated!()
     }
+    #[dbus_method("StopVoiceRecognition")]
+    fn stop_voice_recognition(&mut self, address: String) -> bool {
+        dbus_generated!()
+    }
 }
 
 struct IBluetoothTelephonyCallbackDBus {}
@@ -141,6 +141,7 @@ impl BtHelper {
 /// blocked thread has proceeded and got input from readline().
 pub struct AsyncReadline {
     rl: Arc<Mutex<Editor<BtHelper>>>,
+    prompt: String,
     result: Arc<Mutex<Option<rustyline::Result<String>>>>,
 }
 
@@ -156,8 +157,9 @@ impl Future for AsyncReadline {
         let waker = cx.waker().clone();
         let result_clone = self.result.clone();
         let rl = self.rl.clone();
+        let prompt = self.prompt.clone();
         std::thread::spawn(move || {
-            let readline = rl.lock().unwrap().readline(console_blue!("bluetooth> "));
+            let readline = rl.lock().unwrap().readline(console_blue!(prompt));
             *result_clone.lock().unwrap() = Some(readline);
             waker.wake();
         });
@@ -169,6 +171,7 @@ impl Future for AsyncReadline {
 /// Wrapper of rustyline editor that supports async readline().
 pub struct AsyncEditor {
     rl: Arc<Mutex<Editor<BtHelper>>>,
+    client_context: Arc<Mutex<ClientContext>>,
 }
 
 impl AsyncEditor {
@@ -185,16 +188,22 @@ impl AsyncEditor {
             .completion_type(CompletionType::List);
         let config = builder.build();
         let mut rl = rustyline::Editor::with_config(config)?;
-        let helper = BtHelper { command_rules, client_context };
+        let helper = BtHelper { command_rules, client_context: client_context.clone() };
         rl.set_helper(Some(helper));
-        Ok(AsyncEditor { rl: Arc::new(Mutex::new(rl)) })
+        Ok(AsyncEditor { rl: Arc::new(Mutex::new(rl)), client_context })
     }
 
     /// Does async readline().
     ///
     /// Returns a future that will do the readline() when await-ed. This does not block the thread
     /// but rather yields to the executor while waiting for a command to be entered.
+    /// The prompt shows the selected adapter.
     pub fn readline(&self) -> AsyncReadline {
-        AsyncReadline { rl: self.rl.clone(), result: Arc::new(Mutex::new(None)) }
+        let adapter = self.client_context.lock().unwrap().default_adapter;
+        AsyncReadline {
+            rl: self.rl.clone(),
+            prompt: format!("bluetooth:hci{}> ", adapter),
+            result: Arc::new(Mutex::new(None)),
+        }
     }
 }
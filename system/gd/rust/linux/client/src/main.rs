@@ -8,36 +8,45 @@ use dbus::channel::MatchingReceiver;
 use dbus::message::MatchRule;
 use dbus::nonblock::SyncConnection;
 use dbus_crossroads::Crossroads;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio::time::{sleep, timeout};
 
 use crate::bt_adv::AdvSet;
-use crate::bt_gatt::GattClientContext;
+use crate::bt_gatt::{GattClientContext, GattServerContext};
+use crate::bt_scan::ScanContext;
 use crate::callbacks::{
-    AdminCallback, AdvertisingSetCallback, BtCallback, BtConnectionCallback, BtManagerCallback,
-    BtSocketManagerCallback, MediaCallback, QACallback, ScannerCallback, SuspendCallback,
-    TelephonyCallback,
+    AdminCallback, AdvertisingSetCallback, BatteryManagerCallback, BtCallback,
+    BtConnectionCallback, BtManagerCallback, BtSocketManagerCallback, MediaCallback, QACallback,
+    ScannerCallback, SuspendCallback, TelephonyCallback,
 };
 use crate::command_handler::{CommandHandler, SocketSchedule};
 use crate::dbus_iface::{
-    BluetoothAdminDBus, BluetoothDBus, BluetoothGattDBus, BluetoothManagerDBus, BluetoothMediaDBus,
-    BluetoothQADBus, BluetoothQALegacyDBus, BluetoothSocketManagerDBus, BluetoothTelephonyDBus,
-    SuspendDBus,
+    BatteryManagerDBus, BluetoothAdminDBus, BluetoothDBus, BluetoothGattDBus, BluetoothLoggingDBus,
+    BluetoothManagerDBus, BluetoothMediaDBus, BluetoothQADBus, BluetoothQALegacyDBus,
+    BluetoothSocketManagerDBus, BluetoothTelephonyDBus, SuspendDBus,
 };
 use crate::editor::AsyncEditor;
+use crate::script::ScriptEvent;
 use bt_topshim::topstack;
+use btstack::battery_manager::IBatteryManager;
 use btstack::bluetooth::{BluetoothDevice, IBluetooth};
+use btstack::bluetooth_admin::IBluetoothAdmin;
+use btstack::bluetooth_gatt::IBluetoothGatt;
+use btstack::bluetooth_qa::IBluetoothQA;
+use btstack::socket_manager::IBluetoothSocketManager;
 use btstack::suspend::ISuspend;
 use manager_service::iface_bluetooth_manager::IBluetoothManager;
 
 mod bt_adv;
 mod bt_gatt;
+mod bt_scan;
 mod callbacks;
 mod command_handler;
 mod console;
 mod dbus_arg;
 mod dbus_iface;
 mod editor;
+mod script;
 
 /// Context structure for the client. Used to keep track details about the active adapter and its
 /// state.
@@ -45,8 +54,7 @@ pub(crate) struct ClientContext {
     /// List of adapters and whether they are enabled.
     pub(crate) adapters: HashMap<i32, bool>,
 
-    // TODO(abps) - Change once we have multi-adapter support.
-    /// The default adapter is also the active adapter. Defaults to 0.
+    /// The adapter commands are sent to. Defaults to 0 and can be changed with `adapter select`.
     pub(crate) default_adapter: i32,
 
     /// Current adapter is enabled?
@@ -93,6 +101,12 @@ pub(crate) struct ClientContext {
     /// Proxy for suspend interface.
     pub(crate) suspend_dbus: Option<SuspendDBus>,
 
+    /// Proxy for battery manager interface.
+    pub(crate) battery_manager_dbus: Option<BatteryManagerDBus>,
+
+    /// Proxy for logging interface.
+    pub(crate) logging_dbus: Option<BluetoothLoggingDBus>,
+
     /// Proxy for socket manager interface.
     pub(crate) socket_manager_dbus: Option<BluetoothSocketManagerDBus>,
 
@@ -111,6 +125,16 @@ pub(crate) struct ClientContext {
     /// Internal DBus crossroads object.
     dbus_crossroads: Arc<Mutex<Crossroads>>,
 
+    /// Number of times adapter callbacks were registered. Used to give each registration unique
+    /// object paths, as the stack keeps the callbacks that can't be unregistered.
+    callback_registrations: u32,
+
+    /// Name used in the object paths of the adapter callbacks currently being registered.
+    callback_adapter_name: String,
+
+    /// Object paths of the callbacks registered for the selected adapter.
+    callback_objpaths: Vec<String>,
+
     /// Identifies the callback to receive IScannerCallback method calls.
     scanner_callback_id: Option<u32>,
 
@@ -120,12 +144,21 @@ pub(crate) struct ClientContext {
     /// Identifies the callback to receive IBluetoothAdminPolicyCallback method calls.
     admin_callback_id: Option<u32>,
 
+    /// Identifies the callback to receive IBatteryManagerCallback method calls.
+    battery_manager_callback_id: Option<u32>,
+
     /// Keeps track of active LE scanners.
     active_scanner_ids: HashSet<u8>,
 
+    /// Settings and filter of LE scans, and the scan results.
+    scan_context: ScanContext,
+
     /// Keeps track of advertising sets registered. Map from reg_id to AdvSet.
     adv_sets: HashMap<i32, AdvSet>,
 
+    /// Advertising sets composed field by field. Map from the user given set ID to AdvSet.
+    adv_set_builders: HashMap<String, AdvSet>,
+
     /// Identifies the callback to receive IBluetoothSocketManagerCallback method calls.
     socket_manager_callback_id: Option<u32>,
 
@@ -138,6 +171,9 @@ pub(crate) struct ClientContext {
     /// Data of GATT client preference.
     gatt_client_context: GattClientContext,
 
+    /// Services and attribute values of the GATT server.
+    gatt_server_context: GattServerContext,
+
     /// The schedule when a socket is connected.
     socket_test_schedule: Option<SocketSchedule>,
 
@@ -146,6 +182,9 @@ pub(crate) struct ClientContext {
 
     /// The set of client commands that need to wait for callbacks.
     client_commands_with_callbacks: Vec<String>,
+
+    /// Sends events to the script being run, if any.
+    script_events: Option<mpsc::UnboundedSender<ScriptEvent>>,
 }
 
 impl ClientContext {
@@ -177,30 +216,45 @@ impl ClientContext {
             gatt_dbus: None,
             admin_dbus: None,
             suspend_dbus: None,
+            battery_manager_dbus: None,
+            logging_dbus: None,
             socket_manager_dbus: None,
             telephony_dbus: None,
             media_dbus: None,
             fg: tx,
             dbus_connection,
             dbus_crossroads,
+            callback_registrations: 0,
+            callback_adapter_name: String::new(),
+            callback_objpaths: vec![],
             scanner_callback_id: None,
             advertiser_callback_id: None,
             admin_callback_id: None,
+            battery_manager_callback_id: None,
             active_scanner_ids: HashSet::new(),
+            scan_context: ScanContext::new(),
             adv_sets: HashMap::new(),
+            adv_set_builders: HashMap::new(),
             socket_manager_callback_id: None,
             qa_callback_id: None,
             is_restricted,
             gatt_client_context: GattClientContext::new(),
+            gatt_server_context: GattServerContext::new(),
             socket_test_schedule: None,
             mps_sdp_handle: None,
             client_commands_with_callbacks,
+            script_events: None,
         }
     }
 
     // Sets required values for the adapter when enabling or disabling
     fn set_adapter_enabled(&mut self, hci_interface: i32, enabled: bool) {
         print_info!("hci{} enabled = {}", hci_interface, enabled);
+        self.notify_script_event(if enabled {
+            ScriptEvent::AdapterEnabled(hci_interface)
+        } else {
+            ScriptEvent::AdapterDisabled(hci_interface)
+        });
 
         self.adapters.entry(hci_interface).and_modify(|v| *v = enabled).or_insert(enabled);
 
@@ -219,8 +273,108 @@ impl ClientContext {
         }
     }
 
+    // Makes |hci_interface| the adapter that commands are sent to. All state of the previous
+    // adapter is dropped and the proxies are recreated if the adapter is enabled.
+    fn select_adapter(&mut self, hci_interface: i32, enabled: bool) {
+        self.release_adapter_resources();
+        self.remove_adapter_callbacks();
+
+        self.default_adapter = hci_interface;
+        self.enabled = enabled;
+        self.adapter_ready = false;
+        self.adapter_address = None;
+        self.bonding_attempt = None;
+        self.discovering_state = false;
+        self.found_devices.clear();
+        self.bonded_devices.clear();
+
+        self.adapter_dbus = None;
+        self.qa_legacy_dbus = None;
+        self.qa_dbus = None;
+        self.gatt_dbus = None;
+        self.admin_dbus = None;
+        self.suspend_dbus = None;
+        self.battery_manager_dbus = None;
+        self.logging_dbus = None;
+        self.socket_manager_dbus = None;
+        self.telephony_dbus = None;
+        self.media_dbus = None;
+
+        self.scanner_callback_id = None;
+        self.advertiser_callback_id = None;
+        self.admin_callback_id = None;
+        self.battery_manager_callback_id = None;
+        self.socket_manager_callback_id = None;
+        self.qa_callback_id = None;
+        self.active_scanner_ids.clear();
+        self.scan_context.clear_entries();
+        self.adv_sets.clear();
+        self.adv_set_builders.values_mut().for_each(|s| s.reg_id = None);
+        self.gatt_client_context = GattClientContext::new();
+        self.gatt_server_context = GattServerContext::new();
+        self.socket_test_schedule = None;
+        self.mps_sdp_handle = None;
+
+        if enabled {
+            self.create_adapter_proxy(hci_interface);
+        }
+    }
+
+    // Stops the scanners and advertising sets of the current adapter and unregisters everything
+    // the client registered with it, so the stack doesn't keep them after the adapter is switched.
+    fn release_adapter_resources(&mut self) {
+        if let Some(gatt) = self.gatt_dbus.as_mut() {
+            for scanner_id in self.active_scanner_ids.drain() {
+                gatt.stop_scan(scanner_id);
+                gatt.unregister_scanner(scanner_id);
+            }
+            for adv_id in self.adv_sets.values().filter_map(|s| s.adv_id) {
+                gatt.stop_advertising_set(adv_id);
+            }
+            if let Some(id) = self.scanner_callback_id.take() {
+                gatt.unregister_scanner_callback(id);
+            }
+            if let Some(id) = self.advertiser_callback_id.take() {
+                gatt.unregister_advertiser_callback(id);
+            }
+            if let Some(id) = self.gatt_client_context.client_id.take() {
+                gatt.unregister_client(id);
+            }
+            if let Some(id) = self.gatt_server_context.server_id.take() {
+                gatt.unregister_server(id);
+            }
+        }
+        if let (Some(admin), Some(id)) = (self.admin_dbus.as_mut(), self.admin_callback_id.take()) {
+            admin.unregister_admin_policy_callback(id);
+        }
+        if let (Some(battery_manager), Some(id)) =
+            (self.battery_manager_dbus.as_mut(), self.battery_manager_callback_id.take())
+        {
+            battery_manager.unregister_battery_callback(id);
+        }
+        if let (Some(socket_manager), Some(id)) =
+            (self.socket_manager_dbus.as_mut(), self.socket_manager_callback_id.take())
+        {
+            socket_manager.unregister_callback(id);
+        }
+        if let (Some(qa), Some(id)) = (self.qa_dbus.as_mut(), self.qa_callback_id.take()) {
+            qa.unregister_qa_callback(id);
+        }
+    }
+
+    // Stops exporting the callbacks of the previous registration.
+    fn remove_adapter_callbacks(&mut self) {
+        let mut cr = self.dbus_crossroads.lock().unwrap();
+        for objpath in self.callback_objpaths.drain(..) {
+            cr.remove::<()>(&dbus::Path::from(objpath));
+        }
+        self.callback_adapter_name.clear();
+    }
+
     // Creates adapter proxy, registers callbacks and initializes address.
     fn create_adapter_proxy(&mut self, idx: i32) {
+        self.remove_adapter_callbacks();
+
         let conn = self.dbus_connection.clone();
 
         let dbus = BluetoothDBus::new(conn.clone(), idx);
@@ -239,14 +393,23 @@ impl ClientContext {
 
         self.suspend_dbus = Some(SuspendDBus::new(conn.clone(), idx));
 
+        self.battery_manager_dbus = Some(BatteryManagerDBus::new(conn.clone(), idx));
+
+        self.logging_dbus = Some(BluetoothLoggingDBus::new(conn.clone(), idx));
+
         self.telephony_dbus = Some(BluetoothTelephonyDBus::new(conn.clone(), idx));
 
         self.media_dbus = Some(BluetoothMediaDBus::new(conn.clone(), idx));
 
         // Trigger callback registration in the foreground
+        let adapter = match self.callback_registrations {
+            0 => format!("adapter{}", idx),
+            n => format!("adapter{}_{}", idx, n),
+        };
+        self.callback_registrations += 1;
+        self.callback_adapter_name = adapter.clone();
         let fg = self.fg.clone();
         tokio::spawn(async move {
-            let adapter = String::from(format!("adapter{}", idx));
             // Floss won't export the interface until it is ready to be used.
             // Wait 1 second before registering the callbacks.
             sleep(Duration::from_millis(1000)).await;
@@ -302,6 +465,13 @@ impl ClientContext {
         result
     }
 
+    /// Lets the script being run know about an event.
+    fn notify_script_event(&self, event: ScriptEvent) {
+        if let Some(tx) = &self.script_events {
+            let _ = tx.send(event);
+        }
+    }
+
     fn get_floss_api_version(&mut self) -> (u32, u32) {
         let ver = self.manager_dbus.get_floss_api_version();
         let major = (ver & 0xFFFF_0000) >> 16;
@@ -317,6 +487,8 @@ enum ForegroundActions {
     RunCallback(Box<dyn Fn(Arc<Mutex<ClientContext>>) + Send>), // Run callback in foreground
     RegisterAdapterCallback(String),            // Register callbacks for this adapter
     Readline(rustyline::Result<String>),        // Readline result from rustyline
+    ScriptCommand(String, Vec<String>, oneshot::Sender<bool>), // Run a script command
+    ScriptDone(Result<(), String>),             // Script has finished
 }
 
 /// Runs a command line program that interacts with a Bluetooth stack.
@@ -330,17 +502,40 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .takes_value(true)
                 .help("Executes a non-interactive command"),
         )
+        .arg(
+            Arg::with_name("script")
+                .short("s")
+                .long("script")
+                .takes_value(true)
+                .conflicts_with("command")
+                .help("Executes the commands of a script file non-interactively"),
+        )
         .arg(
             Arg::with_name("timeout")
                 .short("t")
                 .long("timeout")
                 .takes_value(true)
-                .help("Specify a timeout in seconds for a non-interactive command"),
+                .help("Specify a timeout in seconds for a non-interactive command or script"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .takes_value(false)
+                .help("Prints command results and callbacks as one JSON object per line"),
         )
         .get_matches();
     let command = value_t!(matches, "command", String);
     let is_restricted = matches.is_present("restricted");
     let timeout_secs = value_t!(matches, "timeout", u64);
+    console::set_json_output(matches.is_present("json"));
+    let script = match matches.value_of("script") {
+        Some(path) => {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            Some(script::parse_script(&content)?)
+        }
+        None => None,
+    };
 
     topstack::get_runtime().block_on(async move {
         // Connect to D-Bus system bus.
@@ -387,12 +582,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             client_commands_with_callbacks,
         )));
 
+        // Listen to events before the initial adapter state is read so scripts see it.
+        let script_events_rx = if script.is_some() {
+            let (events_tx, events_rx) = mpsc::unbounded_channel::<ScriptEvent>();
+            context.lock().unwrap().script_events = Some(events_tx);
+            Some(events_rx)
+        } else {
+            None
+        };
+
         // Check if manager interface is valid. We only print some help text before failing on the
         // first actual access to the interface (so we can also capture the actual reason the
         // interface isn't valid).
         if !context.lock().unwrap().manager_dbus.is_valid() {
-            println!("Bluetooth manager doesn't seem to be working correctly.");
-            println!("Check if service is running.");
+            print_error!("Bluetooth manager doesn't seem to be working correctly.");
+            print_error!("Check if service is running.");
             return Ok(());
         }
 
@@ -423,8 +627,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
+        // Scripts start right away, they can wait for the adapter themselves.
+        if let (Some(steps), Some(events_rx)) = (script, script_events_rx) {
+            let fg = tx.clone();
+            tokio::spawn(async move {
+                let result = script::run_script(steps, fg.clone(), events_rx).await;
+                let _ = fg.send(ForegroundActions::ScriptDone(result)).await;
+            });
+        }
+
         let handler = CommandHandler::new(context.clone());
-        if let Ok(_) = command {
+        let is_script = context.lock().unwrap().script_events.is_some();
+        if command.is_ok() || is_script {
             // Timeout applies only to non-interactive commands.
             if let Ok(timeout_secs) = timeout_secs {
                 let timeout_duration = Duration::from_secs(timeout_secs);
@@ -434,8 +648,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 )
                 .await
                 {
-                    Ok(_) => {
-                        return Result::Ok(());
+                    Ok(result) => {
+                        return result;
                     }
                     Err(_) => {
                         return Result::Err("btclient timeout".into());
@@ -468,9 +682,11 @@ async fn handle_client_command(
     command: Result<String, clap::Error>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let semaphore_fg = Arc::new(tokio::sync::Semaphore::new(1));
+    let is_script = context.lock().unwrap().script_events.is_some();
+    let mut script_result: Result<(), String> = Ok(());
 
-    // If there are no command arguments, start the interactive shell.
-    if let Err(_) = command {
+    // If there are no command arguments nor script, start the interactive shell.
+    if command.is_err() && !is_script {
         let command_rule_list = handler.get_command_rule_list().clone();
         let context_for_closure = context.clone();
 
@@ -513,7 +729,7 @@ async fn handle_client_command(
                         .unwrap()
                         .connect_all_enabled_profiles(device);
                 } else {
-                    println!("Adapter isn't ready to connect profiles.");
+                    print_error!("Adapter isn't ready to connect profiles.");
                 }
             }
             ForegroundActions::RunCallback(callback) => {
@@ -526,6 +742,11 @@ async fn handle_client_command(
             }
             // Once adapter is ready, register callbacks, get the address and mark it as ready
             ForegroundActions::RegisterAdapterCallback(adapter) => {
+                // The adapter may have been changed since the registration was scheduled.
+                if context.lock().unwrap().callback_adapter_name != adapter {
+                    continue;
+                }
+
                 let cb_objpath: String =
                     format!("/org/chromium/bluetooth/client/{}/bluetooth_callback", adapter);
                 let conn_cb_objpath: String =
@@ -548,6 +769,22 @@ async fn handle_client_command(
                     "/org/chromium/bluetooth/client/{}/bluetooth_telephony_callback",
                     adapter
                 );
+                let battery_manager_cb_objpath: String =
+                    format!("/org/chromium/bluetooth/client/{}/battery_manager_callback", adapter);
+
+                context.lock().unwrap().callback_objpaths = vec![
+                    cb_objpath.clone(),
+                    conn_cb_objpath.clone(),
+                    suspend_cb_objpath.clone(),
+                    scanner_cb_objpath.clone(),
+                    advertiser_cb_objpath.clone(),
+                    admin_cb_objpath.clone(),
+                    socket_manager_cb_objpath.clone(),
+                    qa_cb_objpath.clone(),
+                    media_cb_objpath.clone(),
+                    telephony_cb_objpath.clone(),
+                    battery_manager_cb_objpath.clone(),
+                ];
 
                 let dbus_connection = context.lock().unwrap().dbus_connection.clone();
                 let dbus_crossroads = context.lock().unwrap().dbus_crossroads.clone();
@@ -670,7 +907,6 @@ async fn handle_client_command(
                 context.lock().unwrap().qa_callback_id = Some(qa_callback_id);
 
                 // When adapter is ready, Suspend API is also ready. Register as an observer.
-                // TODO(b/224606285): Implement suspend debug utils in btclient.
                 context.lock().unwrap().suspend_dbus.as_mut().unwrap().register_callback(Box::new(
                     SuspendCallback::new(
                         suspend_cb_objpath,
@@ -711,11 +947,35 @@ async fn handle_client_command(
                     .await
                     .expect("D-Bus error on IBluetoothMedia::RegisterTelephonyCallback");
 
+                // The battery manager is exported separately from the adapter, so a failure here
+                // only disables the battery updates.
+                let battery_manager_callback_id = context
+                    .lock()
+                    .unwrap()
+                    .battery_manager_dbus
+                    .as_mut()
+                    .unwrap()
+                    .rpc
+                    .register_battery_callback(Box::new(BatteryManagerCallback::new(
+                        battery_manager_cb_objpath,
+                        dbus_connection.clone(),
+                        dbus_crossroads.clone(),
+                    )))
+                    .await;
+                match battery_manager_callback_id {
+                    Ok(id) => context.lock().unwrap().battery_manager_callback_id = Some(id),
+                    Err(e) => print_error!("Failed to register battery manager callback: {:?}", e),
+                }
+
                 context.lock().unwrap().adapter_ready = true;
                 let adapter_address = context.lock().unwrap().update_adapter_address();
                 context.lock().unwrap().update_bonded_devices();
 
                 print_info!("Adapter {} is ready", adapter_address);
+                context
+                    .lock()
+                    .unwrap()
+                    .notify_script_event(ScriptEvent::AdapterReady(adapter_address));
 
                 // Run the command with the command arguments as the client is
                 // non-interactive.
@@ -734,6 +994,13 @@ async fn handle_client_command(
                     }
                 }
             }
+            ForegroundActions::ScriptCommand(cmd, args, done) => {
+                let _ = done.send(handler.process_cmd_line(&cmd, &args));
+            }
+            ForegroundActions::ScriptDone(result) => {
+                script_result = result;
+                break;
+            }
             ForegroundActions::Readline(result) => match result {
                 Err(rustyline::error::ReadlineError::Interrupted) => {
                     // Ctrl-C cancels the currently typed line, do nothing and ready to do next
@@ -781,5 +1048,5 @@ async fn handle_client_command(
     semaphore_fg.close();
 
     print_info!("Client exiting");
-    Ok(())
+    script_result.map_err(|e| e.into())
 }
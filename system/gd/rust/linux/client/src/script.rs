@@ -0,0 +1,317 @@
+//! Non-interactive script mode.
+//!
+//! A script is a list of btclient commands, one per line or separated by `;`. Lines starting with
+//! `#` are comments. On top of the regular commands, scripts can use:
+//!
+//! * `wait <event> [args...] [timeout]` - Waits for an event whose arguments start with `args`.
+//!   Events received since the previous `wait` are also considered. The timeout is given with a
+//!   unit, e.g. `500ms` or `30s`, and defaults to 10 seconds.
+//! * `sleep <duration>` - Pauses the script.
+//!
+//! The script stops at the first failed command or wait that times out, and btclient exits with a
+//! non-zero code.
+
+use std::time::Duration;
+
+use bt_topshim::btif::BtBondState;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{sleep, timeout};
+
+use crate::ForegroundActions;
+
+/// Timeout of `wait` when none is given.
+const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Names of the events scripts can wait for, with their arguments.
+const EVENT_USAGE: &[&str] = &[
+    "adapter-enabled <hci>",
+    "adapter-disabled <hci>",
+    "adapter-ready <address>",
+    "discovering <true|false>",
+    "device-found <address>",
+    "bond-state <none|bonding|bonded> <address>",
+    "device-connected <address>",
+    "device-disconnected <address>",
+];
+
+/// An event from the callbacks that scripts can wait for.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ScriptEvent {
+    AdapterEnabled(i32),
+    AdapterDisabled(i32),
+    AdapterReady(String),
+    Discovering(bool),
+    DeviceFound(String),
+    BondState(BtBondState, String),
+    DeviceConnected(String),
+    DeviceDisconnected(String),
+}
+
+impl ScriptEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            ScriptEvent::AdapterEnabled(_) => "adapter-enabled",
+            ScriptEvent::AdapterDisabled(_) => "adapter-disabled",
+            ScriptEvent::AdapterReady(_) => "adapter-ready",
+            ScriptEvent::Discovering(_) => "discovering",
+            ScriptEvent::DeviceFound(_) => "device-found",
+            ScriptEvent::BondState(_, _) => "bond-state",
+            ScriptEvent::DeviceConnected(_) => "device-connected",
+            ScriptEvent::DeviceDisconnected(_) => "device-disconnected",
+        }
+    }
+
+    fn args(&self) -> Vec<String> {
+        match self {
+            ScriptEvent::AdapterEnabled(hci) | ScriptEvent::AdapterDisabled(hci) => {
+                vec![hci.to_string()]
+            }
+            ScriptEvent::Discovering(discovering) => vec![discovering.to_string()],
+            ScriptEvent::BondState(state, address) => {
+                let state = match state {
+                    BtBondState::NotBonded => "none",
+                    BtBondState::Bonding => "bonding",
+                    BtBondState::Bonded => "bonded",
+                };
+                vec![state.to_string(), address.clone()]
+            }
+            ScriptEvent::AdapterReady(address)
+            | ScriptEvent::DeviceFound(address)
+            | ScriptEvent::DeviceConnected(address)
+            | ScriptEvent::DeviceDisconnected(address) => vec![address.clone()],
+        }
+    }
+
+    /// Whether this is the |name| event and its arguments start with |args|.
+    fn matches(&self, name: &str, args: &[String]) -> bool {
+        let event_args = self.args();
+        self.name() == name
+            && args.len() <= event_args.len()
+            && args.iter().zip(event_args.iter()).all(|(a, e)| a.eq_ignore_ascii_case(e))
+    }
+}
+
+/// A step of a script.
+#[derive(Debug, PartialEq)]
+pub(crate) enum ScriptStep {
+    Command(String, Vec<String>),
+    Wait { event: String, args: Vec<String>, timeout: Duration },
+    Sleep(Duration),
+}
+
+/// Parses a duration with a unit, e.g. "500ms" or "5s".
+fn parse_duration(value: &str) -> Option<Duration> {
+    if let Some(ms) = value.strip_suffix("ms") {
+        return ms.parse::<u64>().ok().map(Duration::from_millis);
+    }
+    value.strip_suffix('s')?.parse::<u64>().ok().map(Duration::from_secs)
+}
+
+fn parse_step(cmd: &str, args: &[String]) -> Result<ScriptStep, String> {
+    match cmd {
+        "wait" => {
+            let (event, rest) = args.split_first().ok_or("Usage: wait <event> [args] [timeout]")?;
+            if !EVENT_USAGE.iter().any(|usage| usage.split(' ').next() == Some(event.as_str())) {
+                return Err(format!(
+                    "Unknown event '{}'. Events are:\n  {}",
+                    event,
+                    EVENT_USAGE.join("\n  ")
+                ));
+            }
+            let (args, timeout) = match rest.split_last() {
+                Some((last, init)) => match parse_duration(last) {
+                    Some(timeout) => (init.to_vec(), timeout),
+                    None => (rest.to_vec(), DEFAULT_WAIT_TIMEOUT),
+                },
+                None => (vec![], DEFAULT_WAIT_TIMEOUT),
+            };
+            Ok(ScriptStep::Wait { event: event.clone(), args, timeout })
+        }
+        "sleep" => match args {
+            [duration] => parse_duration(duration)
+                .map(ScriptStep::Sleep)
+                .ok_or(format!("Invalid duration '{}'", duration)),
+            _ => Err("Usage: sleep <duration>".into()),
+        },
+        _ => Ok(ScriptStep::Command(cmd.to_string(), args.to_vec())),
+    }
+}
+
+/// Splits a line into statements on the `;` that are not quoted or escaped, following the quoting
+/// rules of shell_words.
+fn split_statements(line: &str) -> Vec<&str> {
+    let mut statements = vec![];
+    let mut start = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (quote, c) {
+            // Nothing is special inside single quotes.
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => (),
+            (_, '\\') => escaped = true,
+            (Some(_), '"') => quote = None,
+            (Some(_), _) => (),
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (None, ';') => {
+                statements.push(&line[start..i]);
+                start = i + 1;
+            }
+            (None, _) => (),
+        }
+    }
+    statements.push(&line[start..]);
+    statements
+}
+
+/// Parses a script into its steps, along with their line numbers.
+pub(crate) fn parse_script(script: &str) -> Result<Vec<(usize, ScriptStep)>, String> {
+    let mut steps = vec![];
+    for (index, line) in script.lines().enumerate() {
+        let line_number = index + 1;
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        for statement in split_statements(line) {
+            let words = shell_words::split(statement)
+                .map_err(|e| format!("Line {}: {}", line_number, e))?;
+            if let Some((cmd, args)) = words.split_first() {
+                let step =
+                    parse_step(cmd, args).map_err(|e| format!("Line {}: {}", line_number, e))?;
+                steps.push((line_number, step));
+            }
+        }
+    }
+    Ok(steps)
+}
+
+/// Waits for a matching event. Events that don't match are dropped.
+async fn wait_for_event(
+    events: &mut mpsc::UnboundedReceiver<ScriptEvent>,
+    name: &str,
+    args: &[String],
+) -> bool {
+    while let Some(event) = events.recv().await {
+        if event.matches(name, args) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Runs the script steps. Commands are run on the foreground loop so they behave the same as in
+/// the interactive shell.
+pub(crate) async fn run_script(
+    steps: Vec<(usize, ScriptStep)>,
+    fg: mpsc::Sender<ForegroundActions>,
+    mut events: mpsc::UnboundedReceiver<ScriptEvent>,
+) -> Result<(), String> {
+    for (line, step) in steps {
+        match step {
+            ScriptStep::Command(cmd, args) => {
+                let (tx, rx) = oneshot::channel::<bool>();
+                let command_line = format!("{} {}", cmd, args.join(" "));
+                if fg.send(ForegroundActions::ScriptCommand(cmd, args, tx)).await.is_err() {
+                    return Err("Client exited".into());
+                }
+                if !rx.await.unwrap_or(false) {
+                    return Err(format!("Line {}: '{}' failed", line, command_line.trim_end()));
+                }
+            }
+            ScriptStep::Wait { event, args, timeout: duration } => {
+                if !timeout(duration, wait_for_event(&mut events, &event, &args))
+                    .await
+                    .unwrap_or(false)
+                {
+                    return Err(format!(
+                        "Line {}: Timed out after {:?} waiting for {} {}",
+                        line,
+                        duration,
+                        event,
+                        args.join(" ")
+                    ));
+                }
+            }
+            ScriptStep::Sleep(duration) => sleep(duration).await,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_script() {
+        let steps = parse_script(
+            "# Bond and connect\n\
+             adapter enable; wait adapter-enabled 5s\n\
+             \n\
+             bond add AA:BB:CC:DD:EE:FF\n\
+             wait bond-state bonded 30s\n\
+             sleep 500ms\n\
+             wait device-connected AA:BB:CC:DD:EE:FF\n\
+             socket send \"a;b\" 'c;d' e\\;f; sleep 1s",
+        )
+        .unwrap();
+        assert_eq!(
+            steps,
+            vec![
+                (2, ScriptStep::Command("adapter".into(), strings(&["enable"]))),
+                (
+                    2,
+                    ScriptStep::Wait {
+                        event: "adapter-enabled".into(),
+                        args: vec![],
+                        timeout: Duration::from_secs(5)
+                    }
+                ),
+                (4, ScriptStep::Command("bond".into(), strings(&["add", "AA:BB:CC:DD:EE:FF"]))),
+                (
+                    5,
+                    ScriptStep::Wait {
+                        event: "bond-state".into(),
+                        args: strings(&["bonded"]),
+                        timeout: Duration::from_secs(30)
+                    }
+                ),
+                (6, ScriptStep::Sleep(Duration::from_millis(500))),
+                (
+                    7,
+                    ScriptStep::Wait {
+                        event: "device-connected".into(),
+                        args: strings(&["AA:BB:CC:DD:EE:FF"]),
+                        timeout: DEFAULT_WAIT_TIMEOUT
+                    }
+                ),
+                (8, ScriptStep::Command("socket".into(), strings(&["send", "a;b", "c;d", "e;f"]))),
+                (8, ScriptStep::Sleep(Duration::from_secs(1))),
+            ]
+        );
+
+        assert!(parse_script("wait adapter-exploded").unwrap_err().starts_with("Line 1:"));
+        assert!(parse_script("adapter enable\nsleep forever").unwrap_err().starts_with("Line 2:"));
+    }
+
+    #[test]
+    fn test_event_matches() {
+        let event = ScriptEvent::BondState(BtBondState::Bonded, "AA:BB:CC:DD:EE:FF".into());
+        assert!(event.matches("bond-state", &[]));
+        assert!(event.matches("bond-state", &strings(&["bonded"])));
+        assert!(event.matches("bond-state", &strings(&["bonded", "aa:bb:cc:dd:ee:ff"])));
+        assert!(!event.matches("bond-state", &strings(&["bonding"])));
+        assert!(!event.matches("bond-state", &strings(&["bonded", "AA:BB:CC:DD:EE:FF", "x"])));
+        assert!(!event.matches("device-connected", &[]));
+        assert!(ScriptEvent::AdapterEnabled(1).matches("adapter-enabled", &strings(&["1"])));
+        assert!(!ScriptEvent::AdapterEnabled(1).matches("adapter-enabled", &strings(&["0"])));
+    }
+}
@@ -1,36 +1,43 @@
-use log::{error, warn};
+use log::{error, info, warn};
 
 use std::collections::HashMap;
-use std::process::Command;
 use std::sync::{Arc, Mutex};
 
-use crate::powerd_suspend_manager::SuspendManagerContext;
+use crate::rfkill::RfkillState;
+use crate::suspend_manager::SuspendManagerState;
 
 use crate::iface_bluetooth_experimental::IBluetoothExperimental;
 use crate::iface_bluetooth_manager::{
-    AdapterWithEnabled, IBluetoothManager, IBluetoothManagerCallback,
+    AdapterHealth, AdapterWithEnabled, BondBackupExport, BondBackupStatus, DeviceMigrationReport,
+    IBluetoothManager, IBluetoothManagerCallback,
 };
 use crate::state_machine::{
-    state_to_enabled, AdapterState, Message, ProcessState, StateMachineProxy, VirtualHciIndex,
+    state_to_enabled, AdapterState, Invoker, Message, ProcessManager, ProcessState,
+    StateMachineProxy, VirtualHciIndex,
 };
-use crate::{config_util, migrate};
+use crate::{bond_backup, config_util, migrate};
 
-const BLUEZ_INIT_TARGET: &str = "bluetoothd";
 const INVALID_VER: u16 = 0xffff;
 
 /// Implementation of IBluetoothManager.
 pub struct BluetoothManager {
     proxy: StateMachineProxy,
     callbacks: HashMap<u32, Box<dyn IBluetoothManagerCallback + Send>>,
-    suspend_manager_context: Option<Arc<Mutex<SuspendManagerContext>>>,
+    suspend_manager_context: Option<Arc<Mutex<dyn SuspendManagerState>>>,
+    process_manager: Box<dyn ProcessManager + Send>,
 }
 
 impl BluetoothManager {
-    pub fn new(proxy: StateMachineProxy) -> BluetoothManager {
-        BluetoothManager { proxy, callbacks: HashMap::new(), suspend_manager_context: None }
+    pub fn new(proxy: StateMachineProxy, invoker: Invoker) -> BluetoothManager {
+        BluetoothManager {
+            proxy,
+            callbacks: HashMap::new(),
+            suspend_manager_context: None,
+            process_manager: invoker.make_process_manager(),
+        }
     }
 
-    pub fn set_suspend_manager_context(&mut self, context: Arc<Mutex<SuspendManagerContext>>) {
+    pub fn set_suspend_manager_context(&mut self, context: Arc<Mutex<dyn SuspendManagerState>>) {
         self.suspend_manager_context = Some(context);
     }
 
@@ -42,6 +49,17 @@ impl BluetoothManager {
         self.proxy.get_state(hci_device, move |a| Some(a.present)).unwrap_or(false)
     }
 
+    /// Address of the default adapter, known once its controller information was read.
+    fn default_adapter_address(&mut self) -> Option<String> {
+        let default_adapter = self.proxy.get_default_adapter();
+        self.proxy.get_state(default_adapter, move |a| a.get_address())
+    }
+
+    /// Whether |address| is the address of the default adapter.
+    fn is_default_adapter_address(&mut self, address: &str) -> bool {
+        self.default_adapter_address().map_or(false, |a| a.eq_ignore_ascii_case(address))
+    }
+
     pub(crate) fn callback_hci_device_change(&mut self, hci: VirtualHciIndex, present: bool) {
         if present {
             warn!("Presence added: {}", hci);
@@ -71,6 +89,28 @@ impl BluetoothManager {
         }
     }
 
+    pub(crate) fn callback_adapter_health_change(&mut self, health: AdapterHealth) {
+        if health.failed {
+            error!("hci{} failed, gave up restarting it", health.hci_interface);
+        }
+
+        for (_, callback) in &mut self.callbacks {
+            callback.on_adapter_health_changed(health.clone());
+        }
+    }
+
+    pub(crate) fn callback_hci_rfkill_change(&mut self, hci: VirtualHciIndex, rfkill: RfkillState) {
+        if rfkill.is_blocked() {
+            warn!("Blocked by rfkill {}: {:?}", hci, rfkill);
+        } else {
+            info!("Unblocked by rfkill {}", hci);
+        }
+
+        for (_, callback) in &mut self.callbacks {
+            callback.on_hci_rfkill_changed(hci.to_i32(), rfkill.soft, rfkill.hard);
+        }
+    }
+
     pub(crate) fn callback_disconnected(&mut self, id: u32) {
         self.callbacks.remove(&id);
     }
@@ -108,6 +148,11 @@ impl IBluetoothManager for BluetoothManager {
             return;
         }
 
+        if self.proxy.get_state(hci, move |a| Some(a.rfkill.is_blocked())).unwrap_or(false) {
+            warn!("{} is blocked by rfkill, it will start once unblocked.", hci);
+            return;
+        }
+
         self.proxy.start_bluetooth(hci);
     }
 
@@ -122,8 +167,11 @@ impl IBluetoothManager for BluetoothManager {
         // Store that this adapter is meant to be stopped in state machine.
         self.proxy.modify_state(hci, move |a: &mut AdapterState| a.config_enabled = false);
 
-        // Ignore the request if adapter is already disabled.
-        if !self.is_adapter_enabled(hci) {
+        // Ignore the request if adapter is already disabled, unless a restart after a crash is
+        // still waiting on backoff and needs to be cancelled.
+        let waiting_on_backoff =
+            self.proxy.get_state(hci, |a: &AdapterState| a.restart_history.pending_delay).is_some();
+        if !self.is_adapter_enabled(hci) && !waiting_on_backoff {
             warn!("{} is already stopped", hci);
             return;
         }
@@ -158,10 +206,10 @@ impl IBluetoothManager for BluetoothManager {
         config_util::write_floss_enabled(enabled);
 
         if prev != enabled && enabled {
-            if let Err(e) = Command::new("initctl").args(&["stop", BLUEZ_INIT_TARGET]).output() {
-                warn!("Failed to stop bluetoothd: {}", e);
+            if !self.process_manager.stop_bluez() {
+                warn!("Failed to stop BlueZ");
             }
-            migrate::migrate_bluez_devices();
+            migrate::migrate_bluez_devices(self.default_adapter_address().as_deref());
             for hci in self.proxy.get_valid_adapters().iter().map(|a| a.virt_hci) {
                 if config_util::is_hci_n_enabled(hci) {
                     self.proxy.start_bluetooth(hci);
@@ -174,8 +222,8 @@ impl IBluetoothManager for BluetoothManager {
                 }
             }
             migrate::migrate_floss_devices();
-            if let Err(e) = Command::new("initctl").args(&["start", BLUEZ_INIT_TARGET]).output() {
-                warn!("Failed to start bluetoothd: {}", e);
+            if !self.process_manager.start_bluez() {
+                warn!("Failed to start BlueZ");
             }
         }
     }
@@ -186,9 +234,20 @@ impl IBluetoothManager for BluetoothManager {
             .iter()
             // Don't present the queued device to the user.
             .filter(|a| !a.has_queued_present)
-            .map(|a| AdapterWithEnabled {
-                hci_interface: a.virt_hci.to_i32(),
-                enabled: state_to_enabled(a.state),
+            // Blocked devices can't be used until unblocked.
+            .filter(|a| !a.rfkill.is_blocked())
+            .map(|a| {
+                let enabled = state_to_enabled(a.state);
+                let launch_profile = match (enabled, &a.active_launch_profile) {
+                    (true, Some(active)) => active.clone(),
+                    _ => a.launch_profile.clone(),
+                };
+                AdapterWithEnabled {
+                    hci_interface: a.virt_hci.to_i32(),
+                    enabled,
+                    launch_profile,
+                    controller: a.to_controller_info(),
+                }
             })
             .collect::<Vec<AdapterWithEnabled>>()
     }
@@ -209,10 +268,71 @@ impl IBluetoothManager for BluetoothManager {
 
     fn set_tablet_mode(&mut self, tablet_mode: bool) {
         match &self.suspend_manager_context {
-            Some(ctx) => ctx.lock().unwrap().tablet_mode = tablet_mode,
+            Some(ctx) => ctx.lock().unwrap().set_tablet_mode(tablet_mode),
             None => warn!("Context not available to set tablet mode."),
         }
     }
+
+    fn get_adapter_health(&mut self, hci_interface: i32) -> AdapterHealth {
+        let hci = VirtualHciIndex(hci_interface);
+        self.proxy
+            .get_state(hci, move |a| Some(a.restart_history.to_adapter_health(hci)))
+            .unwrap_or(AdapterHealth { hci_interface, ..Default::default() })
+    }
+
+    fn get_migration_report(&mut self, floss_enabled: bool) -> Vec<DeviceMigrationReport> {
+        if floss_enabled {
+            migrate::dry_run_bluez_devices(self.default_adapter_address().as_deref())
+        } else {
+            migrate::dry_run_floss_devices()
+        }
+    }
+
+    fn export_bonded_devices(&mut self, adapter_address: String, key: Vec<u8>) -> BondBackupExport {
+        let floss = self.proxy.get_floss_enabled();
+        let is_default = self.is_default_adapter_address(&adapter_address);
+        match bond_backup::export_bonds(floss, &adapter_address, is_default, &key) {
+            Ok(backup) => BondBackupExport { status: BondBackupStatus::Success, backup },
+            Err(e) => {
+                error!("Failed to export bonded devices of {}: {:?}", adapter_address, e);
+                BondBackupExport { status: e.into(), backup: vec![] }
+            }
+        }
+    }
+
+    fn import_bonded_devices(
+        &mut self,
+        adapter_address: String,
+        backup: Vec<u8>,
+        key: Vec<u8>,
+    ) -> BondBackupStatus {
+        let floss = self.proxy.get_floss_enabled();
+        if floss {
+            // The adapter would overwrite the imported devices with the ones it has in memory.
+            if self.proxy.get_valid_adapters().iter().any(|a| state_to_enabled(a.state)) {
+                warn!("Adapters must be stopped to import bonded devices");
+                return BondBackupStatus::StackBusy;
+            }
+        } else if !self.process_manager.stop_bluez() {
+            warn!("Failed to stop BlueZ");
+            return BondBackupStatus::StackBusy;
+        }
+
+        let is_default = self.is_default_adapter_address(&adapter_address);
+        let result = bond_backup::import_bonds(floss, &adapter_address, is_default, &backup, &key);
+
+        if !floss && !self.process_manager.start_bluez() {
+            warn!("Failed to start BlueZ");
+        }
+
+        match result {
+            Ok(()) => BondBackupStatus::Success,
+            Err(e) => {
+                error!("Failed to import bonded devices to {}: {:?}", adapter_address, e);
+                e.into()
+            }
+        }
+    }
 }
 
 /// Implementation of IBluetoothExperimental
@@ -1,20 +1,84 @@
 use dbus::arg::RefArg;
+use dbus::nonblock::SyncConnection;
 use dbus::strings::Path;
 use dbus_crossroads;
 use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj, generate_dbus_exporter};
 use dbus_projection::prelude::*;
+use num_traits::{FromPrimitive, ToPrimitive};
+use std::sync::Arc;
 
+use bt_utils::socket::MgmtControllerBus;
 use btstack::RPCProxy;
 
 use crate::dbus_arg::{DBusArg, DBusArgError, RefArgToRust};
 use crate::iface_bluetooth_manager::{
-    AdapterWithEnabled, BluetoothManagerMixin, IBluetoothManager, IBluetoothManagerCallback,
+    AdapterControllerInfo, AdapterDebugLevel, AdapterHealth, AdapterLaunchProfile,
+    AdapterWithEnabled, BluetoothManagerMixin, BondBackupExport, BondBackupStatus,
+    DeviceMigrationAction, DeviceMigrationReport, FeatureOverride, IBluetoothManager,
+    IBluetoothManagerCallback, RestartEvent, RestartReason,
 };
 
+impl_dbus_arg_enum!(AdapterDebugLevel);
+impl_dbus_arg_enum!(BondBackupStatus);
+impl_dbus_arg_enum!(DeviceMigrationAction);
+impl_dbus_arg_enum!(FeatureOverride);
+impl_dbus_arg_enum!(MgmtControllerBus);
+impl_dbus_arg_enum!(RestartReason);
+
+#[dbus_propmap(AdapterLaunchProfile)]
+pub struct AdapterLaunchProfileDbus {
+    init_flags: Vec<String>,
+    debug_level: AdapterDebugLevel,
+    ll_privacy: FeatureOverride,
+    devcoredump: FeatureOverride,
+}
+
+#[dbus_propmap(AdapterControllerInfo)]
+pub struct AdapterControllerInfoDbus {
+    address: String,
+    manufacturer: u16,
+    bus: Option<MgmtControllerBus>,
+    supported_settings: u32,
+    current_settings: u32,
+    last_error: Option<u8>,
+}
+
 #[dbus_propmap(AdapterWithEnabled)]
 pub struct AdapterWithEnabledDbus {
     hci_interface: i32,
     enabled: bool,
+    launch_profile: AdapterLaunchProfile,
+    controller: Option<AdapterControllerInfo>,
+}
+
+#[dbus_propmap(RestartEvent)]
+pub struct RestartEventDbus {
+    timestamp_ms: u64,
+    reason: RestartReason,
+}
+
+#[dbus_propmap(AdapterHealth)]
+pub struct AdapterHealthDbus {
+    hci_interface: i32,
+    failed: bool,
+    reset_count: u32,
+    restart_delay_ms: u64,
+    restart_history: Vec<RestartEvent>,
+}
+
+#[dbus_propmap(DeviceMigrationReport)]
+pub struct DeviceMigrationReportDbus {
+    adapter: String,
+    address: String,
+    action: DeviceMigrationAction,
+    converted_keys: Vec<String>,
+    errors: Vec<String>,
+}
+
+#[dbus_propmap(BondBackupExport)]
+pub struct BondBackupExportDbus {
+    status: BondBackupStatus,
+    backup: Vec<u8>,
 }
 
 /// D-Bus projection of IBluetoothManager.
@@ -81,6 +145,31 @@ impl IBluetoothManager for BluetoothManagerDBus {
     fn set_tablet_mode(&mut self, tablet_mode: bool) {
         dbus_generated!()
     }
+
+    #[dbus_method("GetAdapterHealth")]
+    fn get_adapter_health(&mut self, hci_interface: i32) -> AdapterHealth {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetMigrationReport")]
+    fn get_migration_report(&mut self, floss_enabled: bool) -> Vec<DeviceMigrationReport> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("ExportBondedDevices", DBusLog::Disable)]
+    fn export_bonded_devices(&mut self, adapter_address: String, key: Vec<u8>) -> BondBackupExport {
+        dbus_generated!()
+    }
+
+    #[dbus_method("ImportBondedDevices", DBusLog::Disable)]
+    fn import_bonded_devices(
+        &mut self,
+        adapter_address: String,
+        backup: Vec<u8>,
+        key: Vec<u8>,
+    ) -> BondBackupStatus {
+        dbus_generated!()
+    }
 }
 
 /// D-Bus projection of IBluetoothManagerCallback.
@@ -96,4 +185,16 @@ impl IBluetoothManagerCallback for BluetoothManagerCallbackDBus {
 
     #[dbus_method("OnDefaultAdapterChanged")]
     fn on_default_adapter_changed(&mut self, hci_interface: i32) {}
+
+    #[dbus_method("OnAdapterHealthChanged")]
+    fn on_adapter_health_changed(&mut self, health: AdapterHealth) {}
+
+    #[dbus_method("OnHciRfkillChanged")]
+    fn on_hci_rfkill_changed(
+        &mut self,
+        hci_interface: i32,
+        soft_blocked: bool,
+        hard_blocked: bool,
+    ) {
+    }
 }
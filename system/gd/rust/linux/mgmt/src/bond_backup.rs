@@ -0,0 +1,590 @@
+//! The bond_backup module exports the bonded devices of an adapter to an encrypted backup and
+//! imports them back, so they survive replacing the hardware or reimaging the machine.
+//!
+//! A backup is stack-neutral: it can be exported from and imported into either BlueZ or Floss,
+//! possibly on another adapter. Device keys are stored with the Floss names and values, which the
+//! migrate module knows how to convert to BlueZ.
+//!
+//! The backup is a JSON envelope holding the encrypted contents:
+//!   {"version": 1, "nonce": <base64>, "data": <base64>}
+//! The contents are encrypted with AES-256-GCM using a key supplied by the caller, and are:
+//!   {"version": 1, "adapter": <address>, "devices": [{"address": <address>, "keys": {..}}]}
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use configparser::ini::Ini;
+use glob::glob;
+use log::{info, warn};
+use serde_json::{json, Value};
+
+use crate::iface_bluetooth_manager::{
+    BondBackupStatus, DeviceMigrationAction, DeviceMigrationReport,
+};
+use crate::migrate;
+
+/// Version of the backup format. Backups of newer versions are rejected.
+pub const BACKUP_VERSION: u64 = 1;
+
+/// Size of the key backups are encrypted with.
+pub const BACKUP_KEY_SIZE: usize = 32;
+
+/// Size of the AES-GCM nonce.
+const NONCE_SIZE: usize = 12;
+
+/// Floss device keys kept in a backup.
+const BOND_KEYS: [&str; 23] = [
+    "Name",
+    "DevClass",
+    "Appearance",
+    "DevType",
+    "Service",
+    "AddrType",
+    "LinkKey",
+    "LinkKeyType",
+    "PinLength",
+    "SdpDiVendorIdSource",
+    "SdpDiManufacturer",
+    "SdpDiModel",
+    "SdpDiHardwareVersion",
+    "VendorIdSource",
+    "VendorId",
+    "ProductId",
+    "ProductVersion",
+    "LE_KEY_PID",
+    "LE_KEY_PENC",
+    "HidAttrMask",
+    "HidDescriptor",
+    "HidVersion",
+    "HidCountryCode",
+];
+
+/// Floss device keys of which at least one is needed for a device to be bonded.
+const BONDED_KEYS: [&str; 3] = ["LinkKey", "LE_KEY_PENC", "LE_KEY_PID"];
+
+#[derive(Debug, PartialEq)]
+pub enum BackupError {
+    /// The key isn't |BACKUP_KEY_SIZE| bytes.
+    InvalidKey,
+    /// An adapter or device address isn't a valid Bluetooth address.
+    InvalidAddress(String),
+    /// The backup is malformed.
+    InvalidFormat(String),
+    /// The backup was made with a newer format.
+    UnsupportedVersion(u64),
+    /// The backup couldn't be decrypted, because of either a wrong key or tampering.
+    DecryptionFailed,
+    /// The storage of the stack couldn't be read or written.
+    Io(String),
+    /// Some keys of these devices couldn't be converted. The rest of the backup was imported.
+    PartiallyImported(Vec<String>),
+}
+
+impl From<BackupError> for BondBackupStatus {
+    fn from(err: BackupError) -> Self {
+        match err {
+            BackupError::InvalidKey => BondBackupStatus::InvalidKey,
+            BackupError::InvalidAddress(_) => BondBackupStatus::InvalidAddress,
+            BackupError::InvalidFormat(_) | BackupError::UnsupportedVersion(_) => {
+                BondBackupStatus::InvalidBackup
+            }
+            BackupError::DecryptionFailed => BondBackupStatus::DecryptionFailed,
+            BackupError::Io(_) => BondBackupStatus::StorageError,
+            BackupError::PartiallyImported(_) => BondBackupStatus::PartiallyImported,
+        }
+    }
+}
+
+/// A bonded device in a backup.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BondedDevice {
+    /// Lower case address of the device.
+    pub address: String,
+    /// Floss device keys and their values.
+    pub keys: BTreeMap<String, String>,
+}
+
+/// The bonded devices of an adapter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BondBackup {
+    /// Lower case address of the adapter the backup was made from.
+    pub adapter: String,
+    pub devices: Vec<BondedDevice>,
+}
+
+/// Checks that |addr| looks like "00:11:22:33:44:55". Addresses end up in storage paths.
+fn is_valid_address(addr: &str) -> bool {
+    let octets: Vec<&str> = addr.split(':').collect();
+    octets.len() == 6
+        && octets.iter().all(|o| o.len() == 2 && o.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+impl BondedDevice {
+    /// Keeps the backed up keys of a Floss device section. Returns None if it isn't bonded.
+    fn from_floss_section(address: &str, props: &HashMap<String, Option<String>>) -> Option<Self> {
+        let keys: BTreeMap<String, String> = props
+            .iter()
+            .filter(|(k, _)| BOND_KEYS.contains(&k.as_str()))
+            .filter_map(|(k, v)| v.as_ref().map(|v| (k.clone(), v.clone())))
+            .collect();
+
+        if !BONDED_KEYS.iter().any(|k| keys.contains_key(*k)) {
+            return None;
+        }
+
+        Some(BondedDevice { address: address.to_lowercase(), keys })
+    }
+}
+
+impl BondBackup {
+    /// Collects the bonded devices from Floss style sections, sorted by address.
+    fn from_floss_map(
+        adapter: &str,
+        map: &HashMap<String, HashMap<String, Option<String>>>,
+    ) -> Self {
+        let mut devices: Vec<BondedDevice> = map
+            .iter()
+            .filter(|(sec, _)| is_valid_address(sec))
+            .filter_map(|(sec, props)| BondedDevice::from_floss_section(sec, props))
+            .collect();
+        devices.sort_by(|a, b| a.address.cmp(&b.address));
+
+        BondBackup { adapter: adapter.to_lowercase(), devices }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "version": BACKUP_VERSION,
+            "adapter": self.adapter,
+            "devices": self
+                .devices
+                .iter()
+                .map(|d| json!({ "address": d.address, "keys": d.keys }))
+                .collect::<Vec<Value>>(),
+        })
+    }
+
+    fn from_json(value: &Value) -> Result<Self, BackupError> {
+        let invalid = |what: &str| BackupError::InvalidFormat(format!("Missing {}", what));
+
+        let adapter = value["adapter"].as_str().ok_or_else(|| invalid("adapter"))?;
+        if !is_valid_address(adapter) {
+            return Err(BackupError::InvalidAddress(adapter.into()));
+        }
+
+        let mut devices = Vec::new();
+        for device in value["devices"].as_array().ok_or_else(|| invalid("devices"))? {
+            let address = device["address"].as_str().ok_or_else(|| invalid("device address"))?;
+            if !is_valid_address(address) {
+                return Err(BackupError::InvalidAddress(address.into()));
+            }
+            // Drop keys a newer version may have added or that don't belong in a backup.
+            let keys = device["keys"]
+                .as_object()
+                .ok_or_else(|| invalid("device keys"))?
+                .iter()
+                .filter(|(k, _)| BOND_KEYS.contains(&k.as_str()))
+                .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                .collect();
+            devices.push(BondedDevice { address: address.to_lowercase(), keys });
+        }
+
+        Ok(BondBackup { adapter: adapter.to_lowercase(), devices })
+    }
+
+    /// Encrypts the backup with |key|, which must be |BACKUP_KEY_SIZE| bytes.
+    pub fn encrypt(&self, key: &[u8]) -> Result<Vec<u8>, BackupError> {
+        let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| BackupError::InvalidKey)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let contents = self.to_json().to_string();
+        let aad = BACKUP_VERSION.to_string();
+        let data = cipher
+            .encrypt(&nonce, Payload { msg: contents.as_bytes(), aad: aad.as_bytes() })
+            .map_err(|_| BackupError::InvalidFormat("Backup too large".into()))?;
+
+        let envelope = json!({
+            "version": BACKUP_VERSION,
+            "nonce": base64::encode(nonce.as_slice()),
+            "data": base64::encode(&data),
+        });
+        Ok(envelope.to_string().into_bytes())
+    }
+
+    /// Decrypts a backup made by |encrypt| with the same |key|.
+    pub fn decrypt(backup: &[u8], key: &[u8]) -> Result<Self, BackupError> {
+        let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| BackupError::InvalidKey)?;
+        let envelope = serde_json::from_slice::<Value>(backup)
+            .map_err(|e| BackupError::InvalidFormat(e.to_string()))?;
+
+        let version = envelope["version"]
+            .as_u64()
+            .ok_or_else(|| BackupError::InvalidFormat("Missing version".into()))?;
+        if version > BACKUP_VERSION {
+            return Err(BackupError::UnsupportedVersion(version));
+        }
+
+        let decode = |field: &str| {
+            envelope[field]
+                .as_str()
+                .and_then(|v| base64::decode(v).ok())
+                .ok_or_else(|| BackupError::InvalidFormat(format!("Invalid {}", field)))
+        };
+        let nonce = decode("nonce")?;
+        if nonce.len() != NONCE_SIZE {
+            return Err(BackupError::InvalidFormat("Invalid nonce".into()));
+        }
+        let data = decode("data")?;
+
+        let aad = version.to_string();
+        let contents = cipher
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &data, aad: aad.as_bytes() })
+            .map_err(|_| BackupError::DecryptionFailed)?;
+        let value = serde_json::from_slice::<Value>(&contents)
+            .map_err(|e| BackupError::InvalidFormat(e.to_string()))?;
+
+        BondBackup::from_json(&value)
+    }
+}
+
+/// Reads the bonded devices of |adapter| from the BlueZ storage in |dir|.
+fn read_bluez_bonds(dir: &str, adapter: &str) -> Result<BondBackup, BackupError> {
+    let adapter_dir = format!("{}/{}", dir, adapter.to_uppercase());
+    if !Path::new(&adapter_dir).is_dir() {
+        return Err(BackupError::Io(format!("No BlueZ storage for adapter {}", adapter)));
+    }
+
+    let globbed = glob(format!("{}/*:*/info", adapter_dir).as_str())
+        .map_err(|e| BackupError::Io(e.to_string()))?;
+
+    // Convert to Floss, the format kept in backups
+    let mut floss_conf = Ini::new_cs();
+    for info_path in globbed.filter_map(|entry| entry.ok()) {
+        let device = match info_path.parent().and_then(|p| p.file_name()).and_then(|n| n.to_str()) {
+            Some(device) => device.to_string(),
+            None => continue,
+        };
+        let mut report =
+            DeviceMigrationReport::new(adapter, &device, DeviceMigrationAction::Migrated);
+        if !migrate::convert_from_bluez_device(
+            info_path.to_str().unwrap_or_default(),
+            &device,
+            &mut floss_conf,
+            /*is_hid_file=*/ false,
+            &mut report,
+        ) {
+            warn!("Leaving {} out of the backup: {}", device, report.errors.join(", "));
+            continue;
+        }
+
+        let hid_path = info_path.with_file_name("hog-uhid-cache");
+        if hid_path.exists() {
+            migrate::convert_from_bluez_device(
+                hid_path.to_str().unwrap_or_default(),
+                &device,
+                &mut floss_conf,
+                /*is_hid_file=*/ true,
+                &mut report,
+            );
+        }
+    }
+
+    Ok(BondBackup::from_floss_map(adapter, floss_conf.get_map_ref()))
+}
+
+/// Reads the bonded devices of |adapter| from the Floss storage in |dir|.
+fn read_floss_bonds(dir: &str, adapter: &str, is_default: bool) -> Result<BondBackup, BackupError> {
+    let path = migrate::floss_conf_for_adapter(dir, adapter, is_default);
+    let mut floss_conf = Ini::new_cs();
+    let map = floss_conf.load(&path).map_err(|e| BackupError::Io(format!("{}: {}", path, e)))?;
+
+    Ok(BondBackup::from_floss_map(adapter, &map))
+}
+
+/// Writes the devices of |backup| to |adapter| in the BlueZ storage in |dir|.
+fn write_bluez_bonds(dir: &str, adapter: &str, backup: &BondBackup) -> Result<(), BackupError> {
+    let floss_map: HashMap<String, HashMap<String, Option<String>>> = backup
+        .devices
+        .iter()
+        .map(|d| {
+            let keys: HashMap<String, Option<String>> =
+                d.keys.iter().map(|(k, v)| (k.clone(), Some(v.clone()))).collect();
+            (d.address.clone(), keys)
+        })
+        .collect();
+
+    let mut report = Vec::new();
+    migrate::convert_floss_devices(
+        dir,
+        &adapter.to_uppercase(),
+        floss_map,
+        /*dry_run=*/ false,
+        &mut report,
+    );
+    let mut partial = Vec::new();
+    for device in report.iter().filter(|d| !d.errors.is_empty()) {
+        warn!("Partially restored {}: {}", device.address, device.errors.join(", "));
+        partial.push(device.address.clone());
+    }
+
+    if partial.is_empty() {
+        Ok(())
+    } else {
+        Err(BackupError::PartiallyImported(partial))
+    }
+}
+
+/// Writes the devices of |backup| to |adapter| in the Floss storage in |dir|.
+fn write_floss_bonds(
+    dir: &str,
+    adapter: &str,
+    is_default: bool,
+    backup: &BondBackup,
+) -> Result<(), BackupError> {
+    let path = migrate::floss_conf_for_adapter(dir, adapter, is_default);
+
+    // Devices that aren't in the backup are kept
+    let mut floss_conf = Ini::new_cs();
+    let _ = floss_conf.load(&path);
+    if floss_conf.get(migrate::ADAPTER_SECTION_NAME, "Address") == None {
+        floss_conf.set(migrate::ADAPTER_SECTION_NAME, "Address", Some(adapter.to_lowercase()));
+    }
+    for device in &backup.devices {
+        floss_conf.remove_section(&device.address);
+        for (k, v) in &device.keys {
+            floss_conf.set(&device.address, k, Some(v.clone()));
+        }
+    }
+
+    floss_conf.write(&path).map_err(|e| BackupError::Io(format!("{}: {}", path, e)))
+}
+
+/// Exports the bonded devices of |adapter| from Floss or BlueZ, encrypted with |key|. Whether
+/// |adapter| is the default one picks its Floss conf file, see |migrate::floss_conf_for_adapter|.
+pub fn export_bonds(
+    floss: bool,
+    adapter: &str,
+    is_default: bool,
+    key: &[u8],
+) -> Result<Vec<u8>, BackupError> {
+    if !is_valid_address(adapter) {
+        return Err(BackupError::InvalidAddress(adapter.into()));
+    }
+
+    let backup = if floss {
+        read_floss_bonds(migrate::BT_LIBDIR, adapter, is_default)?
+    } else {
+        read_bluez_bonds(migrate::BT_LIBDIR, adapter)?
+    };
+    info!("Exporting {} bonded devices of adapter {}", backup.devices.len(), adapter);
+
+    backup.encrypt(key)
+}
+
+/// Imports the bonded devices of an encrypted |backup| to |adapter| of Floss or BlueZ. Devices
+/// already bonded to the adapter are replaced by the ones in the backup, others are kept. The
+/// stack must not be running since it would overwrite the storage.
+pub fn import_bonds(
+    floss: bool,
+    adapter: &str,
+    is_default: bool,
+    backup: &[u8],
+    key: &[u8],
+) -> Result<(), BackupError> {
+    if !is_valid_address(adapter) {
+        return Err(BackupError::InvalidAddress(adapter.into()));
+    }
+
+    let backup = BondBackup::decrypt(backup, key)?;
+    info!(
+        "Importing {} bonded devices of adapter {} to adapter {}",
+        backup.devices.len(),
+        backup.adapter,
+        adapter
+    );
+
+    if floss {
+        write_floss_bonds(migrate::BT_LIBDIR, adapter, is_default, &backup)
+    } else {
+        write_bluez_bonds(migrate::BT_LIBDIR, adapter, &backup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrate::make_test_dir;
+    use std::fs;
+
+    const TEST_KEY: [u8; BACKUP_KEY_SIZE] = [7; BACKUP_KEY_SIZE];
+
+    fn make_backup() -> BondBackup {
+        BondBackup {
+            adapter: "00:00:00:00:00:aa".into(),
+            devices: vec![BondedDevice {
+                address: "00:11:22:33:44:55".into(),
+                keys: [
+                    ("Name".to_string(), "Keyboard".to_string()),
+                    ("LinkKey".to_string(), "ffeeddccbbaa99887766554433221100".to_string()),
+                    ("LinkKeyType".to_string(), "4".to_string()),
+                ]
+                .into(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let backup = make_backup();
+        let encrypted = backup.encrypt(&TEST_KEY).unwrap();
+        assert!(!String::from_utf8_lossy(&encrypted).contains("Keyboard"));
+        assert_eq!(BondBackup::decrypt(&encrypted, &TEST_KEY), Ok(backup));
+    }
+
+    #[test]
+    fn test_decrypt_failures() {
+        let backup = make_backup();
+        assert_eq!(backup.encrypt(&TEST_KEY[..16]), Err(BackupError::InvalidKey));
+
+        let encrypted = backup.encrypt(&TEST_KEY).unwrap();
+        assert_eq!(
+            BondBackup::decrypt(&encrypted, &[8; BACKUP_KEY_SIZE]),
+            Err(BackupError::DecryptionFailed)
+        );
+
+        // The version is authenticated
+        let mut envelope = serde_json::from_slice::<Value>(&encrypted).unwrap();
+        envelope["version"] = json!(0);
+        assert_eq!(
+            BondBackup::decrypt(envelope.to_string().as_bytes(), &TEST_KEY),
+            Err(BackupError::DecryptionFailed)
+        );
+
+        envelope["version"] = json!(BACKUP_VERSION + 1);
+        assert_eq!(
+            BondBackup::decrypt(envelope.to_string().as_bytes(), &TEST_KEY),
+            Err(BackupError::UnsupportedVersion(BACKUP_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn test_from_json_rejects_bad_addresses() {
+        let mut value = make_backup().to_json();
+        value["devices"][0]["address"] = json!("../../etc");
+        assert_eq!(
+            BondBackup::from_json(&value),
+            Err(BackupError::InvalidAddress("../../etc".into()))
+        );
+    }
+
+    #[test]
+    fn test_from_json_drops_unknown_keys() {
+        let mut value = make_backup().to_json();
+        value["devices"][0]["keys"]["Timestamp"] = json!("1234");
+        let backup = BondBackup::from_json(&value).unwrap();
+        assert_eq!(backup, make_backup());
+    }
+
+    #[test]
+    fn test_bluez_backup_restores_to_floss() {
+        let bluez_dir = make_test_dir("bluez_to_floss_src");
+        let device_dir = format!("{}/00:00:00:00:00:AA/00:11:22:33:44:55", bluez_dir);
+        fs::create_dir_all(&device_dir).unwrap();
+        fs::copy("test/migrate/fake_bluez_info.toml", format!("{}/info", device_dir)).unwrap();
+
+        let backup = read_bluez_bonds(&bluez_dir, "00:00:00:00:00:aa").unwrap();
+        assert_eq!(backup.devices.len(), 1);
+        assert_eq!(backup.devices[0].address, "00:11:22:33:44:55");
+
+        // Restore to another adapter
+        let floss_dir = make_test_dir("bluez_to_floss_dst");
+        let encrypted = backup.encrypt(&TEST_KEY).unwrap();
+        let backup = BondBackup::decrypt(&encrypted, &TEST_KEY).unwrap();
+        write_floss_bonds(&floss_dir, "00:00:00:00:00:bb", true, &backup).unwrap();
+
+        let mut conf = Ini::new_cs();
+        conf.load(format!("{}/bt_config.conf", floss_dir)).unwrap();
+        assert_eq!(conf.get("Adapter", "Address"), Some("00:00:00:00:00:bb".into()));
+        assert_eq!(conf.get("00:11:22:33:44:55", "Name"), Some("Test Device".into()));
+        assert_eq!(
+            conf.get("00:11:22:33:44:55", "LinkKey"),
+            Some("ffeeddccbbaa99887766554433221100".into())
+        );
+
+        fs::remove_dir_all(&bluez_dir).unwrap();
+        fs::remove_dir_all(&floss_dir).unwrap();
+    }
+
+    #[test]
+    fn test_floss_backup_restores_to_bluez() {
+        let dir = make_test_dir("floss_to_bluez");
+        fs::write(
+            format!("{}/bt_config.conf", dir),
+            "[Adapter]\nAddress = 00:00:00:00:00:aa\n\n\
+             [00:11:22:33:44:55]\nName = Keyboard\nMetricsId = 3\n\
+             LinkKey = ffeeddccbbaa99887766554433221100\nLinkKeyType = 4\n\n\
+             [66:77:88:99:aa:bb]\nName = Not bonded\n",
+        )
+        .unwrap();
+
+        let backup = read_floss_bonds(&dir, "00:00:00:00:00:aa", false).unwrap();
+        assert_eq!(backup.devices.len(), 1);
+        assert_eq!(backup.devices[0].keys.get("MetricsId"), None);
+
+        write_bluez_bonds(&dir, "00:00:00:00:00:aa", &backup).unwrap();
+
+        let mut info = Ini::new_cs();
+        info.set_comment_symbols(&['!', '#']);
+        info.load(format!("{}/00:00:00:00:00:AA/00:11:22:33:44:55/info", dir)).unwrap();
+        assert_eq!(info.get("General", "Name"), Some("Keyboard".into()));
+        assert_eq!(info.get("LinkKey", "Key"), Some("00112233445566778899AABBCCDDEEFF".into()));
+        assert!(!Path::new(&format!("{}/00:00:00:00:00:AA/66:77:88:99:AA:BB", dir)).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_floss_restore_to_second_adapter() {
+        let dir = make_test_dir("floss_second_adapter");
+        let default_conf = "[Adapter]\nAddress = 00:00:00:00:00:aa\n";
+        fs::write(format!("{}/bt_config.conf", dir), default_conf).unwrap();
+
+        write_floss_bonds(&dir, "00:00:00:00:00:bb", false, &make_backup()).unwrap();
+        assert_eq!(fs::read_to_string(format!("{}/bt_config.conf", dir)).unwrap(), default_conf);
+
+        let backup = read_floss_bonds(&dir, "00:00:00:00:00:bb", false).unwrap();
+        assert_eq!(backup.devices, make_backup().devices);
+        assert!(Path::new(&format!("{}/bt_config.0000000000bb.conf", dir)).exists());
+        assert!(matches!(
+            read_floss_bonds(&dir, "00:00:00:00:00:cc", false),
+            Err(BackupError::Io(_))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_bluez_restore_reports_partial_import() {
+        let dir = make_test_dir("partial_import");
+        let mut backup = make_backup();
+        backup.devices[0].keys.insert("DevClass".into(), "bad".into());
+
+        assert_eq!(
+            write_bluez_bonds(&dir, "00:00:00:00:00:aa", &backup),
+            Err(BackupError::PartiallyImported(vec!["00:11:22:33:44:55".into()]))
+        );
+        assert_eq!(
+            BondBackupStatus::from(BackupError::PartiallyImported(vec![])),
+            BondBackupStatus::PartiallyImported
+        );
+
+        // The keys that could be converted are still restored.
+        let mut info = Ini::new_cs();
+        info.set_comment_symbols(&['!', '#']);
+        info.load(format!("{}/00:00:00:00:00:AA/00:11:22:33:44:55/info", dir)).unwrap();
+        assert_eq!(info.get("LinkKey", "Key"), Some("00112233445566778899AABBCCDDEEFF".into()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,19 +1,43 @@
+use crate::iface_bluetooth_manager::{AdapterDebugLevel, AdapterLaunchProfile, FeatureOverride};
 use crate::state_machine::{RealHciIndex, VirtualHciIndex};
 
 use log::LevelFilter;
 use serde_json::{Map, Value};
-use std::convert::TryInto;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
 use std::path::Path;
 
 // Directory for Bluetooth hci devices
 pub const HCI_DEVICES_DIR: &str = "/sys/class/bluetooth";
 
+// Directory for rfkill switches
+const RFKILL_DEVICES_DIR: &str = "/sys/class/rfkill";
+
 // File to store the Bluetooth daemon to use (bluez or floss)
 const BLUETOOTH_DAEMON_CURRENT: &str = "/var/lib/bluetooth/bluetooth-daemon.current";
 
-// File to store the config for BluetoothManager
+// Directory and name of the file to store the config for BluetoothManager
+pub const BTMANAGERD_CONF_DIR: &str = "/var/lib/bluetooth";
+pub const BTMANAGERD_CONF_NAME: &str = "btmanagerd.json";
 const BTMANAGERD_CONF: &str = "/var/lib/bluetooth/btmanagerd.json";
 
+/// Version of the config written by BluetoothManager.
+pub const CONFIG_VERSION: u64 = 2;
+
+/// Keys of the config.
+const VERSION_KEY: &str = "version";
+const LOG_LEVEL_KEY: &str = "log_level";
+const ADAPTERS_KEY: &str = "adapters";
+const ENABLED_KEY: &str = "enabled";
+const INIT_FLAGS_KEY: &str = "init_flags";
+const DEBUG_LEVEL_KEY: &str = "debug_level";
+const LL_PRIVACY_KEY: &str = "ll_privacy";
+const DEVCOREDUMP_KEY: &str = "devcoredump";
+const CONFIG_KEYS: [&str; 4] = [VERSION_KEY, LOG_LEVEL_KEY, DEFAULT_ADAPTER_KEY, ADAPTERS_KEY];
+const ADAPTER_KEYS: [&str; 5] =
+    [ENABLED_KEY, INIT_FLAGS_KEY, DEBUG_LEVEL_KEY, LL_PRIVACY_KEY, DEVCOREDUMP_KEY];
+
 /// Folder to keep files which override floss configuration
 const FLOSS_SYSPROPS_OVERRIDE_DIR: &str = "/var/lib/bluetooth/sysprops.conf.d";
 
@@ -53,104 +77,416 @@ pub fn write_floss_enabled(enabled: bool) -> bool {
     .is_ok()
 }
 
-pub fn read_config() -> std::io::Result<String> {
-    std::fs::read_to_string(BTMANAGERD_CONF)
+/// Config of an adapter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdapterConfig {
+    /// Whether the adapter is started when present.
+    pub enabled: bool,
+    /// Options the adapter process is started with.
+    pub launch_profile: AdapterLaunchProfile,
 }
 
-pub fn get_log_level() -> Option<LevelFilter> {
-    get_log_level_internal(read_config().ok()?)
+impl Default for AdapterConfig {
+    fn default() -> Self {
+        AdapterConfig { enabled: true, launch_profile: AdapterLaunchProfile::default() }
+    }
 }
 
-fn get_log_level_internal(config: String) -> Option<LevelFilter> {
-    serde_json::from_str::<Value>(config.as_str())
-        .ok()?
-        .get("log_level")?
-        .as_str()?
-        .parse::<LevelFilter>()
-        .ok()
+/// Config of BluetoothManager. See |ManagerConfig::parse| for the file format.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ManagerConfig {
+    pub log_level: Option<LevelFilter>,
+    pub default_adapter: Option<VirtualHciIndex>,
+    /// Config of each adapter by virtual hci index.
+    pub adapters: BTreeMap<i32, AdapterConfig>,
 }
 
-/// Returns whether hci N is enabled in config; defaults to true.
-pub fn is_hci_n_enabled(hci: VirtualHciIndex) -> bool {
-    read_config().ok().and_then(|config| is_hci_n_enabled_internal(config, hci)).unwrap_or(true)
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    /// The config couldn't be read.
+    Io(String),
+    /// The config isn't valid JSON.
+    Parse(String),
+    /// A field of the config is invalid. |field| is its path, e.g. "adapters.hci0.enabled".
+    InvalidField { field: String, reason: String },
+    /// The config was written by a newer version of BluetoothManager.
+    UnsupportedVersion(u64),
 }
 
-fn is_hci_n_enabled_internal(config: String, hci: VirtualHciIndex) -> Option<bool> {
-    serde_json::from_str::<Value>(config.as_str())
-        .ok()?
-        .get(format!("hci{}", hci.to_i32()))?
-        .as_object()?
-        .get("enabled")?
-        .as_bool()
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "Failed to read config: {}", e),
+            ConfigError::Parse(e) => write!(f, "Config is not valid JSON: {}", e),
+            ConfigError::InvalidField { field, reason } => {
+                write!(f, "Invalid config field \"{}\": {}", field, reason)
+            }
+            ConfigError::UnsupportedVersion(v) => write!(f, "Unsupported config version {}", v),
+        }
+    }
 }
 
-// When we initialize BluetoothManager, we need to make sure the file is a well-formatted json.
-pub fn fix_config_file_format() -> bool {
-    match read_config() {
-        Ok(s) => match serde_json::from_str::<Value>(s.as_str()) {
-            Ok(_) => true,
-            _ => std::fs::write(BTMANAGERD_CONF, "{}").is_ok(),
-        },
-        _ => std::fs::write(BTMANAGERD_CONF, "{}").is_ok(),
-    }
+fn invalid_field(field: &str, reason: &str) -> ConfigError {
+    ConfigError::InvalidField { field: field.into(), reason: reason.into() }
 }
 
-pub fn modify_hci_n_enabled(hci: VirtualHciIndex, enabled: bool) -> bool {
-    if !fix_config_file_format() {
-        return false;
+impl ManagerConfig {
+    /// Parses and validates a config, migrating it to |CONFIG_VERSION| first. All fields are
+    /// optional and unknown fields are rejected, except in unversioned configs where they are
+    /// dropped. The current format is:
+    ///
+    /// {
+    ///   "version": 2,
+    ///   "log_level": "info",
+    ///   "default_adapter": 0,
+    ///   "adapters": {
+    ///     "hci0": {
+    ///       "enabled": true,
+    ///       "init_flags": ["INIT_gd_hal_snoop_logger_filtering=true"],
+    ///       "debug_level": "debug",
+    ///       "ll_privacy": false,
+    ///       "devcoredump": true
+    ///     }
+    ///   }
+    /// }
+    pub fn parse(config: &str) -> Result<Self, ConfigError> {
+        let value =
+            serde_json::from_str::<Value>(config).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        Self::from_value(&migrate_config(value)?)
+    }
+
+    fn from_value(value: &Value) -> Result<Self, ConfigError> {
+        let mut config = ManagerConfig::default();
+        for (key, field) in
+            value.as_object().ok_or_else(|| invalid_field("", "must be an object"))?
+        {
+            match key.as_str() {
+                VERSION_KEY => (),
+                LOG_LEVEL_KEY => {
+                    let level =
+                        field.as_str().and_then(|l| l.parse::<LevelFilter>().ok()).ok_or_else(
+                            || invalid_field(key, "must be off, error, warn, info, debug or trace"),
+                        )?;
+                    config.log_level = Some(level);
+                }
+                DEFAULT_ADAPTER_KEY => {
+                    let hci = field
+                        .as_i64()
+                        .and_then(|i| i32::try_from(i).ok())
+                        .filter(|i| *i >= 0)
+                        .ok_or_else(|| invalid_field(key, "must be a non-negative hci index"))?;
+                    config.default_adapter = Some(VirtualHciIndex(hci));
+                }
+                ADAPTERS_KEY => config.adapters = parse_adapters(field)?,
+                _ => return Err(invalid_field(key, "unknown field")),
+            }
+        }
+        Ok(config)
+    }
+
+    fn to_value(&self) -> Value {
+        let mut o = Map::new();
+        o.insert(VERSION_KEY.into(), Value::from(CONFIG_VERSION));
+        if let Some(level) = self.log_level {
+            o.insert(LOG_LEVEL_KEY.into(), Value::from(level.to_string().to_lowercase()));
+        }
+        if let Some(hci) = self.default_adapter {
+            o.insert(DEFAULT_ADAPTER_KEY.into(), Value::from(hci.to_i32()));
+        }
+        let adapters = self
+            .adapters
+            .iter()
+            .map(|(hci, adapter)| {
+                let mut a = Map::new();
+                a.insert(ENABLED_KEY.into(), Value::Bool(adapter.enabled));
+                let profile = &adapter.launch_profile;
+                if !profile.init_flags.is_empty() {
+                    a.insert(INIT_FLAGS_KEY.into(), Value::from(profile.init_flags.clone()));
+                }
+                let debug_level = match profile.debug_level {
+                    AdapterDebugLevel::Default => None,
+                    AdapterDebugLevel::Debug => Some("debug"),
+                    AdapterDebugLevel::Verbose => Some("verbose"),
+                };
+                if let Some(level) = debug_level {
+                    a.insert(DEBUG_LEVEL_KEY.into(), Value::from(level));
+                }
+                if let Some(enabled) = profile.ll_privacy.to_option() {
+                    a.insert(LL_PRIVACY_KEY.into(), Value::Bool(enabled));
+                }
+                if let Some(enabled) = profile.devcoredump.to_option() {
+                    a.insert(DEVCOREDUMP_KEY.into(), Value::Bool(enabled));
+                }
+                (format!("hci{}", hci), Value::Object(a))
+            })
+            .collect();
+        o.insert(ADAPTERS_KEY.into(), Value::Object(adapters));
+        Value::Object(o)
     }
-    match read_config().ok().and_then(|config| modify_hci_n_enabled_internal(config, hci, enabled))
+
+    /// Serializes the config in the current format.
+    pub fn to_json_string(&self) -> String {
+        serde_json::ser::to_string_pretty(&self.to_value()).unwrap_or_default()
+    }
+
+    /// Returns whether hci N is enabled; defaults to true.
+    pub fn is_hci_enabled(&self, hci: VirtualHciIndex) -> bool {
+        self.adapters.get(&hci.to_i32()).map_or(true, |a| a.enabled)
+    }
+
+    /// Returns the launch profile of hci N.
+    pub fn get_launch_profile(&self, hci: VirtualHciIndex) -> AdapterLaunchProfile {
+        self.adapters.get(&hci.to_i32()).map(|a| a.launch_profile.clone()).unwrap_or_default()
+    }
+
+    /// Returns the desired default adapter; defaults to hci0.
+    pub fn get_default_adapter(&self) -> VirtualHciIndex {
+        self.default_adapter.unwrap_or(DEFAULT_ADAPTER)
+    }
+}
+
+fn parse_adapters(value: &Value) -> Result<BTreeMap<i32, AdapterConfig>, ConfigError> {
+    let mut adapters = BTreeMap::new();
+    for (name, adapter) in
+        value.as_object().ok_or_else(|| invalid_field(ADAPTERS_KEY, "must be an object"))?
     {
-        Some(s) => std::fs::write(BTMANAGERD_CONF, s).is_ok(),
-        _ => false,
-    }
-}
-
-fn modify_hci_n_enabled_internal(
-    config: String,
-    hci: VirtualHciIndex,
-    enabled: bool,
-) -> Option<String> {
-    let hci_str = format!("hci{}", hci.to_i32());
-    let mut o = serde_json::from_str::<Value>(config.as_str()).ok()?;
-    match o.get_mut(hci_str.clone()) {
-        Some(section) => {
-            section.as_object_mut()?.insert("enabled".to_string(), Value::Bool(enabled));
-            serde_json::ser::to_string_pretty(&o).ok()
+        let path = format!("{}.{}", ADAPTERS_KEY, name);
+        let hci =
+            parse_hci_index(name).ok_or_else(|| invalid_field(&path, "must be named hciN"))?;
+        let mut config = AdapterConfig::default();
+        for (key, field) in
+            adapter.as_object().ok_or_else(|| invalid_field(&path, "must be an object"))?
+        {
+            let field_path = format!("{}.{}", path, key);
+            match key.as_str() {
+                ENABLED_KEY => {
+                    config.enabled = field
+                        .as_bool()
+                        .ok_or_else(|| invalid_field(&field_path, "must be a boolean"))?;
+                }
+                INIT_FLAGS_KEY => {
+                    config.launch_profile.init_flags = parse_init_flags(field, &field_path)?;
+                }
+                DEBUG_LEVEL_KEY => {
+                    config.launch_profile.debug_level = match field.as_str() {
+                        Some("default") => AdapterDebugLevel::Default,
+                        Some("debug") => AdapterDebugLevel::Debug,
+                        Some("verbose") => AdapterDebugLevel::Verbose,
+                        _ => {
+                            return Err(invalid_field(
+                                &field_path,
+                                "must be default, debug or verbose",
+                            ))
+                        }
+                    };
+                }
+                LL_PRIVACY_KEY => {
+                    config.launch_profile.ll_privacy = parse_feature_override(field, &field_path)?;
+                }
+                DEVCOREDUMP_KEY => {
+                    config.launch_profile.devcoredump = parse_feature_override(field, &field_path)?;
+                }
+                _ => return Err(invalid_field(&field_path, "unknown field")),
+            }
+        }
+        adapters.insert(hci, config);
+    }
+    Ok(adapters)
+}
+
+/// INIT_ flags are passed through the environment of the init system, so they can't contain
+/// whitespace.
+fn parse_init_flags(value: &Value, path: &str) -> Result<Vec<String>, ConfigError> {
+    let flags = value.as_array().ok_or_else(|| invalid_field(path, "must be an array"))?;
+    flags
+        .iter()
+        .enumerate()
+        .map(|(i, flag)| {
+            flag.as_str()
+                .filter(|f| f.starts_with("INIT_") && !f.contains(char::is_whitespace))
+                .map(String::from)
+                .ok_or_else(|| {
+                    invalid_field(
+                        &format!("{}.{}", path, i),
+                        "must be an INIT_ flag without whitespace",
+                    )
+                })
+        })
+        .collect()
+}
+
+fn parse_feature_override(value: &Value, path: &str) -> Result<FeatureOverride, ConfigError> {
+    match value.as_bool() {
+        Some(true) => Ok(FeatureOverride::Enabled),
+        Some(false) => Ok(FeatureOverride::Disabled),
+        None => Err(invalid_field(path, "must be a boolean")),
+    }
+}
+
+/// Migrates a config to |CONFIG_VERSION|, one version at a time.
+fn migrate_config(mut value: Value) -> Result<Value, ConfigError> {
+    let mut version = match value.get(VERSION_KEY) {
+        Some(v) => {
+            v.as_u64().ok_or_else(|| invalid_field(VERSION_KEY, "must be a positive integer"))?
+        }
+        None => 1,
+    };
+    if version > CONFIG_VERSION {
+        return Err(ConfigError::UnsupportedVersion(version));
+    }
+
+    while version < CONFIG_VERSION {
+        value = match version {
+            1 => migrate_config_v1(value)?,
+            _ => return Err(invalid_field(VERSION_KEY, "must be a positive integer")),
+        };
+        version += 1;
+        value[VERSION_KEY] = Value::from(version);
+    }
+    Ok(value)
+}
+
+/// Drops the fields of |o| that aren't in |known|. |path| is only used for logging.
+fn drop_unknown_fields(o: &mut Map<String, Value>, known: &[&str], path: &str) {
+    o.retain(|key, _| {
+        let is_known = known.contains(&key.as_str());
+        if !is_known {
+            log::warn!("Dropping unknown config field {}{}", path, key);
+        }
+        is_known
+    });
+}
+
+/// Version 1 didn't have a version and kept the adapters at the top level, e.g.
+/// {"hci0": {"enabled": true}}. It wasn't validated either, so unknown fields are dropped instead
+/// of rejecting the whole config.
+fn migrate_config_v1(value: Value) -> Result<Value, ConfigError> {
+    let mut o = match value {
+        Value::Object(o) => o,
+        _ => return Err(invalid_field("", "must be an object")),
+    };
+    let names: Vec<String> = o.keys().filter(|k| parse_hci_index(k).is_some()).cloned().collect();
+    // Keep the adapters that are already in the current format, they are newer.
+    let mut adapters = match o.remove(ADAPTERS_KEY) {
+        Some(Value::Object(adapters)) => adapters,
+        Some(_) => return Err(invalid_field(ADAPTERS_KEY, "must be an object")),
+        None => Map::new(),
+    };
+    for name in names {
+        if let Some(adapter) = o.remove(&name) {
+            adapters.entry(name).or_insert(adapter);
+        }
+    }
+    for (name, adapter) in adapters.iter_mut() {
+        if let Value::Object(adapter) = adapter {
+            drop_unknown_fields(adapter, &ADAPTER_KEYS, &format!("{}.{}.", ADAPTERS_KEY, name));
+        }
+    }
+    drop_unknown_fields(&mut o, &CONFIG_KEYS, "");
+    o.insert(ADAPTERS_KEY.into(), Value::Object(adapters));
+    Ok(Value::Object(o))
+}
+
+/// Reads the config of BluetoothManager. A missing config is the default one.
+pub fn read_manager_config() -> Result<ManagerConfig, ConfigError> {
+    read_manager_config_at(BTMANAGERD_CONF)
+}
+
+fn read_manager_config_at(path: &str) -> Result<ManagerConfig, ConfigError> {
+    match std::fs::read_to_string(path) {
+        Ok(config) => ManagerConfig::parse(&config),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ManagerConfig::default()),
+        Err(e) => Err(ConfigError::Io(e.to_string())),
+    }
+}
+
+pub fn write_manager_config(config: &ManagerConfig) -> bool {
+    std::fs::write(BTMANAGERD_CONF, config.to_json_string()).is_ok()
+}
+
+/// Reads the config, falling back to the default one if it's invalid.
+fn read_manager_config_or_default() -> ManagerConfig {
+    read_manager_config().unwrap_or_else(|e| {
+        log::error!("{}", e);
+        ManagerConfig::default()
+    })
+}
+
+/// Applies |f| to the config and writes it back. An invalid config isn't modified so that it
+/// can be fixed by hand.
+fn modify_manager_config<F: FnOnce(&mut ManagerConfig)>(f: F) -> bool {
+    match read_manager_config() {
+        Ok(mut config) => {
+            f(&mut config);
+            write_manager_config(&config)
+        }
+        Err(e) => {
+            log::error!("Not modifying config: {}", e);
+            false
+        }
+    }
+}
+
+pub fn get_log_level() -> Option<LevelFilter> {
+    read_manager_config_or_default().log_level
+}
+
+/// Returns whether hci N is enabled in config; defaults to true.
+pub fn is_hci_n_enabled(hci: VirtualHciIndex) -> bool {
+    read_manager_config_or_default().is_hci_enabled(hci)
+}
+
+// When we initialize BluetoothManager, we need to make sure the file is a valid config in the
+// current version. Configs that aren't valid JSON are replaced by the default one, while configs
+// with invalid fields or from a newer version are left alone so that they can be fixed by hand.
+pub fn fix_config_file_format() -> bool {
+    fix_config_file_format_at(BTMANAGERD_CONF)
+}
+
+/// Rewrites the config at |path| in the current format. The old file is kept at |path|.bak.
+fn fix_config_file_format_at(path: &str) -> bool {
+    let config = match read_manager_config_at(path) {
+        Ok(config) => config,
+        Err(ConfigError::Parse(e)) => {
+            log::error!("Replacing config that isn't valid JSON: {}", e);
+            ManagerConfig::default()
         }
-        _ => {
-            let mut entry_map = Map::new();
-            entry_map.insert("enabled".to_string(), Value::Bool(enabled));
-            o.as_object_mut()?.insert(hci_str, Value::Object(entry_map));
-            serde_json::ser::to_string_pretty(&o).ok()
+        Err(e) => {
+            log::error!("Not fixing config: {}", e);
+            return false;
         }
+    };
+
+    let new_config = config.to_json_string();
+    match std::fs::read_to_string(path) {
+        Ok(old_config) if old_config == new_config => return true,
+        Ok(_) => {
+            if let Err(e) = std::fs::copy(path, format!("{}.bak", path)) {
+                log::error!("Not fixing config, failed to back it up: {}", e);
+                return false;
+            }
+        }
+        Err(_) => (),
     }
+    std::fs::write(path, new_config).is_ok()
+}
+
+pub fn modify_hci_n_enabled(hci: VirtualHciIndex, enabled: bool) -> bool {
+    modify_manager_config(|config| {
+        config.adapters.entry(hci.to_i32()).or_default().enabled = enabled;
+    })
+}
+
+pub fn get_launch_profile(hci: VirtualHciIndex) -> AdapterLaunchProfile {
+    read_manager_config_or_default().get_launch_profile(hci)
 }
 
 pub fn get_default_adapter() -> VirtualHciIndex {
-    read_config()
-        .ok()
-        .and_then(|config| {
-            serde_json::from_str::<Value>(config.as_str())
-                .ok()?
-                .get(DEFAULT_ADAPTER_KEY)?
-                .as_i64()?
-                .try_into()
-                .ok()
-        })
-        .map(|i| VirtualHciIndex(i))
-        .unwrap_or(DEFAULT_ADAPTER)
+    read_manager_config_or_default().get_default_adapter()
 }
 
 pub fn set_default_adapter(hci: VirtualHciIndex) -> bool {
-    match read_config().ok().and_then(|config| {
-        let mut cfg = serde_json::from_str::<Value>(config.as_str()).ok()?;
-        cfg[DEFAULT_ADAPTER_KEY] = serde_json::to_value(hci.to_i32()).ok().unwrap();
-        serde_json::ser::to_string_pretty(&cfg).ok()
-    }) {
-        Some(s) => std::fs::write(BTMANAGERD_CONF, s).is_ok(),
-        None => false,
-    }
+    modify_manager_config(|config| config.default_adapter = Some(hci))
 }
 
 fn list_hci_devices_string() -> Vec<String> {
@@ -180,6 +516,22 @@ pub fn get_devpath_for_hci(hci: RealHciIndex) -> Option<String> {
     }
 }
 
+/// Get the hci device an rfkill switch belongs to. Switches of Bluetooth controllers are named
+/// after their hci device, other switches (e.g. platform kill switches) return None.
+pub fn get_hci_for_rfkill(idx: u32) -> Option<RealHciIndex> {
+    let name =
+        std::fs::read_to_string(format!("{}/rfkill{}/name", RFKILL_DEVICES_DIR, idx)).ok()?;
+    parse_hci_name(name.trim())
+}
+
+fn parse_hci_name(name: &str) -> Option<RealHciIndex> {
+    parse_hci_index(name).map(RealHciIndex)
+}
+
+fn parse_hci_index(name: &str) -> Option<i32> {
+    name.strip_prefix("hci")?.parse::<i32>().ok().filter(|i| *i >= 0)
+}
+
 pub fn list_pid_files(pid_dir: &str) -> Vec<String> {
     match std::fs::read_dir(pid_dir) {
         Ok(entries) => entries
@@ -234,6 +586,17 @@ pub fn set_adapter_coredump_state(enabled: bool) -> std::io::Result<()> {
     Ok(())
 }
 
+/// Returns whether devcoredump is enabled for |hci|.
+pub fn read_hci_coredump_state(hci: RealHciIndex) -> std::io::Result<bool> {
+    let path = format!("{}/hci{}/device/coredump_disabled", HCI_DEVICES_DIR, hci.to_i32());
+    Ok(std::fs::read_to_string(path)?.trim() == "0")
+}
+
+pub fn set_hci_coredump_state(hci: RealHciIndex, enabled: bool) -> std::io::Result<()> {
+    let path = format!("{}/hci{}/device/coredump_disabled", HCI_DEVICES_DIR, hci.to_i32());
+    std::fs::write(path, format!("{}\n", !enabled as i32))
+}
+
 pub fn write_coredump_state_to_file(enabled: bool) -> bool {
     let data = format!("{}\n", !enabled as i32);
 
@@ -254,77 +617,212 @@ pub fn write_coredump_state_to_file(enabled: bool) -> bool {
 mod tests {
     use super::*;
 
-    fn is_hci_n_enabled_internal_wrapper(config: String, n: i32) -> bool {
-        is_hci_n_enabled_internal(config, VirtualHciIndex(n)).unwrap_or(true)
+    fn is_hci_n_enabled_wrapper(config: &str, n: i32) -> bool {
+        ManagerConfig::parse(config).unwrap().is_hci_enabled(VirtualHciIndex(n))
+    }
+
+    fn invalid_field_of(config: &str) -> Option<String> {
+        match ManagerConfig::parse(config) {
+            Err(ConfigError::InvalidField { field, .. }) => Some(field),
+            _ => None,
+        }
     }
 
     #[test]
     fn parse_log_level() {
-        assert_eq!(
-            get_log_level_internal("{\"log_level\": \"error\"}".to_string()).unwrap(),
-            LevelFilter::Error
-        );
-        assert_eq!(
-            get_log_level_internal("{\"log_level\": \"warn\"}".to_string()).unwrap(),
-            LevelFilter::Warn
-        );
-        assert_eq!(
-            get_log_level_internal("{\"log_level\": \"info\"}".to_string()).unwrap(),
-            LevelFilter::Info
-        );
-        assert_eq!(
-            get_log_level_internal("{\"log_level\": \"debug\"}".to_string()).unwrap(),
-            LevelFilter::Debug
-        );
-        assert_eq!(
-            get_log_level_internal("{\"log_level\": \"trace\"}".to_string()).unwrap(),
-            LevelFilter::Trace
-        );
-        assert_eq!(
-            get_log_level_internal("{\"log_level\": \"random\"}".to_string()).is_none(),
-            true
-        );
+        let log_level = |config: &str| ManagerConfig::parse(config).unwrap().log_level;
+        assert_eq!(log_level("{\"log_level\": \"error\"}"), Some(LevelFilter::Error));
+        assert_eq!(log_level("{\"log_level\": \"warn\"}"), Some(LevelFilter::Warn));
+        assert_eq!(log_level("{\"log_level\": \"info\"}"), Some(LevelFilter::Info));
+        assert_eq!(log_level("{\"log_level\": \"debug\"}"), Some(LevelFilter::Debug));
+        assert_eq!(log_level("{\"log_level\": \"trace\"}"), Some(LevelFilter::Trace));
+        assert_eq!(log_level("{}"), None);
+        assert_eq!(invalid_field_of("{\"log_level\": \"random\"}"), Some("log_level".into()));
     }
 
     #[test]
     fn parse_hci0_enabled() {
-        assert_eq!(
-            is_hci_n_enabled_internal_wrapper("{\"hci0\":\n{\"enabled\": true}}".to_string(), 0),
-            true
-        );
+        assert_eq!(is_hci_n_enabled_wrapper("{\"hci0\":\n{\"enabled\": true}}", 0), true);
     }
 
     #[test]
     fn modify_hci0_enabled() {
-        let modified_string = modify_hci_n_enabled_internal(
-            "{\"hci0\":\n{\"enabled\": false}}".to_string(),
-            VirtualHciIndex(0),
-            true,
-        )
-        .unwrap();
-        assert_eq!(is_hci_n_enabled_internal_wrapper(modified_string, 0), true);
+        let mut config = ManagerConfig::parse("{\"hci0\":\n{\"enabled\": false}}").unwrap();
+        config.adapters.entry(0).or_default().enabled = true;
+        assert_eq!(is_hci_n_enabled_wrapper(&config.to_json_string(), 0), true);
     }
 
     #[test]
     fn modify_hci0_enabled_from_empty() {
-        let modified_string =
-            modify_hci_n_enabled_internal("{}".to_string(), VirtualHciIndex(0), true).unwrap();
-        assert_eq!(is_hci_n_enabled_internal_wrapper(modified_string, 0), true);
+        let mut config = ManagerConfig::parse("{}").unwrap();
+        config.adapters.entry(0).or_default().enabled = false;
+        assert_eq!(is_hci_n_enabled_wrapper(&config.to_json_string(), 0), false);
     }
 
     #[test]
     fn parse_hci0_not_enabled() {
+        assert_eq!(is_hci_n_enabled_wrapper("{\"hci0\":\n{\"enabled\": false}}", 0), false);
+    }
+
+    #[test]
+    fn parse_hci1_not_present() {
+        assert_eq!(is_hci_n_enabled_wrapper("{\"hci0\":\n{\"enabled\": true}}", 1), true);
+    }
+
+    #[test]
+    fn migrate_v1_config() {
+        let config = ManagerConfig::parse(
+            "{\"log_level\": \"debug\", \"default_adapter\": 1, \"hci1\": {\"enabled\": false}}",
+        )
+        .unwrap();
+        assert_eq!(config.log_level, Some(LevelFilter::Debug));
+        assert_eq!(config.get_default_adapter(), VirtualHciIndex(1));
+        assert_eq!(config.is_hci_enabled(VirtualHciIndex(1)), false);
+
+        let value: Value = serde_json::from_str(&config.to_json_string()).unwrap();
+        assert_eq!(value[VERSION_KEY], Value::from(CONFIG_VERSION));
+        assert_eq!(value[ADAPTERS_KEY]["hci1"][ENABLED_KEY], Value::Bool(false));
+        assert!(value.get("hci1").is_none());
+        assert_eq!(ManagerConfig::parse(&config.to_json_string()).unwrap(), config);
+    }
+
+    #[test]
+    fn migrate_v1_config_keeps_adapters() {
+        let config =
+            ManagerConfig::parse("{\"adapters\": {\"hci0\": {\"enabled\": false}}}").unwrap();
+        assert_eq!(config.is_hci_enabled(VirtualHciIndex(0)), false);
+
+        let config = ManagerConfig::parse(
+            "{\"adapters\": {\"hci0\": {\"enabled\": false}}, \"hci0\": {\"enabled\": true}, \
+             \"hci1\": {\"enabled\": false}}",
+        )
+        .unwrap();
+        assert_eq!(config.is_hci_enabled(VirtualHciIndex(0)), false);
+        assert_eq!(config.is_hci_enabled(VirtualHciIndex(1)), false);
+
+        assert_eq!(invalid_field_of("{\"adapters\": []}"), Some("adapters".into()));
+    }
+
+    #[test]
+    fn migrate_v1_config_drops_unknown_fields() {
+        let config = ManagerConfig::parse(
+            "{\"log_level\": \"debug\", \"legacy\": 1, \
+             \"hci0\": {\"enabled\": false, \"powered\": true}}",
+        )
+        .unwrap();
+        assert_eq!(config.log_level, Some(LevelFilter::Debug));
+        assert_eq!(config.is_hci_enabled(VirtualHciIndex(0)), false);
+
+        let value: Value = serde_json::from_str(&config.to_json_string()).unwrap();
+        assert!(value.get("legacy").is_none());
+        assert!(value[ADAPTERS_KEY]["hci0"].get("powered").is_none());
+    }
+
+    #[test]
+    fn fix_config_file() {
+        let dir = std::env::temp_dir().join(format!("config_util_fix_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("btmanagerd.json").to_str().unwrap().to_string();
+        let backup = format!("{}.bak", path);
+
+        // Configs with invalid fields or from a newer version aren't touched.
+        for config in ["{\"version\": 2, \"unknown\": true}", "{\"version\": 3}"] {
+            std::fs::write(&path, config).unwrap();
+            assert!(!fix_config_file_format_at(&path));
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), config);
+            assert!(!Path::new(&backup).exists());
+        }
+
+        // Configs that aren't JSON are replaced, but kept in the backup.
+        std::fs::write(&path, "{").unwrap();
+        assert!(fix_config_file_format_at(&path));
+        assert_eq!(read_manager_config_at(&path), Ok(ManagerConfig::default()));
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "{");
+
+        // Old configs are migrated.
+        std::fs::write(&path, "{\"hci0\": {\"enabled\": false}}").unwrap();
+        assert!(fix_config_file_format_at(&path));
+        let value: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(value[VERSION_KEY], Value::from(CONFIG_VERSION));
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "{\"hci0\": {\"enabled\": false}}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parse_invalid_fields() {
+        assert_eq!(invalid_field_of("{\"default_adapter\": -1}"), Some("default_adapter".into()));
         assert_eq!(
-            is_hci_n_enabled_internal_wrapper("{\"hci0\":\n{\"enabled\": false}}".to_string(), 0),
-            false
+            invalid_field_of("{\"version\": 2, \"adapters\": {\"hci0\": {\"enabled\": 1}}}"),
+            Some("adapters.hci0.enabled".into())
+        );
+        assert_eq!(
+            invalid_field_of("{\"version\": 2, \"adapters\": {\"hci0\": {\"powered\": true}}}"),
+            Some("adapters.hci0.powered".into())
         );
+        assert_eq!(
+            invalid_field_of("{\"version\": 2, \"adapters\": {\"usb0\": {}}}"),
+            Some("adapters.usb0".into())
+        );
+        assert_eq!(invalid_field_of("{\"version\": 2, \"unknown\": true}"), Some("unknown".into()));
+        assert_eq!(invalid_field_of("{\"version\": 0}"), Some("version".into()));
+        assert_eq!(invalid_field_of("[]"), Some("".into()));
+        assert!(matches!(ManagerConfig::parse("{"), Err(ConfigError::Parse(_))));
     }
 
     #[test]
-    fn parse_hci1_not_present() {
+    fn parse_launch_profile() {
+        let config = ManagerConfig::parse(
+            "{\"version\": 2, \"adapters\": {\"hci1\": {\"init_flags\": [\"INIT_redact_log=false\"], \
+             \"debug_level\": \"verbose\", \"ll_privacy\": true, \"devcoredump\": false}}}",
+        )
+        .unwrap();
+        let profile = config.get_launch_profile(VirtualHciIndex(1));
+        assert_eq!(profile.init_flags, vec!["INIT_redact_log=false".to_string()]);
+        assert_eq!(profile.debug_level, AdapterDebugLevel::Verbose);
+        assert_eq!(profile.ll_privacy, FeatureOverride::Enabled);
+        assert_eq!(profile.devcoredump, FeatureOverride::Disabled);
+        assert_eq!(
+            profile.to_args(),
+            vec!["--debug", "--verbose-debug", "--ll-privacy=true", "INIT_redact_log=false"]
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<String>>()
+        );
+        assert!(config.is_hci_enabled(VirtualHciIndex(1)));
+        assert_eq!(ManagerConfig::parse(&config.to_json_string()).unwrap(), config);
+
+        // Other adapters use the default profile.
+        assert_eq!(config.get_launch_profile(DEFAULT_ADAPTER), AdapterLaunchProfile::default());
+        assert!(config.get_launch_profile(DEFAULT_ADAPTER).to_args().is_empty());
+
+        assert_eq!(
+            invalid_field_of(
+                "{\"version\": 2, \"adapters\": {\"hci0\": {\"init_flags\": [\"--hci=1\"]}}}"
+            ),
+            Some("adapters.hci0.init_flags.0".into())
+        );
         assert_eq!(
-            is_hci_n_enabled_internal_wrapper("{\"hci0\":\n{\"enabled\": true}}".to_string(), 1),
-            true
+            invalid_field_of(
+                "{\"version\": 2, \"adapters\": {\"hci0\": {\"debug_level\": \"loud\"}}}"
+            ),
+            Some("adapters.hci0.debug_level".into())
         );
     }
+
+    #[test]
+    fn parse_unsupported_version() {
+        assert_eq!(
+            ManagerConfig::parse("{\"version\": 3}"),
+            Err(ConfigError::UnsupportedVersion(3))
+        );
+    }
+
+    #[test]
+    fn parse_rfkill_hci_name() {
+        assert_eq!(parse_hci_name("hci0"), Some(RealHciIndex(0)));
+        assert_eq!(parse_hci_name("hci12"), Some(RealHciIndex(12)));
+        assert_eq!(parse_hci_name("tpacpi_bluetooth_sw"), None);
+    }
 }
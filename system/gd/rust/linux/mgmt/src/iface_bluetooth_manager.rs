@@ -1,4 +1,6 @@
+use bt_utils::socket::MgmtControllerBus;
 use btstack::RPCProxy;
+use num_derive::{FromPrimitive, ToPrimitive};
 
 use std::sync::{Arc, Mutex};
 
@@ -8,6 +10,184 @@ use crate::bluetooth_manager::BluetoothManager;
 pub struct AdapterWithEnabled {
     pub hci_interface: i32,
     pub enabled: bool,
+    /// Profile the adapter is running with, or will be started with if it's not enabled.
+    pub launch_profile: AdapterLaunchProfile,
+    /// Controller of the adapter, once the manager has read it.
+    pub controller: Option<AdapterControllerInfo>,
+}
+
+/// Controller of an adapter, as read by the manager without starting the adapter process.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AdapterControllerInfo {
+    /// Public address in upper case.
+    pub address: String,
+    /// Company identifier of the controller manufacturer.
+    pub manufacturer: u16,
+    pub bus: Option<MgmtControllerBus>,
+    /// MGMT settings bitmasks, see |bt_utils::socket::mgmt_settings_to_names|.
+    pub supported_settings: u32,
+    pub current_settings: u32,
+    /// Most recent hardware error code reported by the controller.
+    pub last_error: Option<u8>,
+}
+
+/// Debug logging of an adapter process.
+#[derive(Clone, Copy, Debug, PartialEq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum AdapterDebugLevel {
+    /// Default logging of btadapterd.
+    Default = 0,
+    /// Debug logs, same as --debug.
+    Debug,
+    /// Verbose debug logs with all tags, same as --debug --verbose-debug.
+    Verbose,
+}
+
+impl Default for AdapterDebugLevel {
+    fn default() -> Self {
+        AdapterDebugLevel::Default
+    }
+}
+
+/// Per-adapter override of a setting that otherwise applies to all adapters.
+#[derive(Clone, Copy, Debug, PartialEq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum FeatureOverride {
+    /// Follow the setting of all adapters.
+    Default = 0,
+    Enabled,
+    Disabled,
+}
+
+impl Default for FeatureOverride {
+    fn default() -> Self {
+        FeatureOverride::Default
+    }
+}
+
+impl FeatureOverride {
+    pub fn to_option(self) -> Option<bool> {
+        match self {
+            FeatureOverride::Default => None,
+            FeatureOverride::Enabled => Some(true),
+            FeatureOverride::Disabled => Some(false),
+        }
+    }
+}
+
+/// Options an adapter process is started with.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AdapterLaunchProfile {
+    /// Fluoride INIT_ flags passed to btadapterd.
+    pub init_flags: Vec<String>,
+    pub debug_level: AdapterDebugLevel,
+    /// LL privacy, see |IBluetoothExperimental::set_ll_privacy|. It only applies to this adapter
+    /// and takes precedence over the setting of all adapters.
+    pub ll_privacy: FeatureOverride,
+    /// Devcoredump of the adapter, see |IBluetoothExperimental::set_devcoredump|.
+    pub devcoredump: FeatureOverride,
+}
+
+impl AdapterLaunchProfile {
+    /// Returns the arguments to pass to btadapterd. The INIT_ flags must come last.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = match self.debug_level {
+            AdapterDebugLevel::Default => vec![],
+            AdapterDebugLevel::Debug => vec!["--debug".into()],
+            AdapterDebugLevel::Verbose => vec!["--debug".into(), "--verbose-debug".into()],
+        };
+        if let Some(enabled) = self.ll_privacy.to_option() {
+            args.push(format!("--ll-privacy={}", enabled));
+        }
+        args.extend(self.init_flags.iter().cloned());
+        args
+    }
+}
+
+/// Why the manager restarted an adapter on its own.
+#[derive(Clone, Copy, Debug, PartialEq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum RestartReason {
+    /// The adapter process stopped without being asked to.
+    UnexpectedStop = 0,
+    /// The adapter process didn't come up in time.
+    StartTimeout,
+}
+
+/// A restart of an adapter done by the manager.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RestartEvent {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    pub reason: RestartReason,
+}
+
+/// Restart history and recovery status of an adapter.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AdapterHealth {
+    pub hci_interface: i32,
+    /// Whether the manager gave up restarting the adapter. It stays off until started again.
+    pub failed: bool,
+    /// How many times the HCI device was reset to recover the adapter.
+    pub reset_count: u32,
+    /// Delay before the pending restart, or 0 if no restart is waiting on backoff.
+    pub restart_delay_ms: u64,
+    /// Most recent restarts, oldest first.
+    pub restart_history: Vec<RestartEvent>,
+}
+
+/// What a BlueZ/Floss migration does to a device.
+#[derive(Clone, Copy, Debug, PartialEq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum DeviceMigrationAction {
+    /// The device is written to the target stack.
+    Migrated = 0,
+    /// The device couldn't be converted and is left out of the target stack.
+    Dropped,
+    /// The device only exists in the target stack and is deleted from it.
+    Deleted,
+}
+
+/// Result of migrating a single device between BlueZ and Floss.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceMigrationReport {
+    /// Address of the adapter the device is bonded to.
+    pub adapter: String,
+    pub address: String,
+    pub action: DeviceMigrationAction,
+    /// Keys written to the target stack.
+    pub converted_keys: Vec<String>,
+    /// Keys that failed to convert, with the reason.
+    pub errors: Vec<String>,
+}
+
+/// Outcome of exporting or importing bonded devices.
+#[derive(Clone, Copy, Debug, PartialEq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum BondBackupStatus {
+    Success = 0,
+    /// The key isn't 32 bytes.
+    InvalidKey,
+    /// An adapter or device address isn't a valid Bluetooth address.
+    InvalidAddress,
+    /// The backup is malformed or was made with a newer format.
+    InvalidBackup,
+    /// The backup couldn't be decrypted, because of either a wrong key or tampering.
+    DecryptionFailed,
+    /// The storage of the stack couldn't be read or written.
+    StorageError,
+    /// The stack is running and can't take imported devices.
+    StackBusy,
+    /// The backup was imported but some devices couldn't be fully converted.
+    PartiallyImported,
+}
+
+/// Result of exporting bonded devices.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BondBackupExport {
+    pub status: BondBackupStatus,
+    /// The encrypted backup, empty unless |status| is Success.
+    pub backup: Vec<u8>,
 }
 
 /// A mixin of the several interfaces. The naming of the fields in the mixin must match
@@ -55,6 +235,26 @@ pub trait IBluetoothManager {
     /// Set the tablet mode of the device. The device that is in tablet mode does not allow
     /// wakeup by the HID devices.
     fn set_tablet_mode(&mut self, tablet_mode: bool);
+
+    /// Returns the restart history and recovery status of an adapter.
+    fn get_adapter_health(&mut self, hci_interface: i32) -> AdapterHealth;
+
+    /// Computes the device migration that |set_floss_enabled| with |floss_enabled| would do,
+    /// without writing anything.
+    fn get_migration_report(&mut self, floss_enabled: bool) -> Vec<DeviceMigrationReport>;
+
+    /// Exports the bonded devices of the adapter with |adapter_address| from the active stack to
+    /// a backup encrypted with |key|, which must be 32 bytes.
+    fn export_bonded_devices(&mut self, adapter_address: String, key: Vec<u8>) -> BondBackupExport;
+
+    /// Imports the bonded devices of an encrypted |backup| to the adapter with
+    /// |adapter_address| in the active stack. With Floss, all adapters must be stopped.
+    fn import_bonded_devices(
+        &mut self,
+        adapter_address: String,
+        backup: Vec<u8>,
+        key: Vec<u8>,
+    ) -> BondBackupStatus;
 }
 
 /// Interface of Bluetooth Manager callbacks.
@@ -71,4 +271,11 @@ pub trait IBluetoothManagerCallback: RPCProxy {
     ///   * Default adapter is no longer available and we need to use a backup.
     ///   * Desired default adapter re-appears and we should switch back.
     fn on_default_adapter_changed(&mut self, hci_interface: i32);
+
+    /// The manager restarted an adapter, reset it, or gave up on it.
+    fn on_adapter_health_changed(&mut self, health: AdapterHealth);
+
+    /// HCI device is blocked or unblocked by rfkill. A blocked device is stopped and is not
+    /// listed as available until it's unblocked.
+    fn on_hci_rfkill_changed(&mut self, hci_interface: i32, soft_blocked: bool, hard_blocked: bool);
 }
@@ -1,15 +1,19 @@
 pub mod bluetooth_experimental_dbus;
 pub mod bluetooth_manager;
 pub mod bluetooth_manager_dbus;
+pub mod bond_backup;
 pub mod config_util;
 pub mod dbus_arg;
 pub mod dbus_iface;
 pub mod iface_bluetooth_experimental;
 pub mod iface_bluetooth_manager;
+pub mod logind_suspend_manager;
 pub mod migrate;
 pub mod powerd_suspend_manager;
+pub mod rfkill;
 pub mod service_watcher;
 pub mod state_machine;
+pub mod suspend_manager;
 
 // protoc-rust generates all modules and exports them in mod.rs
 // We have to include them all here to make them available for crate export.
@@ -0,0 +1,494 @@
+use btstack::suspend::{ISuspendCallback, SuspendType};
+use btstack::RPCProxy;
+use dbus::arg::OwnedFd;
+use dbus::channel::MatchingReceiver;
+use dbus::message::MatchRule;
+use dbus::nonblock::SyncConnection;
+use dbus_crossroads::Crossroads;
+use dbus_projection::DisconnectWatcher;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::dbus_iface::{export_suspend_callback_dbus_intf, SuspendDBus};
+use crate::service_watcher::ServiceWatcher;
+use crate::suspend::SuspendImminent_Reason;
+use crate::suspend_manager::{
+    get_suspend_type, watch_adapter_suspend, SuspendManagerState, ADAPTER_SERVICE, BTMANAGERD_NAME,
+};
+
+const LOGIND_SERVICE: &str = "org.freedesktop.login1";
+const LOGIND_INTERFACE: &str = "org.freedesktop.login1.Manager";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+const PREPARE_FOR_SLEEP_SIGNAL: &str = "PrepareForSleep";
+const INHIBIT_WHAT: &str = "sleep";
+const INHIBIT_WHY: &str = "Prepare Bluetooth adapter for suspend";
+const INHIBIT_MODE: &str = "delay";
+const LOGIND_DBUS_TIMEOUT: Duration = Duration::from_secs(20);
+
+#[derive(Debug)]
+enum LogindSuspendManagerMessage {
+    LogindStarted,
+    LogindStopped,
+    PrepareForSleepReceived(bool),
+    AdapterFound(dbus::Path<'static>),
+    AdapterRemoved,
+}
+
+/// Callback container for suspend interface callbacks.
+pub(crate) struct LogindSuspendCallback {
+    objpath: String,
+
+    dbus_connection: Arc<SyncConnection>,
+    dbus_crossroads: Arc<Mutex<Crossroads>>,
+
+    context: Arc<Mutex<LogindSuspendManagerContext>>,
+}
+
+impl LogindSuspendCallback {
+    pub(crate) fn new(
+        objpath: String,
+        dbus_connection: Arc<SyncConnection>,
+        dbus_crossroads: Arc<Mutex<Crossroads>>,
+        context: Arc<Mutex<LogindSuspendManagerContext>>,
+    ) -> Self {
+        Self { objpath, dbus_connection, dbus_crossroads, context }
+    }
+}
+
+impl ISuspendCallback for LogindSuspendCallback {
+    fn on_callback_registered(&mut self, callback_id: u32) {
+        log::debug!("Suspend callback registered, callback_id = {}", callback_id);
+    }
+
+    fn on_suspend_ready(&mut self, suspend_id: i32) {
+        // Received when adapter is ready to suspend. Releasing the inhibitor lets logind go on
+        // with the suspend.
+        log::debug!("Suspend ready, adapter suspend_id = {}", suspend_id);
+
+        let mut context = self.context.lock().unwrap();
+        match context.pending_suspend_id {
+            Some(id) if id == suspend_id => context.release_inhibitor(),
+            Some(id) => {
+                log::warn!("Suspend ready for {} but pending suspend is {}", suspend_id, id)
+            }
+            None => log::warn!("Suspend ready but there is no pending PrepareForSleep"),
+        }
+    }
+
+    fn on_resumed(&mut self, suspend_id: i32) {
+        // Received when adapter has resumed. This is just for our information and logind
+        // doesn't need to know about this.
+        log::debug!("Suspend resumed, adapter suspend_id = {}", suspend_id);
+    }
+}
+
+impl RPCProxy for LogindSuspendCallback {
+    fn get_object_id(&self) -> String {
+        self.objpath.clone()
+    }
+
+    fn export_for_rpc(self: Box<Self>) {
+        let cr = self.dbus_crossroads.clone();
+        let iface = export_suspend_callback_dbus_intf(
+            self.dbus_connection.clone(),
+            &mut cr.lock().unwrap(),
+            Arc::new(Mutex::new(DisconnectWatcher::new())),
+        );
+        cr.lock().unwrap().insert(self.get_object_id(), &[iface], Arc::new(Mutex::new(self)));
+    }
+}
+
+/// Holds the necessary information to coordinate suspend between logind and btadapterd.
+pub struct LogindSuspendManagerContext {
+    dbus_crossroads: Arc<Mutex<Crossroads>>,
+    logind_proxy: Option<dbus::nonblock::Proxy<'static, Arc<SyncConnection>>>,
+    adapter_suspend_dbus: Option<SuspendDBus>,
+    /// The delay inhibitor lock. logind waits for it to be closed (or for its delay timeout)
+    /// before suspending.
+    inhibitor: Option<OwnedFd>,
+    pending_suspend_id: Option<i32>,
+    next_suspend_id: i32,
+    tablet_mode: bool,
+}
+
+impl LogindSuspendManagerContext {
+    fn new(dbus_crossroads: Arc<Mutex<Crossroads>>) -> Self {
+        LogindSuspendManagerContext {
+            dbus_crossroads,
+            logind_proxy: None,
+            adapter_suspend_dbus: None,
+            inhibitor: None,
+            pending_suspend_id: None,
+            next_suspend_id: 0,
+            tablet_mode: false,
+        }
+    }
+
+    fn release_inhibitor(&mut self) {
+        if self.inhibitor.take().is_some() {
+            log::debug!("Released sleep inhibitor");
+        }
+    }
+
+    /// Starts a suspend for PrepareForSleep(true). logind gives no suspend id so we make up our
+    /// own. Returns the suspend id and how the adapter should suspend.
+    fn prepare_for_sleep(&mut self) -> (i32, SuspendType) {
+        if self.pending_suspend_id.is_some() {
+            log::warn!("PrepareForSleep(true) received while there is a pending suspend");
+        }
+
+        let suspend_id = self.next_suspend_id;
+        self.next_suspend_id = self.next_suspend_id.wrapping_add(1);
+        self.pending_suspend_id = Some(suspend_id);
+
+        if self.adapter_suspend_dbus.is_none() {
+            // If there is no adapter, that means Bluetooth is not active and we should not hold
+            // the suspend back.
+            log::debug!("Adapter not available, suspend is ready.");
+            self.release_inhibitor();
+        }
+
+        // logind doesn't tell why the system suspends, so wakes are allowed as for an idle
+        // suspend unless the device is in tablet mode.
+        (suspend_id, get_suspend_type(self.tablet_mode, SuspendImminent_Reason::IDLE))
+    }
+
+    /// Ends the pending suspend for PrepareForSleep(false).
+    fn resume_from_sleep(&mut self) {
+        if self.pending_suspend_id.is_none() {
+            log::warn!("Received PrepareForSleep(false) when there is no pending suspend");
+        }
+
+        self.pending_suspend_id = None;
+        // Normally already released in |on_suspend_ready|, but the adapter might not have
+        // answered in time.
+        self.release_inhibitor();
+    }
+}
+
+impl SuspendManagerState for LogindSuspendManagerContext {
+    fn set_tablet_mode(&mut self, tablet_mode: bool) {
+        self.tablet_mode = tablet_mode;
+    }
+}
+
+/// Coordinates suspend events of systemd-logind with btadapter Suspend API.
+///
+/// This is meant for hosts that don't run powerd. It holds a delay inhibitor lock on sleep so
+/// that the adapter gets the chance to prepare for suspend before the system goes down.
+pub struct LogindSuspendManager {
+    context: Arc<Mutex<LogindSuspendManagerContext>>,
+    conn: Arc<SyncConnection>,
+    tx: tokio::sync::mpsc::Sender<LogindSuspendManagerMessage>,
+    rx: tokio::sync::mpsc::Receiver<LogindSuspendManagerMessage>,
+}
+
+impl LogindSuspendManager {
+    /// Instantiates the suspend manager.
+    ///
+    /// `conn` and `dbus_crossroads` are D-Bus objects from `dbus` crate, to be used for both
+    /// communication with logind and btadapterd.
+    pub fn new(conn: Arc<SyncConnection>, dbus_crossroads: Arc<Mutex<Crossroads>>) -> Self {
+        let (tx, rx) = tokio::sync::mpsc::channel::<LogindSuspendManagerMessage>(10);
+        Self {
+            context: Arc::new(Mutex::new(LogindSuspendManagerContext::new(dbus_crossroads))),
+            conn,
+            tx,
+            rx,
+        }
+    }
+
+    pub fn get_suspend_manager_context(&mut self) -> Arc<Mutex<LogindSuspendManagerContext>> {
+        self.context.clone()
+    }
+
+    /// Sets up all required D-Bus listeners.
+    pub async fn init(&mut self) {
+        // Watch events of logind appearing or disappearing.
+        let logind_watcher = ServiceWatcher::new(self.conn.clone(), String::from(LOGIND_SERVICE));
+        let tx1 = self.tx.clone();
+        let tx2 = self.tx.clone();
+        logind_watcher
+            .start_watch(
+                Box::new(move || {
+                    let tx_clone = tx1.clone();
+                    tokio::spawn(async move {
+                        let _ = tx_clone.send(LogindSuspendManagerMessage::LogindStarted).await;
+                    });
+                }),
+                Box::new(move || {
+                    let tx_clone = tx2.clone();
+                    tokio::spawn(async move {
+                        let _ = tx_clone.send(LogindSuspendManagerMessage::LogindStopped).await;
+                    });
+                }),
+            )
+            .await;
+
+        // Watch events of btadapterd appearing or disappearing. BlueZ only implements the
+        // Suspend interface on Chrome OS, where powerd is used instead.
+        watch_adapter_suspend(
+            self.conn.clone(),
+            ADAPTER_SERVICE,
+            self.tx.clone(),
+            LogindSuspendManagerMessage::AdapterFound,
+            || LogindSuspendManagerMessage::AdapterRemoved,
+        )
+        .await;
+
+        // Watch for PrepareForSleep signal from logind.
+        let mr = MatchRule::new_signal(LOGIND_INTERFACE, PREPARE_FOR_SLEEP_SIGNAL)
+            .with_sender(LOGIND_SERVICE)
+            .with_path(LOGIND_PATH);
+        self.conn.add_match_no_cb(&mr.match_str()).await.unwrap();
+
+        let tx = self.tx.clone();
+        self.conn.start_receive(
+            mr,
+            Box::new(move |msg, _conn| {
+                if let Some(start) = msg.get1::<bool>() {
+                    let tx_clone = tx.clone();
+                    tokio::spawn(async move {
+                        let _ = tx_clone
+                            .send(LogindSuspendManagerMessage::PrepareForSleepReceived(start))
+                            .await;
+                    });
+                } else {
+                    log::warn!("received empty PrepareForSleep signal");
+                }
+
+                true
+            }),
+        );
+    }
+
+    /// Starts the event handlers.
+    pub async fn mainloop(&mut self) {
+        loop {
+            let m = self.rx.recv().await;
+
+            if let Some(msg) = m {
+                match msg {
+                    LogindSuspendManagerMessage::LogindStarted => self.on_logind_started().await,
+                    LogindSuspendManagerMessage::LogindStopped => self.on_logind_stopped(),
+                    LogindSuspendManagerMessage::PrepareForSleepReceived(true) => {
+                        self.on_prepare_for_sleep()
+                    }
+                    LogindSuspendManagerMessage::PrepareForSleepReceived(false) => {
+                        self.on_resume_from_sleep().await
+                    }
+                    LogindSuspendManagerMessage::AdapterFound(object_path) => {
+                        self.on_adapter_found(object_path)
+                    }
+                    LogindSuspendManagerMessage::AdapterRemoved => self.on_adapter_removed(),
+                }
+            } else {
+                log::debug!("Exiting suspend manager mainloop");
+                break;
+            }
+        }
+    }
+
+    async fn on_logind_started(&mut self) {
+        log::debug!("logind started, initializing suspend manager");
+
+        if self.context.lock().unwrap().logind_proxy.is_some() {
+            log::warn!("logind session already exists, cleaning up first");
+            self.on_logind_stopped();
+        }
+
+        self.context.lock().unwrap().logind_proxy = Some(dbus::nonblock::Proxy::new(
+            LOGIND_SERVICE,
+            LOGIND_PATH,
+            LOGIND_DBUS_TIMEOUT,
+            self.conn.clone(),
+        ));
+
+        self.take_inhibitor().await;
+    }
+
+    fn on_logind_stopped(&mut self) {
+        log::debug!("logind stopped, cleaning up");
+
+        let mut context = self.context.lock().unwrap();
+        if context.logind_proxy.is_none() {
+            log::warn!("logind session does not exist, ignoring");
+        }
+        context.logind_proxy = None;
+        context.pending_suspend_id = None;
+        context.release_inhibitor();
+    }
+
+    /// Takes a delay inhibitor lock on sleep from logind, if not already held.
+    async fn take_inhibitor(&mut self) {
+        let proxy = {
+            let context = self.context.lock().unwrap();
+            if context.inhibitor.is_some() {
+                return;
+            }
+
+            match &context.logind_proxy {
+                Some(proxy) => proxy.clone(),
+                None => {
+                    log::warn!("Can't take sleep inhibitor without logind session");
+                    return;
+                }
+            }
+        };
+
+        let result: Result<(OwnedFd,), dbus::Error> = proxy
+            .method_call(
+                LOGIND_INTERFACE,
+                "Inhibit",
+                (INHIBIT_WHAT, BTMANAGERD_NAME, INHIBIT_WHY, INHIBIT_MODE),
+            )
+            .await;
+
+        match result {
+            Err(e) => log::error!("Error taking sleep inhibitor: {:?}", e),
+            Ok((fd,)) => {
+                log::debug!("Took sleep inhibitor");
+                self.context.lock().unwrap().inhibitor = Some(fd);
+            }
+        }
+    }
+
+    fn on_prepare_for_sleep(&mut self) {
+        // logind is telling us that system is about to suspend, if available tell btadapterd to
+        // prepare for suspend.
+        let mut context = self.context.lock().unwrap();
+        let (suspend_id, suspend_type) = context.prepare_for_sleep();
+
+        log::debug!("received PrepareForSleep(true), suspend_id = {}", suspend_id);
+
+        if let Some(adapter_suspend_dbus) = &context.adapter_suspend_dbus {
+            let mut suspend_dbus_rpc = adapter_suspend_dbus.rpc.clone();
+            tokio::spawn(async move {
+                let result = suspend_dbus_rpc.suspend(suspend_type, suspend_id).await;
+
+                log::debug!("Adapter suspend call, success = {}", result.is_ok());
+            });
+        }
+    }
+
+    async fn on_resume_from_sleep(&mut self) {
+        // logind is telling us that system has resumed, so we tell btadapterd to resume too and
+        // take the inhibitor again for the next suspend.
+        log::debug!("received PrepareForSleep(false)");
+
+        {
+            let mut context = self.context.lock().unwrap();
+            context.resume_from_sleep();
+
+            if let Some(adapter_suspend_dbus) = &context.adapter_suspend_dbus {
+                let mut suspend_dbus_rpc = adapter_suspend_dbus.rpc.clone();
+                tokio::spawn(async move {
+                    let result = suspend_dbus_rpc.resume().await;
+                    log::debug!("Adapter resume call, success = {}", result.unwrap_or(false));
+                });
+            } else {
+                log::debug!("Adapter is not available, nothing to resume.");
+            }
+        }
+
+        self.take_inhibitor().await;
+    }
+
+    fn on_adapter_found(&mut self, path: dbus::Path<'static>) {
+        log::debug!("Found adapter suspend {:?}", path);
+
+        let conn = self.conn.clone();
+        self.context.lock().unwrap().adapter_suspend_dbus =
+            Some(SuspendDBus::new(conn.clone(), path));
+
+        let crossroads = self.context.lock().unwrap().dbus_crossroads.clone();
+
+        if let Some(adapter_suspend_dbus) = &mut self.context.lock().unwrap().adapter_suspend_dbus {
+            let mut suspend_dbus_rpc = adapter_suspend_dbus.rpc.clone();
+            let context = self.context.clone();
+            tokio::spawn(async move {
+                let suspend_cb_objpath: String =
+                    format!("/org/chromium/bluetooth/Manager/suspend_callback");
+                let status = suspend_dbus_rpc
+                    .register_callback(Box::new(LogindSuspendCallback::new(
+                        suspend_cb_objpath,
+                        conn,
+                        crossroads,
+                        context.clone(),
+                    )))
+                    .await;
+                log::debug!("Suspend::RegisterCallback success = {}", status.unwrap_or(false));
+            });
+        }
+    }
+
+    fn on_adapter_removed(&mut self) {
+        log::debug!("Adapter suspend removed");
+
+        let mut context = self.context.lock().unwrap();
+        context.adapter_suspend_dbus = None;
+
+        // The adapter can't answer anymore so don't hold back a suspend in progress.
+        if context.pending_suspend_id.is_some() {
+            context.release_inhibitor();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::os::unix::io::IntoRawFd;
+
+    fn new_context() -> LogindSuspendManagerContext {
+        let mut context = LogindSuspendManagerContext::new(Arc::new(Mutex::new(Crossroads::new())));
+        // Any open fd can stand in for the inhibitor lock.
+        let fd = File::open("/dev/null").unwrap().into_raw_fd();
+        context.inhibitor = Some(unsafe { OwnedFd::new(fd) });
+        context
+    }
+
+    #[test]
+    fn test_prepare_for_sleep_without_adapter() {
+        let mut context = new_context();
+
+        let (suspend_id, suspend_type) = context.prepare_for_sleep();
+        assert_eq!(suspend_id, 0);
+        assert!(matches!(suspend_type, SuspendType::AllowWakeFromHid));
+        assert_eq!(context.pending_suspend_id, Some(0));
+        // Nothing to wait for without an adapter.
+        assert!(context.inhibitor.is_none());
+
+        context.resume_from_sleep();
+        assert_eq!(context.pending_suspend_id, None);
+
+        // Every suspend gets a new id.
+        let (suspend_id, _) = context.prepare_for_sleep();
+        assert_eq!(suspend_id, 1);
+    }
+
+    #[test]
+    fn test_prepare_for_sleep_in_tablet_mode() {
+        let mut context = new_context();
+        context.set_tablet_mode(true);
+
+        let (_, suspend_type) = context.prepare_for_sleep();
+        assert!(matches!(suspend_type, SuspendType::NoWakesAllowed));
+
+        context.set_tablet_mode(false);
+        context.resume_from_sleep();
+        let (_, suspend_type) = context.prepare_for_sleep();
+        assert!(matches!(suspend_type, SuspendType::AllowWakeFromHid));
+    }
+
+    #[test]
+    fn test_resume_from_sleep_releases_inhibitor() {
+        let mut context = new_context();
+        context.pending_suspend_id = Some(3);
+
+        context.resume_from_sleep();
+        assert_eq!(context.pending_suspend_id, None);
+        assert!(context.inhibitor.is_none());
+    }
+}
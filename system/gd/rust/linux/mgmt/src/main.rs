@@ -11,6 +11,7 @@ use dbus_projection::DisconnectWatcher;
 use dbus_tokio::connection;
 use log::LevelFilter;
 use manager_service::bluetooth_manager::BluetoothManager;
+use manager_service::logind_suspend_manager::LogindSuspendManager;
 use manager_service::powerd_suspend_manager::PowerdSuspendManager;
 use manager_service::{bluetooth_experimental_dbus, iface_bluetooth_manager};
 use manager_service::{bluetooth_manager_dbus, config_util, state_machine};
@@ -30,6 +31,14 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .default_value("syslog")
                 .help("Select log output"),
         )
+        .arg(
+            Arg::with_name("suspend-manager")
+                .long("suspend-manager")
+                .takes_value(true)
+                .possible_values(&["powerd", "logind"])
+                .default_value("powerd")
+                .help("Select the service to coordinate suspend with"),
+        )
         .get_matches();
 
     let is_debug = matches.is_present("debug");
@@ -73,7 +82,8 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
         state_machine::Invoker::UpstartInvoker
     };
 
-    let context = state_machine::create_new_state_machine_context(invoker);
+    let mut context = state_machine::create_new_state_machine_context(invoker);
+    context.set_default_log_level(level_filter);
     let proxy = context.get_proxy();
 
     // The resource is a task that should be spawned onto a tokio compatible
@@ -107,7 +117,7 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let om = cr.lock().unwrap().object_manager();
     cr.lock().unwrap().insert("/", &[om], {});
 
-    let bluetooth_manager = Arc::new(Mutex::new(Box::new(BluetoothManager::new(proxy))));
+    let bluetooth_manager = Arc::new(Mutex::new(Box::new(BluetoothManager::new(proxy, invoker))));
 
     // Set up the disconnect watcher to monitor client disconnects.
     let disconnect_watcher = Arc::new(Mutex::new(DisconnectWatcher::new()));
@@ -150,17 +160,34 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     cr.lock().unwrap().insert("/org/chromium/bluetooth/Manager", &[iface, iface_exp], mixin);
 
-    let mut powerd_suspend_manager = PowerdSuspendManager::new(conn.clone(), cr);
-
-    bluetooth_manager
-        .lock()
-        .unwrap()
-        .set_suspend_manager_context(powerd_suspend_manager.get_suspend_manager_context());
-
-    tokio::spawn(async move {
-        powerd_suspend_manager.init().await;
-        powerd_suspend_manager.mainloop().await;
-    });
+    match matches.value_of("suspend-manager").unwrap_or("powerd") {
+        "logind" => {
+            let mut logind_suspend_manager = LogindSuspendManager::new(conn.clone(), cr);
+
+            bluetooth_manager
+                .lock()
+                .unwrap()
+                .set_suspend_manager_context(logind_suspend_manager.get_suspend_manager_context());
+
+            tokio::spawn(async move {
+                logind_suspend_manager.init().await;
+                logind_suspend_manager.mainloop().await;
+            });
+        }
+        _ => {
+            let mut powerd_suspend_manager = PowerdSuspendManager::new(conn.clone(), cr);
+
+            bluetooth_manager
+                .lock()
+                .unwrap()
+                .set_suspend_manager_context(powerd_suspend_manager.get_suspend_manager_context());
+
+            tokio::spawn(async move {
+                powerd_suspend_manager.init().await;
+                powerd_suspend_manager.mainloop().await;
+            });
+        }
+    }
 
     tokio::spawn(async move {
         state_machine::mainloop(context, bluetooth_manager).await;
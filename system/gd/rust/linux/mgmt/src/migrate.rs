@@ -20,10 +20,15 @@ use glob::glob;
 
 use log::{debug, error, info, warn};
 
-const BT_LIBDIR: &str = "/var/lib/bluetooth";
-const FLOSS_CONF_FILE: &str = "/var/lib/bluetooth/bt_config.conf";
+use crate::iface_bluetooth_manager::{DeviceMigrationAction, DeviceMigrationReport};
 
-const ADAPTER_SECTION_NAME: &str = "Adapter";
+pub(crate) const BT_LIBDIR: &str = "/var/lib/bluetooth";
+// Floss keeps the devices of each adapter in its own conf file. The default adapter uses
+// bt_config.conf and other adapters use bt_config.<address>.conf
+const FLOSS_CONF_FILE_NAME: &str = "bt_config.conf";
+const FLOSS_CONF_FILE_GLOB: &str = "bt_config*.conf";
+
+pub(crate) const ADAPTER_SECTION_NAME: &str = "Adapter";
 const GENERAL_SECTION_NAME: &str = "General";
 const LINKKEY_SECTION_NAME: &str = "LinkKey";
 const DEVICEID_SECTION_NAME: &str = "DeviceID";
@@ -157,6 +162,39 @@ impl DeviceKey {
     }
 }
 
+impl DeviceMigrationReport {
+    pub(crate) fn new(adapter: &str, address: &str, action: DeviceMigrationAction) -> Self {
+        Self {
+            adapter: adapter.to_uppercase(),
+            address: address.to_uppercase(),
+            action,
+            converted_keys: vec![],
+            errors: vec![],
+        }
+    }
+
+    /// Marks the device as dropped because of |err|.
+    fn drop_device(&mut self, err: String) {
+        self.action = DeviceMigrationAction::Dropped;
+        self.converted_keys.clear();
+        self.errors.push(err);
+    }
+}
+
+/// Logs what a migration did to each device.
+fn log_report(report: &[DeviceMigrationReport]) {
+    for device in report {
+        info!(
+            "Migration of {} on adapter {}: {:?}, converted [{}], errors [{}]",
+            device.address,
+            device.adapter,
+            device.action,
+            device.converted_keys.join(", "),
+            device.errors.join(", ")
+        );
+    }
+}
+
 fn hex_str_to_dec_str(str: String) -> Result<String, String> {
     match u32::from_str_radix(str.trim_start_matches("0x"), 16) {
         Ok(str) => Ok(format!("{}", str)),
@@ -262,14 +300,16 @@ fn reverse_endianness(str: String, uppercase: bool) -> Result<String, String> {
 /// * `addr` - A string slice that holds the address of the BlueZ device that we're converting
 /// * `floss_conf` - The Floss Ini that we're adding to
 /// * `is_hid_file` - Whether the file is a BlueZ hog-uhid-cache file or BlueZ info file
+/// * `report` - The report of the device to add converted keys and errors to
 ///
 /// # Returns
 /// Whether the conversion was successful or not
-fn convert_from_bluez_device(
+pub(crate) fn convert_from_bluez_device(
     filename: &str,
     addr: &str,
     floss_conf: &mut Ini,
     is_hid_file: bool,
+    report: &mut DeviceMigrationReport,
 ) -> bool {
     // Floss device address strings need to be lower case
     let addr_lower = addr.to_lowercase();
@@ -285,6 +325,7 @@ fn convert_from_bluez_device(
                 err, addr
             );
             floss_conf.remove_section(addr_lower.as_str());
+            report.drop_device(format!("{}: {}", filename, err));
             return false;
         }
     };
@@ -292,6 +333,7 @@ fn convert_from_bluez_device(
     // Floss will not load the HID info unless it sees this key and BlueZ does not have a matching key
     if is_hid_file {
         floss_conf.set(addr_lower.as_str(), "HidAttrMask", Some("0".into()));
+        report.converted_keys.push("HidAttrMask".into());
     }
 
     for (sec, props) in bluez_map {
@@ -315,6 +357,7 @@ fn convert_from_bluez_device(
                     "LE_KEY_PID",
                     Some(format!("{}{:02x}{}", irk, addr_type, addr_lower.replace(":", ""))),
                 );
+                report.converted_keys.push("LE_KEY_PID".into());
                 true
             }
             "PeripheralLongTermKey" | LTK_SECTION_NAME => {
@@ -351,6 +394,7 @@ fn convert_from_bluez_device(
                     "LE_KEY_PENC",
                     Some(ltk.try_into().unwrap_or_default()),
                 );
+                report.converted_keys.push("LE_KEY_PENC".into());
                 true
             }
             _ => false,
@@ -465,10 +509,12 @@ fn convert_from_bluez_device(
                                     err, addr
                                 );
                                 floss_conf.remove_section(addr_lower.as_str());
+                                report.drop_device(format!("{}: {}", key.key, err));
                                 return false;
                             }
                         };
                         floss_conf.set(addr_lower.as_str(), key.key.clone(), Some(new_val));
+                        report.converted_keys.push(key.key.into());
                     }
                 }
                 None => {
@@ -481,59 +527,193 @@ fn convert_from_bluez_device(
     true
 }
 
+/// Returns the path of the default Floss conf file in |dir|.
+pub(crate) fn floss_conf_path(dir: &str) -> String {
+    format!("{}/{}", dir, FLOSS_CONF_FILE_NAME)
+}
+
+/// Finds all Floss conf files in |dir|, the default one first.
+fn find_floss_confs(dir: &str) -> Vec<String> {
+    let default_path = floss_conf_path(dir);
+    let mut confs: Vec<String> = match glob(format!("{}/{}", dir, FLOSS_CONF_FILE_GLOB).as_str()) {
+        Ok(globbed) => globbed
+            .filter_map(|entry| entry.ok())
+            .map(|path| path.to_str().unwrap_or_default().to_string())
+            .filter(|path| *path != default_path)
+            .collect(),
+        Err(_) => vec![],
+    };
+    if Path::new(&default_path).exists() {
+        confs.insert(0, default_path);
+    }
+    confs
+}
+
+/// Reads the adapter address of a Floss conf file, in lower case.
+fn read_floss_adapter_addr(filename: &str) -> Option<String> {
+    let mut conf = Ini::new_cs();
+    conf.load(filename).ok()?;
+    conf.get(ADAPTER_SECTION_NAME, "Address").map(|addr| addr.to_lowercase())
+}
+
+/// Returns the Floss conf file of the adapter with |adapter_addr| in |dir|.
+///
+/// An adapter keeps using the conf file that has its address. Otherwise the default conf file is
+/// used if |is_default| and it doesn't belong to another adapter yet, and a conf file named after
+/// the address is used for other adapters.
+pub(crate) fn floss_conf_for_adapter(dir: &str, adapter_addr: &str, is_default: bool) -> String {
+    let adapter_addr = adapter_addr.to_lowercase();
+    if let Some(path) = find_floss_confs(dir)
+        .into_iter()
+        .find(|path| read_floss_adapter_addr(path).as_ref() == Some(&adapter_addr))
+    {
+        return path;
+    }
+
+    let default_path = floss_conf_path(dir);
+    if is_default && read_floss_adapter_addr(&default_path).is_none() {
+        default_path
+    } else {
+        format!("{}/bt_config.{}.conf", dir, adapter_addr.replace(":", ""))
+    }
+}
+
+/// Picks the Floss conf file to migrate each BlueZ adapter to, see |floss_conf_for_adapter|.
+///
+/// The |default_adapter| gets the first pick of the default conf file, then the other adapters in
+/// order. Only a single adapter can take over the default conf file.
+fn map_adapters_to_floss_confs(
+    dir: &str,
+    adapters: &[String],
+    default_adapter: Option<&str>,
+) -> HashMap<String, String> {
+    let mut sorted = adapters.to_vec();
+    sorted.sort_by_key(|adapter| {
+        (!default_adapter.map_or(false, |d| adapter.eq_ignore_ascii_case(d)), adapter.clone())
+    });
+
+    let default_path = floss_conf_path(dir);
+    let mut map: HashMap<String, String> = HashMap::new();
+    for adapter in sorted {
+        let default_taken = map.values().any(|path| *path == default_path);
+        let path = floss_conf_for_adapter(dir, &adapter, !default_taken);
+        map.insert(adapter, path);
+    }
+    map
+}
+
 /// This is the main function that handles the device migration from BlueZ to Floss.
-pub fn migrate_bluez_devices() {
+///
+/// The devices of each BlueZ adapter are migrated to the Floss conf file of that adapter. The
+/// |default_adapter| is the address of the adapter Floss makes the default one, if known.
+pub fn migrate_bluez_devices(default_adapter: Option<&str>) {
+    log_report(&migrate_bluez_devices_in(BT_LIBDIR, default_adapter, /*dry_run=*/ false));
+}
+
+/// Computes the device migration from BlueZ to Floss without writing anything.
+pub fn dry_run_bluez_devices(default_adapter: Option<&str>) -> Vec<DeviceMigrationReport> {
+    migrate_bluez_devices_in(BT_LIBDIR, default_adapter, /*dry_run=*/ true)
+}
+
+fn migrate_bluez_devices_in(
+    dir: &str,
+    default_adapter: Option<&str>,
+    dry_run: bool,
+) -> Vec<DeviceMigrationReport> {
+    let mut report: Vec<DeviceMigrationReport> = Vec::new();
+
     // Maps adapter address to Ini
     let mut adapter_conf_map: HashMap<String, Ini> = HashMap::new();
 
+    // Adapters without devices still need their Floss devices removed
+    // In BlueZ, adapters look like /var/lib/bluetooth/<adapter address>
+    if let Ok(globbed) = glob(format!("{}/*:*", dir).as_str()) {
+        for entry in globbed.filter_map(|entry| entry.ok()).filter(|path| path.is_dir()) {
+            let adapter_addr = entry.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            adapter_conf_map.entry(adapter_addr.into()).or_insert(Ini::new_cs());
+        }
+    }
+
+    // The default adapter may have no BlueZ storage, its Floss devices are still deleted
+    if let Some(default_adapter) = default_adapter {
+        if !adapter_conf_map.keys().any(|adapter| adapter.eq_ignore_ascii_case(default_adapter)) {
+            adapter_conf_map.insert(default_adapter.to_uppercase(), Ini::new_cs());
+        }
+    }
+
+    if adapter_conf_map.is_empty() {
+        warn!("Didn't find any BlueZ adapters to migrate");
+        return report;
+    }
+    let adapters: Vec<String> = adapter_conf_map.keys().cloned().collect();
+    let conf_paths = map_adapters_to_floss_confs(dir, &adapters, default_adapter);
+
     // Find and parse all device files
     // In BlueZ, device info files look like /var/lib/bluetooth/<adapter address>/<device address>/info
-    let globbed = match glob(format!("{}/*:*/*:*/info", BT_LIBDIR).as_str()) {
+    let globbed = match glob(format!("{}/*:*/*:*/info", dir).as_str()) {
         Ok(v) => v,
         Err(_) => {
             warn!("Didn't find any BlueZ adapters to migrate");
-            return;
+            return report;
         }
     };
     for entry in globbed {
         let info_path = entry.unwrap_or_default();
-        let hid_path = info_path.to_str().unwrap_or_default().replace("info", "hog-uhid-cache");
+        let hid_path = info_path.with_file_name("hog-uhid-cache");
         let addrs = info_path.to_str().unwrap_or_default().split('/').collect::<Vec<&str>>();
         let adapter_addr = addrs[addrs.len() - 3];
         let device_addr = addrs[addrs.len() - 2];
-        // Create new Ini file if it doesn't already exist
-        adapter_conf_map.entry(adapter_addr.into()).or_insert(Ini::new_cs());
-        if !convert_from_bluez_device(
+        let mut device_report =
+            DeviceMigrationReport::new(adapter_addr, device_addr, DeviceMigrationAction::Migrated);
+        // Convert the HID info too if we have it
+        if convert_from_bluez_device(
             info_path.to_str().unwrap_or_default(),
             device_addr,
             adapter_conf_map.get_mut(adapter_addr).unwrap_or(&mut Ini::new_cs()),
             /*is_hid_file=*/ false,
-        ) {
-            continue;
-        }
-
-        // Check if we have HID info
-        if Path::new(hid_path.as_str()).exists() {
+            &mut device_report,
+        ) && hid_path.exists()
+        {
             convert_from_bluez_device(
-                hid_path.as_str(),
+                hid_path.to_str().unwrap_or_default(),
                 device_addr,
                 adapter_conf_map.get_mut(adapter_addr).unwrap_or(&mut Ini::new_cs()),
                 /*is_hid_file=*/ true,
+                &mut device_report,
             );
         }
+        report.push(device_report);
     }
 
-    // Write migration to appropriate adapter files
-    // TODO(b/232138101): Update for multi-adapter support
+    // Write migration to the Floss conf file of each adapter
     for (adapter, conf) in adapter_conf_map.iter_mut() {
+        let conf_path = &conf_paths[adapter];
+        // Devices that failed to convert are already reported as dropped
+        let dropped: Vec<String> = report
+            .iter()
+            .filter(|device| device.adapter.eq_ignore_ascii_case(adapter))
+            .filter(|device| device.action == DeviceMigrationAction::Dropped)
+            .map(|device| device.address.to_lowercase())
+            .collect();
+
+        // A conf file that doesn't belong to any adapter yet now belongs to this one
+        conf.set(ADAPTER_SECTION_NAME, "Address", Some(adapter.to_lowercase()));
         let mut existing_conf = Ini::new_cs();
-        match existing_conf.load(FLOSS_CONF_FILE) {
+        match existing_conf.load(conf_path) {
             Ok(ini) => {
                 let devices = conf.sections();
                 for (sec, props) in ini {
+                    if sec.contains(":") && dropped.contains(&sec.to_lowercase()) {
+                        continue;
+                    }
                     // Drop devices that don't exist in BlueZ
                     if sec.contains(":") && !devices.contains(&sec) {
                         info!("Dropping a device in Floss that doesn't exist in BlueZ");
+                        report.push(DeviceMigrationReport::new(
+                            adapter,
+                            &sec,
+                            DeviceMigrationAction::Deleted,
+                        ));
                         continue;
                     }
                     // Keep keys that weren't transferrable
@@ -545,14 +725,18 @@ pub fn migrate_bluez_devices() {
                 }
             }
             // Conf file doesn't exist yet
-            Err(_) => {
-                conf.set(ADAPTER_SECTION_NAME, "Address", Some(adapter.clone()));
-            }
+            Err(_) => {}
+        }
+        if dry_run {
+            continue;
         }
         // Write contents to file
-        match conf.write(FLOSS_CONF_FILE) {
+        match conf.write(conf_path) {
             Ok(_) => {
-                info!("Successfully migrated devices from BlueZ to Floss for adapter {}", adapter);
+                info!(
+                    "Successfully migrated devices from BlueZ to Floss for adapter {} to {}",
+                    adapter, conf_path
+                );
             }
             Err(err) => {
                 error!(
@@ -562,6 +746,8 @@ pub fn migrate_bluez_devices() {
             }
         }
     }
+
+    report
 }
 
 /// Helper function in Floss to BlueZ conversion that takes a Floss device that already
@@ -602,8 +788,16 @@ fn merge_and_write_bluez_conf(filepath: String, conf: &mut Ini) {
 /// Helper function that does the conversion from Floss to BlueZ for a single adapter
 ///
 /// # Arguments
+/// * `dir` - A string slice that holds the BlueZ storage directory to write devices to
 /// * `filename` - A string slice that holds the path of the Floss conf file to get device info from
-fn convert_floss_conf(filename: &str) {
+/// * `dry_run` - Whether to only fill |report| without writing to BlueZ
+/// * `report` - The list of device reports to add to
+fn convert_floss_conf(
+    dir: &str,
+    filename: &str,
+    dry_run: bool,
+    report: &mut Vec<DeviceMigrationReport>,
+) {
     let mut floss_conf = Ini::new_cs();
     let floss_map = match floss_conf.load(filename) {
         Ok(map) => map,
@@ -624,6 +818,58 @@ fn convert_floss_conf(filename: &str) {
         }
     };
 
+    let devices = convert_floss_devices(dir, &adapter_addr, floss_map, dry_run, report);
+
+    // Delete devices that exist in BlueZ but not in Floss
+    match glob(format!("{}/{}/*:*", dir, adapter_addr).as_str()) {
+        Ok(globbed) => {
+            for entry in globbed {
+                let pathbuf = entry.unwrap_or_default();
+                let addrs = pathbuf.to_str().unwrap_or_default().split('/').collect::<Vec<&str>>();
+                let device_addr: String = addrs[addrs.len() - 1].into();
+                if !devices.contains(&device_addr.to_lowercase()) {
+                    report.push(DeviceMigrationReport::new(
+                        &adapter_addr,
+                        &device_addr,
+                        DeviceMigrationAction::Deleted,
+                    ));
+                    if dry_run {
+                        continue;
+                    }
+                    match fs::remove_dir_all(pathbuf) {
+                        Ok(_) => (),
+                        Err(err) => {
+                            warn!(
+                                "Error removing {} during Floss to BlueZ device migration: {}",
+                                device_addr, err
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Helper function that converts the devices of a single Floss adapter and writes them to BlueZ
+///
+/// # Arguments
+/// * `dir` - A string slice that holds the BlueZ storage directory to write devices to
+/// * `adapter_addr` - A string slice that holds the upper case address of the adapter
+/// * `floss_map` - The sections of the Floss conf, non-device sections are ignored
+/// * `dry_run` - Whether to only fill |report| without writing to BlueZ
+/// * `report` - The list of device reports to add to
+///
+/// # Returns
+/// The lower case addresses of the converted devices
+pub(crate) fn convert_floss_devices(
+    dir: &str,
+    adapter_addr: &str,
+    floss_map: HashMap<String, HashMap<String, Option<String>>>,
+    dry_run: bool,
+    report: &mut Vec<DeviceMigrationReport>,
+) -> Vec<String> {
     // BlueZ info file map
     let mut info_map: HashMap<&str, DeviceKey> = [
         // General
@@ -719,6 +965,8 @@ fn convert_floss_conf(filename: &str) {
         // Keep track of Floss devices we've seen so we can remove BlueZ devices that don't exist on Floss
         devices.push(sec.clone());
         let device_addr = sec.to_uppercase();
+        let mut device_report =
+            DeviceMigrationReport::new(adapter_addr, &device_addr, DeviceMigrationAction::Migrated);
         let mut bluez_info = Ini::new_cs();
         let mut bluez_hid = Ini::new_cs();
         let mut is_hid: bool = false;
@@ -731,6 +979,7 @@ fn convert_floss_conf(filename: &str) {
                 bluez_info.set(LTK_SECTION_NAME, "EDiv", Some(format!("{}", ltk.ediv)));
                 bluez_info.set(LTK_SECTION_NAME, "Authenticated", Some(format!("{}", ltk.auth)));
                 bluez_info.set(LTK_SECTION_NAME, "EncSize", Some(format!("{}", ltk.len)));
+                device_report.converted_keys.push(LTK_SECTION_NAME.into());
                 continue;
             }
             // Convert matching info file keys
@@ -740,10 +989,12 @@ fn convert_floss_conf(filename: &str) {
                         Ok(val) => val,
                         Err(err) => {
                             warn!("Error converting Floss to Bluez key for adapter {}, device {}, key {}: {}", adapter_addr, device_addr, k, err);
+                            device_report.errors.push(format!("{}: {}", k, err));
                             continue;
                         }
                     };
                     bluez_info.set(key.section, key.key.clone(), Some(new_val));
+                    device_report.converted_keys.push(format!("{}.{}", key.section, key.key));
                     continue;
                 }
                 None => {
@@ -758,10 +1009,12 @@ fn convert_floss_conf(filename: &str) {
                         Ok(val) => val,
                         Err(err) => {
                             warn!("Error converting Floss to Bluez key for adapter {}, device {}, key {}: {}", adapter_addr, device_addr, k, err);
+                            device_report.errors.push(format!("{}: {}", k, err));
                             continue;
                         }
                     };
                     bluez_hid.set(key.section, key.key.clone(), Some(new_val));
+                    device_report.converted_keys.push(format!("{}.{}", key.section, key.key));
                 }
                 None => {
                     debug!("No key match: {}", k)
@@ -769,7 +1022,12 @@ fn convert_floss_conf(filename: &str) {
             }
         }
 
-        let path = format!("{}/{}/{}", BT_LIBDIR, adapter_addr, device_addr);
+        report.push(device_report);
+        if dry_run {
+            continue;
+        }
+
+        let path = format!("{}/{}/{}", dir, adapter_addr, device_addr);
 
         // Create BlueZ device dir and all its parents if they're missing
         match fs::create_dir_all(path.clone()) {
@@ -787,45 +1045,44 @@ fn convert_floss_conf(filename: &str) {
         }
     }
 
-    // Delete devices that exist in BlueZ but not in Floss
-    match glob(format!("{}/{}/*:*", BT_LIBDIR, adapter_addr).as_str()) {
-        Ok(globbed) => {
-            for entry in globbed {
-                let pathbuf = entry.unwrap_or_default();
-                let addrs = pathbuf.to_str().unwrap_or_default().split('/').collect::<Vec<&str>>();
-                let device_addr: String = addrs[addrs.len() - 1].into();
-                if !devices.contains(&device_addr.to_lowercase()) {
-                    match fs::remove_dir_all(pathbuf) {
-                        Ok(_) => (),
-                        Err(err) => {
-                            warn!(
-                                "Error removing {} during Floss to BlueZ device migration: {}",
-                                device_addr, err
-                            );
-                        }
-                    }
-                }
-            }
-        }
-        _ => (),
-    }
+    devices
 }
 
 /// This is the main function that handles the device migration from Floss to BlueZ.
+///
+/// The devices of each Floss conf file go back to the BlueZ storage of the adapter the conf file
+/// belongs to.
 pub fn migrate_floss_devices() {
-    // Find and parse all Floss conf files
-    // TODO(b/232138101): Currently Floss only supports a single adapter; update here for multi-adapter support
-    let globbed = match glob(FLOSS_CONF_FILE) {
-        Ok(v) => v,
-        Err(_) => {
-            warn!("Didn't find Floss conf file to migrate");
-            return;
-        }
-    };
+    log_report(&migrate_floss_devices_in(BT_LIBDIR, /*dry_run=*/ false));
+}
 
-    for entry in globbed {
-        convert_floss_conf(entry.unwrap_or_default().to_str().unwrap_or_default());
+/// Computes the device migration from Floss to BlueZ without writing anything.
+pub fn dry_run_floss_devices() -> Vec<DeviceMigrationReport> {
+    migrate_floss_devices_in(BT_LIBDIR, /*dry_run=*/ true)
+}
+
+fn migrate_floss_devices_in(dir: &str, dry_run: bool) -> Vec<DeviceMigrationReport> {
+    let mut report: Vec<DeviceMigrationReport> = Vec::new();
+
+    let confs = find_floss_confs(dir);
+    if confs.is_empty() {
+        warn!("Didn't find any Floss conf files to migrate");
+        return report;
+    }
+    for conf in confs {
+        convert_floss_conf(dir, &conf, dry_run, &mut report);
     }
+
+    report
+}
+
+/// Creates an empty directory to lay out BlueZ and Floss storage in, for tests.
+#[cfg(test)]
+pub(crate) fn make_test_dir(name: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("btmanagerd_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir.to_str().unwrap().to_string()
 }
 
 #[cfg(test)]
@@ -1031,12 +1288,18 @@ mod tests {
     fn test_convert_from_bluez_device() {
         let test_addr = "00:11:22:33:44:55";
         let mut conf = Ini::new_cs();
+        let mut report = DeviceMigrationReport::new(
+            "00:00:00:00:00:AA",
+            test_addr,
+            DeviceMigrationAction::Migrated,
+        );
         assert_eq!(
             convert_from_bluez_device(
                 "test/migrate/fake_bluez_info.toml",
                 test_addr,
                 &mut conf,
-                false
+                false,
+                &mut report
             ),
             true
         );
@@ -1045,10 +1308,16 @@ mod tests {
                 "test/migrate/fake_bluez_hid.toml",
                 test_addr,
                 &mut conf,
-                true
+                true,
+                &mut report
             ),
             true
         );
+        assert_eq!(report.action, DeviceMigrationAction::Migrated);
+        assert!(report.converted_keys.contains(&"LinkKey".to_string()));
+        assert!(report.converted_keys.contains(&"LE_KEY_PENC".to_string()));
+        assert!(report.converted_keys.contains(&"HidDescriptor".to_string()));
+        assert!(report.errors.is_empty());
 
         assert_eq!(conf.get(test_addr, "Name"), Some(String::from("Test Device")));
         assert_eq!(conf.get(test_addr, "DevClass"), Some(String::from("2360344")));
@@ -1096,4 +1365,283 @@ mod tests {
         assert_eq!(conf.get(test_addr, "HidVersion"), Some(String::from("273")));
         assert_eq!(conf.get(test_addr, "HidCountryCode"), Some(String::from("3")));
     }
+
+    fn add_bluez_device(dir: &str, adapter: &str, device: &str) {
+        let path = format!("{}/{}/{}", dir, adapter, device);
+        fs::create_dir_all(&path).unwrap();
+        fs::copy("test/migrate/fake_bluez_info.toml", format!("{}/info", path)).unwrap();
+    }
+
+    fn load_conf(path: &str) -> Ini {
+        let mut conf = Ini::new_cs();
+        conf.load(path).unwrap();
+        conf
+    }
+
+    /// Lists the Floss conf files in |dir|.
+    fn list_floss_confs(dir: &str) -> Vec<String> {
+        let mut confs: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| name.starts_with("bt_config"))
+            .collect();
+        confs.sort();
+        confs
+    }
+
+    #[test]
+    fn test_floss_conf_for_adapter() {
+        let dir = make_test_dir("conf_for_adapter");
+        let default_conf = floss_conf_path(&dir);
+        let conf_bb = format!("{}/bt_config.0000000000bb.conf", dir);
+
+        // Without conf files the default adapter takes the default one.
+        assert_eq!(floss_conf_for_adapter(&dir, "00:00:00:00:00:AA", true), default_conf);
+        assert_eq!(floss_conf_for_adapter(&dir, "00:00:00:00:00:BB", false), conf_bb);
+
+        // An adapter keeps the conf file with its address.
+        fs::write(&default_conf, "[Adapter]\nAddress = 00:00:00:00:00:bb\n").unwrap();
+        assert_eq!(floss_conf_for_adapter(&dir, "00:00:00:00:00:BB", false), default_conf);
+        assert_eq!(
+            floss_conf_for_adapter(&dir, "00:00:00:00:00:AA", true),
+            format!("{}/bt_config.0000000000aa.conf", dir)
+        );
+
+        fs::write(&default_conf, "").unwrap();
+        fs::write(&conf_bb, "[Adapter]\nAddress = 00:00:00:00:00:bb\n").unwrap();
+        assert_eq!(floss_conf_for_adapter(&dir, "00:00:00:00:00:BB", true), conf_bb);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_bluez_two_adapters() {
+        let dir = make_test_dir("bluez_two_adapters");
+        add_bluez_device(&dir, "00:00:00:00:00:AA", "00:11:22:33:44:55");
+        add_bluez_device(&dir, "00:00:00:00:00:BB", "66:77:88:99:AA:BB");
+        // Existing Floss conf of the second adapter with a stale device and a Floss only key
+        fs::write(
+            format!("{}/bt_config.conf", dir),
+            "[Adapter]\nAddress = 00:00:00:00:00:bb\n\n\
+             [66:77:88:99:aa:bb]\nFlossOnly = 1\n\n\
+             [de:ad:be:ef:00:00]\nName = Stale\n",
+        )
+        .unwrap();
+
+        let mut report = migrate_bluez_devices_in(&dir, None, false);
+        report.sort_by(|a, b| a.address.cmp(&b.address));
+
+        let conf = load_conf(&format!("{}/bt_config.conf", dir));
+        assert_eq!(conf.get(ADAPTER_SECTION_NAME, "Address"), Some("00:00:00:00:00:bb".into()));
+        assert_eq!(conf.get("66:77:88:99:aa:bb", "Name"), Some("Test Device".into()));
+        assert_eq!(conf.get("66:77:88:99:aa:bb", "FlossOnly"), Some("1".into()));
+        assert_eq!(conf.get("de:ad:be:ef:00:00", "Name"), None);
+        assert_eq!(conf.get("00:11:22:33:44:55", "Name"), None);
+
+        // The other adapter gets a conf file of its own.
+        let conf = load_conf(&format!("{}/bt_config.0000000000aa.conf", dir));
+        assert_eq!(conf.get(ADAPTER_SECTION_NAME, "Address"), Some("00:00:00:00:00:aa".into()));
+        assert_eq!(conf.get("00:11:22:33:44:55", "Name"), Some("Test Device".into()));
+        assert_eq!(conf.get("66:77:88:99:aa:bb", "Name"), None);
+
+        assert_eq!(report.len(), 3);
+        assert_eq!(report[0].address, "00:11:22:33:44:55");
+        assert_eq!(report[0].adapter, "00:00:00:00:00:AA");
+        assert_eq!(report[0].action, DeviceMigrationAction::Migrated);
+        assert_eq!(report[1].address, "66:77:88:99:AA:BB");
+        assert_eq!(report[1].adapter, "00:00:00:00:00:BB");
+        assert_eq!(report[1].action, DeviceMigrationAction::Migrated);
+        assert_eq!(report[2].address, "DE:AD:BE:EF:00:00");
+        assert_eq!(report[2].action, DeviceMigrationAction::Deleted);
+        assert_eq!(
+            list_floss_confs(&dir),
+            vec!["bt_config.0000000000aa.conf".to_string(), "bt_config.conf".to_string()]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_bluez_default_adapter() {
+        let dir = make_test_dir("bluez_default_adapter");
+        add_bluez_device(&dir, "00:00:00:00:00:BB", "66:77:88:99:AA:BB");
+        add_bluez_device(&dir, "00:00:00:00:00:CC", "00:11:22:33:44:55");
+
+        // The default adapter takes the default conf file, even without BlueZ storage.
+        let report = migrate_bluez_devices_in(&dir, Some("00:00:00:00:00:aa"), false);
+        assert!(report.iter().all(|device| device.action == DeviceMigrationAction::Migrated));
+
+        let conf = load_conf(&format!("{}/bt_config.conf", dir));
+        assert_eq!(conf.get(ADAPTER_SECTION_NAME, "Address"), Some("00:00:00:00:00:aa".into()));
+        let conf = load_conf(&format!("{}/bt_config.0000000000bb.conf", dir));
+        assert_eq!(conf.get("66:77:88:99:aa:bb", "Name"), Some("Test Device".into()));
+        let conf = load_conf(&format!("{}/bt_config.0000000000cc.conf", dir));
+        assert_eq!(conf.get("00:11:22:33:44:55", "Name"), Some("Test Device".into()));
+
+        // The conf files are kept when the default adapter changes.
+        migrate_bluez_devices_in(&dir, Some("00:00:00:00:00:bb"), false);
+        assert_eq!(list_floss_confs(&dir).len(), 3);
+        let conf = load_conf(&format!("{}/bt_config.0000000000bb.conf", dir));
+        assert_eq!(conf.get("66:77:88:99:aa:bb", "Name"), Some("Test Device".into()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_floss_two_adapters() {
+        let dir = make_test_dir("floss_two_adapters");
+        fs::write(
+            format!("{}/bt_config.conf", dir),
+            "[Adapter]\nAddress = 00:00:00:00:00:aa\n\n[00:11:22:33:44:55]\nName = Device A\n",
+        )
+        .unwrap();
+        fs::write(
+            format!("{}/bt_config.0000000000bb.conf", dir),
+            "[Adapter]\nAddress = 00:00:00:00:00:bb\n\n[66:77:88:99:aa:bb]\nName = Device B\n",
+        )
+        .unwrap();
+        // BlueZ devices that no longer exist in Floss
+        add_bluez_device(&dir, "00:00:00:00:00:AA", "DE:AD:BE:EF:00:00");
+        add_bluez_device(&dir, "00:00:00:00:00:BB", "DE:AD:BE:EF:00:01");
+
+        let report = migrate_floss_devices_in(&dir, false);
+
+        let info = load_conf(&format!("{}/00:00:00:00:00:AA/00:11:22:33:44:55/info", dir));
+        assert_eq!(info.get(GENERAL_SECTION_NAME, "Name"), Some("Device A".into()));
+        let info = load_conf(&format!("{}/00:00:00:00:00:BB/66:77:88:99:AA:BB/info", dir));
+        assert_eq!(info.get(GENERAL_SECTION_NAME, "Name"), Some("Device B".into()));
+        assert!(!Path::new(&format!("{}/00:00:00:00:00:AA/66:77:88:99:AA:BB", dir)).exists());
+        assert!(!Path::new(&format!("{}/00:00:00:00:00:AA/DE:AD:BE:EF:00:00", dir)).exists());
+        assert!(!Path::new(&format!("{}/00:00:00:00:00:BB/DE:AD:BE:EF:00:01", dir)).exists());
+        assert_eq!(
+            report.iter().filter(|device| device.action == DeviceMigrationAction::Deleted).count(),
+            2
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_floss_keeps_other_adapters() {
+        let dir = make_test_dir("floss_other_adapters");
+        fs::write(
+            format!("{}/bt_config.conf", dir),
+            "[Adapter]\nAddress = 00:00:00:00:00:aa\n\n[00:11:22:33:44:55]\nName = Device A\n",
+        )
+        .unwrap();
+        // Adapters without a Floss conf file are kept as is.
+        add_bluez_device(&dir, "00:00:00:00:00:BB", "66:77:88:99:AA:BB");
+
+        let report = migrate_floss_devices_in(&dir, false);
+
+        let info = load_conf(&format!("{}/00:00:00:00:00:AA/00:11:22:33:44:55/info", dir));
+        assert_eq!(info.get(GENERAL_SECTION_NAME, "Name"), Some("Device A".into()));
+        assert!(Path::new(&format!("{}/00:00:00:00:00:BB/66:77:88:99:AA:BB/info", dir)).exists());
+        assert!(report.iter().all(|device| device.adapter == "00:00:00:00:00:AA"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_convert_from_bluez_device_error() {
+        let dir = make_test_dir("bluez_device_error");
+        let info = format!("{}/info", dir);
+        fs::write(&info, "[General]\nName=Bad Device\nClass=0xZZ\n").unwrap();
+
+        let test_addr = "00:11:22:33:44:55";
+        let mut conf = Ini::new_cs();
+        let mut report = DeviceMigrationReport::new(
+            "00:00:00:00:00:AA",
+            test_addr,
+            DeviceMigrationAction::Migrated,
+        );
+        assert_eq!(
+            convert_from_bluez_device(&info, test_addr, &mut conf, false, &mut report),
+            false
+        );
+        assert_eq!(conf.get(test_addr, "Name"), None);
+        assert_eq!(report.action, DeviceMigrationAction::Dropped);
+        assert!(report.converted_keys.is_empty());
+        assert_eq!(report.errors.len(), 1);
+        assert!(report.errors[0].starts_with("DevClass: "));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_dry_run_bluez_to_floss() {
+        let dir = make_test_dir("dry_run_bluez");
+        add_bluez_device(&dir, "00:00:00:00:00:AA", "00:11:22:33:44:55");
+        let floss_conf =
+            "[Adapter]\nAddress = 00:00:00:00:00:aa\n\n[de:ad:be:ef:00:00]\nName = Stale\n";
+        fs::write(format!("{}/bt_config.conf", dir), floss_conf).unwrap();
+
+        let mut report = migrate_bluez_devices_in(&dir, None, true);
+        report.sort_by(|a, b| a.address.cmp(&b.address));
+
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].address, "00:11:22:33:44:55");
+        assert_eq!(report[0].adapter, "00:00:00:00:00:AA");
+        assert_eq!(report[0].action, DeviceMigrationAction::Migrated);
+        assert!(report[0].converted_keys.contains(&"Name".to_string()));
+        assert_eq!(report[1].address, "DE:AD:BE:EF:00:00");
+        assert_eq!(report[1].action, DeviceMigrationAction::Deleted);
+
+        // Nothing is written
+        assert_eq!(fs::read_to_string(format!("{}/bt_config.conf", dir)).unwrap(), floss_conf);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_dropped_device_reported_once() {
+        let dir = make_test_dir("dropped_once");
+        let device_dir = format!("{}/00:00:00:00:00:AA/00:11:22:33:44:55", dir);
+        fs::create_dir_all(&device_dir).unwrap();
+        fs::write(format!("{}/info", device_dir), "[General]\nName=Bad Device\nClass=0xZZ\n")
+            .unwrap();
+        // The device was already migrated before.
+        fs::write(
+            format!("{}/bt_config.conf", dir),
+            "[Adapter]\nAddress = 00:00:00:00:00:aa\n\n[00:11:22:33:44:55]\nName = Bad Device\n",
+        )
+        .unwrap();
+
+        let report = migrate_bluez_devices_in(&dir, None, true);
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].address, "00:11:22:33:44:55");
+        assert_eq!(report[0].action, DeviceMigrationAction::Dropped);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_dry_run_floss_to_bluez() {
+        let dir = make_test_dir("dry_run_floss");
+        fs::write(
+            format!("{}/bt_config.conf", dir),
+            "[Adapter]\nAddress = 00:00:00:00:00:aa\n\n\
+             [00:11:22:33:44:55]\nName = Device A\nDevClass = bad\n",
+        )
+        .unwrap();
+        add_bluez_device(&dir, "00:00:00:00:00:AA", "DE:AD:BE:EF:00:00");
+
+        let mut report = migrate_floss_devices_in(&dir, true);
+        report.sort_by(|a, b| a.address.cmp(&b.address));
+
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].address, "00:11:22:33:44:55");
+        assert_eq!(report[0].action, DeviceMigrationAction::Migrated);
+        assert_eq!(report[0].converted_keys, vec!["General.Name".to_string()]);
+        assert_eq!(report[0].errors.len(), 1);
+        assert!(report[0].errors[0].starts_with("DevClass: "));
+        assert_eq!(report[1].address, "DE:AD:BE:EF:00:00");
+        assert_eq!(report[1].action, DeviceMigrationAction::Deleted);
+
+        // Nothing is written or removed
+        assert!(!Path::new(&format!("{}/00:00:00:00:00:AA/00:11:22:33:44:55", dir)).exists());
+        assert!(Path::new(&format!("{}/00:00:00:00:00:AA/DE:AD:BE:EF:00:00/info", dir)).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
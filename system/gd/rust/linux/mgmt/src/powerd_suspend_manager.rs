@@ -1,4 +1,4 @@
-use btstack::suspend::{ISuspendCallback, SuspendType};
+use btstack::suspend::ISuspendCallback;
 use btstack::RPCProxy;
 use dbus::channel::MatchingReceiver;
 use dbus::message::MatchRule;
@@ -13,17 +13,17 @@ use crate::dbus_iface::{export_suspend_callback_dbus_intf, SuspendDBus};
 use crate::service_watcher::ServiceWatcher;
 use crate::suspend::{
     RegisterSuspendDelayReply, RegisterSuspendDelayRequest, SuspendDone, SuspendImminent,
-    SuspendImminent_Reason, SuspendReadinessInfo,
+    SuspendReadinessInfo,
+};
+use crate::suspend_manager::{
+    get_suspend_type, watch_adapter_suspend, SuspendManagerState, ADAPTER_SERVICE, BTMANAGERD_NAME,
 };
 
 const POWERD_SERVICE: &str = "org.chromium.PowerManager";
 const POWERD_INTERFACE: &str = "org.chromium.PowerManager";
 const POWERD_PATH: &str = "/org/chromium/PowerManager";
-const ADAPTER_SERVICE: &str = "org.chromium.bluetooth";
-const ADAPTER_SUSPEND_INTERFACE: &str = "org.chromium.bluetooth.Suspend";
 const SUSPEND_IMMINENT_SIGNAL: &str = "SuspendImminent";
 const SUSPEND_DONE_SIGNAL: &str = "SuspendDone";
-const BTMANAGERD_NAME: &str = "Bluetooth Manager";
 // powerd might take more than 2 seconds at initialization, and thus we use
 // D-Bus default timeout duration herer to cover this case, as other D-Bus
 // clients of powerd do.
@@ -171,6 +171,12 @@ pub struct SuspendManagerContext {
     pub tablet_mode: bool,
 }
 
+impl SuspendManagerState for SuspendManagerContext {
+    fn set_tablet_mode(&mut self, tablet_mode: bool) {
+        self.tablet_mode = tablet_mode;
+    }
+}
+
 /// Coordinates suspend events of Chromium OS's powerd with btadapter Suspend API.
 pub struct PowerdSuspendManager {
     context: Arc<Mutex<SuspendManagerContext>>,
@@ -227,52 +233,19 @@ impl PowerdSuspendManager {
             )
             .await;
 
-        // Watch events of btadapterd appearing or disappearing.
-        let mut adapter_watcher =
-            ServiceWatcher::new(self.conn.clone(), String::from(ADAPTER_SERVICE));
-        let tx1 = self.tx.clone();
-        let tx2 = self.tx.clone();
-        adapter_watcher
-            .start_watch_interface(
-                String::from(ADAPTER_SUSPEND_INTERFACE),
-                Box::new(move |path| {
-                    let tx_clone = tx1.clone();
-                    tokio::spawn(async move {
-                        let _ = tx_clone.send(SuspendManagerMessage::AdapterFound(path)).await;
-                    });
-                }),
-                Box::new(move || {
-                    let tx_clone = tx2.clone();
-                    tokio::spawn(async move {
-                        let _ = tx_clone.send(SuspendManagerMessage::AdapterRemoved).await;
-                    });
-                }),
-            )
-            .await;
-
-        // Watch events of bluez appearing or disappearing.
+        // Watch events of btadapterd or bluez appearing or disappearing.
         // This is with the assumption that only one instance of btadapterd and bluez can be alive
         // at a time.
-        let mut bluez_watcher = ServiceWatcher::new(self.conn.clone(), String::from(BLUEZ_SERVICE));
-        let tx1 = self.tx.clone();
-        let tx2 = self.tx.clone();
-        bluez_watcher
-            .start_watch_interface(
-                String::from(ADAPTER_SUSPEND_INTERFACE),
-                Box::new(move |path| {
-                    let tx_clone = tx1.clone();
-                    tokio::spawn(async move {
-                        let _ = tx_clone.send(SuspendManagerMessage::AdapterFound(path)).await;
-                    });
-                }),
-                Box::new(move || {
-                    let tx_clone = tx2.clone();
-                    tokio::spawn(async move {
-                        let _ = tx_clone.send(SuspendManagerMessage::AdapterRemoved).await;
-                    });
-                }),
+        for service in [ADAPTER_SERVICE, BLUEZ_SERVICE].iter() {
+            watch_adapter_suspend(
+                self.conn.clone(),
+                service,
+                self.tx.clone(),
+                SuspendManagerMessage::AdapterFound,
+                || SuspendManagerMessage::AdapterRemoved,
             )
             .await;
+        }
 
         // Watch for SuspendImminent signal from powerd.
         let mr = MatchRule::new_signal(POWERD_INTERFACE, SUSPEND_IMMINENT_SIGNAL)
@@ -458,20 +431,7 @@ impl PowerdSuspendManager {
                 tokio::spawn(async move {
                     let result = suspend_dbus_rpc
                         .suspend(
-                            match (tablet_mode, suspend_imminent.get_reason()) {
-                                // No wakes allowed on tablet mode.
-                                (true, _) => SuspendType::NoWakesAllowed,
-
-                                // When not in tablet mode, choose wake type based on suspend
-                                // reason.
-                                (false, SuspendImminent_Reason::IDLE) => {
-                                    SuspendType::AllowWakeFromHid
-                                }
-                                (false, SuspendImminent_Reason::LID_CLOSED) => {
-                                    SuspendType::NoWakesAllowed
-                                }
-                                (false, SuspendImminent_Reason::OTHER) => SuspendType::Other,
-                            },
+                            get_suspend_type(tablet_mode, suspend_imminent.get_reason()),
                             suspend_imminent.get_suspend_id(),
                         )
                         .await;
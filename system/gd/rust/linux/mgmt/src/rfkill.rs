@@ -0,0 +1,124 @@
+//! Parsing of events read from /dev/rfkill.
+//!
+//! See include/uapi/linux/rfkill.h in the kernel for the layout of the events.
+
+use std::convert::TryFrom;
+
+/// Path of the rfkill control device.
+pub const RFKILL_DEV_PATH: &str = "/dev/rfkill";
+
+/// Size of the original rfkill event. Newer kernels may append fields which are ignored.
+pub const RFKILL_EVENT_SIZE_V1: usize = 8;
+
+/// rfkill type of Bluetooth switches.
+const RFKILL_TYPE_BLUETOOTH: u8 = 2;
+
+/// Operation of an rfkill event.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RfkillOp {
+    /// A switch was added. Sent for all existing switches when the device is opened.
+    Add,
+    /// A switch was removed.
+    Del,
+    /// The state of a switch changed.
+    Change,
+    /// The state of all switches of a type changed. Only written by userspace.
+    ChangeAll,
+}
+
+impl TryFrom<u8> for RfkillOp {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(RfkillOp::Add),
+            1 => Ok(RfkillOp::Del),
+            2 => Ok(RfkillOp::Change),
+            3 => Ok(RfkillOp::ChangeAll),
+            _ => Err(()),
+        }
+    }
+}
+
+/// An event read from /dev/rfkill.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RfkillEvent {
+    /// Index of the switch, as in /sys/class/rfkill/rfkill<idx>.
+    pub idx: u32,
+    pub rfkill_type: u8,
+    pub op: RfkillOp,
+    /// Blocked by software, e.g. airplane mode.
+    pub soft: bool,
+    /// Blocked by a hardware kill switch.
+    pub hard: bool,
+}
+
+impl RfkillEvent {
+    /// Parses an event. Returns None if it's too short or has an unknown operation.
+    pub fn parse(buf: &[u8]) -> Option<RfkillEvent> {
+        if buf.len() < RFKILL_EVENT_SIZE_V1 {
+            return None;
+        }
+
+        Some(RfkillEvent {
+            idx: u32::from_ne_bytes([buf[0], buf[1], buf[2], buf[3]]),
+            rfkill_type: buf[4],
+            op: RfkillOp::try_from(buf[5]).ok()?,
+            soft: buf[6] != 0,
+            hard: buf[7] != 0,
+        })
+    }
+
+    /// Whether this event is about a Bluetooth switch.
+    pub fn is_bluetooth(&self) -> bool {
+        self.rfkill_type == RFKILL_TYPE_BLUETOOTH
+    }
+}
+
+/// Block state of an adapter, combining all the switches that apply to it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RfkillState {
+    pub soft: bool,
+    pub hard: bool,
+}
+
+impl RfkillState {
+    pub fn is_blocked(&self) -> bool {
+        self.soft || self.hard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_event() {
+        let mut buf = vec![0u8; RFKILL_EVENT_SIZE_V1];
+        buf[0..4].copy_from_slice(&3u32.to_ne_bytes());
+        buf[4] = RFKILL_TYPE_BLUETOOTH;
+        buf[5] = 2;
+        buf[6] = 1;
+        assert_eq!(
+            RfkillEvent::parse(&buf),
+            Some(RfkillEvent {
+                idx: 3,
+                rfkill_type: RFKILL_TYPE_BLUETOOTH,
+                op: RfkillOp::Change,
+                soft: true,
+                hard: false,
+            })
+        );
+        assert!(RfkillEvent::parse(&buf).unwrap().is_bluetooth());
+
+        // Extended events from newer kernels parse the same.
+        buf.push(0);
+        assert_eq!(RfkillEvent::parse(&buf).map(|e| e.idx), Some(3));
+    }
+
+    #[test]
+    fn parse_invalid_event() {
+        assert_eq!(RfkillEvent::parse(&[0u8; 4]), None);
+        assert_eq!(RfkillEvent::parse(&[0, 0, 0, 0, 2, 9, 0, 0]), None);
+    }
+}
@@ -1,30 +1,60 @@
 use crate::bluetooth_manager::BluetoothManager;
 use crate::config_util;
+use crate::iface_bluetooth_manager::{
+    AdapterControllerInfo, AdapterHealth, AdapterLaunchProfile, RestartEvent, RestartReason,
+};
+use crate::migrate;
+use crate::rfkill::{RfkillEvent, RfkillOp, RfkillState, RFKILL_DEV_PATH};
 use bt_common::time::Alarm;
 use bt_utils::socket::{
-    BtSocket, HciChannels, MgmtCommand, MgmtCommandResponse, MgmtEvent, HCI_DEV_NONE,
+    BtSocket, HciChannels, MgmtCommand, MgmtCommandOpcode, MgmtCommandResponse, MgmtControllerBus,
+    MgmtControllerInfo, MgmtControllerType, MgmtEvent, MgmtExtendedIndex, HCI_DEV_NONE,
 };
 
-use log::{debug, error, info, warn};
+use log::{debug, error, info, warn, LevelFilter};
 use nix::sys::signal::{self, Signal};
 use nix::unistd::Pid;
 use regex::Regex;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::convert::TryFrom;
 use std::fmt::{Display, Formatter};
+use std::io::Read;
+use std::os::unix::fs::OpenOptionsExt;
 use std::process::{Child, Command, Stdio};
 use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
 use std::sync::{Arc, Mutex};
-use tokio::io::unix::AsyncFd;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::unix::{AsyncFd, AsyncFdReadyMutGuard};
 use tokio::sync::mpsc;
 use tokio::time::{Duration, Instant};
 
 /// Directory for Bluetooth pid file
 pub const PID_DIR: &str = "/var/run/bluetooth";
 
+/// Upstart job and systemd unit of the BlueZ daemon.
+const BLUEZ_UPSTART_JOB: &str = "bluetoothd";
+const BLUEZ_SYSTEMD_UNIT: &str = "bluetooth.service";
+
 /// Number of times to try restarting before resetting the adapter.
 pub const RESET_ON_RESTART_COUNT: i32 = 2;
 
+/// Restarts within this window count towards crash loop detection.
+pub const CRASH_LOOP_WINDOW: Duration = Duration::from_secs(600);
+
+/// Number of restarts within |CRASH_LOOP_WINDOW| at which the adapter is marked failed instead of
+/// being restarted again.
+pub const CRASH_LOOP_MAX_RESTARTS: usize = 8;
+
+/// Delay before the second restart within |CRASH_LOOP_WINDOW|. It doubles for each further
+/// restart, up to |RESTART_BACKOFF_MAX|. The first restart is always immediate.
+pub const RESTART_BACKOFF_BASE: Duration = Duration::from_secs(1);
+
+/// Longest delay before restarting an adapter.
+pub const RESTART_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// Number of restarts kept in the history of each adapter.
+pub const RESTART_HISTORY_SIZE: usize = 20;
+
 /// Time to wait from when IndexRemoved is sent to mgmt socket to when we send
 /// it to the state machine. This debounce exists because when the Index is
 /// removed due to adapter lost, userspace requires some time to actually close
@@ -79,7 +109,7 @@ impl Display for VirtualHciIndex {
 }
 
 /// Hci index that maps to real system index.
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd)]
 pub struct RealHciIndex(pub i32);
 impl RealHciIndex {
     pub(crate) fn to_i32(&self) -> i32 {
@@ -110,19 +140,37 @@ pub enum Message {
     PidChange(inotify::EventMask, Option<String>),
     CallbackDisconnected(u32),
     CommandTimeout(VirtualHciIndex),
+    RestartBackoffExpired(VirtualHciIndex),
+    RfkillChange(RfkillEvent, Option<RealHciIndex>),
     SetDesiredDefaultAdapter(VirtualHciIndex),
+    ConfigChanged,
+    ControllerInfo(RealHciIndex, MgmtControllerInfo, Option<MgmtControllerBus>),
+    ControllerError(RealHciIndex, u8),
 }
 
 pub struct StateMachineContext {
     tx: mpsc::Sender<Message>,
     rx: mpsc::Receiver<Message>,
     state_machine: StateMachineInternal,
+
+    /// Log level to use when the config doesn't set one.
+    default_log_level: LevelFilter,
 }
 
 impl StateMachineContext {
     fn new(state_machine: StateMachineInternal) -> StateMachineContext {
         let (tx, rx) = mpsc::channel::<Message>(10);
-        StateMachineContext { tx: tx, rx: rx, state_machine: state_machine }
+        StateMachineContext {
+            tx: tx,
+            rx: rx,
+            state_machine: state_machine,
+            default_log_level: LevelFilter::Info,
+        }
+    }
+
+    /// Sets the log level to restore when the config no longer sets one.
+    pub fn set_default_log_level(&mut self, level: LevelFilter) {
+        self.default_log_level = level;
     }
 
     pub fn get_proxy(&self) -> StateMachineProxy {
@@ -142,7 +190,7 @@ impl StateMachineContext {
 pub fn create_new_state_machine_context(invoker: Invoker) -> StateMachineContext {
     let floss_enabled = config_util::is_floss_enabled();
     let desired_adapter = config_util::get_default_adapter();
-    let process_manager = StateMachineInternal::make_process_manager(invoker);
+    let process_manager = invoker.make_process_manager();
 
     StateMachineContext::new(StateMachineInternal::new(
         process_manager,
@@ -345,6 +393,172 @@ fn configure_pid(pid_tx: mpsc::Sender<Message>) {
     });
 }
 
+// Configure inotify on the config directory to reload the config of BluetoothManager when it's
+// written.
+fn configure_config_watch(config_tx: mpsc::Sender<Message>) {
+    let mut config_detector = match inotify::Inotify::init() {
+        Ok(i) => i,
+        Err(e) => {
+            warn!("Failed to init inotify, config won't be reloaded: {}", e);
+            return;
+        }
+    };
+    // Config writers may either write the file in place or move a new file over it.
+    if let Err(e) = config_detector.add_watch(
+        config_util::BTMANAGERD_CONF_DIR,
+        inotify::WatchMask::CLOSE_WRITE | inotify::WatchMask::MOVED_TO,
+    ) {
+        warn!(
+            "Failed to watch {}, config won't be reloaded: {}",
+            config_util::BTMANAGERD_CONF_DIR,
+            e
+        );
+        return;
+    }
+
+    tokio::spawn(async move {
+        debug!("Spawned config notify task");
+
+        let mut config_afd =
+            AsyncFd::new(config_detector).expect("failed to add async fd for config detector");
+
+        loop {
+            let mut fd_ready = match config_afd.readable_mut().await {
+                Ok(guard) => guard,
+                Err(e) => {
+                    error!("Failed to wait on {}: {:?}", config_util::BTMANAGERD_CONF_DIR, e);
+                    break;
+                }
+            };
+            let mut buffer: [u8; 1024] = [0; 1024];
+            let changed = match fd_ready.try_io(|inner| inner.get_mut().read_events(&mut buffer)) {
+                Ok(Ok(events)) => events.into_iter().any(|event| {
+                    event_name_to_string(event.name).as_deref()
+                        == Some(config_util::BTMANAGERD_CONF_NAME)
+                }),
+                Ok(Err(e)) => {
+                    error!("Failed to read {} events: {}", config_util::BTMANAGERD_CONF_DIR, e);
+                    break;
+                }
+                // Would block, readiness was cleared so wait again.
+                Err(_) => false,
+            };
+
+            if changed {
+                // The state machine may be busy, drop the event rather than the watcher.
+                if let Err(e) =
+                    config_tx.send_timeout(Message::ConfigChanged, TX_SEND_TIMEOUT_DURATION).await
+                {
+                    error!("Dropping config change: {}", e);
+                }
+            }
+        }
+    });
+}
+
+// Whether a controller of the extended index list is a primary controller, i.e. one that shows
+// up in the plain index list.
+fn is_primary(controller: &MgmtExtendedIndex) -> bool {
+    controller.get_controller_type() == Some(MgmtControllerType::Primary)
+}
+
+fn opcode_is(opcode: u16, expected: MgmtCommandOpcode) -> bool {
+    opcode == u16::from(expected)
+}
+
+/// Controllers whose information was asked for, in the order of the requests. MGMT answers the
+/// commands of a socket in order. A controller is only reported as present once its information
+/// is known, so that the adapter starts with its address known.
+#[derive(Default)]
+struct PendingControllerInfo {
+    indexes: VecDeque<u16>,
+}
+
+impl PendingControllerInfo {
+    async fn request(
+        &mut self,
+        hci_tx: &mpsc::Sender<Message>,
+        guard: &mut AsyncFdReadyMutGuard<'_, BtSocket>,
+        index: u16,
+    ) {
+        let command = MgmtCommand::ReadControllerInfo(index);
+        if guard.get_inner_mut().write_mgmt_packet(command.into()) < 0 {
+            warn!("Failed to read the controller information of hci{}", index);
+            on_index_added(hci_tx, RealHciIndex(index.into())).await;
+            return;
+        }
+        self.indexes.push_back(index);
+    }
+
+    async fn complete(
+        &mut self,
+        hci_tx: &mpsc::Sender<Message>,
+        result: Option<(MgmtControllerInfo, Option<MgmtControllerBus>)>,
+    ) {
+        // Failures don't say which controller they are about, so they go to the oldest request.
+        let position = match &result {
+            Some((info, _)) => self.indexes.iter().position(|index| *index == info.index),
+            None => Some(0),
+        };
+        let index = match position.and_then(|position| self.indexes.remove(position)) {
+            Some(index) => index,
+            None => return,
+        };
+        let hci = RealHciIndex(index.into());
+        match result {
+            Some((info, bus)) => {
+                let _ = hci_tx
+                    .send_timeout(Message::ControllerInfo(hci, info, bus), TX_SEND_TIMEOUT_DURATION)
+                    .await;
+            }
+            None => warn!("Failed to read the controller information of {}", hci),
+        }
+        on_index_added(hci_tx, hci).await;
+    }
+}
+
+async fn on_index_added(hci_tx: &mpsc::Sender<Message>, hci: RealHciIndex) {
+    // We need devpath for an index or we don't use it.
+    if let Some(d) = config_util::get_devpath_for_hci(hci) {
+        let _ = hci_tx
+            .send_timeout(
+                Message::AdapterStateChange(AdapterStateActions::HciDevicePresence(d, hci, true)),
+                TX_SEND_TIMEOUT_DURATION,
+            )
+            .await
+            .unwrap();
+    } else {
+        error!("Could not get devpath for {}", hci);
+    }
+}
+
+fn on_index_removed(hci_tx: &mpsc::Sender<Message>, hci: RealHciIndex) {
+    let devpath = config_util::get_devpath_for_hci(hci).unwrap_or(String::new());
+    // Only send presence removed if the device is removed
+    // and not when userchannel takes exclusive access. This needs to
+    // be delayed a bit for when the socket legitimately disappears as
+    // it takes some time for userspace to close the socket.
+    //
+    // It's possible for devpath to be empty in this case because the
+    // index is being removed. Handlers of HciDevicePresence need to
+    // be aware of this case.
+    let txl = hci_tx.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(INDEX_REMOVED_DEBOUNCE_TIME).await;
+        if !config_util::check_hci_device_exists(hci) {
+            let _ = txl
+                .send_timeout(
+                    Message::AdapterStateChange(AdapterStateActions::HciDevicePresence(
+                        devpath, hci, false,
+                    )),
+                    TX_SEND_TIMEOUT_DURATION,
+                )
+                .await
+                .unwrap();
+        }
+    });
+}
+
 // Configure the HCI socket listener and prepare the system to receive mgmt events for index added
 // and index removed.
 fn configure_hci(hci_tx: mpsc::Sender<Message>) {
@@ -377,6 +591,11 @@ fn configure_hci(hci_tx: mpsc::Sender<Message>) {
     tokio::spawn(async move {
         debug!("Spawned hci notify task");
 
+        // Bus of each controller, from the extended index list and events. The list is read
+        // before the information of the controllers it has.
+        let mut buses: HashMap<u16, MgmtControllerBus> = HashMap::new();
+        let mut pending = PendingControllerInfo::default();
+
         // Make this into an AsyncFD and start using it for IO
         let mut hci_afd = AsyncFd::new(btsock).expect("Failed to add async fd for BT socket.");
 
@@ -384,8 +603,10 @@ fn configure_hci(hci_tx: mpsc::Sender<Message>) {
         match hci_afd.writable_mut().await {
             Ok(mut guard) => {
                 let _ = guard.try_io(|sock| {
-                    let command = MgmtCommand::ReadIndexList;
-                    sock.get_mut().write_mgmt_packet(command.into());
+                    sock.get_mut().write_mgmt_packet(MgmtCommand::ReadIndexList.into());
+                    // The extended list has the bus of each controller. Once it's read, the kernel
+                    // sends extended index events instead of the plain ones.
+                    sock.get_mut().write_mgmt_packet(MgmtCommand::ReadExtendedIndexList.into());
                     Ok(())
                 });
             }
@@ -408,85 +629,78 @@ fn configure_hci(hci_tx: mpsc::Sender<Message>) {
                         debug!("Got a valid mgmt event: {:?}", ev);
 
                         match ev {
-                            MgmtEvent::CommandComplete { opcode: _, status: _, response } => {
-                                if let MgmtCommandResponse::ReadIndexList {
-                                    num_intf: _,
-                                    interfaces,
-                                } = response
-                                {
-                                    for hci in interfaces {
-                                        let hci = RealHciIndex(hci.into());
-                                        debug!("IndexList response: {}", hci);
-                                        // We need devpath for an index or we don't use it.
-                                        if let Some(d) = config_util::get_devpath_for_hci(hci) {
-                                            let _ = hci_tx
-                                                .send_timeout(
-                                                    Message::AdapterStateChange(
-                                                        AdapterStateActions::HciDevicePresence(
-                                                            d, hci, true,
-                                                        ),
-                                                    ),
-                                                    TX_SEND_TIMEOUT_DURATION,
-                                                )
-                                                .await
-                                                .unwrap();
-                                        } else {
-                                            error!("IndexList: Could not get devpath for {}", hci);
+                            MgmtEvent::CommandComplete { opcode, status: _, response } => {
+                                match response {
+                                    MgmtCommandResponse::ReadIndexList {
+                                        num_intf: _,
+                                        interfaces,
+                                    } => {
+                                        for index in interfaces {
+                                            debug!("IndexList response: hci{}", index);
+                                            pending.request(&hci_tx, &mut guard, index).await;
                                         }
                                     }
+                                    MgmtCommandResponse::ReadExtendedIndexList { controllers } => {
+                                        for c in controllers.iter().filter(|c| is_primary(c)) {
+                                            if let Some(bus) = c.get_bus() {
+                                                buses.insert(c.index, bus);
+                                            }
+                                        }
+                                    }
+                                    MgmtCommandResponse::ReadControllerInfo(info) => {
+                                        let bus = buses.get(&info.index).cloned();
+                                        pending.complete(&hci_tx, Some((info, bus))).await;
+                                    }
+                                    MgmtCommandResponse::DataUnused
+                                        if opcode_is(
+                                            opcode,
+                                            MgmtCommandOpcode::ReadControllerInfo,
+                                        ) =>
+                                    {
+                                        pending.complete(&hci_tx, None).await;
+                                    }
+                                    _ => (),
                                 }
                             }
-                            MgmtEvent::IndexAdded(hci) => {
-                                let hci = RealHciIndex(hci.into());
-                                debug!("IndexAdded: {}", hci);
-                                // We need devpath for an index or we don't use it.
-                                if let Some(d) = config_util::get_devpath_for_hci(hci) {
-                                    let _ = hci_tx
-                                        .send_timeout(
-                                            Message::AdapterStateChange(
-                                                AdapterStateActions::HciDevicePresence(
-                                                    d, hci, true,
-                                                ),
-                                            ),
-                                            TX_SEND_TIMEOUT_DURATION,
-                                        )
-                                        .await
-                                        .unwrap();
-                                } else {
-                                    error!("IndexAdded: Could not get devpath for {}", hci);
+                            MgmtEvent::CommandStatus { opcode, status }
+                                if status != 0
+                                    && opcode_is(opcode, MgmtCommandOpcode::ReadControllerInfo) =>
+                            {
+                                pending.complete(&hci_tx, None).await;
+                            }
+                            MgmtEvent::ExtendedIndexAdded(c) if is_primary(&c) => {
+                                if let Some(bus) = c.get_bus() {
+                                    buses.insert(c.index, bus);
                                 }
+                                debug!("ExtendedIndexAdded: hci{}", c.index);
+                                pending.request(&hci_tx, &mut guard, c.index).await;
+                            }
+                            MgmtEvent::ExtendedIndexRemoved(c) if is_primary(&c) => {
+                                let hci = RealHciIndex(c.index.into());
+                                debug!("ExtendedIndexRemoved: {}", hci);
+                                on_index_removed(&hci_tx, hci);
+                            }
+                            MgmtEvent::IndexAdded(index) => {
+                                debug!("IndexAdded: hci{}", index);
+                                pending.request(&hci_tx, &mut guard, index).await;
                             }
                             MgmtEvent::IndexRemoved(hci) => {
                                 let hci = RealHciIndex(hci.into());
                                 debug!("IndexRemoved: {}", hci);
-                                let devpath =
-                                    config_util::get_devpath_for_hci(hci).unwrap_or(String::new());
-                                // Only send presence removed if the device is removed
-                                // and not when userchannel takes exclusive access. This needs to
-                                // be delayed a bit for when the socket legitimately disappears as
-                                // it takes some time for userspace to close the socket.
-                                //
-                                // It's possible for devpath to be empty in this case because the
-                                // index is being removed. Handlers of HciDevicePresence need to
-                                // be aware of this case.
-                                let txl = hci_tx.clone();
-                                tokio::spawn(async move {
-                                    tokio::time::sleep(INDEX_REMOVED_DEBOUNCE_TIME).await;
-                                    if !config_util::check_hci_device_exists(hci) {
-                                        let _ = txl
-                                            .send_timeout(
-                                                Message::AdapterStateChange(
-                                                    AdapterStateActions::HciDevicePresence(
-                                                        devpath, hci, false,
-                                                    ),
-                                                ),
-                                                TX_SEND_TIMEOUT_DURATION,
-                                            )
-                                            .await
-                                            .unwrap();
-                                    }
-                                });
+                                on_index_removed(&hci_tx, hci);
+                            }
+                            MgmtEvent::ControllerError { index, error_code } => {
+                                let hci = RealHciIndex(index.into());
+                                warn!("Controller error on {}: {:#04x}", hci, error_code);
+                                let _ = hci_tx
+                                    .send_timeout(
+                                        Message::ControllerError(hci, error_code),
+                                        TX_SEND_TIMEOUT_DURATION,
+                                    )
+                                    .await;
                             }
+                            // Other events are only meaningful to the sender of the command.
+                            _ => (),
                         }
                     }
                 } else {
@@ -498,6 +712,70 @@ fn configure_hci(hci_tx: mpsc::Sender<Message>) {
     });
 }
 
+// Configure the rfkill listener to follow soft and hard blocks of Bluetooth switches. When opened,
+// /dev/rfkill first reports all existing switches.
+fn configure_rfkill(rfkill_tx: mpsc::Sender<Message>) {
+    let file = match std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(nix::fcntl::OFlag::O_NONBLOCK.bits())
+        .open(RFKILL_DEV_PATH)
+    {
+        Ok(f) => f,
+        Err(e) => {
+            warn!("Failed to open {}, ignoring rfkill: {}", RFKILL_DEV_PATH, e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        debug!("Spawned rfkill notify task");
+
+        let mut rfkill_afd = AsyncFd::new(file).expect("Failed to add async fd for rfkill.");
+
+        loop {
+            let mut fd_ready = match rfkill_afd.readable_mut().await {
+                Ok(guard) => guard,
+                Err(e) => {
+                    error!("Failed to wait on {}: {:?}", RFKILL_DEV_PATH, e);
+                    break;
+                }
+            };
+
+            // Each read returns a single event.
+            let mut buffer: [u8; 64] = [0; 64];
+            match fd_ready.try_io(|inner| inner.get_mut().read(&mut buffer)) {
+                Ok(Ok(len)) => match RfkillEvent::parse(&buffer[..len]) {
+                    Some(event) if event.is_bluetooth() => {
+                        debug!("Got rfkill event: {:?}", event);
+                        let hci = match event.op {
+                            RfkillOp::Del => None,
+                            _ => config_util::get_hci_for_rfkill(event.idx),
+                        };
+                        // The state machine may be busy, drop the event rather than the task.
+                        if let Err(e) = rfkill_tx
+                            .send_timeout(
+                                Message::RfkillChange(event, hci),
+                                TX_SEND_TIMEOUT_DURATION,
+                            )
+                            .await
+                        {
+                            error!("Dropping rfkill change: {}", e);
+                        }
+                    }
+                    Some(_) => (),
+                    None => warn!("Invalid rfkill event of {} bytes", len),
+                },
+                Ok(Err(e)) => {
+                    error!("Failed to read {}: {}", RFKILL_DEV_PATH, e);
+                    break;
+                }
+                // Would block, readiness was cleared so wait again.
+                Err(_) => (),
+            }
+        }
+    });
+}
+
 /// Handle command timeouts per hci interface.
 struct CommandTimeout {
     pub waker: Arc<Alarm>,
@@ -603,6 +881,8 @@ pub async fn mainloop(
     // system.
     configure_hci(context.tx.clone());
     configure_pid(context.tx.clone());
+    configure_rfkill(context.tx.clone());
+    configure_config_watch(context.tx.clone());
 
     // Listen for all messages and act on them
     loop {
@@ -672,6 +952,20 @@ pub async fn mainloop(
                         (next_state, action) =
                             context.state_machine.action_on_bluetooth_stopped(hci);
                         cmd_timeout.lock().unwrap().handle_timeout_action(hci, action);
+
+                        // Restarting after a crash may be delayed to back off from a crash loop.
+                        if let Some(delay) = context.state_machine.get_restart_delay(hci) {
+                            let backoff_tx = context.tx.clone();
+                            tokio::spawn(async move {
+                                tokio::time::sleep(delay).await;
+                                let _ = backoff_tx
+                                    .send_timeout(
+                                        Message::RestartBackoffExpired(hci),
+                                        TX_SEND_TIMEOUT_DURATION,
+                                    )
+                                    .await;
+                            });
+                        }
                     }
 
                     AdapterStateActions::HciDevicePresence(devpath, i, present) => {
@@ -686,10 +980,14 @@ pub async fn mainloop(
                         };
                         hci = context.state_machine.get_updated_virtual_id(devpath.clone(), *i);
 
-                        // If this is really a new hci device, load the enabled state from the disk.
+                        // If this is really a new hci device, load the enabled state and launch
+                        // profile from the disk.
                         if previous_real_hci.is_none() {
+                            let config_enabled = config_util::is_hci_n_enabled(hci);
+                            let launch_profile = config_util::get_launch_profile(hci);
                             context.state_machine.modify_state(hci, |a: &mut AdapterState| {
-                                a.config_enabled = config_util::is_hci_n_enabled(hci);
+                                a.config_enabled = config_enabled;
+                                a.launch_profile = launch_profile.clone();
                             });
                         }
 
@@ -776,6 +1074,10 @@ pub async fn mainloop(
                         .unwrap()
                         .callback_hci_enabled_change(hci, next_enabled);
                 }
+
+                if let Some(health) = context.state_machine.take_changed_health(hci) {
+                    bluetooth_manager.lock().unwrap().callback_adapter_health_change(health);
+                }
             }
 
             // Monitored pid directory has a change
@@ -792,7 +1094,7 @@ pub async fn mainloop(
                                 .parse::<i32>()
                                 .unwrap_or(0);
                             debug!("Sending bluetooth started action for {}, pid={}", hci, pid);
-                            let _ = context
+                            if let Err(e) = context
                                 .tx
                                 .send_timeout(
                                     Message::AdapterStateChange(
@@ -801,7 +1103,9 @@ pub async fn mainloop(
                                     TX_SEND_TIMEOUT_DURATION,
                                 )
                                 .await
-                                .unwrap();
+                            {
+                                error!("{}: Dropping bluetooth started action: {}", hci, e);
+                            }
                             let handle = tokio::spawn(async move {
                                 debug!("{}: Spawned process monitor", hci);
                                 loop {
@@ -893,6 +1197,59 @@ pub async fn mainloop(
                     });
                     bluetooth_manager.lock().unwrap().callback_hci_device_change(hci, true);
                 }
+
+                if let Some(health) = context.state_machine.take_changed_health(hci) {
+                    bluetooth_manager.lock().unwrap().callback_adapter_health_change(health);
+                }
+            }
+
+            // Restart a crashed adapter once the backoff has expired
+            Message::RestartBackoffExpired(hci) => {
+                let prev_state = context.state_machine.get_process_state(hci);
+                let (next_state, action) =
+                    context.state_machine.action_on_restart_backoff_expired(hci);
+                cmd_timeout.lock().unwrap().handle_timeout_action(hci, action);
+
+                info!(
+                    "{}: Restart backoff expired, Previous State({:?}), Next State({:?})",
+                    hci, prev_state, next_state
+                );
+
+                if let Some(health) = context.state_machine.take_changed_health(hci) {
+                    bluetooth_manager.lock().unwrap().callback_adapter_health_change(health);
+                }
+            }
+
+            // An rfkill switch has changed
+            Message::RfkillChange(event, real_hci) => {
+                for (hci, rfkill) in
+                    context.state_machine.action_on_rfkill_changed(&event, real_hci)
+                {
+                    let prev_state = context.state_machine.get_process_state(hci);
+                    let (next_state, action) =
+                        context.state_machine.action_on_rfkill_state_changed(hci);
+                    cmd_timeout.lock().unwrap().handle_timeout_action(hci, action);
+
+                    info!(
+                        "{}: rfkill {:?}, Previous State({:?}), Next State({:?})",
+                        hci, rfkill, prev_state, next_state
+                    );
+
+                    let prev_enabled = state_to_enabled(prev_state);
+                    let next_enabled = state_to_enabled(next_state);
+                    if prev_enabled != next_enabled {
+                        bluetooth_manager
+                            .lock()
+                            .unwrap()
+                            .callback_hci_enabled_change(hci, next_enabled);
+                    }
+
+                    bluetooth_manager.lock().unwrap().callback_hci_rfkill_change(hci, rfkill);
+
+                    if let Some(health) = context.state_machine.take_changed_health(hci) {
+                        bluetooth_manager.lock().unwrap().callback_adapter_health_change(health);
+                    }
+                }
             }
 
             Message::SetDesiredDefaultAdapter(hci) => {
@@ -908,6 +1265,59 @@ pub async fn mainloop(
                     AdapterChangeAction::DoNothing => (),
                 }
             }
+
+            Message::ControllerInfo(real_hci, info, bus) => {
+                context.state_machine.action_on_controller_info(real_hci, info, bus);
+            }
+
+            Message::ControllerError(real_hci, error_code) => {
+                context.state_machine.action_on_controller_error(real_hci, error_code);
+            }
+
+            // The config file has been written
+            Message::ConfigChanged => {
+                let config = match config_util::read_manager_config() {
+                    Ok(config) => config,
+                    Err(e) => {
+                        error!("Ignoring config change: {}", e);
+                        continue;
+                    }
+                };
+                info!("Reloading config");
+
+                log::set_max_level(config.log_level.unwrap_or(context.default_log_level));
+
+                if let AdapterChangeAction::NewDefaultAdapter(new_hci) =
+                    context.state_machine.action_on_config_default_adapter(&config)
+                {
+                    bluetooth_manager.lock().unwrap().callback_default_adapter_change(new_hci);
+                }
+
+                for hci in context.state_machine.action_on_launch_profile_changed(&config) {
+                    info!("{}: Launch profile changed, restarting", hci);
+                    let tx = context.tx.clone();
+                    tokio::spawn(async move {
+                        let _ = tx
+                            .send(Message::AdapterStateChange(
+                                AdapterStateActions::RestartBluetooth(hci),
+                            ))
+                            .await;
+                    });
+                }
+
+                for (hci, enabled) in context.state_machine.action_on_config_changed(&config) {
+                    info!("{}: Config changed to enabled={}", hci, enabled);
+                    let action = if enabled {
+                        AdapterStateActions::StartBluetooth(hci)
+                    } else {
+                        AdapterStateActions::StopBluetooth(hci)
+                    };
+                    let tx = context.tx.clone();
+                    tokio::spawn(async move {
+                        let _ = tx.send(Message::AdapterStateChange(action)).await;
+                    });
+                }
+            }
         }
     }
 }
@@ -921,7 +1331,8 @@ pub trait ProcessManager {
     /// * `virtual_hci` - Virtual index of adapter used for apis.
     /// * `real_hci` - Real index of the adapter on the system. This can
     ///                  change during a single boot.
-    fn start(&mut self, virtual_hci: VirtualHciIndex, real_hci: RealHciIndex);
+    /// * `args` - Arguments to start the adapter process with. INIT_ flags come last.
+    fn start(&mut self, virtual_hci: VirtualHciIndex, real_hci: RealHciIndex, args: &[String]);
 
     /// Stop the adapter process.
     ///
@@ -929,8 +1340,27 @@ pub trait ProcessManager {
     /// * `virtual_hci` - Virtual index of adapter used for apis.
     /// * `real_hci` - Real index of the adapter on the system.
     fn stop(&mut self, virtual_hci: VirtualHciIndex, real_hci: RealHciIndex);
+
+    /// Start the BlueZ daemon. Returns false if it couldn't be asked to start.
+    fn start_bluez(&mut self) -> bool;
+
+    /// Stop the BlueZ daemon, e.g. to write its storage. Returns false if it couldn't be asked to
+    /// stop.
+    fn stop_bluez(&mut self) -> bool;
+}
+
+/// Runs |program| with |args|. Returns false if it couldn't be run.
+fn run_init_command(program: &str, args: &[&str]) -> bool {
+    match Command::new(program).args(args).output() {
+        Ok(_) => true,
+        Err(e) => {
+            error!("Failed to run {} {}: {}", program, args.join(" "), e);
+            false
+        }
+    }
 }
 
+#[derive(Clone, Copy)]
 pub enum Invoker {
     #[allow(dead_code)]
     NativeInvoker,
@@ -938,6 +1368,16 @@ pub enum Invoker {
     UpstartInvoker,
 }
 
+impl Invoker {
+    pub(crate) fn make_process_manager(self) -> Box<dyn ProcessManager + Send> {
+        match self {
+            Invoker::NativeInvoker => Box::new(NativeInvoker::new()),
+            Invoker::SystemdInvoker => Box::new(SystemdInvoker::new()),
+            Invoker::UpstartInvoker => Box::new(UpstartInvoker::new()),
+        }
+    }
+}
+
 pub struct NativeInvoker {
     process_container: Option<Child>,
     bluetooth_pid: u32,
@@ -950,9 +1390,10 @@ impl NativeInvoker {
 }
 
 impl ProcessManager for NativeInvoker {
-    fn start(&mut self, virtual_hci: VirtualHciIndex, real_hci: RealHciIndex) {
+    fn start(&mut self, virtual_hci: VirtualHciIndex, real_hci: RealHciIndex, args: &[String]) {
         let new_process = Command::new("/usr/bin/btadapterd")
             .arg(format!("INDEX={} HCI={}", virtual_hci.to_i32(), real_hci.to_i32()))
+            .args(args)
             .stdout(Stdio::piped())
             .spawn()
             .expect("cannot open");
@@ -970,6 +1411,16 @@ impl ProcessManager for NativeInvoker {
             }
         }
     }
+
+    fn start_bluez(&mut self) -> bool {
+        error!("Can't start BlueZ without an init system");
+        false
+    }
+
+    fn stop_bluez(&mut self) -> bool {
+        error!("Can't stop BlueZ without an init system");
+        false
+    }
 }
 
 pub struct UpstartInvoker {}
@@ -981,13 +1432,14 @@ impl UpstartInvoker {
 }
 
 impl ProcessManager for UpstartInvoker {
-    fn start(&mut self, virtual_hci: VirtualHciIndex, real_hci: RealHciIndex) {
+    fn start(&mut self, virtual_hci: VirtualHciIndex, real_hci: RealHciIndex, args: &[String]) {
         if let Err(e) = Command::new("initctl")
             .args(&[
                 "start",
                 "btadapterd",
                 format!("INDEX={}", virtual_hci.to_i32()).as_str(),
                 format!("HCI={}", real_hci.to_i32()).as_str(),
+                format!("ARGS={}", args.join(" ")).as_str(),
             ])
             .output()
         {
@@ -1008,6 +1460,14 @@ impl ProcessManager for UpstartInvoker {
             error!("Failed to stop btadapterd: {}", e);
         }
     }
+
+    fn start_bluez(&mut self) -> bool {
+        run_init_command("initctl", &["start", BLUEZ_UPSTART_JOB])
+    }
+
+    fn stop_bluez(&mut self) -> bool {
+        run_init_command("initctl", &["stop", BLUEZ_UPSTART_JOB])
+    }
 }
 
 pub struct SystemdInvoker {}
@@ -1019,7 +1479,14 @@ impl SystemdInvoker {
 }
 
 impl ProcessManager for SystemdInvoker {
-    fn start(&mut self, virtual_hci: VirtualHciIndex, real_hci: RealHciIndex) {
+    fn start(&mut self, virtual_hci: VirtualHciIndex, real_hci: RealHciIndex, args: &[String]) {
+        // The unit reads the arguments from an environment file named after its instance.
+        let env_path =
+            format!("{}/btadapterd_{}_{}.env", PID_DIR, virtual_hci.to_i32(), real_hci.to_i32());
+        if let Err(e) = std::fs::write(&env_path, format!("BTADAPTERD_ARGS={}\n", args.join(" "))) {
+            error!("Failed to write {}: {}", env_path, e);
+        }
+
         Command::new("systemctl")
             .args(&[
                 "restart",
@@ -1040,6 +1507,14 @@ impl ProcessManager for SystemdInvoker {
             .output()
             .expect("failed to stop bluetooth");
     }
+
+    fn start_bluez(&mut self) -> bool {
+        run_init_command("systemctl", &["start", BLUEZ_SYSTEMD_UNIT])
+    }
+
+    fn stop_bluez(&mut self) -> bool {
+        run_init_command("systemctl", &["stop", BLUEZ_SYSTEMD_UNIT])
+    }
 }
 
 /// Stored state of each adapter in the state machine.
@@ -1074,6 +1549,30 @@ pub struct AdapterState {
 
     /// How many times this adapter has attempted to restart without success.
     pub restart_count: i32,
+
+    /// Restarts done on this adapter, used to detect crash loops.
+    pub restart_history: RestartHistory,
+
+    /// Whether this adapter is blocked by rfkill. A blocked adapter isn't started.
+    pub rfkill: RfkillState,
+
+    /// Options to start the adapter process with, from the config.
+    pub launch_profile: AdapterLaunchProfile,
+
+    /// Options the adapter process was last started with.
+    pub active_launch_profile: Option<AdapterLaunchProfile>,
+
+    /// Floss conf file the adapter process was last started with.
+    pub config_file: Option<String>,
+
+    /// Controller information, read over MGMT whenever the hci device shows up.
+    pub controller_info: Option<MgmtControllerInfo>,
+
+    /// Bus the controller is attached to, if the kernel reports it.
+    pub bus: Option<MgmtControllerBus>,
+
+    /// Most recent hardware error code reported by the controller.
+    pub last_controller_error: Option<u8>,
 }
 
 impl AdapterState {
@@ -1088,8 +1587,136 @@ impl AdapterState {
             config_enabled: false,
             pid: 0,
             restart_count: 0,
+            restart_history: RestartHistory::default(),
+            rfkill: RfkillState::default(),
+            launch_profile: AdapterLaunchProfile::default(),
+            active_launch_profile: None,
+            config_file: None,
+            controller_info: None,
+            bus: None,
+            last_controller_error: None,
+        }
+    }
+
+    /// Public address of the controller in upper case, once its information was read.
+    pub fn get_address(&self) -> Option<String> {
+        self.controller_info.as_ref().map(|info| {
+            info.address.iter().rev().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(":")
+        })
+    }
+
+    pub fn to_controller_info(&self) -> Option<AdapterControllerInfo> {
+        let info = self.controller_info.as_ref()?;
+        Some(AdapterControllerInfo {
+            address: self.get_address()?,
+            manufacturer: info.manufacturer,
+            bus: self.bus,
+            supported_settings: info.supported_settings,
+            current_settings: info.current_settings,
+            last_error: self.last_controller_error,
+        })
+    }
+}
+
+/// Restart bookkeeping of an adapter.
+#[derive(Clone, Debug, Default)]
+pub struct RestartHistory {
+    /// Most recent restarts, oldest first, with the time they happened at.
+    events: VecDeque<(Instant, RestartEvent)>,
+
+    /// Restarts before this time don't count towards crash loop detection.
+    counted_since: Option<Instant>,
+
+    /// How many times the HCI device was reset to recover the adapter.
+    pub reset_count: u32,
+
+    /// Whether restarting was given up on.
+    pub failed: bool,
+
+    /// Delay of the restart waiting on backoff, if any.
+    pub pending_delay: Option<Duration>,
+
+    /// Whether anything changed since the last time clients were notified.
+    changed: bool,
+}
+
+impl RestartHistory {
+    /// Records a restart and returns how many restarts happened within |CRASH_LOOP_WINDOW|,
+    /// including this one.
+    fn record(&mut self, reason: RestartReason) -> usize {
+        let now = Instant::now();
+        let timestamp_ms =
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+
+        if self.events.len() >= RESTART_HISTORY_SIZE {
+            self.events.pop_front();
+        }
+        self.events.push_back((now, RestartEvent { timestamp_ms, reason }));
+        self.changed = true;
+
+        let counted_since = self.counted_since;
+        self.events
+            .iter()
+            .filter(|(at, _)| {
+                now.duration_since(*at) <= CRASH_LOOP_WINDOW
+                    && counted_since.map_or(true, |since| *at >= since)
+            })
+            .count()
+    }
+
+    fn record_reset(&mut self) {
+        self.reset_count += 1;
+        self.changed = true;
+    }
+
+    fn set_failed(&mut self) {
+        self.failed = true;
+        self.pending_delay = None;
+        self.changed = true;
+    }
+
+    fn set_pending_delay(&mut self, delay: Duration) {
+        self.pending_delay = Some(delay);
+        self.changed = true;
+    }
+
+    fn take_pending_delay(&mut self) -> Option<Duration> {
+        let delay = self.pending_delay.take();
+        if delay.is_some() {
+            self.changed = true;
+        }
+        delay
+    }
+
+    /// Clears the failed state when the adapter is started again. Earlier restarts no longer count
+    /// towards crash loop detection.
+    fn clear_failed(&mut self) {
+        if self.failed {
+            self.failed = false;
+            self.counted_since = Some(Instant::now());
+            self.changed = true;
         }
     }
+
+    pub fn to_adapter_health(&self, hci: VirtualHciIndex) -> AdapterHealth {
+        AdapterHealth {
+            hci_interface: hci.to_i32(),
+            failed: self.failed,
+            reset_count: self.reset_count,
+            restart_delay_ms: self.pending_delay.map_or(0, |d| d.as_millis() as u64),
+            restart_history: self.events.iter().map(|(_, e)| e.clone()).collect(),
+        }
+    }
+}
+
+/// Delay before restarting an adapter given the number of restarts within |CRASH_LOOP_WINDOW|.
+fn restart_backoff(recent_restarts: usize) -> Duration {
+    if recent_restarts <= 1 {
+        return Duration::ZERO;
+    }
+
+    let exponent = std::cmp::min(recent_restarts - 2, 16) as u32;
+    std::cmp::min(RESTART_BACKOFF_BASE * 2u32.pow(exponent), RESTART_BACKOFF_MAX)
 }
 
 /// Internal and core implementation of the state machine.
@@ -1103,6 +1730,11 @@ struct StateMachineInternal {
     /// Desired default adapter.
     desired_adapter: VirtualHciIndex,
 
+    /// Default adapter of the config when it was last loaded. The desired default adapter only
+    /// follows the config when this changes, so that one set through the API isn't reverted by
+    /// unrelated config changes.
+    config_default_adapter: VirtualHciIndex,
+
     /// Keep track of per hci state. Key = hci id, Value = State. This must be a BTreeMap because
     /// we depend on ordering for |get_lowest_available_adapter|.
     state: Arc<Mutex<BTreeMap<VirtualHciIndex, AdapterState>>>,
@@ -1112,6 +1744,24 @@ struct StateMachineInternal {
 
     /// Process manager implementation.
     process_manager: Box<dyn ProcessManager + Send>,
+
+    /// Bluetooth rfkill switches by their rfkill index.
+    rfkill_switches: HashMap<u32, RfkillSwitch>,
+
+    /// Devcoredump state to restore once an adapter started with an override stops.
+    coredump_restore: HashMap<VirtualHciIndex, (RealHciIndex, bool)>,
+
+    /// Controller information of hci devices that weren't reported present yet.
+    pending_controller_info: HashMap<RealHciIndex, (MgmtControllerInfo, Option<MgmtControllerBus>)>,
+}
+
+/// A Bluetooth rfkill switch.
+#[derive(Debug)]
+struct RfkillSwitch {
+    /// The hci device this switch belongs to, or None if it applies to all adapters (e.g. a
+    /// platform kill switch).
+    hci: Option<RealHciIndex>,
+    state: RfkillState,
 }
 
 #[derive(Debug, PartialEq)]
@@ -1146,17 +1796,13 @@ impl StateMachineInternal {
             floss_enabled: Arc::new(AtomicBool::new(floss_enabled)),
             default_adapter: Arc::new(AtomicI32::new(desired_adapter.to_i32())),
             desired_adapter,
+            config_default_adapter: desired_adapter,
             state: Arc::new(Mutex::new(BTreeMap::new())),
             process_monitor: Arc::new(Mutex::new(HashMap::new())),
             process_manager: process_manager,
-        }
-    }
-
-    pub(crate) fn make_process_manager(invoker: Invoker) -> Box<dyn ProcessManager + Send> {
-        match invoker {
-            Invoker::NativeInvoker => Box::new(NativeInvoker::new()),
-            Invoker::SystemdInvoker => Box::new(SystemdInvoker::new()),
-            Invoker::UpstartInvoker => Box::new(UpstartInvoker::new()),
+            rfkill_switches: HashMap::new(),
+            coredump_restore: HashMap::new(),
+            pending_controller_info: HashMap::new(),
         }
     }
 
@@ -1361,28 +2007,162 @@ impl StateMachineInternal {
         return AdapterChangeAction::DoNothing;
     }
 
-    /// Returns the next state and an action to reset timer if we are starting bluetooth process.
-    pub fn action_start_bluetooth(
+    /// Follows a change of the default adapter in a reloaded |config|.
+    fn action_on_config_default_adapter(
         &mut self,
-        hci: VirtualHciIndex,
-    ) -> (ProcessState, CommandTimeoutAction) {
-        let state = self.get_process_state(hci);
-        let present = self.get_state(hci, move |a: &AdapterState| Some(a.present)).unwrap_or(false);
-        let floss_enabled = self.get_floss_enabled();
+        config: &config_util::ManagerConfig,
+    ) -> AdapterChangeAction {
+        let adapter = config.get_default_adapter();
+        if adapter == self.config_default_adapter {
+            return AdapterChangeAction::DoNothing;
+        }
 
-        match state {
-            // If adapter is off, we should turn it on when present and floss is enabled.
-            // If adapter is turning on and we get another start request, we should just
-            // repeat the same action which resets the timeout mechanism.
-            ProcessState::Off | ProcessState::TurningOn if present && floss_enabled => {
-                self.modify_state(hci, move |s: &mut AdapterState| {
-                    s.state = ProcessState::TurningOn
-                });
-                self.process_manager.start(hci, self.get_real_hci_by_virtual_id(hci));
-                (ProcessState::TurningOn, CommandTimeoutAction::ResetTimer)
+        debug!("Changing desired default adapter to {}", adapter);
+        self.config_default_adapter = adapter;
+        self.set_desired_default_adapter(adapter)
+    }
+
+    /// Starts the adapter process with its launch profile.
+    fn start_process(&mut self, hci: VirtualHciIndex) {
+        let real_hci = self.get_real_hci_by_virtual_id(hci);
+        let profile = self
+            .get_state(hci, |a: &AdapterState| Some(a.launch_profile.clone()))
+            .unwrap_or_default();
+
+        // Devcoredump is a sysfs attribute of the controller, which outlives the process. The
+        // override is reverted when the process stops. LL privacy is passed in the arguments.
+        self.restore_coredump(hci);
+        if let Some(enabled) = profile.devcoredump.to_option() {
+            match config_util::read_hci_coredump_state(real_hci) {
+                Ok(previous) if previous == enabled => (),
+                Ok(previous) => match config_util::set_hci_coredump_state(real_hci, enabled) {
+                    Ok(()) => {
+                        self.coredump_restore.insert(hci, (real_hci, previous));
+                    }
+                    Err(e) => warn!("{}: Failed to set devcoredump state: {}", hci, e),
+                },
+                Err(e) => warn!("{}: Failed to read devcoredump state: {}", hci, e),
             }
-            // Otherwise (enabled states) no op
-            _ => (state, CommandTimeoutAction::DoNothing),
+        }
+
+        let config_file = self.get_config_file(hci);
+        let mut args: Vec<String> = match &config_file {
+            Some(path) => vec![format!("--config-file={}", path)],
+            None => {
+                warn!("{}: Address unknown, starting with the default conf file", hci);
+                vec![]
+            }
+        };
+        args.extend(profile.to_args());
+
+        let active = profile.clone();
+        self.modify_state(hci, |a: &mut AdapterState| {
+            a.active_launch_profile = Some(active.clone());
+            a.config_file = config_file.clone();
+        });
+        self.process_manager.start(hci, real_hci, &args);
+    }
+
+    /// Picks the Floss conf file of an adapter from its address, see
+    /// |migrate::floss_conf_for_adapter|. The desired default adapter, or any adapter while it
+    /// isn't present, may take over the default conf file unless a running adapter uses it.
+    fn get_config_file(&self, hci: VirtualHciIndex) -> Option<String> {
+        let address = self.get_state(hci, |a: &AdapterState| a.get_address())?;
+        let default_conf = migrate::floss_conf_path(migrate::BT_LIBDIR);
+        let (desired_present, default_in_use) = {
+            let state = self.state.lock().unwrap();
+            (
+                state.get(&self.desired_adapter).map_or(false, |a| a.present),
+                state.values().any(|a| {
+                    a.virt_hci != hci
+                        && a.state != ProcessState::Off
+                        && a.config_file.as_ref() == Some(&default_conf)
+                }),
+            )
+        };
+        let is_default = (hci == self.desired_adapter || !desired_present) && !default_in_use;
+        Some(migrate::floss_conf_for_adapter(migrate::BT_LIBDIR, &address, is_default))
+    }
+
+    /// Restores devcoredump of the adapter if it was overridden to start it.
+    fn restore_coredump(&mut self, hci: VirtualHciIndex) {
+        if let Some((real_hci, enabled)) = self.coredump_restore.remove(&hci) {
+            if let Err(e) = config_util::set_hci_coredump_state(real_hci, enabled) {
+                warn!("{}: Failed to restore devcoredump state: {}", hci, e);
+            }
+        }
+    }
+
+    /// Updates the launch profile of each adapter from a reloaded config. Returns the enabled
+    /// adapters that need a restart for the new profile to take effect.
+    pub fn action_on_launch_profile_changed(
+        &mut self,
+        config: &config_util::ManagerConfig,
+    ) -> Vec<VirtualHciIndex> {
+        let adapters: Vec<VirtualHciIndex> = self.state.lock().unwrap().keys().cloned().collect();
+        adapters
+            .into_iter()
+            .filter(|&hci| {
+                let profile = config.get_launch_profile(hci);
+                if self.get_state(hci, |a: &AdapterState| Some(a.launch_profile.clone()))
+                    == Some(profile.clone())
+                {
+                    return false;
+                }
+                self.modify_state(hci, |a: &mut AdapterState| a.launch_profile = profile.clone());
+                config.is_hci_enabled(hci) && state_to_enabled(self.get_process_state(hci))
+            })
+            .collect()
+    }
+
+    /// Updates the enabled state of each adapter from a reloaded config. Returns the adapters whose
+    /// enabled state changed, along with their new state.
+    pub fn action_on_config_changed(
+        &mut self,
+        config: &config_util::ManagerConfig,
+    ) -> Vec<(VirtualHciIndex, bool)> {
+        let adapters: Vec<VirtualHciIndex> = self.state.lock().unwrap().keys().cloned().collect();
+        adapters
+            .into_iter()
+            .filter_map(|hci| {
+                let enabled = config.is_hci_enabled(hci);
+                if self.get_state(hci, |a: &AdapterState| Some(a.config_enabled)) == Some(enabled) {
+                    return None;
+                }
+                self.modify_state(hci, |a: &mut AdapterState| a.config_enabled = enabled);
+                Some((hci, enabled))
+            })
+            .collect()
+    }
+
+    /// Returns the next state and an action to reset timer if we are starting bluetooth process.
+    pub fn action_start_bluetooth(
+        &mut self,
+        hci: VirtualHciIndex,
+    ) -> (ProcessState, CommandTimeoutAction) {
+        let state = self.get_process_state(hci);
+        let present = self.get_state(hci, move |a: &AdapterState| Some(a.present)).unwrap_or(false);
+        let floss_enabled = self.get_floss_enabled();
+        let blocked =
+            self.get_state(hci, |a: &AdapterState| Some(a.rfkill.is_blocked())).unwrap_or(false);
+
+        match state {
+            // If adapter is off, we should turn it on when present, floss is enabled and it's not
+            // blocked by rfkill.
+            // If adapter is turning on and we get another start request, we should just
+            // repeat the same action which resets the timeout mechanism.
+            ProcessState::Off | ProcessState::TurningOn if present && floss_enabled && !blocked => {
+                self.modify_state(hci, move |s: &mut AdapterState| {
+                    s.state = ProcessState::TurningOn;
+                    // Starting explicitly skips any restart waiting on backoff.
+                    s.restart_history.take_pending_delay();
+                    s.restart_history.clear_failed();
+                });
+                self.start_process(hci);
+                (ProcessState::TurningOn, CommandTimeoutAction::ResetTimer)
+            }
+            // Otherwise (enabled states) no op
+            _ => (state, CommandTimeoutAction::DoNothing),
         }
     }
 
@@ -1398,7 +2178,19 @@ impl StateMachineInternal {
         }
 
         let state = self.get_process_state(hci);
+        let waiting_on_backoff = self
+            .get_state(hci, |a: &AdapterState| Some(a.restart_history.pending_delay.is_some()))
+            .unwrap_or(false);
+
         match state {
+            // The process isn't running while waiting to be restarted, just cancel the restart.
+            ProcessState::TurningOn if waiting_on_backoff => {
+                self.modify_state(hci, |s: &mut AdapterState| {
+                    s.state = ProcessState::Off;
+                    s.restart_history.take_pending_delay();
+                });
+                (ProcessState::Off, CommandTimeoutAction::CancelTimer)
+            }
             // If adapter is turning off and we get another stop request, we should just
             // repeat the same action which resets the timeout mechanism.
             ProcessState::On | ProcessState::TurningOff => {
@@ -1484,6 +2276,8 @@ impl StateMachineInternal {
         &mut self,
         hci: VirtualHciIndex,
     ) -> (ProcessState, CommandTimeoutAction) {
+        self.restore_coredump(hci);
+
         let state = self.get_process_state(hci);
         let (present, config_enabled) = self
             .get_state(hci, move |a: &AdapterState| Some((a.present, a.config_enabled)))
@@ -1498,13 +2292,28 @@ impl StateMachineInternal {
             }
             ProcessState::Restarting if floss_enabled && config_enabled => {
                 self.modify_state(hci, |s: &mut AdapterState| s.state = ProcessState::TurningOn);
-                self.process_manager.start(hci, self.get_real_hci_by_virtual_id(hci));
+                self.start_process(hci);
                 (ProcessState::TurningOn, CommandTimeoutAction::ResetTimer)
             }
             // Running bluetooth stopped unexpectedly.
             ProcessState::On if floss_enabled && config_enabled => {
                 let restart_count =
                     self.get_state(hci, |a: &AdapterState| Some(a.restart_count)).unwrap_or(0);
+                let recent_restarts = self.record_restart(hci, RestartReason::UnexpectedStop);
+
+                // Stop trying if the adapter keeps on crashing.
+                if recent_restarts >= CRASH_LOOP_MAX_RESTARTS {
+                    error!(
+                        "{} stopped unexpectedly {} times within {:?}, giving up.",
+                        hci, recent_restarts, CRASH_LOOP_WINDOW
+                    );
+                    self.modify_state(hci, |s: &mut AdapterState| {
+                        s.state = ProcessState::Off;
+                        s.restart_count = 0;
+                        s.restart_history.set_failed();
+                    });
+                    return (ProcessState::Off, CommandTimeoutAction::CancelTimer);
+                }
 
                 // If we've restarted a number of times, attempt to use the reset mechanism instead
                 // of retrying a start.
@@ -1517,6 +2326,7 @@ impl StateMachineInternal {
                     self.modify_state(hci, |s: &mut AdapterState| {
                         s.state = ProcessState::Off;
                         s.restart_count = 0;
+                        s.restart_history.record_reset();
                     });
                     let real_hci = self
                         .get_state(hci, |a: &AdapterState| Some(a.real_hci))
@@ -1524,16 +2334,28 @@ impl StateMachineInternal {
                     self.reset_hci(real_hci);
                     (ProcessState::Off, CommandTimeoutAction::CancelTimer)
                 } else {
+                    let delay = restart_backoff(recent_restarts);
                     warn!(
-                        "{} stopped unexpectedly, try restarting (attempt #{})",
+                        "{} stopped unexpectedly, try restarting in {:?} (attempt #{})",
                         hci,
+                        delay,
                         restart_count + 1
                     );
                     self.modify_state(hci, |s: &mut AdapterState| {
                         s.state = ProcessState::TurningOn;
                         s.restart_count = s.restart_count + 1;
                     });
-                    self.process_manager.start(hci, self.get_real_hci_by_virtual_id(hci));
+
+                    // The process is started once the backoff expires, see
+                    // |action_on_restart_backoff_expired|.
+                    if delay > Duration::ZERO {
+                        self.modify_state(hci, |s: &mut AdapterState| {
+                            s.restart_history.set_pending_delay(delay)
+                        });
+                        return (ProcessState::TurningOn, CommandTimeoutAction::CancelTimer);
+                    }
+
+                    self.start_process(hci);
                     (ProcessState::TurningOn, CommandTimeoutAction::ResetTimer)
                 }
             }
@@ -1574,6 +2396,23 @@ impl StateMachineInternal {
             ProcessState::TurningOn | ProcessState::PendingRestart if config_enabled => {
                 let restart_count =
                     self.get_state(hci, |a: &AdapterState| Some(a.restart_count)).unwrap_or(0);
+                let recent_restarts = self.record_restart(hci, RestartReason::StartTimeout);
+
+                // Stop trying if the adapter keeps on failing. The command timeout already spaces
+                // these restarts out so there is no additional backoff.
+                if recent_restarts >= CRASH_LOOP_MAX_RESTARTS {
+                    error!(
+                        "{} timed out while starting, {} restarts within {:?}, giving up.",
+                        hci, recent_restarts, CRASH_LOOP_WINDOW
+                    );
+                    self.modify_state(hci, |s: &mut AdapterState| {
+                        s.state = ProcessState::Off;
+                        s.restart_count = 0;
+                        s.restart_history.set_failed();
+                    });
+                    self.process_manager.stop(hci, self.get_real_hci_by_virtual_id(hci));
+                    return StateMachineTimeoutActions::Noop;
+                }
 
                 // If we've restarted a number of times, attempt to use the reset mechanism instead
                 // of retrying a start.
@@ -1586,6 +2425,7 @@ impl StateMachineInternal {
                     self.modify_state(hci, |s: &mut AdapterState| {
                         s.state = ProcessState::Off;
                         s.restart_count = 0;
+                        s.restart_history.record_reset();
                     });
                     let real_hci = self
                         .get_state(hci, |s: &AdapterState| Some(s.real_hci))
@@ -1604,7 +2444,7 @@ impl StateMachineInternal {
                         s.restart_count = s.restart_count + 1;
                     });
                     self.process_manager.stop(hci, self.get_real_hci_by_virtual_id(hci));
-                    self.process_manager.start(hci, self.get_real_hci_by_virtual_id(hci));
+                    self.start_process(hci);
                     StateMachineTimeoutActions::RetryStart
                 }
             }
@@ -1617,6 +2457,187 @@ impl StateMachineInternal {
         }
     }
 
+    /// Triggered when the backoff before restarting a crashed adapter expires. Starts the adapter
+    /// unless it was started or disabled in the meantime.
+    pub fn action_on_restart_backoff_expired(
+        &mut self,
+        hci: VirtualHciIndex,
+    ) -> (ProcessState, CommandTimeoutAction) {
+        let state = self.get_process_state(hci);
+        let pending = self
+            .get_state(hci, |a: &AdapterState| Some(a.restart_history.pending_delay.is_some()))
+            .unwrap_or(false);
+
+        if !pending {
+            return (state, CommandTimeoutAction::DoNothing);
+        }
+
+        self.modify_state(hci, |s: &mut AdapterState| {
+            s.restart_history.take_pending_delay();
+        });
+
+        let config_enabled =
+            self.get_state(hci, |a: &AdapterState| Some(a.config_enabled)).unwrap_or(false);
+        let floss_enabled = self.get_floss_enabled();
+
+        match state {
+            ProcessState::TurningOn if floss_enabled && config_enabled => {
+                self.start_process(hci);
+                (ProcessState::TurningOn, CommandTimeoutAction::ResetTimer)
+            }
+            _ => {
+                info!("{}: Restart is no longer needed after backoff from {:?}", hci, state);
+                self.modify_state(hci, |s: &mut AdapterState| s.state = ProcessState::Off);
+                (ProcessState::Off, CommandTimeoutAction::CancelTimer)
+            }
+        }
+    }
+
+    /// Updates the state of a Bluetooth rfkill switch. Returns the adapters whose block state
+    /// changed, along with their new state.
+    pub fn action_on_rfkill_changed(
+        &mut self,
+        event: &RfkillEvent,
+        hci: Option<RealHciIndex>,
+    ) -> Vec<(VirtualHciIndex, RfkillState)> {
+        let state = RfkillState { soft: event.soft, hard: event.hard };
+        match event.op {
+            RfkillOp::Del => {
+                self.rfkill_switches.remove(&event.idx);
+            }
+            RfkillOp::Add | RfkillOp::Change => {
+                let hci = hci.or(self.rfkill_switches.get(&event.idx).and_then(|s| s.hci));
+                self.rfkill_switches.insert(event.idx, RfkillSwitch { hci, state });
+            }
+            RfkillOp::ChangeAll => {
+                self.rfkill_switches.values_mut().for_each(|s| s.state = state);
+            }
+        }
+
+        let adapters: Vec<VirtualHciIndex> = self.state.lock().unwrap().keys().cloned().collect();
+        adapters
+            .into_iter()
+            .filter_map(|hci| {
+                let rfkill = self.get_rfkill_state(hci);
+                if self.get_state(hci, |a: &AdapterState| Some(a.rfkill)) == Some(rfkill) {
+                    return None;
+                }
+                self.modify_state(hci, |a: &mut AdapterState| a.rfkill = rfkill);
+                Some((hci, rfkill))
+            })
+            .collect()
+    }
+
+    /// Keeps the controller information of an hci device that was read over MGMT. It's read
+    /// before the device is reported present, and stored in its adapter state once it is.
+    pub fn action_on_controller_info(
+        &mut self,
+        hci: RealHciIndex,
+        info: MgmtControllerInfo,
+        bus: Option<MgmtControllerBus>,
+    ) {
+        self.pending_controller_info.insert(hci, (info, bus));
+    }
+
+    /// Records a hardware error reported by the controller of an hci device.
+    pub fn action_on_controller_error(&mut self, hci: RealHciIndex, error_code: u8) {
+        if let Some(virt_hci) = self.get_virtual_id_by_real_id(hci) {
+            self.modify_state(virt_hci, move |a: &mut AdapterState| {
+                a.last_controller_error = Some(error_code)
+            });
+        }
+    }
+
+    /// Stops an adapter that got blocked by rfkill, or brings it back to its desired state once
+    /// unblocked.
+    pub fn action_on_rfkill_state_changed(
+        &mut self,
+        hci: VirtualHciIndex,
+    ) -> (ProcessState, CommandTimeoutAction) {
+        let state = self.get_process_state(hci);
+        let (blocked, config_enabled) = self
+            .get_state(hci, |a: &AdapterState| Some((a.rfkill.is_blocked(), a.config_enabled)))
+            .unwrap_or((false, false));
+
+        if blocked {
+            let waiting_on_backoff = self
+                .get_state(hci, |a: &AdapterState| Some(a.restart_history.pending_delay.is_some()))
+                .unwrap_or(false);
+
+            match state {
+                // The process isn't running while waiting to be restarted.
+                ProcessState::TurningOn if waiting_on_backoff => {
+                    self.modify_state(hci, |s: &mut AdapterState| {
+                        s.state = ProcessState::Off;
+                        s.restart_history.take_pending_delay();
+                    });
+                    (ProcessState::Off, CommandTimeoutAction::CancelTimer)
+                }
+                ProcessState::On | ProcessState::TurningOn | ProcessState::PendingRestart => {
+                    self.modify_state(hci, |s: &mut AdapterState| {
+                        s.state = ProcessState::TurningOff
+                    });
+                    self.process_manager.stop(hci, self.get_real_hci_by_virtual_id(hci));
+                    (ProcessState::TurningOff, CommandTimeoutAction::ResetTimer)
+                }
+                // Already stopping, just don't start again afterwards.
+                ProcessState::Restarting => {
+                    self.modify_state(hci, |s: &mut AdapterState| {
+                        s.state = ProcessState::TurningOff
+                    });
+                    (ProcessState::TurningOff, CommandTimeoutAction::DoNothing)
+                }
+                _ => (state, CommandTimeoutAction::DoNothing),
+            }
+        } else {
+            match state {
+                ProcessState::Off if config_enabled => self.action_start_bluetooth(hci),
+                // Still stopping from the block, start again afterwards.
+                ProcessState::TurningOff if config_enabled => {
+                    self.modify_state(hci, |s: &mut AdapterState| {
+                        s.state = ProcessState::Restarting
+                    });
+                    (ProcessState::Restarting, CommandTimeoutAction::DoNothing)
+                }
+                _ => (state, CommandTimeoutAction::DoNothing),
+            }
+        }
+    }
+
+    /// Combines the rfkill switches that apply to an adapter.
+    fn get_rfkill_state(&self, hci: VirtualHciIndex) -> RfkillState {
+        let real_hci = self.get_real_hci_by_virtual_id(hci);
+        self.rfkill_switches.values().filter(|s| s.hci.map_or(true, |h| h == real_hci)).fold(
+            RfkillState::default(),
+            |acc, s| RfkillState { soft: acc.soft || s.state.soft, hard: acc.hard || s.state.hard },
+        )
+    }
+
+    /// Records a restart of the adapter and returns the number of restarts within
+    /// |CRASH_LOOP_WINDOW|.
+    fn record_restart(&mut self, hci: VirtualHciIndex, reason: RestartReason) -> usize {
+        match self.state.lock().unwrap().get_mut(&hci) {
+            Some(a) => a.restart_history.record(reason),
+            None => 0,
+        }
+    }
+
+    /// Returns the delay of the restart waiting on backoff, if any.
+    fn get_restart_delay(&self, hci: VirtualHciIndex) -> Option<Duration> {
+        self.get_state(hci, |a: &AdapterState| a.restart_history.pending_delay)
+    }
+
+    /// Returns the health of the adapter if it changed since the last call.
+    fn take_changed_health(&mut self, hci: VirtualHciIndex) -> Option<AdapterHealth> {
+        let mut state = self.state.lock().unwrap();
+        let adapter = state.get_mut(&hci)?;
+        if !adapter.restart_history.changed {
+            return None;
+        }
+        adapter.restart_history.changed = false;
+        Some(adapter.restart_history.to_adapter_health(hci))
+    }
+
     /// Handle when an hci device presence has changed.
     ///
     /// This will start adapters that are configured to be enabled if the presence is newly added.
@@ -1631,12 +2652,28 @@ impl StateMachineInternal {
         let prev_present = self.get_state(hci, |a: &AdapterState| Some(a.present)).unwrap_or(false);
         let prev_state = self.get_process_state(hci);
 
+        // The controller information is needed to start the adapter, e.g. to pick its conf file.
+        let real_hci = self.get_real_hci_by_virtual_id(hci);
+        if let Some((info, bus)) = self.pending_controller_info.remove(&real_hci) {
+            if present {
+                self.modify_state(hci, move |a: &mut AdapterState| {
+                    a.controller_info = Some(info.clone());
+                    a.bus = bus;
+                });
+            }
+        }
+
         // No-op if same as previous present.
         if prev_present == present {
             return (prev_state, AdapterChangeAction::DoNothing, CommandTimeoutAction::DoNothing);
         }
 
-        self.modify_state(hci, |a: &mut AdapterState| a.present = present);
+        // The rfkill switch of an hci device may be reported before the device itself.
+        let rfkill = self.get_rfkill_state(hci);
+        self.modify_state(hci, |a: &mut AdapterState| {
+            a.present = present;
+            a.rfkill = rfkill;
+        });
         let floss_enabled = self.get_floss_enabled();
 
         let (next_state, timeout_action) =
@@ -1682,6 +2719,7 @@ impl StateMachineInternal {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::iface_bluetooth_manager::AdapterDebugLevel;
     use std::collections::VecDeque;
 
     #[derive(Debug, PartialEq)]
@@ -1710,7 +2748,7 @@ mod tests {
     }
 
     impl ProcessManager for MockProcessManager {
-        fn start(&mut self, _virt: VirtualHciIndex, _real: RealHciIndex) {
+        fn start(&mut self, _virt: VirtualHciIndex, _real: RealHciIndex, _args: &[String]) {
             self.expectations.push(match self.last_command.pop_front() {
                 Some(x) => {
                     if x == ExecutedCommand::Start {
@@ -1735,6 +2773,14 @@ mod tests {
                 None => Some(format!("Got [Stop], Expected: None")),
             });
         }
+
+        fn start_bluez(&mut self) -> bool {
+            true
+        }
+
+        fn stop_bluez(&mut self) -> bool {
+            true
+        }
     }
 
     impl Drop for MockProcessManager {
@@ -2077,6 +3123,362 @@ mod tests {
         });
     }
 
+    #[test]
+    fn crash_loop_backs_off_then_fails() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut process_manager = MockProcessManager::new();
+            // Initial start, then one start for each restart before giving up.
+            for _ in 0..CRASH_LOOP_MAX_RESTARTS {
+                process_manager.expect_start();
+            }
+            // Start again after being marked failed.
+            process_manager.expect_start();
+            let mut state_machine = make_state_machine(process_manager);
+            state_machine.action_on_hci_presence_changed(DEFAULT_ADAPTER, true);
+            state_machine.set_config_enabled(DEFAULT_ADAPTER, true);
+            state_machine.action_start_bluetooth(DEFAULT_ADAPTER);
+            state_machine.action_on_bluetooth_started(0, DEFAULT_ADAPTER);
+
+            // First crash restarts immediately.
+            assert_eq!(
+                state_machine.action_on_bluetooth_stopped(DEFAULT_ADAPTER),
+                (ProcessState::TurningOn, CommandTimeoutAction::ResetTimer)
+            );
+            assert_eq!(state_machine.get_restart_delay(DEFAULT_ADAPTER), None);
+            state_machine.action_on_bluetooth_started(0, DEFAULT_ADAPTER);
+
+            // Further crashes wait for an increasing backoff.
+            for restarts in 2..CRASH_LOOP_MAX_RESTARTS {
+                assert_eq!(
+                    state_machine.action_on_bluetooth_stopped(DEFAULT_ADAPTER),
+                    (ProcessState::TurningOn, CommandTimeoutAction::CancelTimer)
+                );
+                assert_eq!(
+                    state_machine.get_restart_delay(DEFAULT_ADAPTER),
+                    Some(restart_backoff(restarts))
+                );
+                assert_eq!(
+                    state_machine.action_on_restart_backoff_expired(DEFAULT_ADAPTER),
+                    (ProcessState::TurningOn, CommandTimeoutAction::ResetTimer)
+                );
+                state_machine.action_on_bluetooth_started(0, DEFAULT_ADAPTER);
+            }
+
+            // Give up once the limit is reached.
+            assert_eq!(
+                state_machine.action_on_bluetooth_stopped(DEFAULT_ADAPTER),
+                (ProcessState::Off, CommandTimeoutAction::CancelTimer)
+            );
+            let health = state_machine.take_changed_health(DEFAULT_ADAPTER).unwrap();
+            assert!(health.failed);
+            assert_eq!(health.restart_history.len(), CRASH_LOOP_MAX_RESTARTS);
+            assert_eq!(state_machine.take_changed_health(DEFAULT_ADAPTER), None);
+
+            // Starting again clears the failed state.
+            state_machine.action_start_bluetooth(DEFAULT_ADAPTER);
+            assert_eq!(state_machine.get_process_state(DEFAULT_ADAPTER), ProcessState::TurningOn);
+            assert!(!state_machine.take_changed_health(DEFAULT_ADAPTER).unwrap().failed);
+        })
+    }
+
+    #[test]
+    fn restart_backoff_skipped_when_disabled() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut process_manager = MockProcessManager::new();
+            process_manager.expect_start();
+            process_manager.expect_start();
+            let mut state_machine = make_state_machine(process_manager);
+            state_machine.action_on_hci_presence_changed(DEFAULT_ADAPTER, true);
+            state_machine.set_config_enabled(DEFAULT_ADAPTER, true);
+            state_machine.action_start_bluetooth(DEFAULT_ADAPTER);
+            state_machine.action_on_bluetooth_started(0, DEFAULT_ADAPTER);
+            state_machine.action_on_bluetooth_stopped(DEFAULT_ADAPTER);
+            state_machine.action_on_bluetooth_started(0, DEFAULT_ADAPTER);
+            state_machine.action_on_bluetooth_stopped(DEFAULT_ADAPTER);
+            assert!(state_machine.get_restart_delay(DEFAULT_ADAPTER).is_some());
+
+            // Disabling the adapter while waiting cancels the restart.
+            state_machine.set_config_enabled(DEFAULT_ADAPTER, false);
+            assert_eq!(
+                state_machine.action_on_restart_backoff_expired(DEFAULT_ADAPTER),
+                (ProcessState::Off, CommandTimeoutAction::CancelTimer)
+            );
+            assert_eq!(
+                state_machine.action_on_restart_backoff_expired(DEFAULT_ADAPTER),
+                (ProcessState::Off, CommandTimeoutAction::DoNothing)
+            );
+        })
+    }
+
+    #[test]
+    fn stop_cancels_restart_backoff() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut process_manager = MockProcessManager::new();
+            process_manager.expect_start();
+            process_manager.expect_start();
+            let mut state_machine = make_state_machine(process_manager);
+            state_machine.action_on_hci_presence_changed(DEFAULT_ADAPTER, true);
+            state_machine.set_config_enabled(DEFAULT_ADAPTER, true);
+            state_machine.action_start_bluetooth(DEFAULT_ADAPTER);
+            state_machine.action_on_bluetooth_started(0, DEFAULT_ADAPTER);
+            state_machine.action_on_bluetooth_stopped(DEFAULT_ADAPTER);
+            state_machine.action_on_bluetooth_started(0, DEFAULT_ADAPTER);
+            state_machine.action_on_bluetooth_stopped(DEFAULT_ADAPTER);
+            assert!(state_machine.get_restart_delay(DEFAULT_ADAPTER).is_some());
+            state_machine.take_changed_health(DEFAULT_ADAPTER);
+
+            // Stopping while waiting turns the adapter off right away without a process to stop.
+            assert_eq!(
+                state_machine.action_stop_bluetooth(DEFAULT_ADAPTER),
+                (ProcessState::Off, CommandTimeoutAction::CancelTimer)
+            );
+            assert_eq!(state_machine.get_restart_delay(DEFAULT_ADAPTER), None);
+            let health = state_machine.take_changed_health(DEFAULT_ADAPTER).unwrap();
+            assert_eq!(health.restart_delay_ms, 0);
+
+            // The expired backoff no longer starts the adapter.
+            assert_eq!(
+                state_machine.action_on_restart_backoff_expired(DEFAULT_ADAPTER),
+                (ProcessState::Off, CommandTimeoutAction::DoNothing)
+            );
+        })
+    }
+
+    #[test]
+    fn restart_backoff_grows_exponentially() {
+        assert_eq!(restart_backoff(1), Duration::ZERO);
+        assert_eq!(restart_backoff(2), RESTART_BACKOFF_BASE);
+        assert_eq!(restart_backoff(3), RESTART_BACKOFF_BASE * 2);
+        assert_eq!(restart_backoff(4), RESTART_BACKOFF_BASE * 4);
+        assert_eq!(restart_backoff(100), RESTART_BACKOFF_MAX);
+    }
+
+    fn rfkill_event(idx: u32, op: RfkillOp, soft: bool) -> RfkillEvent {
+        RfkillEvent { idx, rfkill_type: 2, op, soft, hard: false }
+    }
+
+    #[test]
+    fn rfkill_block_stops_and_unblock_restores() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut process_manager = MockProcessManager::new();
+            process_manager.expect_start();
+            // Stopped when blocked.
+            process_manager.expect_stop();
+            // Started again when unblocked.
+            process_manager.expect_start();
+            let mut state_machine = make_state_machine(process_manager);
+            state_machine.action_on_hci_presence_changed(DEFAULT_ADAPTER, true);
+            state_machine.set_config_enabled(DEFAULT_ADAPTER, true);
+            state_machine.action_start_bluetooth(DEFAULT_ADAPTER);
+            state_machine.action_on_bluetooth_started(0, DEFAULT_ADAPTER);
+
+            let blocked = RfkillState { soft: true, hard: false };
+            assert_eq!(
+                state_machine.action_on_rfkill_changed(
+                    &rfkill_event(0, RfkillOp::Add, true),
+                    Some(RealHciIndex(0))
+                ),
+                vec![(DEFAULT_ADAPTER, blocked)]
+            );
+            assert_eq!(
+                state_machine.action_on_rfkill_state_changed(DEFAULT_ADAPTER),
+                (ProcessState::TurningOff, CommandTimeoutAction::ResetTimer)
+            );
+            state_machine.action_on_bluetooth_stopped(DEFAULT_ADAPTER);
+            assert_eq!(state_machine.get_process_state(DEFAULT_ADAPTER), ProcessState::Off);
+
+            // Doesn't start while blocked.
+            state_machine.action_start_bluetooth(DEFAULT_ADAPTER);
+            assert_eq!(state_machine.get_process_state(DEFAULT_ADAPTER), ProcessState::Off);
+
+            assert_eq!(
+                state_machine
+                    .action_on_rfkill_changed(&rfkill_event(0, RfkillOp::Change, false), None),
+                vec![(DEFAULT_ADAPTER, RfkillState::default())]
+            );
+            assert_eq!(
+                state_machine.action_on_rfkill_state_changed(DEFAULT_ADAPTER),
+                (ProcessState::TurningOn, CommandTimeoutAction::ResetTimer)
+            );
+        })
+    }
+
+    #[test]
+    fn rfkill_switch_applies_to_its_adapter() {
+        let process_manager = MockProcessManager::new();
+        let mut state_machine = make_state_machine(process_manager);
+        state_machine.get_updated_virtual_id("/fake/bt0".into(), RealHciIndex(0));
+        state_machine.get_updated_virtual_id("/fake/bt1".into(), RealHciIndex(1));
+
+        // Only the adapter of the switch is blocked.
+        assert_eq!(
+            state_machine.action_on_rfkill_changed(
+                &rfkill_event(1, RfkillOp::Add, true),
+                Some(RealHciIndex(1))
+            ),
+            vec![(ALT_ADAPTER, RfkillState { soft: true, hard: false })]
+        );
+
+        // Switches that don't belong to an hci device block all adapters.
+        assert_eq!(
+            state_machine.action_on_rfkill_changed(&rfkill_event(5, RfkillOp::Add, true), None),
+            vec![(DEFAULT_ADAPTER, RfkillState { soft: true, hard: false })]
+        );
+        assert_eq!(
+            state_machine.action_on_rfkill_changed(&rfkill_event(5, RfkillOp::Del, false), None),
+            vec![(DEFAULT_ADAPTER, RfkillState::default())]
+        );
+    }
+
+    #[test]
+    fn controller_info_is_stored_per_adapter() {
+        let process_manager = MockProcessManager::new();
+        let mut state_machine = make_state_machine(process_manager);
+        state_machine.get_updated_virtual_id("/fake/bt0".into(), RealHciIndex(0));
+        state_machine.get_updated_virtual_id("/fake/bt1".into(), RealHciIndex(1));
+
+        let info = MgmtControllerInfo {
+            index: 1,
+            address: [0x66, 0x55, 0x44, 0x33, 0x22, 0x11],
+            version: 0x0c,
+            manufacturer: 0x2,
+            supported_settings: 0x3feff,
+            current_settings: 0x281,
+            class_of_device: 0,
+            name: "floss".into(),
+            short_name: String::new(),
+        };
+        state_machine.action_on_controller_info(
+            RealHciIndex(1),
+            info.clone(),
+            Some(MgmtControllerBus::Usb),
+        );
+        // The information is kept until the hci device is reported present.
+        assert_eq!(
+            state_machine.get_state(ALT_ADAPTER, |a: &AdapterState| a.controller_info.clone()),
+            None
+        );
+        state_machine.action_on_hci_presence_changed(ALT_ADAPTER, true);
+        state_machine.action_on_controller_error(RealHciIndex(1), 0x42);
+        // Indexes without an adapter don't make one.
+        state_machine.action_on_controller_info(RealHciIndex(5), info.clone(), None);
+        state_machine.action_on_controller_error(RealHciIndex(5), 0x42);
+
+        assert_eq!(
+            state_machine.get_state(ALT_ADAPTER, |a: &AdapterState| a.controller_info.clone()),
+            Some(info)
+        );
+        assert_eq!(
+            state_machine.get_state(ALT_ADAPTER, |a: &AdapterState| a.get_address()),
+            Some("11:22:33:44:55:66".to_string())
+        );
+        assert_eq!(
+            state_machine.get_state(ALT_ADAPTER, |a: &AdapterState| a.bus),
+            Some(MgmtControllerBus::Usb)
+        );
+        assert_eq!(
+            state_machine.get_state(ALT_ADAPTER, |a: &AdapterState| a.last_controller_error),
+            Some(0x42)
+        );
+        assert_eq!(
+            state_machine.get_state(DEFAULT_ADAPTER, |a: &AdapterState| a.controller_info.clone()),
+            None
+        );
+        assert!(!state_machine.is_known(VirtualHciIndex(5)));
+    }
+
+    #[test]
+    fn config_change_updates_enabled_adapters() {
+        let process_manager = MockProcessManager::new();
+        let mut state_machine = make_state_machine(process_manager);
+        state_machine.get_updated_virtual_id("/fake/bt0".into(), RealHciIndex(0));
+        state_machine.get_updated_virtual_id("/fake/bt1".into(), RealHciIndex(1));
+        state_machine.set_config_enabled(DEFAULT_ADAPTER, true);
+        state_machine.set_config_enabled(ALT_ADAPTER, true);
+
+        let mut config = config_util::ManagerConfig::default();
+        assert_eq!(state_machine.action_on_config_changed(&config), vec![]);
+
+        config.adapters.insert(
+            ALT_ADAPTER.to_i32(),
+            config_util::AdapterConfig { enabled: false, ..Default::default() },
+        );
+        assert_eq!(state_machine.action_on_config_changed(&config), vec![(ALT_ADAPTER, false)]);
+        assert_eq!(state_machine.get_state(ALT_ADAPTER, |a| Some(a.config_enabled)), Some(false));
+
+        // Nothing changes when the same config is reloaded.
+        assert_eq!(state_machine.action_on_config_changed(&config), vec![]);
+
+        config.adapters.clear();
+        assert_eq!(state_machine.action_on_config_changed(&config), vec![(ALT_ADAPTER, true)]);
+    }
+
+    #[test]
+    fn config_change_only_follows_changed_default_adapter() {
+        let process_manager = MockProcessManager::new();
+        let mut state_machine = make_state_machine(process_manager);
+        state_machine.get_updated_virtual_id("/fake/bt0".into(), RealHciIndex(0));
+        state_machine.get_updated_virtual_id("/fake/bt1".into(), RealHciIndex(1));
+        state_machine.action_on_hci_presence_changed(DEFAULT_ADAPTER, true);
+        state_machine.action_on_hci_presence_changed(ALT_ADAPTER, true);
+
+        // The default adapter set through the API stays when the config is reloaded.
+        state_machine.set_desired_default_adapter(ALT_ADAPTER);
+        let mut config = config_util::ManagerConfig::default();
+        assert!(matches!(
+            state_machine.action_on_config_default_adapter(&config),
+            AdapterChangeAction::DoNothing
+        ));
+        assert_eq!(state_machine.desired_adapter, ALT_ADAPTER);
+
+        config.default_adapter = Some(ALT_ADAPTER);
+        state_machine.set_desired_default_adapter(DEFAULT_ADAPTER);
+        assert!(matches!(
+            state_machine.action_on_config_default_adapter(&config),
+            AdapterChangeAction::NewDefaultAdapter(hci) if hci == ALT_ADAPTER
+        ));
+        assert_eq!(state_machine.desired_adapter, ALT_ADAPTER);
+    }
+
+    #[test]
+    fn launch_profile_change_restarts_enabled_adapters() {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut process_manager = MockProcessManager::new();
+            process_manager.expect_start();
+            let mut state_machine = make_state_machine(process_manager);
+            state_machine.get_updated_virtual_id("/fake/bt0".into(), RealHciIndex(0));
+            state_machine.get_updated_virtual_id("/fake/bt1".into(), RealHciIndex(1));
+            state_machine.action_on_hci_presence_changed(DEFAULT_ADAPTER, true);
+            state_machine.set_config_enabled(DEFAULT_ADAPTER, true);
+            state_machine.action_start_bluetooth(DEFAULT_ADAPTER);
+            state_machine.action_on_bluetooth_started(0, DEFAULT_ADAPTER);
+
+            let mut config = config_util::ManagerConfig::default();
+            assert_eq!(state_machine.action_on_launch_profile_changed(&config), vec![]);
+
+            // Only the running adapter needs a restart.
+            let mut adapter = config_util::AdapterConfig::default();
+            adapter.launch_profile.debug_level = AdapterDebugLevel::Debug;
+            config.adapters.insert(DEFAULT_ADAPTER.to_i32(), adapter.clone());
+            config.adapters.insert(ALT_ADAPTER.to_i32(), adapter);
+            assert_eq!(
+                state_machine.action_on_launch_profile_changed(&config),
+                vec![DEFAULT_ADAPTER]
+            );
+            assert_eq!(
+                state_machine.get_state(ALT_ADAPTER, |a| Some(a.launch_profile.debug_level)),
+                Some(AdapterDebugLevel::Debug)
+            );
+            assert_eq!(state_machine.action_on_launch_profile_changed(&config), vec![]);
+
+            // The running adapter reports the profile it was started with.
+            assert_eq!(
+                state_machine.get_state(DEFAULT_ADAPTER, |a| a.active_launch_profile.clone()),
+                Some(AdapterLaunchProfile::default())
+            );
+        })
+    }
+
     #[test]
     fn test_updated_virtual_id() {
         let process_manager = MockProcessManager::new();
@@ -0,0 +1,88 @@
+//! Code shared by the suspend managers that coordinate system suspend with btadapterd.
+
+use btstack::suspend::SuspendType;
+use dbus::nonblock::SyncConnection;
+use std::sync::Arc;
+use tokio::sync::mpsc::Sender;
+
+use crate::service_watcher::ServiceWatcher;
+use crate::suspend::SuspendImminent_Reason;
+
+pub(crate) const ADAPTER_SERVICE: &str = "org.chromium.bluetooth";
+pub(crate) const ADAPTER_SUSPEND_INTERFACE: &str = "org.chromium.bluetooth.Suspend";
+pub(crate) const BTMANAGERD_NAME: &str = "Bluetooth Manager";
+
+/// Suspend manager state that is controlled through the Manager API.
+pub trait SuspendManagerState: Send {
+    /// Sets whether the device is in tablet mode. No wakes are allowed in tablet mode.
+    fn set_tablet_mode(&mut self, tablet_mode: bool);
+}
+
+/// Chooses how the adapter should suspend.
+pub(crate) fn get_suspend_type(tablet_mode: bool, reason: SuspendImminent_Reason) -> SuspendType {
+    match (tablet_mode, reason) {
+        // No wakes allowed on tablet mode.
+        (true, _) => SuspendType::NoWakesAllowed,
+
+        // When not in tablet mode, choose wake type based on suspend reason.
+        (false, SuspendImminent_Reason::IDLE) => SuspendType::AllowWakeFromHid,
+        (false, SuspendImminent_Reason::LID_CLOSED) => SuspendType::NoWakesAllowed,
+        (false, SuspendImminent_Reason::OTHER) => SuspendType::Other,
+    }
+}
+
+/// Watches |service| for the adapter Suspend interface. The messages made by |on_found| and
+/// |on_removed| are sent to |tx| when the interface appears and when the service goes away.
+pub(crate) async fn watch_adapter_suspend<M: Send + 'static>(
+    conn: Arc<SyncConnection>,
+    service: &str,
+    tx: Sender<M>,
+    on_found: fn(dbus::Path<'static>) -> M,
+    on_removed: fn() -> M,
+) {
+    let mut watcher = ServiceWatcher::new(conn, String::from(service));
+    let tx1 = tx.clone();
+    let tx2 = tx;
+    watcher
+        .start_watch_interface(
+            String::from(ADAPTER_SUSPEND_INTERFACE),
+            Box::new(move |path| {
+                let tx_clone = tx1.clone();
+                tokio::spawn(async move {
+                    let _ = tx_clone.send(on_found(path)).await;
+                });
+            }),
+            Box::new(move || {
+                let tx_clone = tx2.clone();
+                tokio::spawn(async move {
+                    let _ = tx_clone.send(on_removed()).await;
+                });
+            }),
+        )
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_suspend_type() {
+        assert!(matches!(
+            get_suspend_type(false, SuspendImminent_Reason::IDLE),
+            SuspendType::AllowWakeFromHid
+        ));
+        assert!(matches!(
+            get_suspend_type(false, SuspendImminent_Reason::LID_CLOSED),
+            SuspendType::NoWakesAllowed
+        ));
+        assert!(matches!(
+            get_suspend_type(false, SuspendImminent_Reason::OTHER),
+            SuspendType::Other
+        ));
+        assert!(matches!(
+            get_suspend_type(true, SuspendImminent_Reason::IDLE),
+            SuspendType::NoWakesAllowed
+        ));
+    }
+}
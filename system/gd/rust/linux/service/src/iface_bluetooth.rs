@@ -17,6 +17,7 @@ use btstack::bluetooth::{
     Bluetooth, BluetoothDevice, IBluetooth, IBluetoothCallback, IBluetoothConnectionCallback,
     IBluetoothQALegacy,
 };
+use btstack::dis::DeviceInformationValues;
 use btstack::socket_manager::{
     BluetoothServerSocket, BluetoothSocket, BluetoothSocketManager, CallbackId,
     IBluetoothSocketManager, IBluetoothSocketManagerCallbacks, SocketId, SocketResult,
@@ -87,6 +88,14 @@ impl IBluetoothCallback for BluetoothCallbackDBus {
     ) {
         dbus_generated!()
     }
+    #[dbus_method("OnDeviceInformationChanged")]
+    fn on_device_information_changed(
+        &mut self,
+        remote_device: BluetoothDevice,
+        values: DeviceInformationValues,
+    ) {
+        dbus_generated!()
+    }
     #[dbus_method("OnAddressChanged")]
     fn on_address_changed(&mut self, addr: String) {
         dbus_generated!()
@@ -273,6 +282,17 @@ pub struct BtVendorProductInfoDBus {
     version: u16,
 }
 
+#[dbus_propmap(DeviceInformationValues)]
+pub struct DeviceInformationValuesDBus {
+    manufacturer_name: String,
+    model_number: String,
+    serial_number: String,
+    hardware_revision: String,
+    firmware_revision: String,
+    software_revision: String,
+    system_id: Vec<u8>,
+}
+
 fn read_propmap_value<T: 'static + DirectDBus>(
     propmap: &dbus::arg::PropMap,
     key: &str,
@@ -642,6 +662,11 @@ impl IBluetooth for IBluetoothDBus {
         dbus_generated!()
     }
 
+    #[dbus_method("GetRemoteDeviceInformation", DBusLog::Disable)]
+    fn get_remote_device_information(&self, device: BluetoothDevice) -> DeviceInformationValues {
+        dbus_generated!()
+    }
+
     #[dbus_method("GetRemoteRSSI", DBusLog::Disable)]
     fn get_remote_rssi(&self, device: BluetoothDevice) -> i8 {
         dbus_generated!()
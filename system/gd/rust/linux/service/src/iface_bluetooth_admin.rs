@@ -1,6 +1,10 @@
-use btstack::bluetooth_admin::{IBluetoothAdmin, IBluetoothAdminPolicyCallback, PolicyEffect};
+use btstack::bluetooth_admin::{
+    DevicePolicy, IBluetoothAdmin, IBluetoothAdminPolicyCallback, PolicyBlockReason, PolicyEffect,
+    VendorProductId,
+};
 
 use dbus::arg::RefArg;
+use dbus::nonblock::SyncConnection;
 use dbus::Path;
 use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj, generate_dbus_exporter};
 
@@ -8,21 +12,41 @@ use dbus_projection::prelude::*;
 
 use crate::dbus_arg::{DBusArg, DBusArgError, RefArgToRust};
 
-use bt_topshim::btif::Uuid128Bit;
+use bt_topshim::btif::{BtTransport, Uuid128Bit};
 
 use btstack::bluetooth::BluetoothDevice;
 use btstack::RPCProxy;
 
+use num_traits::{FromPrimitive, ToPrimitive};
+
+use std::sync::Arc;
+
 struct IBluetoothAdminDBus {}
 
 struct IBluetoothAdminPolicyCallbackDBus {}
 
+impl_dbus_arg_enum!(PolicyBlockReason);
+
 #[dbus_propmap(PolicyEffect)]
 pub struct PolicyEffectDBus {
     pub service_blocked: Vec<Uuid128Bit>,
+    pub device_blocked: PolicyBlockReason,
     pub affected: bool,
 }
 
+#[dbus_propmap(VendorProductId)]
+pub struct VendorProductIdDBus {
+    pub vendor_id: u16,
+    pub product_id: u16,
+}
+
+#[dbus_propmap(DevicePolicy)]
+pub struct DevicePolicyDBus {
+    pub denied_addresses: Vec<String>,
+    pub allowed_bonding_ids: Vec<VendorProductId>,
+    pub allowed_transport: BtTransport,
+}
+
 #[dbus_proxy_obj(AdminPolicyCallback, "org.chromium.bluetooth.AdminPolicyCallback")]
 impl IBluetoothAdminPolicyCallback for IBluetoothAdminPolicyCallbackDBus {
     #[dbus_method("OnServiceAllowlistChanged")]
@@ -38,6 +62,16 @@ impl IBluetoothAdminPolicyCallback for IBluetoothAdminPolicyCallbackDBus {
     ) {
         dbus_generated!()
     }
+
+    #[dbus_method("OnDevicePolicyChanged")]
+    fn on_device_policy_changed(&mut self, policy: DevicePolicy) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnDevicePolicyViolation")]
+    fn on_device_policy_violation(&mut self, device: BluetoothDevice, reason: PolicyBlockReason) {
+        dbus_generated!()
+    }
 }
 
 #[generate_dbus_exporter(export_bluetooth_admin_dbus_intf, "org.chromium.bluetooth.BluetoothAdmin")]
@@ -57,6 +91,16 @@ impl IBluetoothAdmin for IBluetoothAdminDBus {
         dbus_generated!()
     }
 
+    #[dbus_method("SetDevicePolicy")]
+    fn set_device_policy(&mut self, policy: DevicePolicy) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetDevicePolicy")]
+    fn get_device_policy(&self) -> DevicePolicy {
+        dbus_generated!()
+    }
+
     #[dbus_method("GetDevicePolicyEffect")]
     fn get_device_policy_effect(&self, device: BluetoothDevice) -> Option<PolicyEffect> {
         dbus_generated!()
@@ -0,0 +1,182 @@
+use bt_topshim::profiles::avrcp::{
+    AvrcpBrowseItem, AvrcpBrowseItemType, AvrcpBrowseScope, AvrcpPlayStatus, PlayerMetadata,
+};
+use btstack::bluetooth_media::{
+    AvrcpPassthroughCommand, IBluetoothAvrcpController, IBluetoothAvrcpControllerCallback,
+};
+use btstack::RPCProxy;
+
+use dbus::arg::RefArg;
+use dbus::nonblock::SyncConnection;
+use dbus::strings::Path;
+
+use dbus_macros::{dbus_method, dbus_propmap, dbus_proxy_obj, generate_dbus_exporter};
+
+use dbus_projection::prelude::*;
+
+use crate::dbus_arg::{DBusArg, DBusArgError, RefArgToRust};
+
+use num_traits::{FromPrimitive, ToPrimitive};
+
+use std::sync::Arc;
+
+impl_dbus_arg_enum!(AvrcpBrowseItemType);
+impl_dbus_arg_enum!(AvrcpBrowseScope);
+impl_dbus_arg_enum!(AvrcpPassthroughCommand);
+impl_dbus_arg_enum!(AvrcpPlayStatus);
+
+#[dbus_propmap(AvrcpBrowseItem)]
+pub struct AvrcpBrowseItemDBus {
+    item_type: AvrcpBrowseItemType,
+    uid: u64,
+    name: String,
+    playable: bool,
+    metadata: PlayerMetadata,
+}
+
+#[allow(dead_code)]
+struct AvrcpControllerCallbackDBus {}
+
+#[dbus_proxy_obj(AvrcpControllerCallback, "org.chromium.bluetooth.AvrcpControllerCallback")]
+impl IBluetoothAvrcpControllerCallback for AvrcpControllerCallbackDBus {
+    #[dbus_method("OnTargetConnectionStateChanged")]
+    fn on_target_connection_state_changed(&mut self, addr: String, connected: bool) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnTrackChanged")]
+    fn on_track_changed(&mut self, addr: String, metadata: PlayerMetadata) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnPlayStatusChanged")]
+    fn on_play_status_changed(&mut self, addr: String, status: AvrcpPlayStatus) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnPlayPositionChanged", DBusLog::Disable)]
+    fn on_play_position_changed(&mut self, addr: String, length: u32, position: u32) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnBrowseItems")]
+    fn on_browse_items(&mut self, addr: String, success: bool, items: Vec<AvrcpBrowseItem>) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnFolderChanged")]
+    fn on_folder_changed(&mut self, addr: String, num_items: u32) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnBrowsedPlayerChanged")]
+    fn on_browsed_player_changed(&mut self, addr: String, num_items: u32, depth: u32) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnAddressedPlayerChanged")]
+    fn on_addressed_player_changed(&mut self, addr: String, player_id: u16) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnNowPlayingChanged")]
+    fn on_now_playing_changed(&mut self, addr: String) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("OnAvailablePlayersChanged")]
+    fn on_available_players_changed(&mut self, addr: String) {
+        dbus_generated!()
+    }
+}
+
+#[allow(dead_code)]
+struct IBluetoothAvrcpControllerDBus {}
+
+#[generate_dbus_exporter(
+    export_bluetooth_avrcp_controller_dbus_intf,
+    "org.chromium.bluetooth.AvrcpController"
+)]
+impl IBluetoothAvrcpController for IBluetoothAvrcpControllerDBus {
+    #[dbus_method("RegisterAvrcpControllerCallback")]
+    fn register_avrcp_controller_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothAvrcpControllerCallback + Send>,
+    ) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetConnectedTargets")]
+    fn get_connected_targets(&self) -> Vec<String> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SendPassthroughCommand")]
+    fn send_passthrough_command(
+        &mut self,
+        address: String,
+        command: AvrcpPassthroughCommand,
+    ) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetMetadata")]
+    fn get_metadata(&self, address: String) -> PlayerMetadata {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetPlayStatus")]
+    fn get_play_status(&self, address: String) -> AvrcpPlayStatus {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetPlayPosition", DBusLog::Disable)]
+    fn get_play_position(&self, address: String) -> u32 {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetPlayLength", DBusLog::Disable)]
+    fn get_play_length(&self, address: String) -> u32 {
+        dbus_generated!()
+    }
+
+    #[dbus_method("RefreshPlayStatus")]
+    fn refresh_play_status(&mut self, address: String) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetPlayerList")]
+    fn get_player_list(&mut self, address: String, start: u32, end: u32) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetNowPlayingList")]
+    fn get_now_playing_list(&mut self, address: String, start: u32, end: u32) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetFolderList")]
+    fn get_folder_list(&mut self, address: String, start: u32, end: u32) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("ChangeFolder")]
+    fn change_folder(&mut self, address: String, uid: Option<u64>) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetBrowsedPlayer")]
+    fn set_browsed_player(&mut self, address: String, player_id: u16) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("SetAddressedPlayer")]
+    fn set_addressed_player(&mut self, address: String, player_id: u16) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("PlayItem")]
+    fn play_item(&mut self, address: String, scope: AvrcpBrowseScope, uid: u64) -> bool {
+        dbus_generated!()
+    }
+}
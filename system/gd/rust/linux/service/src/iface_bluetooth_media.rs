@@ -159,10 +159,13 @@ impl DBusArg for PlayerMetadata {
         return Ok(metadata);
     }
 
-    fn to_dbus(
-        _metadata: PlayerMetadata,
-    ) -> Result<dbus::arg::PropMap, Box<dyn std::error::Error>> {
-        Ok(std::collections::HashMap::new())
+    fn to_dbus(metadata: PlayerMetadata) -> Result<dbus::arg::PropMap, Box<dyn std::error::Error>> {
+        let mut map: dbus::arg::PropMap = std::collections::HashMap::new();
+        map.insert(String::from("title"), dbus::arg::Variant(Box::new(metadata.title)));
+        map.insert(String::from("artist"), dbus::arg::Variant(Box::new(metadata.artist)));
+        map.insert(String::from("album"), dbus::arg::Variant(Box::new(metadata.album)));
+        map.insert(String::from("length_us"), dbus::arg::Variant(Box::new(metadata.length_us)));
+        Ok(map)
     }
 
     fn log(metadata: &PlayerMetadata) -> String {
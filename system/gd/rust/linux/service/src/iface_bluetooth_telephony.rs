@@ -104,4 +104,8 @@ impl IBluetoothTelephony for IBluetoothTelephonyDBus {
     fn audio_disconnect(&mut self, address: String) {
         dbus_generated!()
     }
+    #[dbus_method("StopVoiceRecognition")]
+    fn stop_voice_recognition(&mut self, address: String) -> bool {
+        dbus_generated!()
+    }
 }
@@ -1,8 +1,14 @@
-use crate::dbus_arg::DBusArg;
+use crate::dbus_arg::{DBusArg, DBusArgError};
 
-use btstack::bluetooth_logging::IBluetoothLogging;
+use btstack::bluetooth_logging::{IBluetoothLogging, Level, LogTargetKind};
+use dbus::nonblock::SyncConnection;
 use dbus_macros::{dbus_method, generate_dbus_exporter};
 use dbus_projection::prelude::*;
+use num_traits::{FromPrimitive, ToPrimitive};
+use std::sync::Arc;
+
+impl_dbus_arg_enum!(Level);
+impl_dbus_arg_enum!(LogTargetKind);
 
 struct IBluetoothLoggingDBus {}
 
@@ -17,4 +23,29 @@ impl IBluetoothLogging for IBluetoothLoggingDBus {
     fn set_debug_logging(&mut self, enabled: bool) {
         dbus_generated!()
     }
+
+    #[dbus_method("SetLogLevel")]
+    fn set_log_level(&mut self, kind: LogTargetKind, target: String, level: Level) -> bool {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetLogLevel")]
+    fn get_log_level(&self, kind: LogTargetKind, target: String) -> Level {
+        dbus_generated!()
+    }
+
+    #[dbus_method("ClearLogLevel")]
+    fn clear_log_level(&mut self, kind: LogTargetKind, target: String) {
+        dbus_generated!()
+    }
+
+    #[dbus_method("GetLogBuffer", DBusLog::Disable)]
+    fn get_log_buffer(&self) -> Vec<String> {
+        dbus_generated!()
+    }
+
+    #[dbus_method("ClearLogBuffer")]
+    fn clear_log_buffer(&mut self) {
+        dbus_generated!()
+    }
 }
@@ -17,6 +17,7 @@ use crate::iface_battery_manager;
 use crate::iface_battery_provider_manager;
 use crate::iface_bluetooth;
 use crate::iface_bluetooth_admin;
+use crate::iface_bluetooth_avrcp_controller;
 use crate::iface_bluetooth_gatt;
 use crate::iface_bluetooth_media;
 use crate::iface_bluetooth_qa;
@@ -130,6 +131,13 @@ impl InterfaceManager {
             disconnect_watcher.clone(),
         );
 
+        let avrcp_controller_iface =
+            iface_bluetooth_avrcp_controller::export_bluetooth_avrcp_controller_dbus_intf(
+                conn.clone(),
+                &mut cr.lock().unwrap(),
+                disconnect_watcher.clone(),
+            );
+
         let battery_provider_manager_iface =
             iface_battery_provider_manager::export_battery_provider_manager_dbus_intf(
                 conn.clone(),
@@ -218,6 +226,12 @@ impl InterfaceManager {
                             &[telephony_iface],
                             bluetooth_media.clone(),
                         );
+
+                        cr.lock().unwrap().insert(
+                            Self::make_object_name(virt_index, "avrcp_controller"),
+                            &[avrcp_controller_iface],
+                            bluetooth_media.clone(),
+                        );
                     }
                     BluetoothAPI::Battery => {
                         cr.lock().unwrap().insert(
@@ -17,6 +17,7 @@ use bt_topshim::{btif::get_btinterface, topstack};
 use btstack::{
     battery_manager::BatteryManager,
     battery_provider_manager::BatteryProviderManager,
+    battery_server::BatteryServer,
     battery_service::BatteryService,
     bluetooth::{Bluetooth, IBluetooth, SigData},
     bluetooth_admin::BluetoothAdmin,
@@ -35,6 +36,7 @@ mod iface_battery_manager;
 mod iface_battery_provider_manager;
 mod iface_bluetooth;
 mod iface_bluetooth_admin;
+mod iface_bluetooth_avrcp_controller;
 mod iface_bluetooth_gatt;
 mod iface_bluetooth_media;
 mod iface_bluetooth_qa;
@@ -44,6 +46,8 @@ mod interface_manager;
 
 const DBUS_SERVICE_NAME: &str = "org.chromium.bluetooth";
 const ADMIN_SETTINGS_FILE_PATH: &str = "/var/lib/bluetooth/admin_policy.json";
+const DIS_CONFIG_FILE_PATH: &str = "/etc/bluetooth/device_information.json";
+const MEDIA_KEY_MAP_FILE_PATH: &str = "/etc/bluetooth/media_key_map.json";
 // The maximum ACL disconnect timeout is 3.5s defined by BTA_DM_DISABLE_TIMER_MS
 // and BTA_DM_DISABLE_TIMER_RETRIAL_MS
 const STACK_TURN_OFF_TIMEOUT_MS: Duration = Duration::from_millis(4000);
@@ -87,6 +91,20 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .short("v")
                 .help("Enables VERBOSE and additional tags for debug logging. Use with --debug."),
         )
+        .arg(
+            Arg::with_name("ll-privacy")
+                .long("ll-privacy")
+                .takes_value(true)
+                .possible_values(&["true", "false"])
+                .help("Overrides LL privacy for this adapter"),
+        )
+        .arg(
+            Arg::with_name("config-file")
+                .long("config-file")
+                .value_name("PATH")
+                .takes_value(true)
+                .help("The config file to keep the devices of this adapter in"),
+        )
         .arg(Arg::from_usage("[init-flags] 'Fluoride INIT_ flags'").multiple(true))
         .arg(
             Arg::with_name("log-output")
@@ -128,6 +146,17 @@ fn main() -> Result<(), Box<dyn Error>> {
     // Forward --hci to Fluoride.
     init_flags.push(format!("--hci={}", hci_index));
 
+    // Forward --config-file to Fluoride.
+    if let Some(config_file) = matches.value_of("config-file") {
+        init_flags.push(format!("--config-file={}", config_file));
+    }
+
+    // Forward --ll-privacy to Fluoride. It takes precedence over the sysprops files, which are
+    // shared by all adapters.
+    if let Some(enabled) = matches.value_of("ll-privacy") {
+        init_flags.push(format!("--sysprops=bluetooth.core.gap.le.privacy.enabled:{}", enabled));
+    }
+
     let logging = Arc::new(Mutex::new(Box::new(BluetoothLogging::new(is_debug, log_output))));
     // TODO(b/307171804): Investigate why connecting to unix syslog might fail.
     // Retry it a few times. Ignore the failure if fails too many times.
@@ -169,6 +198,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         tx.clone(),
         intf.clone(),
         battery_provider_manager.clone(),
+        String::from(MEDIA_KEY_MAP_FILE_PATH),
     ))));
     let bluetooth_admin = Arc::new(Mutex::new(Box::new(BluetoothAdmin::new(
         String::from(ADMIN_SETTINGS_FILE_PATH),
@@ -198,8 +228,13 @@ fn main() -> Result<(), Box<dyn Error>> {
     ))));
     let bluetooth_qa = Arc::new(Mutex::new(Box::new(BluetoothQA::new(tx.clone()))));
 
-    let dis =
-        Arc::new(Mutex::new(Box::new(DeviceInformation::new(bluetooth_gatt.clone(), tx.clone()))));
+    let dis = Arc::new(Mutex::new(Box::new(DeviceInformation::new(
+        String::from(DIS_CONFIG_FILE_PATH),
+        bluetooth_gatt.clone(),
+        tx.clone(),
+    ))));
+    let battery_server =
+        Arc::new(Mutex::new(Box::new(BatteryServer::new(bluetooth_gatt.clone(), tx.clone()))));
 
     topstack::get_runtime().block_on(async {
         // Connect to D-Bus system bus.
@@ -223,6 +258,7 @@ fn main() -> Result<(), Box<dyn Error>> {
             battery_service.clone(),
             battery_manager.clone(),
             battery_provider_manager.clone(),
+            battery_server.clone(),
             bluetooth_media.clone(),
             suspend.clone(),
             bt_sock_mgr.clone(),
@@ -259,6 +295,7 @@ fn main() -> Result<(), Box<dyn Error>> {
             let adapter = bluetooth.clone();
             bluetooth_media.lock().unwrap().set_adapter(adapter.clone());
             bluetooth_admin.lock().unwrap().set_adapter(adapter.clone());
+            dis.lock().unwrap().set_adapter(adapter.clone());
 
             let mut bluetooth = bluetooth.lock().unwrap();
             bluetooth.init(init_flags);
@@ -0,0 +1,517 @@
+//! Exports the host battery level through a Battery Service (BAS) on the GATT server.
+
+use log;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
+
+use crate::battery_service::CHARACTERISTIC_BATTERY_LEVEL;
+use crate::bluetooth_gatt::{
+    value_at_offset, BluetoothGatt, BluetoothGattCharacteristic, BluetoothGattDescriptor,
+    BluetoothGattService, GattDbElementType, IBluetoothGatt, IBluetoothGattServerCallback,
+};
+use crate::uuid::{self, UuidHelper};
+use crate::{Message, RPCProxy};
+use bt_topshim::btif::Uuid128Bit;
+use bt_topshim::profiles::gatt::{GattStatus, LePhy};
+
+/// Random uuid generated for registering against gatt server.
+const BAS_APP_RANDOM_UUID: &str = "5d3a5a5c-8a0b-4d5e-9f3e-6c7b0e2a41f1";
+
+/// UUID for the Client Characteristic Configuration descriptor.
+const CCC_DESCRIPTOR_UUID: &str = "00002902-0000-1000-8000-00805F9B34FB";
+
+/// Enables notifications in the Client Characteristic Configuration value.
+const CCC_NOTIFICATION_ENABLED: u8 = 0x01;
+
+/// Where the kernel exposes power supplies, including the host battery.
+const POWER_SUPPLY_PATH: &str = "/sys/class/power_supply";
+
+/// How often the host battery level is checked for changes to notify.
+const BATTERY_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Handles exporting the host battery level as a Battery Service.
+pub struct BatteryServer {
+    /// Reference to Gatt server implementation to export service.
+    bluetooth_gatt: Arc<Mutex<Box<BluetoothGatt>>>,
+
+    /// Server id (available once we are registered).
+    gatt_server_id: Option<i32>,
+
+    /// Handle for the Battery Level characteristic.
+    battery_level_handle: Option<i32>,
+
+    /// Handle for the Client Characteristic Configuration descriptor of the Battery Level.
+    ccc_handle: Option<i32>,
+
+    /// Remote devices that enabled battery level notifications.
+    subscribers: HashSet<String>,
+
+    /// Battery level last read from the host.
+    battery_level: Option<u8>,
+
+    /// Task that periodically checks the host battery level.
+    poll_task: Option<JoinHandle<()>>,
+
+    /// Sender for stack mainloop.
+    tx: Sender<Message>,
+}
+
+impl BatteryServer {
+    pub fn new(bluetooth_gatt: Arc<Mutex<Box<BluetoothGatt>>>, tx: Sender<Message>) -> Self {
+        Self {
+            bluetooth_gatt,
+            gatt_server_id: None,
+            battery_level_handle: None,
+            ccc_handle: None,
+            subscribers: HashSet::new(),
+            battery_level: None,
+            poll_task: None,
+            tx,
+        }
+    }
+
+    pub(crate) fn initialize(&mut self) {
+        // Hosts without a battery, e.g. desktops, have nothing to export.
+        self.battery_level = read_host_battery_level();
+        if self.battery_level.is_none() {
+            log::info!("No host battery found, not exporting BAS");
+            return;
+        }
+
+        let callback = Box::new(BatteryServerCallbacks::new(self.tx.clone()));
+
+        // First register for callbacks with the server.
+        self.bluetooth_gatt.lock().unwrap().register_server(
+            BAS_APP_RANDOM_UUID.to_string(),
+            callback,
+            /*eatt_support=*/ true,
+        );
+    }
+
+    pub(crate) fn handle_action(&mut self, action: &BatteryServerActions) {
+        match action {
+            BatteryServerActions::Registered(status, server_id) => {
+                if status != &GattStatus::Success {
+                    log::error!("BAS failed to register callbacks. Status={:?}", status);
+                    return;
+                }
+
+                self.gatt_server_id = Some(*server_id);
+
+                // Construct and add Battery service.
+                let mut service = BluetoothGattService::new(
+                    UuidHelper::from_string(uuid::BAS).expect("BAS uuid is malformed"),
+                    /*instance_id=*/ 0,
+                    GattDbElementType::PrimaryService.into(),
+                );
+
+                let mut battery_level = BluetoothGattCharacteristic::new(
+                    battery_level_uuid(),
+                    /*instance_id=*/ 0,
+                    BluetoothGattCharacteristic::PROPERTY_READ
+                        | BluetoothGattCharacteristic::PROPERTY_NOTIFY,
+                    BluetoothGattCharacteristic::PERMISSION_READ,
+                );
+                battery_level.descriptors.push(BluetoothGattDescriptor::new(
+                    UuidHelper::from_string(CCC_DESCRIPTOR_UUID).expect("CCC uuid is malformed"),
+                    /*instance_id=*/ 0,
+                    BluetoothGattCharacteristic::PERMISSION_READ
+                        | BluetoothGattCharacteristic::PERMISSION_WRITE,
+                ));
+                service.characteristics.push(battery_level);
+
+                self.bluetooth_gatt.lock().unwrap().add_service(*server_id, service);
+            }
+
+            BatteryServerActions::ServiceAdded(status, service) => {
+                if status != &GattStatus::Success {
+                    return;
+                }
+
+                let battery_level_uuid = battery_level_uuid();
+                let ccc_uuid =
+                    UuidHelper::from_string(CCC_DESCRIPTOR_UUID).expect("CCC uuid is malformed");
+
+                // Find the Battery Level characteristic we inserted before and store the handles
+                // for it and its configuration descriptor.
+                for characteristic in &service.characteristics {
+                    if characteristic.uuid == battery_level_uuid {
+                        self.battery_level_handle = Some(characteristic.instance_id);
+                        self.ccc_handle = characteristic
+                            .descriptors
+                            .iter()
+                            .find(|descriptor| descriptor.uuid == ccc_uuid)
+                            .map(|descriptor| descriptor.instance_id);
+                    }
+                }
+
+                self.start_polling();
+            }
+
+            BatteryServerActions::OnCharacteristicReadRequest(
+                addr,
+                trans_id,
+                offset,
+                _is_long,
+                handle,
+            ) => {
+                if self.battery_level_handle != Some(*handle) {
+                    return;
+                }
+
+                self.battery_level = read_host_battery_level().or(self.battery_level);
+                let result = match self.battery_level {
+                    Some(level) => value_at_offset(vec![level], *offset),
+                    None => Err(GattStatus::Error),
+                };
+                self.send_read_response(addr, *trans_id, *offset, result);
+            }
+
+            BatteryServerActions::OnDescriptorReadRequest(
+                addr,
+                trans_id,
+                offset,
+                _is_long,
+                handle,
+            ) => {
+                if self.ccc_handle != Some(*handle) {
+                    return;
+                }
+
+                let value = if self.subscribers.contains(addr) {
+                    vec![CCC_NOTIFICATION_ENABLED, 0]
+                } else {
+                    vec![0, 0]
+                };
+                self.send_read_response(addr, *trans_id, *offset, value_at_offset(value, *offset));
+            }
+
+            BatteryServerActions::OnDescriptorWriteRequest(
+                addr,
+                trans_id,
+                offset,
+                need_rsp,
+                handle,
+                value,
+            ) => {
+                if self.ccc_handle != Some(*handle) {
+                    return;
+                }
+
+                if value.first().map_or(false, |v| v & CCC_NOTIFICATION_ENABLED != 0) {
+                    self.subscribers.insert(addr.clone());
+                } else {
+                    self.subscribers.remove(addr);
+                }
+
+                if *need_rsp {
+                    self.send_response(
+                        addr,
+                        *trans_id,
+                        GattStatus::Success,
+                        *offset,
+                        value.clone(),
+                    );
+                }
+            }
+
+            BatteryServerActions::OnServerConnectionState(addr, connected) => {
+                // Notifications sent to a disconnected client would go to a stale connection.
+                if !connected {
+                    self.subscribers.remove(addr);
+                }
+            }
+
+            BatteryServerActions::PollBatteryLevel => {
+                let level = match read_host_battery_level() {
+                    Some(level) => level,
+                    None => return,
+                };
+                if self.battery_level == Some(level) {
+                    return;
+                }
+                self.battery_level = Some(level);
+
+                if let (Some(server_id), Some(handle)) =
+                    (self.gatt_server_id, self.battery_level_handle)
+                {
+                    for addr in self.subscribers.iter() {
+                        self.bluetooth_gatt.lock().unwrap().send_notification(
+                            server_id,
+                            addr.clone(),
+                            handle,
+                            /*confirm=*/ false,
+                            vec![level],
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn send_response(
+        &self,
+        addr: &String,
+        trans_id: i32,
+        status: GattStatus,
+        offset: i32,
+        value: Vec<u8>,
+    ) {
+        if let Some(server_id) = self.gatt_server_id {
+            self.bluetooth_gatt.lock().unwrap().send_response(
+                server_id,
+                addr.clone(),
+                trans_id,
+                status,
+                offset,
+                value,
+            );
+        }
+    }
+
+    fn send_read_response(
+        &self,
+        addr: &String,
+        trans_id: i32,
+        offset: i32,
+        result: Result<Vec<u8>, GattStatus>,
+    ) {
+        match result {
+            Ok(value) => self.send_response(addr, trans_id, GattStatus::Success, offset, value),
+            Err(status) => self.send_response(addr, trans_id, status, offset, vec![]),
+        }
+    }
+
+    fn start_polling(&mut self) {
+        if self.poll_task.is_some() {
+            return;
+        }
+
+        let txl = self.tx.clone();
+        self.poll_task = Some(tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(BATTERY_POLL_INTERVAL).await;
+                let _ =
+                    txl.send(Message::BatteryServer(BatteryServerActions::PollBatteryLevel)).await;
+            }
+        }));
+    }
+}
+
+impl Drop for BatteryServer {
+    fn drop(&mut self) {
+        if let Some(task) = self.poll_task.take() {
+            task.abort();
+        }
+    }
+}
+
+fn battery_level_uuid() -> Uuid128Bit {
+    UuidHelper::parse_string(CHARACTERISTIC_BATTERY_LEVEL)
+        .expect("Battery Level uuid is malformed")
+        .uu
+}
+
+/// Reads the charge percentage of the host battery from sysfs.
+fn read_host_battery_level() -> Option<u8> {
+    read_battery_level_in(Path::new(POWER_SUPPLY_PATH))
+}
+
+/// Reads the charge percentage of the first battery in a power supply class directory. Batteries
+/// of peripherals, which the kernel reports with a "Device" scope, are skipped.
+fn read_battery_level_in(power_supply_path: &Path) -> Option<u8> {
+    let read_attr = |supply: &Path, attr: &str| {
+        fs::read_to_string(supply.join(attr)).ok().map(|v| v.trim().to_string())
+    };
+
+    fs::read_dir(power_supply_path).ok()?.filter_map(|entry| entry.ok()).find_map(|entry| {
+        let supply = entry.path();
+        if read_attr(&supply, "type")? != "Battery"
+            || read_attr(&supply, "scope").map_or(false, |scope| scope == "Device")
+        {
+            return None;
+        }
+        read_attr(&supply, "capacity")?.parse::<u8>().ok().map(|level| level.min(100))
+    })
+}
+
+// Callbacks and actions we need to handle for BAS.
+pub enum BatteryServerActions {
+    Registered(GattStatus, i32),
+    ServiceAdded(GattStatus, BluetoothGattService),
+    /// Params: addr, trans_id, offset, is_long, handle
+    OnCharacteristicReadRequest(String, i32, i32, bool, i32),
+    /// Params: addr, trans_id, offset, is_long, handle
+    OnDescriptorReadRequest(String, i32, i32, bool, i32),
+    /// Params: addr, trans_id, offset, need_rsp, handle, value
+    OnDescriptorWriteRequest(String, i32, i32, bool, i32, Vec<u8>),
+    /// Params: addr, connected
+    OnServerConnectionState(String, bool),
+    PollBatteryLevel,
+}
+
+// Handle callbacks for BAS to register
+struct BatteryServerCallbacks {
+    // Sender to the main loop
+    tx: Sender<Message>,
+}
+
+impl BatteryServerCallbacks {
+    fn new(tx: Sender<Message>) -> Self {
+        Self { tx }
+    }
+
+    fn send(&self, action: BatteryServerActions) {
+        let txl = self.tx.clone();
+        tokio::spawn(async move {
+            let _ = txl.send(Message::BatteryServer(action)).await;
+        });
+    }
+}
+
+impl IBluetoothGattServerCallback for BatteryServerCallbacks {
+    fn on_server_registered(&mut self, status: GattStatus, server_id: i32) {
+        self.send(BatteryServerActions::Registered(status, server_id));
+    }
+
+    fn on_service_added(&mut self, status: GattStatus, service: BluetoothGattService) {
+        self.send(BatteryServerActions::ServiceAdded(status, service));
+    }
+
+    fn on_characteristic_read_request(
+        &mut self,
+        addr: String,
+        trans_id: i32,
+        offset: i32,
+        is_long: bool,
+        handle: i32,
+    ) {
+        self.send(BatteryServerActions::OnCharacteristicReadRequest(
+            addr, trans_id, offset, is_long, handle,
+        ));
+    }
+
+    fn on_descriptor_read_request(
+        &mut self,
+        addr: String,
+        trans_id: i32,
+        offset: i32,
+        is_long: bool,
+        handle: i32,
+    ) {
+        self.send(BatteryServerActions::OnDescriptorReadRequest(
+            addr, trans_id, offset, is_long, handle,
+        ));
+    }
+
+    fn on_descriptor_write_request(
+        &mut self,
+        addr: String,
+        trans_id: i32,
+        offset: i32,
+        _len: i32,
+        _is_prep: bool,
+        need_rsp: bool,
+        handle: i32,
+        value: Vec<u8>,
+    ) {
+        self.send(BatteryServerActions::OnDescriptorWriteRequest(
+            addr, trans_id, offset, need_rsp, handle, value,
+        ));
+    }
+
+    fn on_server_connection_state(&mut self, _server_id: i32, connected: bool, addr: String) {
+        self.send(BatteryServerActions::OnServerConnectionState(addr, connected));
+    }
+
+    // Remaining callbacks are unhandled
+
+    fn on_service_removed(&mut self, _status: GattStatus, _handle: i32) {}
+    fn on_characteristic_write_request(
+        &mut self,
+        _addr: String,
+        _trans_id: i32,
+        _offset: i32,
+        _len: i32,
+        _is_prep: bool,
+        _need_rsp: bool,
+        _handle: i32,
+        _value: Vec<u8>,
+    ) {
+    }
+    fn on_execute_write(&mut self, _addr: String, _trans_id: i32, _exec_write: bool) {}
+    fn on_notification_sent(&mut self, _addr: String, _status: GattStatus) {}
+    fn on_mtu_changed(&mut self, _addr: String, _mtu: i32) {}
+    fn on_phy_update(
+        &mut self,
+        _addr: String,
+        _tx_phy: LePhy,
+        _rx_phy: LePhy,
+        _status: GattStatus,
+    ) {
+    }
+    fn on_phy_read(&mut self, _addr: String, _tx_phy: LePhy, _rx_phy: LePhy, _status: GattStatus) {}
+    fn on_connection_updated(
+        &mut self,
+        _addr: String,
+        _interval: i32,
+        _latency: i32,
+        _timeout: i32,
+        _status: GattStatus,
+    ) {
+    }
+    fn on_subrate_change(
+        &mut self,
+        _addr: String,
+        _subrate_factor: i32,
+        _latency: i32,
+        _cont_num: i32,
+        _timeout: i32,
+        _status: GattStatus,
+    ) {
+    }
+}
+
+impl RPCProxy for BatteryServerCallbacks {
+    fn get_object_id(&self) -> String {
+        "BAS Gatt Server Callback".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_battery_level() {
+        let root =
+            std::env::temp_dir().join(format!("floss_battery_server_test_{}", std::process::id()));
+        let supply = |name: &str, attrs: &[(&str, &str)]| {
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            for (attr, value) in attrs {
+                fs::write(dir.join(attr), format!("{}\n", value)).unwrap();
+            }
+        };
+
+        // No power supply directory at all.
+        assert_eq!(read_battery_level_in(&root), None);
+
+        // Mains and peripheral batteries are not the host battery.
+        supply("AC", &[("type", "Mains")]);
+        supply("hid-mouse-battery", &[("type", "Battery"), ("scope", "Device"), ("capacity", "5")]);
+        assert_eq!(read_battery_level_in(&root), None);
+
+        supply("BAT0", &[("type", "Battery"), ("capacity", "150")]);
+        assert_eq!(read_battery_level_in(&root), Some(100));
+        supply("BAT0", &[("type", "Battery"), ("scope", "System"), ("capacity", "42")]);
+        assert_eq!(read_battery_level_in(&root), Some(42));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
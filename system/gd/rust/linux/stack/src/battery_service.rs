@@ -24,6 +24,25 @@ use tokio::sync::mpsc::Sender;
 /// specification.
 pub const CHARACTERISTIC_BATTERY_LEVEL: &str = "00002A1-9000-0100-0800-000805F9B34FB";
 
+/// The UUID corresponding to the Characteristic Presentation Format descriptor, which BAS uses to
+/// tell apart the batteries of a device with multiple BAS instances.
+pub const DESCRIPTOR_PRESENTATION_FORMAT: &str = "00002904-0000-1000-8000-00805F9B34FB";
+
+/// Namespace of the description values assigned by the Bluetooth SIG in a Presentation Format.
+const PRESENTATION_FORMAT_NAMESPACE_BT_SIG: u8 = 0x01;
+
+/// A Battery Level characteristic from one of the BAS instances of a remote device.
+struct BatteryLevelCharacteristic {
+    /// Handle of the Battery Level characteristic.
+    handle: i32,
+    /// Handle of the Presentation Format descriptor, if the characteristic has one.
+    presentation_format_handle: Option<i32>,
+    /// Last read or notified battery level.
+    level: Option<u32>,
+    /// Description of the battery taken from the Presentation Format, e.g. "left".
+    variant: String,
+}
+
 /// Represents the Floss BatteryService implementation.
 pub struct BatteryService {
     gatt: Arc<Mutex<Box<BluetoothGatt>>>,
@@ -38,9 +57,9 @@ pub struct BatteryService {
     client_id: Option<i32>,
     /// Cached battery info keyed by remote device.
     battery_sets: HashMap<String, BatterySet>,
-    /// Found handles for battery levels. Required for faster
-    /// refreshes than initiating another search.
-    handles: HashMap<String, i32>,
+    /// Found battery level characteristics of all BAS instances, keyed by remote device. Required
+    /// for faster refreshes than initiating another search.
+    handles: HashMap<String, Vec<BatteryLevelCharacteristic>>,
 }
 
 /// Enum for GATT callbacks to relay messages to the main processing thread. Newly supported
@@ -54,6 +73,8 @@ pub enum BatteryServiceActions {
     OnSearchComplete(String, Vec<BluetoothGattService>, GattStatus),
     /// Params: addr, status, handle, value
     OnCharacteristicRead(String, GattStatus, i32, Vec<u8>),
+    /// Params: addr, status, handle, value
+    OnDescriptorRead(String, GattStatus, i32, Vec<u8>),
     /// Params: addr, handle, value
     OnNotify(String, i32, Vec<u8>),
     /// Params: remote_device, transport
@@ -161,74 +182,115 @@ impl BatteryService {
                     debug!("GATT service discovery for {} failed with status {:?}", addr, status);
                     return;
                 }
-                let (bas_uuid, battery_level_uuid) = match (
+                let (bas_uuid, battery_level_uuid, presentation_format_uuid) = match (
                     UuidHelper::parse_string(uuid::BAS),
                     UuidHelper::parse_string(CHARACTERISTIC_BATTERY_LEVEL),
+                    UuidHelper::parse_string(DESCRIPTOR_PRESENTATION_FORMAT),
                 ) {
-                    (Some(bas_uuid), Some(battery_level_uuid)) => (bas_uuid, battery_level_uuid),
-                    _ => return,
-                };
-                // TODO(b/233101174): handle multiple instances of BAS
-                let bas = match services.iter().find(|service| service.uuid == bas_uuid.uu) {
-                    Some(bas) => bas,
-                    None => {
-                        self.callbacks.for_all_callbacks(|callback| {
-                            callback.on_battery_service_status_updated(
-                                addr.clone(),
-                                BatteryServiceStatus::BatteryServiceNotSupported,
-                            )
-                        });
-                        return;
+                    (Some(bas_uuid), Some(battery_level_uuid), Some(presentation_format_uuid)) => {
+                        (bas_uuid, battery_level_uuid, presentation_format_uuid)
                     }
+                    _ => return,
                 };
-                let battery_level = match bas
-                    .characteristics
+                let bas_instances = services
                     .iter()
-                    .find(|characteristic| characteristic.uuid == battery_level_uuid.uu)
-                {
-                    Some(battery_level) => battery_level,
-                    None => {
-                        debug!("Device {} has no BatteryLevel characteristic", addr);
-                        return;
-                    }
+                    .filter(|service| service.uuid == bas_uuid.uu)
+                    .collect::<Vec<_>>();
+                if bas_instances.is_empty() {
+                    self.callbacks.for_all_callbacks(|callback| {
+                        callback.on_battery_service_status_updated(
+                            addr.clone(),
+                            BatteryServiceStatus::BatteryServiceNotSupported,
+                        )
+                    });
+                    return;
+                }
+                // Devices with multiple batteries, e.g. earbuds and their case, expose one BAS
+                // instance per battery, each with a Presentation Format describing the battery.
+                let battery_levels = bas_instances
+                    .iter()
+                    .filter_map(|bas| {
+                        bas.characteristics
+                            .iter()
+                            .find(|characteristic| characteristic.uuid == battery_level_uuid.uu)
+                    })
+                    .map(|battery_level| BatteryLevelCharacteristic {
+                        handle: battery_level.instance_id,
+                        presentation_format_handle: battery_level
+                            .descriptors
+                            .iter()
+                            .find(|descriptor| descriptor.uuid == presentation_format_uuid.uu)
+                            .map(|descriptor| descriptor.instance_id),
+                        level: None,
+                        variant: "".to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                let battery_levels = match self.handles.get(&addr) {
+                    Some(cached) => carry_over_levels(cached, battery_levels),
+                    None => battery_levels,
                 };
+                if battery_levels.is_empty() {
+                    debug!("Device {} has no BatteryLevel characteristic", addr);
+                    return;
+                }
                 let client_id = match self.client_id {
                     Some(id) => id,
                     None => return,
                 };
-                let handle = battery_level.instance_id;
-                self.handles.insert(addr.clone(), handle.clone());
-                self.gatt.lock().unwrap().register_for_notification(
-                    client_id,
-                    addr.clone(),
-                    handle,
-                    true,
-                );
-                if let None = self.battery_sets.get(&addr) {
+                // Cached levels are kept until the fresh reads below arrive.
+                for battery_level in battery_levels.iter() {
+                    self.gatt.lock().unwrap().register_for_notification(
+                        client_id,
+                        addr.clone(),
+                        battery_level.handle,
+                        true,
+                    );
+                    if let Some(handle) = battery_level.presentation_format_handle {
+                        self.gatt.lock().unwrap().read_descriptor(
+                            client_id,
+                            addr.clone(),
+                            handle,
+                            0,
+                        );
+                    }
                     self.gatt.lock().unwrap().read_characteristic(
                         client_id,
-                        addr,
-                        battery_level.instance_id,
+                        addr.clone(),
+                        battery_level.handle,
                         0,
                     );
                 }
+                self.handles.insert(addr, battery_levels);
             }
 
-            BatteryServiceActions::OnCharacteristicRead(addr, status, _handle, value) => {
+            BatteryServiceActions::OnCharacteristicRead(addr, status, handle, value) => {
                 if status != GattStatus::Success {
                     return;
                 }
-                let battery_info = self.set_battery_info(&addr, &value);
-                self.callbacks.for_all_callbacks(|callback| {
-                    callback.on_battery_info_updated(addr.clone(), battery_info.clone());
-                });
+                if let Some(battery_info) = self.set_battery_level(&addr, handle, &value) {
+                    self.callbacks.for_all_callbacks(|callback| {
+                        callback.on_battery_info_updated(addr.clone(), battery_info.clone());
+                    });
+                }
+            }
+
+            BatteryServiceActions::OnDescriptorRead(addr, status, handle, value) => {
+                if status != GattStatus::Success {
+                    return;
+                }
+                if let Some(battery_info) = self.set_battery_variant(&addr, handle, &value) {
+                    self.callbacks.for_all_callbacks(|callback| {
+                        callback.on_battery_info_updated(addr.clone(), battery_info.clone());
+                    });
+                }
             }
 
-            BatteryServiceActions::OnNotify(addr, _handle, value) => {
-                let battery_info = self.set_battery_info(&addr, &value);
-                self.callbacks.for_all_callbacks(|callback| {
-                    callback.on_battery_info_updated(addr.clone(), battery_info.clone());
-                });
+            BatteryServiceActions::OnNotify(addr, handle, value) => {
+                if let Some(battery_info) = self.set_battery_level(&addr, handle, &value) {
+                    self.callbacks.for_all_callbacks(|callback| {
+                        callback.on_battery_info_updated(addr.clone(), battery_info.clone());
+                    });
+                }
             }
 
             BatteryServiceActions::Connect(device, transport) => {
@@ -244,23 +306,65 @@ impl BatteryService {
         }
     }
 
-    fn set_battery_info(&mut self, remote_address: &String, value: &Vec<u8>) -> BatterySet {
+    /// Updates the level of the battery with the given Battery Level handle. Returns the new
+    /// battery info of the device if the handle is known.
+    fn set_battery_level(
+        &mut self,
+        remote_address: &String,
+        handle: i32,
+        value: &Vec<u8>,
+    ) -> Option<BatterySet> {
         let level: Vec<_> = value.iter().cloned().chain(iter::repeat(0 as u8)).take(4).collect();
         let level = u32::from_le_bytes(level.try_into().unwrap());
         debug!("BAS received battery level for {}: {}", remote_address.clone(), level);
-        let battery_set = self.battery_sets.entry(remote_address.clone()).or_insert_with(|| {
-            BatterySet::new(
-                remote_address.clone(),
-                uuid::BAS.to_string(),
-                "BAS".to_string(),
-                vec![Battery { percentage: level, variant: "".to_string() }],
-            )
-        });
+        let battery_level = self
+            .handles
+            .get_mut(remote_address)?
+            .iter_mut()
+            .find(|battery_level| battery_level.handle == handle)?;
+        battery_level.level = Some(level);
+        self.set_battery_info(remote_address)
+    }
+
+    /// Updates the description of the battery with the given Presentation Format handle. Returns
+    /// the new battery info of the device if the level of that battery is already known.
+    fn set_battery_variant(
+        &mut self,
+        remote_address: &String,
+        handle: i32,
+        value: &Vec<u8>,
+    ) -> Option<BatterySet> {
+        let variant = parse_presentation_format_description(value)?;
+        debug!("BAS received battery description for {}: {}", remote_address.clone(), variant);
+        let battery_level = self
+            .handles
+            .get_mut(remote_address)?
+            .iter_mut()
+            .find(|battery_level| battery_level.presentation_format_handle == Some(handle))?;
+        battery_level.variant = variant;
+        battery_level.level?;
+        self.set_battery_info(remote_address)
+    }
+
+    /// Rebuilds the BatterySet of the device from the levels read so far and reports it to the
+    /// BatteryProviderManager.
+    fn set_battery_info(&mut self, remote_address: &String) -> Option<BatterySet> {
+        let batteries = batteries_from_levels(self.handles.get(remote_address)?);
+        if batteries.is_empty() {
+            return None;
+        }
+        let battery_set = BatterySet::new(
+            remote_address.clone(),
+            uuid::BAS.to_string(),
+            "BAS".to_string(),
+            batteries,
+        );
+        self.battery_sets.insert(remote_address.clone(), battery_set.clone());
         self.battery_provider_manager
             .lock()
             .unwrap()
             .set_battery_info(self.battery_provider_id, battery_set.clone());
-        battery_set.clone()
+        Some(battery_set)
     }
 
     fn init_device(&self, remote_address: String, transport: BtTransport) {
@@ -308,11 +412,18 @@ impl BatteryService {
             Some(id) => id,
             None => return false,
         };
-        let handle = match self.handles.get(&remote_address) {
-            Some(id) => *id,
+        let battery_levels = match self.handles.get(&remote_address) {
+            Some(battery_levels) => battery_levels,
             None => return false,
         };
-        self.gatt.lock().unwrap().read_characteristic(client_id, remote_address.clone(), handle, 0);
+        for battery_level in battery_levels {
+            self.gatt.lock().unwrap().read_characteristic(
+                client_id,
+                remote_address.clone(),
+                battery_level.handle,
+                0,
+            );
+        }
         true
     }
 
@@ -322,6 +433,64 @@ impl BatteryService {
     }
 }
 
+/// Combines the Battery Level characteristics of all BAS instances of a device into batteries.
+/// Characteristics whose level is not known yet are skipped.
+fn batteries_from_levels(battery_levels: &[BatteryLevelCharacteristic]) -> Vec<Battery> {
+    battery_levels
+        .iter()
+        .filter_map(|battery_level| {
+            battery_level
+                .level
+                .map(|percentage| Battery { percentage, variant: battery_level.variant.clone() })
+        })
+        .collect()
+}
+
+/// Copies the known levels and descriptions of cached Battery Level characteristics onto the
+/// freshly discovered ones with the same handle.
+fn carry_over_levels(
+    cached: &[BatteryLevelCharacteristic],
+    mut battery_levels: Vec<BatteryLevelCharacteristic>,
+) -> Vec<BatteryLevelCharacteristic> {
+    for battery_level in battery_levels.iter_mut() {
+        if let Some(old) = cached.iter().find(|old| old.handle == battery_level.handle) {
+            battery_level.level = old.level;
+            battery_level.variant = old.variant.clone();
+        }
+    }
+    battery_levels
+}
+
+/// Returns the battery description from a Characteristic Presentation Format value, if it is one
+/// of the descriptions assigned by the Bluetooth SIG.
+fn parse_presentation_format_description(value: &Vec<u8>) -> Option<String> {
+    // Presentation Format: format (1), exponent (1), unit (2), namespace (1), description (2).
+    if value.len() < 7 || value[4] != PRESENTATION_FORMAT_NAMESPACE_BT_SIG {
+        return None;
+    }
+    let description = match u16::from_le_bytes([value[5], value[6]]) {
+        0x0100 => "front",
+        0x0101 => "back",
+        0x0102 => "top",
+        0x0103 => "bottom",
+        0x0104 => "upper",
+        0x0105 => "lower",
+        0x0106 => "main",
+        0x0107 => "backup",
+        0x0108 => "auxiliary",
+        0x0109 => "supplementary",
+        0x010A => "flash",
+        0x010B => "inside",
+        0x010C => "outside",
+        0x010D => "left",
+        0x010E => "right",
+        0x010F => "internal",
+        0x0110 => "external",
+        _ => return None,
+    };
+    Some(description.to_string())
+}
+
 /// Status enum for relaying the state of BAS or a particular device.
 pub enum BatteryServiceStatus {
     /// Device does not report support for BAS.
@@ -476,11 +645,19 @@ impl IBluetoothGattCallback for GattCallback {
 
     fn on_descriptor_read(
         &mut self,
-        _addr: String,
-        _status: GattStatus,
-        _handle: i32,
-        _value: Vec<u8>,
+        addr: String,
+        status: GattStatus,
+        handle: i32,
+        value: Vec<u8>,
     ) {
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let _ = tx
+                .send(Message::BatteryService(BatteryServiceActions::OnDescriptorRead(
+                    addr, status, handle, value,
+                )))
+                .await;
+        });
     }
 
     fn on_descriptor_write(&mut self, _addr: String, _status: GattStatus, _handle: i32) {}
@@ -507,3 +684,66 @@ impl RPCProxy for GattCallback {
         "BAS Gatt Callback".to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn battery_level(handle: i32, level: Option<u32>, variant: &str) -> BatteryLevelCharacteristic {
+        BatteryLevelCharacteristic {
+            handle,
+            presentation_format_handle: Some(handle + 1),
+            level,
+            variant: variant.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_presentation_format_description() {
+        // uint8, exponent 0, unit percentage, Bluetooth SIG namespace, description "left".
+        let left = vec![0x04, 0x00, 0xad, 0x27, 0x01, 0x0d, 0x01];
+        assert_eq!(parse_presentation_format_description(&left), Some("left".to_string()));
+
+        let mut right = left.clone();
+        right[5] = 0x0e;
+        assert_eq!(parse_presentation_format_description(&right), Some("right".to_string()));
+
+        // Unknown descriptions, other namespaces and truncated values have no description.
+        let mut unknown = left.clone();
+        unknown[5] = 0xff;
+        assert_eq!(parse_presentation_format_description(&unknown), None);
+        let mut vendor = left.clone();
+        vendor[4] = 0x02;
+        assert_eq!(parse_presentation_format_description(&vendor), None);
+        assert_eq!(parse_presentation_format_description(&left[..6].to_vec()), None);
+    }
+
+    #[test]
+    fn test_batteries_from_multiple_instances() {
+        let levels = vec![
+            battery_level(0x10, Some(80), "left"),
+            battery_level(0x20, None, "right"),
+            battery_level(0x30, Some(35), ""),
+        ];
+        let batteries = batteries_from_levels(&levels);
+        assert_eq!(batteries.len(), 2);
+        assert_eq!((batteries[0].percentage, batteries[0].variant.as_str()), (80, "left"));
+        assert_eq!((batteries[1].percentage, batteries[1].variant.as_str()), (35, ""));
+
+        assert!(batteries_from_levels(&[battery_level(0x10, None, "left")]).is_empty());
+    }
+
+    #[test]
+    fn test_carry_over_levels() {
+        let cached = vec![battery_level(0x10, Some(80), "left"), battery_level(0x20, None, "")];
+        let discovered = vec![
+            battery_level(0x10, None, ""),
+            battery_level(0x20, None, ""),
+            battery_level(0x30, None, ""),
+        ];
+        let levels = carry_over_levels(&cached, discovered);
+        assert_eq!((levels[0].level, levels[0].variant.as_str()), (Some(80), "left"));
+        assert_eq!(levels[1].level, None);
+        assert_eq!(levels[2].level, None);
+    }
+}
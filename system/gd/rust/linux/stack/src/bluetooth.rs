@@ -41,12 +41,13 @@ use tokio::task::JoinHandle;
 use tokio::time;
 
 use crate::battery_service::BatteryServiceActions;
-use crate::bluetooth_admin::{BluetoothAdmin, IBluetoothAdmin};
+use crate::bluetooth_admin::{BluetoothAdmin, IBluetoothAdmin, PolicyBlockReason};
 use crate::bluetooth_gatt::{
     BluetoothGatt, GattActions, IBluetoothGatt, IScannerCallback, ScanResult,
 };
 use crate::bluetooth_media::{BluetoothMedia, IBluetoothMedia, MediaActions};
 use crate::callbacks::Callbacks;
+use crate::dis::DeviceInformationValues;
 use crate::socket_manager::SocketActions;
 use crate::uuid::{Profile, UuidHelper, HOGP};
 use crate::{APIMessage, BluetoothAPI, Message, RPCProxy, SuspendMode};
@@ -199,6 +200,9 @@ pub trait IBluetooth {
     /// Get the address type of the remote device.
     fn get_remote_address_type(&self, device: BluetoothDevice) -> BtAddrType;
 
+    /// Gets the Device Information Service values read from the remote device.
+    fn get_remote_device_information(&self, device: BluetoothDevice) -> DeviceInformationValues;
+
     /// Get the RSSI of the remote device.
     fn get_remote_rssi(&self, device: BluetoothDevice) -> i8;
 
@@ -343,6 +347,9 @@ struct BluetoothDeviceContext {
     /// If supported UUIDs weren't available in EIR, wait for services to be
     /// resolved to connect.
     pub wait_to_connect: bool,
+
+    /// Device Information Service values read from the remote device.
+    pub device_information: DeviceInformationValues,
 }
 
 impl BluetoothDeviceContext {
@@ -362,6 +369,7 @@ impl BluetoothDeviceContext {
             properties: HashMap::new(),
             services_resolved: false,
             wait_to_connect: false,
+            device_information: DeviceInformationValues::default(),
         };
         device.update_properties(&properties);
         device
@@ -392,6 +400,26 @@ impl BluetoothDeviceContext {
     pub(crate) fn seen(&mut self) {
         self.last_seen = Instant::now();
     }
+
+    /// Stores the Device Information Service values of the device. A PNP ID is only used as the
+    /// vendor and product info if it isn't already known, e.g. from SDP. Returns the properties
+    /// that changed.
+    pub(crate) fn set_device_information(
+        &mut self,
+        values: DeviceInformationValues,
+        pnp_id: Option<BtVendorProductInfo>,
+    ) -> Vec<BluetoothProperty> {
+        self.device_information = values;
+
+        let properties = match pnp_id {
+            Some(vpi) if !self.properties.contains_key(&BtPropertyType::VendorProductInfo) => {
+                vec![BluetoothProperty::VendorProductInfo(vpi)]
+            }
+            _ => vec![],
+        };
+        self.update_properties(&properties);
+        properties
+    }
 }
 
 /// Structure to track all the signals for SIGTERM.
@@ -415,6 +443,13 @@ pub trait IBluetoothCallback: RPCProxy {
         props: Vec<BtPropertyType>,
     );
 
+    /// When the Device Information Service values of a device change.
+    fn on_device_information_changed(
+        &mut self,
+        remote_device: BluetoothDevice,
+        values: DeviceInformationValues,
+    );
+
     /// When any of the adapter local address is changed.
     fn on_address_changed(&mut self, addr: String);
 
@@ -596,7 +631,10 @@ impl Bluetooth {
                 self.hh.as_mut().unwrap().activate_hogp(false);
             }
 
-            Profile::A2dpSource | Profile::Hfp | Profile::AvrcpTarget => {
+            Profile::A2dpSource
+            | Profile::Hfp
+            | Profile::AvrcpTarget
+            | Profile::AvrcpController => {
                 self.bluetooth_media.lock().unwrap().disable_profile(profile);
             }
             // Ignore profiles that we don't connect.
@@ -618,7 +656,10 @@ impl Bluetooth {
                 self.hh.as_mut().unwrap().activate_hogp(true);
             }
 
-            Profile::A2dpSource | Profile::Hfp | Profile::AvrcpTarget => {
+            Profile::A2dpSource
+            | Profile::Hfp
+            | Profile::AvrcpTarget
+            | Profile::AvrcpController => {
                 self.bluetooth_media.lock().unwrap().enable_profile(profile);
             }
             // Ignore profiles that we don't connect.
@@ -636,7 +677,10 @@ impl Bluetooth {
 
             Profile::Hogp => Some(self.hh.as_ref().unwrap().is_hogp_activated),
 
-            Profile::A2dpSource | Profile::Hfp | Profile::AvrcpTarget => {
+            Profile::A2dpSource
+            | Profile::Hfp
+            | Profile::AvrcpTarget
+            | Profile::AvrcpController => {
                 self.bluetooth_media.lock().unwrap().is_profile_enabled(profile)
             }
             // Ignore profiles that we don't connect.
@@ -1140,6 +1184,119 @@ impl Bluetooth {
     fn clear_uhid(&mut self) {
         self.uhid_wakeup_source.clear();
     }
+
+    /// Disconnects |device| if it violates the admin device policy. A bonded device that isn't
+    /// allowed to bond is refused by removing its bond. Otherwise the bond of a blocked device is
+    /// kept so that it can connect again once the policy allows it. Returns true if the device was
+    /// blocked.
+    fn enforce_device_policy(&mut self, device: &BluetoothDevice, transport: BtTransport) -> bool {
+        let is_bonded = self.get_bond_state_by_addr(&device.address) == BtBondState::Bonded;
+        let reason = self
+            .bluetooth_admin
+            .lock()
+            .unwrap()
+            .get_device_block_reason(device, transport, is_bonded);
+        if reason == PolicyBlockReason::NotBlocked {
+            return false;
+        }
+
+        warn!(
+            "Device [{}] is blocked by admin policy: {:?}",
+            DisplayAddress(&RawAddress::from_string(&device.address).unwrap_or_default()),
+            reason
+        );
+        self.bluetooth_admin.lock().unwrap().on_device_policy_violation(device, reason);
+
+        // Removing the bond also disconnects the device.
+        if reason == PolicyBlockReason::BondingNotAllowed && self.remove_bond(device.clone()) {
+            return true;
+        }
+
+        let txl = self.tx.clone();
+        let device = device.clone();
+        tokio::spawn(async move {
+            let _ = txl.send(Message::DisconnectDevice(device)).await;
+        });
+        true
+    }
+
+    /// Re-checks the connected devices against the updated admin device policy.
+    pub fn handle_admin_policy_changed(&mut self) {
+        let connected = self
+            .bonded_devices
+            .values()
+            .chain(self.found_devices.values())
+            .filter(|d| d.acl_state == BtAclState::Connected)
+            .map(|d| (d.info.clone(), d.acl_reported_transport))
+            .collect::<Vec<(BluetoothDevice, BtTransport)>>();
+
+        for (device, transport) in connected {
+            self.enforce_device_policy(&device, transport);
+        }
+    }
+
+    /// Stores the Device Information Service values read from a remote device. A PNP ID is used
+    /// as the vendor and product info of the device if it isn't already known from SDP.
+    pub(crate) fn set_remote_device_information(
+        &mut self,
+        address: &String,
+        values: DeviceInformationValues,
+        pnp_id: Option<BtVendorProductInfo>,
+    ) {
+        let device = match self.bonded_devices.get_mut(address) {
+            None => self.found_devices.get_mut(address),
+            some => some,
+        };
+        let (info, properties) = match device {
+            Some(d) => {
+                (d.info.clone(), update_device_information(d, &mut self.callbacks, values, pnp_id))
+            }
+            None => return,
+        };
+        if properties.is_empty() {
+            return;
+        }
+
+        self.bluetooth_admin
+            .lock()
+            .unwrap()
+            .on_remote_device_properties_changed(&info, &properties);
+
+        // Bonding restrictions depend on the vendor and product ID, see
+        // |remote_device_properties_changed|.
+        if self.get_bond_state_by_addr(address) == BtBondState::Bonded {
+            self.enforce_device_policy(&info, BtTransport::Auto);
+        }
+    }
+}
+
+/// Stores the Device Information Service values of |device| and notifies |callbacks| of the
+/// values and the properties that changed. Returns the changed properties.
+fn update_device_information(
+    device: &mut BluetoothDeviceContext,
+    callbacks: &mut Callbacks<dyn IBluetoothCallback + Send>,
+    values: DeviceInformationValues,
+    pnp_id: Option<BtVendorProductInfo>,
+) -> Vec<BluetoothProperty> {
+    let values_changed = device.device_information != values;
+    let properties = device.set_device_information(values, pnp_id);
+
+    let info = device.info.clone();
+    if values_changed {
+        let values = device.device_information.clone();
+        callbacks.for_all_callbacks(|callback| {
+            callback.on_device_information_changed(info.clone(), values.clone());
+        });
+    }
+    if !properties.is_empty() {
+        callbacks.for_all_callbacks(|callback| {
+            callback.on_device_properties_changed(
+                info.clone(),
+                properties.iter().map(|prop| prop.get_type()).collect(),
+            );
+        });
+    }
+    properties
 }
 
 #[btif_callbacks_dispatcher(dispatch_base_callbacks, BaseCallbacks)]
@@ -1441,7 +1598,7 @@ impl BtifBluetoothCallbacks for Bluetooth {
             callback.on_device_found(device.info.clone());
         });
 
-        self.bluetooth_admin.lock().unwrap().on_device_found(&device.info);
+        self.bluetooth_admin.lock().unwrap().on_device_found(&device.info, &properties);
     }
 
     fn discovery_state(&mut self, state: BtDiscoveryState) {
@@ -1593,9 +1750,14 @@ impl BtifBluetoothCallbacks for Bluetooth {
             // on it.
             device.services_resolved = false;
             self.bonded_devices.insert(address.clone(), device);
-            self.fetch_remote_uuids(device_info);
-            if self.get_wake_allowed_device_bonded() {
-                self.create_uhid_for_suspend_wakesource();
+            // Remote initiated bonding is not checked before it completes, so check it here. A
+            // device whose IDs are not known yet is checked again once service discovery reads
+            // them.
+            if !self.enforce_device_policy(&device_info, BtTransport::Auto) {
+                self.fetch_remote_uuids(device_info);
+                if self.get_wake_allowed_device_bonded() {
+                    self.create_uhid_for_suspend_wakesource();
+                }
             }
         } else {
             // If we're bonding, we need to update the found devices list
@@ -1664,6 +1826,7 @@ impl BtifBluetoothCallbacks for Bluetooth {
 
                 let info = d.info.clone();
 
+                let mut services_resolved = false;
                 if !d.services_resolved {
                     let has_uuids = properties.iter().any(|prop| match prop {
                         BluetoothProperty::Uuids(uu) => uu.len() > 0,
@@ -1672,6 +1835,7 @@ impl BtifBluetoothCallbacks for Bluetooth {
 
                     // Services are resolved when uuids are fetched.
                     d.services_resolved |= has_uuids;
+                    services_resolved = has_uuids;
                 }
 
                 if d.wait_to_connect && d.services_resolved {
@@ -1695,10 +1859,24 @@ impl BtifBluetoothCallbacks for Bluetooth {
                     );
                 });
 
-                self.bluetooth_admin
-                    .lock()
-                    .unwrap()
-                    .on_remote_device_properties_changed(&info, &properties);
+                let is_bonded = self.get_bond_state_by_addr(&address) == BtBondState::Bonded;
+                {
+                    let mut admin = self.bluetooth_admin.lock().unwrap();
+                    admin.on_remote_device_properties_changed(&info, &properties);
+                    if services_resolved && is_bonded {
+                        admin.on_remote_services_resolved(&info);
+                    }
+                }
+
+                // The vendor and product ID are usually only known after bonding, so bonding
+                // restrictions are checked again when they arrive or when service discovery
+                // completes without them.
+                let has_vpi = properties
+                    .iter()
+                    .any(|prop| matches!(prop, BluetoothProperty::VendorProductInfo(_)));
+                if (has_vpi || services_resolved) && is_bonded {
+                    self.enforce_device_policy(&info, BtTransport::Auto);
+                }
             }
             None => (),
         }
@@ -1781,6 +1959,10 @@ impl BtifBluetoothCallbacks for Bluetooth {
                             self.connection_callbacks.for_all_callbacks(|callback| {
                                 callback.on_device_connected(device.clone());
                             });
+                            // Don't bring up profiles on a device that is being disconnected.
+                            if self.enforce_device_policy(&device, link_type) {
+                                return;
+                            }
                             let tx = self.tx.clone();
                             let transport = match self.get_remote_type(bluetooth_device.clone()) {
                                 BtDeviceType::Bredr => BtTransport::Bredr,
@@ -2136,6 +2318,21 @@ impl IBluetooth for Bluetooth {
             _ => self.get_remote_type(device.clone()),
         };
 
+        let block_reason = self
+            .bluetooth_admin
+            .lock()
+            .unwrap()
+            .get_device_block_reason(&device, transport, /*bonding=*/ true);
+        if block_reason != PolicyBlockReason::NotBlocked {
+            warn!(
+                "Can't create bond. Device {} is blocked by admin policy: {:?}",
+                DisplayAddress(&address),
+                block_reason
+            );
+            self.bluetooth_admin.lock().unwrap().on_device_policy_violation(&device, block_reason);
+            return false;
+        }
+
         // There could be a race between bond complete and bond cancel, which makes
         // |cancelling_devices| in a wrong state. Remove the device just in case.
         if self.cancelling_devices.remove(&address) {
@@ -2383,6 +2580,13 @@ impl IBluetooth for Bluetooth {
         }
     }
 
+    fn get_remote_device_information(&self, device: BluetoothDevice) -> DeviceInformationValues {
+        match self.get_remote_device_if_found(&device.address) {
+            Some(d) => d.device_information.clone(),
+            None => DeviceInformationValues::default(),
+        }
+    }
+
     fn get_remote_rssi(&self, device: BluetoothDevice) -> i8 {
         match self.get_remote_device_property(&device, &BtPropertyType::RemoteRssi) {
             Some(BluetoothProperty::RemoteRssi(rssi)) => rssi,
@@ -2897,3 +3101,149 @@ impl IBluetoothQALegacy for Bluetooth {
         self.send_hid_data_internal(addr, data)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vendor_product_info(product_id: u16) -> BtVendorProductInfo {
+        BtVendorProductInfo { vendor_id_src: 2, vendor_id: 0x046d, product_id, version: 1 }
+    }
+
+    fn product_id_of(device: &BluetoothDeviceContext) -> Option<u16> {
+        match device.properties.get(&BtPropertyType::VendorProductInfo) {
+            Some(BluetoothProperty::VendorProductInfo(vpi)) => Some(vpi.product_id),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_set_device_information() {
+        let mut device = BluetoothDeviceContext::new(
+            BtBondState::NotBonded,
+            BtAclState::Connected,
+            BluetoothDevice::new("11:22:33:44:55:66".to_string(), "".to_string()),
+            Instant::now(),
+            vec![],
+        );
+
+        let values =
+            DeviceInformationValues { model_number: "Model 1".to_string(), ..Default::default() };
+        let changed = device.set_device_information(values.clone(), None);
+        assert!(changed.is_empty());
+        assert_eq!(device.device_information, values);
+
+        // The PNP ID fills in the vendor and product info when nothing else reported it.
+        let changed = device.set_device_information(values.clone(), Some(vendor_product_info(1)));
+        assert_eq!(changed.len(), 1);
+        assert_eq!(product_id_of(&device), Some(1));
+
+        // Vendor and product info that is already known is not overridden.
+        let changed = device.set_device_information(values, Some(vendor_product_info(2)));
+        assert!(changed.is_empty());
+        assert_eq!(product_id_of(&device), Some(1));
+    }
+
+    /// Records the device information and property changes it's notified of.
+    struct TestBluetoothCallback {
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl IBluetoothCallback for TestBluetoothCallback {
+        fn on_adapter_property_changed(&mut self, _prop: BtPropertyType) {}
+
+        fn on_device_properties_changed(
+            &mut self,
+            _remote_device: BluetoothDevice,
+            props: Vec<BtPropertyType>,
+        ) {
+            self.events.lock().unwrap().push(format!("properties {:?}", props));
+        }
+
+        fn on_device_information_changed(
+            &mut self,
+            _remote_device: BluetoothDevice,
+            values: DeviceInformationValues,
+        ) {
+            self.events.lock().unwrap().push(format!("information {}", values.model_number));
+        }
+
+        fn on_address_changed(&mut self, _addr: String) {}
+
+        fn on_name_changed(&mut self, _name: String) {}
+
+        fn on_discoverable_changed(&mut self, _discoverable: bool) {}
+
+        fn on_device_found(&mut self, _remote_device: BluetoothDevice) {}
+
+        fn on_device_cleared(&mut self, _remote_device: BluetoothDevice) {}
+
+        fn on_discovering_changed(&mut self, _discovering: bool) {}
+
+        fn on_ssp_request(
+            &mut self,
+            _remote_device: BluetoothDevice,
+            _cod: u32,
+            _variant: BtSspVariant,
+            _passkey: u32,
+        ) {
+        }
+
+        fn on_pin_request(
+            &mut self,
+            _remote_device: BluetoothDevice,
+            _cod: u32,
+            _min_16_digit: bool,
+        ) {
+        }
+
+        fn on_pin_display(&mut self, _remote_device: BluetoothDevice, _pincode: String) {}
+
+        fn on_bond_state_changed(&mut self, _status: u32, _device_address: String, _state: u32) {}
+
+        fn on_sdp_search_complete(
+            &mut self,
+            _remote_device: BluetoothDevice,
+            _searched_uuid: Uuid128Bit,
+            _sdp_records: Vec<BtSdpRecord>,
+        ) {
+        }
+
+        fn on_sdp_record_created(&mut self, _record: BtSdpRecord, _handle: i32) {}
+    }
+
+    impl RPCProxy for TestBluetoothCallback {}
+
+    #[test]
+    fn test_update_device_information_notifies_callbacks() {
+        let (tx, _rx) = crate::Stack::create_channel();
+        let mut callbacks: Callbacks<dyn IBluetoothCallback + Send> =
+            Callbacks::new(tx, Message::AdapterCallbackDisconnected);
+        let events = Arc::new(Mutex::new(vec![]));
+        callbacks.add_callback(Box::new(TestBluetoothCallback { events: events.clone() }));
+        let mut device = BluetoothDeviceContext::new(
+            BtBondState::NotBonded,
+            BtAclState::Connected,
+            BluetoothDevice::new("11:22:33:44:55:66".to_string(), "".to_string()),
+            Instant::now(),
+            vec![],
+        );
+
+        // A DIS only update changes no property but still reaches the callbacks.
+        let values =
+            DeviceInformationValues { model_number: "Model 1".to_string(), ..Default::default() };
+        let changed = update_device_information(&mut device, &mut callbacks, values.clone(), None);
+        assert!(changed.is_empty());
+        assert_eq!(*events.lock().unwrap(), vec!["information Model 1".to_string()]);
+
+        // Reading the same values again only reports the new PNP ID.
+        events.lock().unwrap().clear();
+        update_device_information(
+            &mut device,
+            &mut callbacks,
+            values,
+            Some(vendor_product_info(1)),
+        );
+        assert_eq!(*events.lock().unwrap(), vec!["properties [VendorProductInfo]".to_string()]);
+    }
+}
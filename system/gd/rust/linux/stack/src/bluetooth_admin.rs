@@ -1,6 +1,7 @@
 //! Anything related to the Admin API (IBluetoothAdmin).
 
 use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
 use std::fs::File;
 use std::io::{Read, Result, Write};
 use std::sync::{Arc, Mutex};
@@ -10,8 +11,11 @@ use crate::callbacks::Callbacks;
 use crate::uuid::UuidHelper;
 use crate::{Message, RPCProxy};
 
-use bt_topshim::btif::{BluetoothProperty, Uuid128Bit};
+use bt_topshim::btif::{
+    BluetoothProperty, BtDeviceType, BtTransport, BtVendorProductInfo, RawAddress, Uuid128Bit,
+};
 use log::{info, warn};
+use num_derive::{FromPrimitive, ToPrimitive};
 use serde_json::{json, Value};
 use tokio::sync::mpsc::Sender;
 
@@ -23,6 +27,10 @@ pub trait IBluetoothAdmin {
     fn set_allowed_services(&mut self, services: Vec<Uuid128Bit>) -> bool;
     /// Get the allowlist in UUIDs
     fn get_allowed_services(&self) -> Vec<Uuid128Bit>;
+    /// Overwrite the current device policy and store it to a file.
+    fn set_device_policy(&mut self, policy: DevicePolicy) -> bool;
+    /// Get the current device policy
+    fn get_device_policy(&self) -> DevicePolicy;
     /// Get the PolicyEffect struct of a device
     fn get_device_policy_effect(&self, device: BluetoothDevice) -> Option<PolicyEffect>;
     /// Register client callback
@@ -34,12 +42,59 @@ pub trait IBluetoothAdmin {
     fn unregister_admin_policy_callback(&mut self, callback_id: u32) -> bool;
 }
 
+/// A vendor and product ID pair as reported in the Device ID (DI) profile or PNP ID.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct VendorProductId {
+    pub vendor_id: u16,
+    /// Product ID of the device. 0 matches every product of the vendor.
+    pub product_id: u16,
+}
+
+/// Device level restrictions enforced on top of the service allowlist.
+#[derive(PartialEq, Clone, Debug)]
+pub struct DevicePolicy {
+    /// Addresses ("AA:BB:CC:DD:EE:FF") or OUIs ("AA:BB:CC") of devices that are not allowed to
+    /// connect or bond.
+    pub denied_addresses: Vec<String>,
+    /// Vendor and product IDs of devices allowed to bond. Empty means all devices are allowed.
+    pub allowed_bonding_ids: Vec<VendorProductId>,
+    /// The only transport remote devices may use. `BtTransport::Auto` allows all transports.
+    pub allowed_transport: BtTransport,
+}
+
+impl Default for DevicePolicy {
+    fn default() -> Self {
+        DevicePolicy {
+            denied_addresses: vec![],
+            allowed_bonding_ids: vec![],
+            allowed_transport: BtTransport::Auto,
+        }
+    }
+}
+
+/// Reason why a remote device is blocked by the device policy.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum PolicyBlockReason {
+    NotBlocked = 0,
+    /// The address or OUI of the device is in the denylist.
+    AddressDenied,
+    /// The vendor and product ID of the device are not allowed to bond, or the device didn't
+    /// report them.
+    BondingNotAllowed,
+    /// The device uses a transport that is not allowed.
+    TransportNotAllowed,
+}
+
 /// Information of the effects to a remote device by the admin policies
 #[derive(PartialEq, Clone, Debug)]
 pub struct PolicyEffect {
     /// Array of services that are blocked by policy
     pub service_blocked: Vec<Uuid128Bit>,
-    /// Indicate if the device has an adapter-supported profile that is blocked by the policy
+    /// Reason the device is blocked from connecting or bonding by the device policy
+    pub device_blocked: PolicyBlockReason,
+    /// Indicate if the device has an adapter-supported profile that is blocked by the policy, or
+    /// if the device itself is blocked
     pub affected: bool,
 }
 
@@ -56,14 +111,32 @@ pub trait IBluetoothAdminPolicyCallback: RPCProxy {
         device: BluetoothDevice,
         new_policy_effect: Option<PolicyEffect>,
     );
+    /// This gets called when the device policy changed.
+    fn on_device_policy_changed(&mut self, policy: DevicePolicy);
+    /// This gets called when a device is refused to bond or disconnected because it violates the
+    /// device policy.
+    fn on_device_policy_violation(&mut self, device: BluetoothDevice, reason: PolicyBlockReason);
+}
+
+/// Remote device properties that the device policy is evaluated against.
+#[derive(Clone, Debug)]
+struct RemoteDevicePolicyInfo {
+    uuids: Option<Vec<Uuid128Bit>>,
+    device_type: BtDeviceType,
+    vendor_product_id: Option<VendorProductId>,
+    /// Whether |vendor_product_id| is final: the device reported its IDs, or service discovery
+    /// completed without them.
+    vendor_product_id_resolved: bool,
 }
 
 pub struct BluetoothAdmin {
     path: String,
     adapter: Option<Arc<Mutex<Box<Bluetooth>>>>,
     allowed_services: HashSet<Uuid128Bit>,
+    device_policy: DevicePolicy,
     callbacks: Callbacks<dyn IBluetoothAdminPolicyCallback + Send>,
     device_policy_affect_cache: HashMap<BluetoothDevice, Option<PolicyEffect>>,
+    remote_device_info: HashMap<BluetoothDevice, RemoteDevicePolicyInfo>,
     tx: Sender<Message>,
 }
 
@@ -74,8 +147,10 @@ impl BluetoothAdmin {
             path,
             adapter: None,
             allowed_services: HashSet::new(), //empty means allowed all services
+            device_policy: DevicePolicy::default(),
             callbacks: Callbacks::new(tx.clone(), Message::AdminCallbackDisconnected),
             device_policy_affect_cache: HashMap::new(),
+            remote_device_info: HashMap::new(),
             tx: tx.clone(),
         };
 
@@ -126,10 +201,45 @@ impl BluetoothAdmin {
             .iter()
             .filter_map(|v| UuidHelper::from_string(v.as_str()?))
             .collect();
+        // The device policy was added later, so a config without it is still valid.
+        let device_policy = match json.get("device_policy") {
+            Some(policy) => Self::device_policy_from_json(policy)?,
+            None => DevicePolicy::default(),
+        };
+        // Validate the whole file before applying any of it so a bad file changes nothing.
+        let device_policy = Self::normalize_device_policy(device_policy)?;
         self.set_allowed_services(allowed_services);
+        self.update_device_policy(device_policy)?;
         Some(true)
     }
 
+    fn device_policy_from_json(json: &Value) -> Option<DevicePolicy> {
+        let denied_addresses = json
+            .get("denied_addresses")?
+            .as_array()?
+            .iter()
+            .map(|v| Some(String::from(v.as_str()?)))
+            .collect::<Option<Vec<String>>>()?;
+        let allowed_bonding_ids = json
+            .get("allowed_bonding_ids")?
+            .as_array()?
+            .iter()
+            .map(|v| {
+                Some(VendorProductId {
+                    vendor_id: v.get("vendor_id")?.as_u64()?.try_into().ok()?,
+                    product_id: v.get("product_id")?.as_u64()?.try_into().ok()?,
+                })
+            })
+            .collect::<Option<Vec<VendorProductId>>>()?;
+        let allowed_transport = match json.get("allowed_transport")?.as_str()? {
+            "auto" => BtTransport::Auto,
+            "bredr" => BtTransport::Bredr,
+            "le" => BtTransport::Le,
+            _ => return None,
+        };
+        Some(DevicePolicy { denied_addresses, allowed_bonding_ids, allowed_transport })
+    }
+
     fn write_config(&self) -> Result<()> {
         let mut f = File::create(&self.path)?;
         f.write_all(self.get_config_string().as_bytes()).and_then(|_| {
@@ -139,26 +249,218 @@ impl BluetoothAdmin {
     }
 
     fn get_config_string(&self) -> String {
+        let policy = &self.device_policy;
         serde_json::to_string_pretty(&json!({
             "allowed_services":
                 self.get_allowed_services()
                     .iter()
                     .map(UuidHelper::to_string)
-                    .collect::<Vec<String>>()
+                    .collect::<Vec<String>>(),
+            "device_policy": {
+                "denied_addresses": policy.denied_addresses,
+                "allowed_bonding_ids":
+                    policy.allowed_bonding_ids
+                        .iter()
+                        .map(|id| json!({ "vendor_id": id.vendor_id, "product_id": id.product_id }))
+                        .collect::<Vec<Value>>(),
+                "allowed_transport": match policy.allowed_transport {
+                    BtTransport::Auto => "auto",
+                    BtTransport::Bredr => "bredr",
+                    BtTransport::Le => "le",
+                },
+            },
         }))
         .ok()
         .unwrap()
     }
 
-    fn new_device_policy_effect(&self, uuids: Option<Vec<Uuid128Bit>>) -> Option<PolicyEffect> {
-        uuids.map(|uuids| {
-            let service_blocked = self.get_blocked_services(&uuids);
-            let affected = self.get_affected_status(&service_blocked);
-            PolicyEffect { service_blocked, affected }
-        })
+    /// Stores a new device policy without notifying anyone. Returns None if the policy is
+    /// malformed, otherwise whether the stored policy changed.
+    fn update_device_policy(&mut self, policy: DevicePolicy) -> Option<bool> {
+        let policy = Self::normalize_device_policy(policy)?;
+
+        if self.device_policy == policy {
+            return Some(false);
+        }
+
+        self.device_policy = policy;
+        Some(true)
+    }
+
+    /// Normalizes the denylist of a policy. Returns None if any entry is malformed.
+    fn normalize_device_policy(policy: DevicePolicy) -> Option<DevicePolicy> {
+        let denied_addresses = match policy
+            .denied_addresses
+            .iter()
+            .map(|entry| Self::normalize_denied_address(entry))
+            .collect::<Option<Vec<String>>>()
+        {
+            Some(addresses) => addresses,
+            None => {
+                warn!("Invalid address in device denylist: {:?}", policy.denied_addresses);
+                return None;
+            }
+        };
+        Some(DevicePolicy { denied_addresses, ..policy })
+    }
+
+    /// Normalizes a denylist entry, which is either a full address or an OUI (the first three
+    /// octets of an address). Returns None if the entry is malformed.
+    fn normalize_denied_address(entry: &str) -> Option<String> {
+        let octets = entry.split(':').collect::<Vec<&str>>();
+        if octets.len() == 6 {
+            return RawAddress::from_string(entry).map(|addr| addr.to_string().to_uppercase());
+        }
+
+        if octets.len() != 3
+            || octets.iter().any(|o| o.len() != 2 || u8::from_str_radix(o, 16).is_err())
+        {
+            return None;
+        }
+        Some(entry.to_uppercase())
+    }
+
+    fn is_address_denied(&self, address: &str) -> bool {
+        let address = address.to_uppercase();
+        self.device_policy.denied_addresses.iter().any(|entry| address.starts_with(entry.as_str()))
     }
 
-    pub fn on_device_found(&mut self, remote_device: &BluetoothDevice) {
+    fn is_bonding_id_allowed(&self, id: &VendorProductId) -> bool {
+        self.device_policy.allowed_bonding_ids.is_empty()
+            || self.device_policy.allowed_bonding_ids.iter().any(|allowed| {
+                allowed.vendor_id == id.vendor_id
+                    && (allowed.product_id == 0 || allowed.product_id == id.product_id)
+            })
+    }
+
+    /// Returns the reason |remote_device| is blocked by the device policy.
+    ///
+    /// |transport| is the transport the device is using, or `BtTransport::Auto` to derive it from
+    /// the device type. The vendor and product ID are only checked if |bonding| is set. Until the
+    /// IDs are resolved, a device whose IDs are unknown isn't blocked, so that service discovery
+    /// can read them. Once resolved, missing IDs can't bond while only some IDs are allowed.
+    pub fn get_device_block_reason(
+        &self,
+        remote_device: &BluetoothDevice,
+        transport: BtTransport,
+        bonding: bool,
+    ) -> PolicyBlockReason {
+        if self.is_address_denied(&remote_device.address) {
+            return PolicyBlockReason::AddressDenied;
+        }
+
+        let info = self.remote_device_info.get(remote_device);
+        let transport = match (transport, info.map(|i| &i.device_type)) {
+            (BtTransport::Auto, Some(BtDeviceType::Bredr)) => BtTransport::Bredr,
+            (BtTransport::Auto, Some(BtDeviceType::Ble)) => BtTransport::Le,
+            (transport, _) => transport,
+        };
+        let allowed_transport = self.device_policy.allowed_transport;
+        if allowed_transport != BtTransport::Auto
+            && transport != BtTransport::Auto
+            && transport != allowed_transport
+        {
+            return PolicyBlockReason::TransportNotAllowed;
+        }
+
+        if bonding {
+            let allowed = match info {
+                Some(RemoteDevicePolicyInfo { vendor_product_id: Some(id), .. }) => {
+                    self.is_bonding_id_allowed(id)
+                }
+                Some(info) if info.vendor_product_id_resolved => {
+                    self.device_policy.allowed_bonding_ids.is_empty()
+                }
+                _ => true,
+            };
+            if !allowed {
+                return PolicyBlockReason::BondingNotAllowed;
+            }
+        }
+
+        PolicyBlockReason::NotBlocked
+    }
+
+    /// Reports to the clients that |remote_device| was blocked by the device policy.
+    pub fn on_device_policy_violation(
+        &mut self,
+        remote_device: &BluetoothDevice,
+        reason: PolicyBlockReason,
+    ) {
+        self.callbacks.for_all_callbacks(|cb| {
+            cb.on_device_policy_violation(remote_device.clone(), reason);
+        });
+    }
+
+    fn new_device_policy_effect(
+        &self,
+        remote_device: &BluetoothDevice,
+        uuids: Option<Vec<Uuid128Bit>>,
+    ) -> Option<PolicyEffect> {
+        let device_blocked =
+            self.get_device_block_reason(remote_device, BtTransport::Auto, /*bonding=*/ true);
+        if uuids.is_none() && device_blocked == PolicyBlockReason::NotBlocked {
+            return None;
+        }
+
+        let service_blocked = self.get_blocked_services(&uuids.unwrap_or_default());
+        let affected = device_blocked != PolicyBlockReason::NotBlocked
+            || self.get_affected_status(&service_blocked);
+        Some(PolicyEffect { service_blocked, device_blocked, affected })
+    }
+
+    /// Caches the properties the device policy depends on. Returns true if any of them changed.
+    fn update_remote_device_info(
+        &mut self,
+        remote_device: &BluetoothDevice,
+        properties: &Vec<BluetoothProperty>,
+    ) -> bool {
+        let info = self.remote_device_info.entry(remote_device.clone()).or_insert(
+            RemoteDevicePolicyInfo {
+                uuids: None,
+                device_type: BtDeviceType::Unknown,
+                vendor_product_id: None,
+                vendor_product_id_resolved: false,
+            },
+        );
+
+        let mut changed = false;
+        for prop in properties {
+            match prop {
+                BluetoothProperty::Uuids(uuids) => {
+                    // Always re-evaluate on UUID updates since these are the services blocked.
+                    changed = true;
+                    info.uuids = Some(uuids.iter().map(|&x| x.uu.clone()).collect());
+                }
+                BluetoothProperty::TypeOfDevice(device_type) => {
+                    changed |= info.device_type != *device_type;
+                    info.device_type = device_type.clone();
+                }
+                BluetoothProperty::VendorProductInfo(BtVendorProductInfo {
+                    vendor_id,
+                    product_id,
+                    ..
+                }) => {
+                    // A zero vendor ID means the device did not report its IDs.
+                    let id = Some(*vendor_id)
+                        .filter(|&vendor_id| vendor_id != 0)
+                        .map(|vendor_id| VendorProductId { vendor_id, product_id: *product_id });
+                    changed |= info.vendor_product_id != id || !info.vendor_product_id_resolved;
+                    info.vendor_product_id = id;
+                    info.vendor_product_id_resolved = true;
+                }
+                _ => {}
+            }
+        }
+        changed
+    }
+
+    pub fn on_device_found(
+        &mut self,
+        remote_device: &BluetoothDevice,
+        properties: &Vec<BluetoothProperty>,
+    ) {
+        self.update_remote_device_info(remote_device, properties);
         self.device_policy_affect_cache.insert(remote_device.clone(), None).or_else(|| {
             self.callbacks.for_all_callbacks(|cb| {
                 cb.on_device_policy_effect_changed(remote_device.clone(), None);
@@ -169,6 +471,7 @@ impl BluetoothAdmin {
 
     pub fn on_device_cleared(&mut self, remote_device: &BluetoothDevice) {
         self.device_policy_affect_cache.remove(remote_device);
+        self.remote_device_info.remove(remote_device);
     }
 
     pub fn on_remote_device_properties_changed(
@@ -176,19 +479,28 @@ impl BluetoothAdmin {
         remote_device: &BluetoothDevice,
         properties: &Vec<BluetoothProperty>,
     ) {
-        let new_uuids = properties.iter().find_map(|p| match p {
-            BluetoothProperty::Uuids(uuids) => {
-                Some(uuids.iter().map(|&x| x.uu.clone()).collect::<Vec<Uuid128Bit>>())
-            }
-            _ => None,
-        });
-
-        // No need to update policy effect if remote UUID is not changed.
-        if new_uuids.is_none() {
+        // No need to update policy effect if neither remote UUID nor device info is changed.
+        if !self.update_remote_device_info(remote_device, properties) {
             return;
         }
+        self.update_device_policy_effect(remote_device);
+    }
+
+    /// Marks the vendor and product ID of |remote_device| as final once service discovery
+    /// completed, even if the device didn't report them.
+    pub fn on_remote_services_resolved(&mut self, remote_device: &BluetoothDevice) {
+        match self.remote_device_info.get_mut(remote_device) {
+            Some(info) if !info.vendor_product_id_resolved => {
+                info.vendor_product_id_resolved = true;
+            }
+            _ => return,
+        }
+        self.update_device_policy_effect(remote_device);
+    }
 
-        let new_effect = self.new_device_policy_effect(new_uuids);
+    fn update_device_policy_effect(&mut self, remote_device: &BluetoothDevice) {
+        let uuids = self.remote_device_info.get(remote_device).and_then(|i| i.uuids.clone());
+        let new_effect = self.new_device_policy_effect(remote_device, uuids);
         let cur_effect = self.device_policy_affect_cache.get(remote_device);
 
         if cur_effect.is_none() || *cur_effect.unwrap() != new_effect.clone() {
@@ -236,7 +548,7 @@ impl IBluetoothAdmin for BluetoothAdmin {
 
             for (device, effect) in self.device_policy_affect_cache.clone().iter() {
                 let uuids = adapter.lock().unwrap().get_remote_uuids(device.clone());
-                let new_effect = self.new_device_policy_effect(Some(uuids));
+                let new_effect = self.new_device_policy_effect(device, Some(uuids));
 
                 if new_effect.clone() != *effect {
                     self.callbacks.for_all_callbacks(|cb| {
@@ -255,6 +567,53 @@ impl IBluetoothAdmin for BluetoothAdmin {
         self.allowed_services.iter().cloned().collect()
     }
 
+    fn set_device_policy(&mut self, policy: DevicePolicy) -> bool {
+        if self.adapter.is_none() {
+            warn!("Adapter is not ready, device policy is not changed");
+            return false;
+        }
+
+        match self.update_device_policy(policy) {
+            // Invalid policy.
+            None => false,
+            // Device policy is not changed.
+            Some(false) => true,
+            Some(true) => {
+                if self.write_config().is_err() {
+                    warn!("Failed to write config");
+                }
+
+                let policy = self.get_device_policy();
+                self.callbacks.for_all_callbacks(|cb| {
+                    cb.on_device_policy_changed(policy.clone());
+                });
+
+                // Let the adapter disconnect the devices that are now blocked.
+                let txl = self.tx.clone();
+                tokio::spawn(async move {
+                    let _ = txl.send(Message::AdminPolicyChanged).await;
+                });
+
+                for (device, effect) in self.device_policy_affect_cache.clone().iter() {
+                    let uuids = self.remote_device_info.get(device).and_then(|i| i.uuids.clone());
+                    let new_effect = self.new_device_policy_effect(device, uuids);
+
+                    if new_effect.clone() != *effect {
+                        self.callbacks.for_all_callbacks(|cb| {
+                            cb.on_device_policy_effect_changed(device.clone(), new_effect.clone())
+                        });
+                        self.device_policy_affect_cache.insert(device.clone(), new_effect.clone());
+                    }
+                }
+                true
+            }
+        }
+    }
+
+    fn get_device_policy(&self) -> DevicePolicy {
+        self.device_policy.clone()
+    }
+
     fn get_device_policy_effect(&self, device: BluetoothDevice) -> Option<PolicyEffect> {
         if let Some(effect) = self.device_policy_affect_cache.get(&device) {
             effect.clone()
@@ -278,10 +637,15 @@ impl IBluetoothAdmin for BluetoothAdmin {
 
 #[cfg(test)]
 mod tests {
-    use crate::bluetooth_admin::{BluetoothAdmin, IBluetoothAdmin};
+    use crate::bluetooth::BluetoothDevice;
+    use crate::bluetooth_admin::{
+        BluetoothAdmin, DevicePolicy, IBluetoothAdmin, PolicyBlockReason, VendorProductId,
+    };
     use crate::uuid::UuidHelper;
     use crate::Stack;
-    use bt_topshim::btif::Uuid128Bit;
+    use bt_topshim::btif::{
+        BluetoothProperty, BtDeviceType, BtTransport, BtVendorProductInfo, Uuid128Bit,
+    };
 
     // A workaround needed for linking. For more details, check the comment in
     // system/gd/rust/topshim/facade/src/main.rs
@@ -374,4 +738,187 @@ mod tests {
         assert_eq!(get_sorted_allowed_services(&admin), allowed_services_128);
         assert_eq!(get_sorted_allowed_services_from_config(&admin), allowed_services);
     }
+
+    #[test]
+    fn test_device_policy() {
+        let (tx, _) = Stack::create_channel();
+        let mut admin = BluetoothAdmin::new(String::from(""), tx.clone());
+        let keyboard = BluetoothDevice::new(String::from("11:22:33:44:55:66"), String::from(""));
+        let headset = BluetoothDevice::new(String::from("AA:BB:CC:DD:EE:FF"), String::from(""));
+        admin.on_device_found(
+            &keyboard,
+            &vec![
+                BluetoothProperty::TypeOfDevice(BtDeviceType::Ble),
+                BluetoothProperty::VendorProductInfo(BtVendorProductInfo {
+                    vendor_id_src: 1,
+                    vendor_id: 0x1234,
+                    product_id: 0x5678,
+                    version: 0,
+                }),
+            ],
+        );
+        admin
+            .on_device_found(&headset, &vec![BluetoothProperty::TypeOfDevice(BtDeviceType::Bredr)]);
+
+        // Default admin allows everything
+        for device in [&keyboard, &headset] {
+            assert_eq!(
+                admin.get_device_block_reason(device, BtTransport::Auto, true),
+                PolicyBlockReason::NotBlocked
+            );
+        }
+
+        // The policy can't be changed through the API before the adapter is ready
+        let policy = DevicePolicy {
+            denied_addresses: vec![String::from("AA:BB:CC")],
+            ..DevicePolicy::default()
+        };
+        assert!(!admin.set_device_policy(policy));
+        assert_eq!(admin.get_device_policy(), DevicePolicy::default());
+
+        // Malformed denylist entries are rejected
+        let policy = DevicePolicy {
+            denied_addresses: vec![String::from("AA:BB:CC:DD")],
+            ..DevicePolicy::default()
+        };
+        assert_eq!(admin.update_device_policy(policy), None);
+        assert_eq!(admin.get_device_policy(), DevicePolicy::default());
+
+        // OUI entries match every device of the vendor
+        let policy = DevicePolicy {
+            denied_addresses: vec![String::from("aa:bb:cc")],
+            ..DevicePolicy::default()
+        };
+        admin.update_device_policy(policy);
+        assert_eq!(admin.get_device_policy().denied_addresses, vec![String::from("AA:BB:CC")]);
+        assert_eq!(
+            admin.get_device_block_reason(&headset, BtTransport::Auto, false),
+            PolicyBlockReason::AddressDenied
+        );
+        assert_eq!(
+            admin.get_device_block_reason(&keyboard, BtTransport::Auto, false),
+            PolicyBlockReason::NotBlocked
+        );
+
+        // The transport is taken from the device type unless given explicitly
+        let policy = DevicePolicy { allowed_transport: BtTransport::Le, ..DevicePolicy::default() };
+        admin.update_device_policy(policy);
+        assert_eq!(
+            admin.get_device_block_reason(&headset, BtTransport::Auto, false),
+            PolicyBlockReason::TransportNotAllowed
+        );
+        assert_eq!(
+            admin.get_device_block_reason(&keyboard, BtTransport::Auto, false),
+            PolicyBlockReason::NotBlocked
+        );
+        assert_eq!(
+            admin.get_device_block_reason(&keyboard, BtTransport::Bredr, false),
+            PolicyBlockReason::TransportNotAllowed
+        );
+
+        // Vendor and product IDs are only checked on bonding. Unknown IDs are only blocked once
+        // service discovery completed without them.
+        let policy = DevicePolicy {
+            allowed_bonding_ids: vec![VendorProductId { vendor_id: 0x1234, product_id: 0x1111 }],
+            ..DevicePolicy::default()
+        };
+        admin.update_device_policy(policy);
+        assert_eq!(
+            admin.get_device_block_reason(&keyboard, BtTransport::Auto, false),
+            PolicyBlockReason::NotBlocked
+        );
+        assert_eq!(
+            admin.get_device_block_reason(&keyboard, BtTransport::Auto, true),
+            PolicyBlockReason::BondingNotAllowed
+        );
+        assert_eq!(
+            admin.get_device_block_reason(&headset, BtTransport::Auto, false),
+            PolicyBlockReason::NotBlocked
+        );
+        assert_eq!(
+            admin.get_device_block_reason(&headset, BtTransport::Auto, true),
+            PolicyBlockReason::NotBlocked
+        );
+        admin.on_remote_services_resolved(&headset);
+        assert_eq!(
+            admin.get_device_block_reason(&headset, BtTransport::Auto, false),
+            PolicyBlockReason::NotBlocked
+        );
+        assert_eq!(
+            admin.get_device_block_reason(&headset, BtTransport::Auto, true),
+            PolicyBlockReason::BondingNotAllowed
+        );
+
+        // Product ID 0 allows every product of the vendor
+        let policy = DevicePolicy {
+            allowed_bonding_ids: vec![VendorProductId { vendor_id: 0x1234, product_id: 0 }],
+            ..DevicePolicy::default()
+        };
+        admin.update_device_policy(policy);
+        assert_eq!(
+            admin.get_device_block_reason(&keyboard, BtTransport::Auto, true),
+            PolicyBlockReason::NotBlocked
+        );
+    }
+
+    #[test]
+    fn test_device_policy_config() {
+        let (tx, _) = Stack::create_channel();
+        let mut admin = BluetoothAdmin::new(String::from(""), tx.clone());
+        let policy = DevicePolicy {
+            denied_addresses: vec![String::from("AA:BB:CC"), String::from("11:22:33:44:55:66")],
+            allowed_bonding_ids: vec![VendorProductId { vendor_id: 0x1234, product_id: 0x5678 }],
+            allowed_transport: BtTransport::Bredr,
+        };
+
+        // valid configuration
+        assert_eq!(
+            admin.load_config_from_json(&json!({
+                "allowed_services": Vec::<String>::new(),
+                "device_policy": {
+                    "denied_addresses": ["AA:BB:CC", "11:22:33:44:55:66"],
+                    "allowed_bonding_ids": [{ "vendor_id": 0x1234, "product_id": 0x5678 }],
+                    "allowed_transport": "bredr",
+                },
+            })),
+            Some(true)
+        );
+        assert_eq!(admin.get_device_policy(), policy);
+
+        // configuration written is loaded back to the same policy
+        let config = serde_json::from_str::<Value>(admin.get_config_string().as_str()).unwrap();
+        admin.update_device_policy(DevicePolicy::default());
+        assert_eq!(admin.load_config_from_json(&config), Some(true));
+        assert_eq!(admin.get_device_policy(), policy);
+
+        // invalid configuration
+        assert_eq!(
+            admin.load_config_from_json(&json!({
+                "allowed_services": Vec::<String>::new(),
+                "device_policy": {
+                    "denied_addresses": [],
+                    "allowed_bonding_ids": [{ "vendor_id": 0x12345, "product_id": 0 }],
+                    "allowed_transport": "bredr",
+                },
+            })),
+            None
+        );
+        // config should remain unchanged
+        assert_eq!(admin.get_device_policy(), policy);
+
+        // a malformed denylist leaves the allowlist untouched as well
+        assert_eq!(
+            admin.load_config_from_json(&json!({
+                "allowed_services": ["0000110a-0000-1000-8000-00805f9b34fb"],
+                "device_policy": {
+                    "denied_addresses": ["not an address"],
+                    "allowed_bonding_ids": [],
+                    "allowed_transport": "auto",
+                },
+            })),
+            None
+        );
+        assert_eq!(admin.get_allowed_services(), Vec::<Uuid128Bit>::new());
+        assert_eq!(admin.get_device_policy(), policy);
+    }
 }
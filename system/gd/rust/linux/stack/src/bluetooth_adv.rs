@@ -68,7 +68,7 @@ pub struct AdvertiseData {
 }
 
 /// Parameters of the periodic advertising packet for BLE advertising set.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct PeriodicAdvertisingParameters {
     /// Whether TX Power level will be included.
     pub include_tx_power: bool,
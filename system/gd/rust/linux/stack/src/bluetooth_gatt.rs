@@ -771,6 +771,15 @@ impl BluetoothGattCharacteristic {
     }
 }
 
+/// Returns the part of an attribute value a (long) read at |offset| asks for, or InvalidOffset if
+/// the offset is past the end of the value.
+pub(crate) fn value_at_offset(value: Vec<u8>, offset: i32) -> Result<Vec<u8>, GattStatus> {
+    match usize::try_from(offset) {
+        Ok(offset) if offset <= value.len() => Ok(value[offset..].to_vec()),
+        _ => Err(GattStatus::InvalidOffset),
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 /// Represents a GATT Service.
 pub struct BluetoothGattService {
@@ -4687,6 +4696,15 @@ mod tests {
         assert_eq!(Uuid::from(expected), uuid.unwrap());
     }
 
+    #[test]
+    fn test_value_at_offset() {
+        assert_eq!(value_at_offset(vec![1, 2], 0), Ok(vec![1, 2]));
+        assert_eq!(value_at_offset(vec![1, 2], 1), Ok(vec![2]));
+        assert_eq!(value_at_offset(vec![1, 2], 2), Ok(vec![]));
+        assert_eq!(value_at_offset(vec![1, 2], 3), Err(GattStatus::InvalidOffset));
+        assert_eq!(value_at_offset(vec![1, 2], -1), Err(GattStatus::InvalidOffset));
+    }
+
     #[test]
     fn test_context_map_clients() {
         let (tx, _rx) = crate::Stack::create_channel();
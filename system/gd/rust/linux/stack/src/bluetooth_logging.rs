@@ -1,9 +1,110 @@
 use bt_common::init_flags;
-use log::LevelFilter;
+use log::{LevelFilter, Log, Metadata, Record};
+use num_derive::{FromPrimitive, ToPrimitive};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 use syslog::{BasicLogger, Error, Facility, Formatter3164};
 
 use log_panics;
 
+/// Number of recent log lines kept in memory.
+const LOG_BUFFER_CAPACITY: usize = 2000;
+
+/// Most verbose level of the log lines kept in memory. It doesn't depend on the levels of the
+/// targets so the buffer also has the lines that were filtered out of the log output.
+const LOG_BUFFER_LEVEL: LevelFilter = LevelFilter::Debug;
+
+/// Log levels that can be set for a target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum Level {
+    Off = 0,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<Level> for LevelFilter {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Off => LevelFilter::Off,
+            Level::Error => LevelFilter::Error,
+            Level::Warn => LevelFilter::Warn,
+            Level::Info => LevelFilter::Info,
+            Level::Debug => LevelFilter::Debug,
+            Level::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+impl From<LevelFilter> for Level {
+    fn from(level: LevelFilter) -> Self {
+        match level {
+            LevelFilter::Off => Level::Off,
+            LevelFilter::Error => Level::Error,
+            LevelFilter::Warn => Level::Warn,
+            LevelFilter::Info => Level::Info,
+            LevelFilter::Debug => Level::Debug,
+            LevelFilter::Trace => Level::Trace,
+        }
+    }
+}
+
+impl Level {
+    /// Converts to the tag level used by libbluetooth. libbluetooth has no level to turn
+    /// logging off completely so FATAL is the closest.
+    fn to_log_tag_level(self) -> i32 {
+        match self {
+            Level::Off => init_flags::LOG_TAG_FATAL,
+            Level::Error => init_flags::LOG_TAG_ERROR,
+            Level::Warn => init_flags::LOG_TAG_WARN,
+            Level::Info => init_flags::LOG_TAG_INFO,
+            Level::Debug => init_flags::LOG_TAG_DEBUG,
+            Level::Trace => init_flags::LOG_TAG_VERBOSE,
+        }
+    }
+
+    /// Converts from the tag level used by libbluetooth. NOTICE has no equivalent and is
+    /// reported as Info.
+    fn from_log_tag_level(level: i32) -> Self {
+        match level {
+            init_flags::LOG_TAG_FATAL => Level::Off,
+            init_flags::LOG_TAG_ERROR => Level::Error,
+            init_flags::LOG_TAG_WARN => Level::Warn,
+            init_flags::LOG_TAG_DEBUG => Level::Debug,
+            init_flags::LOG_TAG_VERBOSE => Level::Trace,
+            _ => Level::Info,
+        }
+    }
+}
+
+/// Where a log target is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum LogTargetKind {
+    /// A module path in the Linux stack (e.g. "bt_topshim::profiles::gatt"), which also applies
+    /// to its submodules.
+    Module = 0,
+    /// A log tag in libbluetooth (e.g. "bt_btm").
+    Tag,
+}
+
+impl LogTargetKind {
+    /// Checks that |target| is well formed for this kind of target.
+    fn is_valid_target(self, target: &str) -> bool {
+        let is_identifier =
+            |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        match self {
+            LogTargetKind::Module => target.split("::").all(is_identifier),
+            LogTargetKind::Tag => is_identifier(target),
+        }
+    }
+}
+
 /// API to modify log levels.
 pub trait IBluetoothLogging {
     /// Check whether debug logging is enabled.
@@ -11,6 +112,163 @@ pub trait IBluetoothLogging {
 
     /// Change whether debug logging is enabled.
     fn set_debug_logging(&mut self, enabled: bool);
+
+    /// Sets the log level of a target of the given kind. Returns false if the target isn't
+    /// well formed.
+    fn set_log_level(&mut self, kind: LogTargetKind, target: String, level: Level) -> bool;
+
+    /// Gets the log level that applies to a target of the given kind.
+    fn get_log_level(&self, kind: LogTargetKind, target: String) -> Level;
+
+    /// Removes the log level of a target of the given kind so it follows the default log level
+    /// again.
+    fn clear_log_level(&mut self, kind: LogTargetKind, target: String);
+
+    /// Returns the recent log lines of the Linux stack up to the debug level, oldest first. The
+    /// lines are kept whatever the log levels of their targets are.
+    fn get_log_buffer(&self) -> Vec<String>;
+
+    /// Drops all the log lines kept in memory.
+    fn clear_log_buffer(&mut self);
+}
+
+/// Log levels of the Linux stack modules.
+struct TargetLevels {
+    default_level: LevelFilter,
+    /// Levels of module paths, the most specific first.
+    overrides: Vec<(String, LevelFilter)>,
+}
+
+impl TargetLevels {
+    fn new(default_level: LevelFilter) -> Self {
+        Self { default_level, overrides: vec![] }
+    }
+
+    /// Gets the level of the most specific target matching the given module path.
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.overrides
+            .iter()
+            .find(|(t, _)| {
+                target == t.as_str()
+                    || (target.starts_with(t.as_str()) && target[t.len()..].starts_with("::"))
+            })
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default_level)
+    }
+
+    fn set(&mut self, target: String, level: LevelFilter) {
+        self.remove(&target);
+        self.overrides.push((target, level));
+        self.overrides.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+    }
+
+    /// Returns false if |target| had no level.
+    fn remove(&mut self, target: &str) -> bool {
+        let len = self.overrides.len();
+        self.overrides.retain(|(t, _)| t != target);
+        self.overrides.len() != len
+    }
+
+    /// The `log` crate drops records above the max level before reaching the logger, so it
+    /// has to be the most verbose of all levels.
+    fn max_level(&self) -> LevelFilter {
+        self.overrides.iter().map(|(_, level)| *level).fold(self.default_level, std::cmp::max)
+    }
+
+    /// Records up to the least verbose of all levels are logged whatever their target is.
+    fn min_level(&self) -> LevelFilter {
+        self.overrides.iter().map(|(_, level)| *level).fold(self.default_level, std::cmp::min)
+    }
+}
+
+/// State shared between |BluetoothLogging| and the installed logger.
+struct LoggerState {
+    levels: RwLock<TargetLevels>,
+    /// Snapshot of |TargetLevels::min_level| so most records are let through without locking.
+    min_level: AtomicUsize,
+    buffer: Mutex<VecDeque<String>>,
+}
+
+impl LoggerState {
+    fn new(default_level: LevelFilter) -> Self {
+        Self {
+            levels: RwLock::new(TargetLevels::new(default_level)),
+            min_level: AtomicUsize::new(default_level as usize),
+            buffer: Mutex::new(VecDeque::with_capacity(LOG_BUFFER_CAPACITY)),
+        }
+    }
+
+    /// Records that are neither logged nor buffered are dropped by the `log` crate.
+    fn max_level(levels: &TargetLevels) -> LevelFilter {
+        std::cmp::max(levels.max_level(), LOG_BUFFER_LEVEL)
+    }
+
+    fn is_buffered(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= LOG_BUFFER_LEVEL
+    }
+
+    fn is_enabled(&self, metadata: &Metadata) -> bool {
+        // |log::Level| and |LevelFilter| share their values.
+        if metadata.level() as usize <= self.min_level.load(Ordering::Relaxed) {
+            return true;
+        }
+        metadata.level() <= self.levels.read().unwrap().level_for(metadata.target())
+    }
+
+    /// Applies |update| to the levels and refreshes what depends on them.
+    fn update_levels<F: FnOnce(&mut TargetLevels) -> bool>(&self, update: F) -> bool {
+        let mut levels = self.levels.write().unwrap();
+        if !update(&mut levels) {
+            return false;
+        }
+        self.min_level.store(levels.min_level() as usize, Ordering::Relaxed);
+        log::set_max_level(Self::max_level(&levels));
+        true
+    }
+
+    fn push_line(&self, line: String) {
+        let mut buffer = self.buffer.lock().unwrap();
+        if buffer.len() >= LOG_BUFFER_CAPACITY {
+            buffer.pop_front();
+        }
+        buffer.push_back(line);
+    }
+}
+
+/// Logger keeping recent lines and applying the per-target levels before passing records to
+/// the actual output.
+struct FilteringLogger {
+    inner: Box<dyn Log>,
+    state: Arc<LoggerState>,
+}
+
+impl Log for FilteringLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.state.is_buffered(metadata) || self.state.is_enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.state.is_buffered(record.metadata()) {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+            let line = format!(
+                "{}.{:03} {} {}: {}",
+                timestamp.as_secs(),
+                timestamp.subsec_millis(),
+                record.level(),
+                record.target(),
+                record.args()
+            );
+            self.state.push_line(line);
+        }
+
+        if self.state.is_enabled(record.metadata()) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
 }
 
 /// Logging related implementation.
@@ -18,19 +276,25 @@ pub struct BluetoothLogging {
     is_debug: bool,
     is_stderr: bool,
     is_initialized: bool,
+    state: Arc<LoggerState>,
 }
 
 impl BluetoothLogging {
     pub fn new(is_debug: bool, log_output: &str) -> Self {
         let is_stderr = log_output == "stderr";
-        Self { is_debug, is_stderr, is_initialized: false }
+        let level = if is_debug { LevelFilter::Debug } else { LevelFilter::Info };
+        Self {
+            is_debug,
+            is_stderr,
+            is_initialized: false,
+            state: Arc::new(LoggerState::new(level)),
+        }
     }
 
     pub fn initialize(&mut self) -> Result<(), Error> {
-        let level = if self.is_debug { LevelFilter::Debug } else { LevelFilter::Info };
-
-        if self.is_stderr {
-            env_logger::Builder::new().filter(None, level).init();
+        // Filtering is done by |FilteringLogger| so the inner logger accepts everything.
+        let inner: Box<dyn Log> = if self.is_stderr {
+            Box::new(env_logger::Builder::new().filter(None, LevelFilter::Trace).build())
         } else {
             let formatter = Formatter3164 {
                 facility: Facility::LOG_USER,
@@ -39,9 +303,13 @@ impl BluetoothLogging {
                 pid: 0,
             };
 
-            let logger = syslog::unix(formatter)?;
-            let _ = log::set_boxed_logger(Box::new(BasicLogger::new(logger)))
-                .map(|()| log::set_max_level(level));
+            Box::new(BasicLogger::new(syslog::unix(formatter)?))
+        };
+
+        let level = LoggerState::max_level(&self.state.levels.read().unwrap());
+        let logger = FilteringLogger { inner, state: self.state.clone() };
+        let _ = log::set_boxed_logger(Box::new(logger)).map(|()| log::set_max_level(level));
+        if !self.is_stderr {
             log_panics::init();
         }
         self.is_initialized = true;
@@ -63,7 +331,10 @@ impl IBluetoothLogging for BluetoothLogging {
 
         // Update log level in Linux stack.
         let level = if self.is_debug { LevelFilter::Debug } else { LevelFilter::Info };
-        log::set_max_level(level);
+        self.state.update_levels(|levels| {
+            levels.default_level = level;
+            true
+        });
 
         // Update log level in libbluetooth.
         let level =
@@ -77,4 +348,186 @@ impl IBluetoothLogging for BluetoothLogging {
 
         log::info!("Setting debug logging to {}", self.is_debug);
     }
+
+    fn set_log_level(&mut self, kind: LogTargetKind, target: String, level: Level) -> bool {
+        if !self.is_initialized || !kind.is_valid_target(&target) {
+            return false;
+        }
+
+        match kind {
+            LogTargetKind::Module => {
+                self.state.update_levels(|levels| {
+                    levels.set(target.clone(), level.into());
+                    true
+                });
+            }
+            LogTargetKind::Tag => {
+                init_flags::update_log_level_for_tag(&target, level.to_log_tag_level())
+            }
+        }
+
+        log::info!("Setting log level of {:?} {} to {:?}", kind, target, level);
+        true
+    }
+
+    fn get_log_level(&self, kind: LogTargetKind, target: String) -> Level {
+        match kind {
+            LogTargetKind::Module => self.state.levels.read().unwrap().level_for(&target).into(),
+            LogTargetKind::Tag => {
+                Level::from_log_tag_level(init_flags::get_log_level_for_tag(&target))
+            }
+        }
+    }
+
+    fn clear_log_level(&mut self, kind: LogTargetKind, target: String) {
+        match kind {
+            LogTargetKind::Module => {
+                if !self.state.update_levels(|levels| levels.remove(&target)) {
+                    return;
+                }
+            }
+            LogTargetKind::Tag => init_flags::clear_log_level_for_tag(&target),
+        }
+
+        log::info!("Cleared log level of {:?} {}", kind, target);
+    }
+
+    fn get_log_buffer(&self) -> Vec<String> {
+        self.state.buffer.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn clear_log_buffer(&mut self) {
+        self.state.buffer.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_for_target_override() {
+        let mut levels = TargetLevels::new(LevelFilter::Info);
+        levels.set("btstack::bluetooth".into(), LevelFilter::Debug);
+        levels.set("btstack::bluetooth::sub".into(), LevelFilter::Trace);
+
+        assert_eq!(levels.level_for("btstack::bluetooth"), LevelFilter::Debug);
+        assert_eq!(levels.level_for("btstack::bluetooth::other"), LevelFilter::Debug);
+        // The most specific target wins.
+        assert_eq!(levels.level_for("btstack::bluetooth::sub::inner"), LevelFilter::Trace);
+        // A target only sharing a prefix with an override is not a sub-module of it.
+        assert_eq!(levels.level_for("btstack::bluetooth_gatt"), LevelFilter::Info);
+
+        // Setting a target again replaces its level.
+        levels.set("btstack::bluetooth".into(), LevelFilter::Warn);
+        assert_eq!(levels.level_for("btstack::bluetooth::other"), LevelFilter::Warn);
+        assert!(levels.remove("btstack::bluetooth"));
+        assert!(!levels.remove("btstack::bluetooth"));
+        assert_eq!(levels.level_for("btstack::bluetooth::other"), LevelFilter::Info);
+    }
+
+    #[test]
+    fn test_level_for_default() {
+        let mut levels = TargetLevels::new(LevelFilter::Warn);
+        assert_eq!(levels.level_for("btstack::bluetooth"), LevelFilter::Warn);
+
+        levels.set("btstack::bluetooth_media".into(), LevelFilter::Off);
+        assert_eq!(levels.level_for("btstack::bluetooth"), LevelFilter::Warn);
+        assert_eq!(levels.level_for("btstack"), LevelFilter::Warn);
+    }
+
+    #[test]
+    fn test_max_min_level() {
+        let mut levels = TargetLevels::new(LevelFilter::Info);
+        assert_eq!(levels.max_level(), LevelFilter::Info);
+        assert_eq!(levels.min_level(), LevelFilter::Info);
+
+        // Less verbose overrides don't lower the max level.
+        levels.set("btstack::bluetooth".into(), LevelFilter::Error);
+        assert_eq!(levels.max_level(), LevelFilter::Info);
+        assert_eq!(levels.min_level(), LevelFilter::Error);
+
+        levels.set("btstack::bluetooth_gatt".into(), LevelFilter::Debug);
+        levels.set("btstack::bluetooth_media".into(), LevelFilter::Trace);
+        assert_eq!(levels.max_level(), LevelFilter::Trace);
+
+        levels.remove("btstack::bluetooth_media");
+        assert_eq!(levels.max_level(), LevelFilter::Debug);
+    }
+
+    #[test]
+    fn test_is_enabled() {
+        let state = LoggerState::new(LevelFilter::Info);
+        fn metadata(level: log::Level, target: &str) -> Metadata {
+            Metadata::builder().level(level).target(target).build()
+        }
+        assert!(state.is_enabled(&metadata(log::Level::Info, "btstack::bluetooth")));
+        assert!(!state.is_enabled(&metadata(log::Level::Debug, "btstack::bluetooth")));
+
+        state.update_levels(|levels| {
+            levels.set("btstack::bluetooth".into(), LevelFilter::Debug);
+            levels.set("btstack::bluetooth_media".into(), LevelFilter::Warn);
+            true
+        });
+        assert!(state.is_enabled(&metadata(log::Level::Debug, "btstack::bluetooth")));
+        assert!(!state.is_enabled(&metadata(log::Level::Info, "btstack::bluetooth_media")));
+        assert!(state.is_enabled(&metadata(log::Level::Warn, "btstack::bluetooth_media")));
+        assert!(state.is_enabled(&metadata(log::Level::Info, "btstack::bluetooth_gatt")));
+    }
+
+    #[test]
+    fn test_log_buffer_ignores_target_levels() {
+        /// Keeps the targets of the records it gets.
+        struct TargetsLogger(Arc<Mutex<Vec<String>>>);
+        impl Log for TargetsLogger {
+            fn enabled(&self, _metadata: &Metadata) -> bool {
+                true
+            }
+            fn log(&self, record: &Record) {
+                self.0.lock().unwrap().push(record.target().into());
+            }
+            fn flush(&self) {}
+        }
+
+        let output = Arc::new(Mutex::new(vec![]));
+        let state = Arc::new(LoggerState::new(LevelFilter::Info));
+        let logger = FilteringLogger {
+            inner: Box::new(TargetsLogger(output.clone())),
+            state: state.clone(),
+        };
+        state.update_levels(|levels| {
+            levels.set("btstack::bluetooth_media".into(), LevelFilter::Off);
+            true
+        });
+
+        let log = |level: log::Level, target: &str| {
+            logger
+                .log(&Record::builder().level(level).target(target).args(format_args!("")).build())
+        };
+        log(log::Level::Info, "btstack::bluetooth");
+        log(log::Level::Debug, "btstack::bluetooth");
+        log(log::Level::Trace, "btstack::bluetooth");
+        log(log::Level::Error, "btstack::bluetooth_media");
+
+        // Only the records passing the target levels are logged.
+        assert_eq!(*output.lock().unwrap(), vec!["btstack::bluetooth".to_string()]);
+        // The buffer keeps the records up to its own level whatever their target is.
+        let buffer: Vec<String> = state.buffer.lock().unwrap().iter().cloned().collect();
+        assert_eq!(buffer.len(), 3);
+        assert!(buffer[0].ends_with(" INFO btstack::bluetooth: "));
+        assert!(buffer[1].ends_with(" DEBUG btstack::bluetooth: "));
+        assert!(buffer[2].ends_with(" ERROR btstack::bluetooth_media: "));
+    }
+
+    #[test]
+    fn test_valid_targets() {
+        assert!(LogTargetKind::Module.is_valid_target("bt_topshim::profiles::gatt"));
+        assert!(LogTargetKind::Module.is_valid_target("btstack"));
+        assert!(!LogTargetKind::Module.is_valid_target(""));
+        assert!(!LogTargetKind::Module.is_valid_target("btstack::"));
+        assert!(!LogTargetKind::Module.is_valid_target("bt btm"));
+        assert!(LogTargetKind::Tag.is_valid_target("bt_btm"));
+        assert!(!LogTargetKind::Tag.is_valid_target("btstack::bluetooth"));
+        assert!(!LogTargetKind::Tag.is_valid_target(""));
+    }
 }
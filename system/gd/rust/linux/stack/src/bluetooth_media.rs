@@ -10,7 +10,8 @@ use bt_topshim::profiles::a2dp::{
     BtavConnectionState, PresentationPosition,
 };
 use bt_topshim::profiles::avrcp::{
-    Avrcp, AvrcpCallbacks, AvrcpCallbacksDispatcher, PlayerMetadata,
+    Avrcp, AvrcpBrowseItem, AvrcpBrowseScope, AvrcpCallbacks, AvrcpCallbacksDispatcher, AvrcpCtrl,
+    AvrcpCtrlCallbacks, AvrcpCtrlCallbacksDispatcher, AvrcpPlayStatus, PlayerMetadata,
 };
 use bt_topshim::profiles::hfp::interop_insert_call_when_sco_start;
 use bt_topshim::profiles::hfp::{
@@ -22,14 +23,16 @@ use bt_topshim::profiles::ProfileConnectionState;
 use bt_topshim::{metrics, topstack};
 use bt_utils::at_command_parser::{calculate_battery_percent, parse_at_command_data};
 use bt_utils::uhid_hfp::{
-    OutputEvent, UHidHfp, BLUETOOTH_TELEPHONY_UHID_REPORT_ID, UHID_INPUT_HOOK_SWITCH,
-    UHID_INPUT_PHONE_MUTE, UHID_OUTPUT_MUTE, UHID_OUTPUT_NONE, UHID_OUTPUT_OFF_HOOK,
-    UHID_OUTPUT_RING,
+    OutputEvent, UHidHfp, BLUETOOTH_TELEPHONY_UHID_REPORT_ID, UHID_CONSUMER_VOICE_COMMAND,
+    UHID_INPUT_HOOK_SWITCH, UHID_INPUT_PHONE_MUTE, UHID_OUTPUT_MUTE, UHID_OUTPUT_NONE,
+    UHID_OUTPUT_OFF_HOOK, UHID_OUTPUT_RING,
 };
-use bt_utils::uinput::UInput;
+use bt_utils::uinput::{KeyMap, KeyMapClass, KeySource, UInput};
 
 use itertools::Itertools;
 use log::{debug, info, warn};
+use num_derive::{FromPrimitive, ToPrimitive};
+use serde_json::Value;
 use std::collections::{HashMap, HashSet};
 use std::convert::{TryFrom, TryInto};
 use std::sync::Arc;
@@ -70,6 +73,35 @@ const CONNECT_AS_INITIATOR_TIMEOUT_SEC: u64 = 5;
 const MEDIA_AUDIO_PROFILES: &[uuid::Profile] =
     &[uuid::Profile::A2dpSink, uuid::Profile::Hfp, uuid::Profile::AvrcpController];
 
+/// Parses the media key map config, which replaces the default uinput key maps per device class:
+///
+///   {"remote_control": [{"source": "avrcp", "op": 68, "key": 164}],
+///    "handsfree": [{"source": "voice_recognition", "key": 582}]}
+///
+/// Returns None if any entry is malformed.
+fn parse_key_map_config(json: &Value) -> Option<Vec<(KeyMapClass, Vec<KeyMap>)>> {
+    json.as_object()?
+        .iter()
+        .map(|(name, keys)| {
+            let class = KeyMapClass::from_name(name)?;
+            let key_map = keys
+                .as_array()?
+                .iter()
+                .map(|key| {
+                    let source = match key.get("source")?.as_str()? {
+                        "avrcp" => KeySource::Avrcp(u8::try_from(key.get("op")?.as_u64()?).ok()?),
+                        "voice_recognition" => KeySource::HfpVoiceRecognition,
+                        _ => return None,
+                    };
+                    let uinput = u32::try_from(key.get("key")?.as_u64()?).ok()?;
+                    Some(KeyMap { source, uinput })
+                })
+                .collect::<Option<Vec<KeyMap>>>()?;
+            Some((class, key_map))
+        })
+        .collect()
+}
+
 pub trait IBluetoothMedia {
     ///
     fn register_callback(&mut self, callback: Box<dyn IBluetoothMediaCallback + Send>) -> bool;
@@ -235,12 +267,98 @@ pub trait IBluetoothTelephony {
     fn audio_connect(&mut self, address: String) -> bool;
     /// Stops the audio connection to <address>.
     fn audio_disconnect(&mut self, address: String);
+    /// Ends the voice recognition session started by <address>, e.g. once the host voice
+    /// assistant is dismissed.
+    fn stop_voice_recognition(&mut self, address: String) -> bool;
 }
 
 pub trait IBluetoothTelephonyCallback: RPCProxy {
     fn on_telephony_use(&mut self, addr: String, state: bool);
 }
 
+/// Passthrough commands that can be sent to a remote AVRCP target.
+#[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, Clone, Copy)]
+#[repr(u32)]
+pub enum AvrcpPassthroughCommand {
+    VolumeUp = 0x41,
+    VolumeDown = 0x42,
+    Play = 0x44,
+    Stop = 0x45,
+    Pause = 0x46,
+    Rewind = 0x48,
+    FastForward = 0x49,
+    Forward = 0x4B,
+    Backward = 0x4C,
+}
+
+/// API for acting as an AVRCP controller toward a remote target, such as a phone.
+pub trait IBluetoothAvrcpController {
+    /// Registers a callback for the connection state, play status, metadata and browse results
+    /// of remote targets. Returns true once registered.
+    fn register_avrcp_controller_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothAvrcpControllerCallback + Send>,
+    ) -> bool;
+
+    /// Returns the addresses of the connected remote targets.
+    fn get_connected_targets(&self) -> Vec<String>;
+    /// Presses and releases a key on the remote target.
+    fn send_passthrough_command(
+        &mut self,
+        address: String,
+        command: AvrcpPassthroughCommand,
+    ) -> bool;
+    /// Gets the last known metadata of the current track.
+    fn get_metadata(&self, address: String) -> PlayerMetadata;
+    /// Gets the last known play status.
+    fn get_play_status(&self, address: String) -> AvrcpPlayStatus;
+    /// Gets the last known play position in milliseconds.
+    fn get_play_position(&self, address: String) -> u32;
+    /// Gets the length of the current track in milliseconds, as last reported with the play
+    /// position.
+    fn get_play_length(&self, address: String) -> u32;
+    /// Requests the metadata and play status again. Results are sent through callbacks.
+    fn refresh_play_status(&mut self, address: String) -> bool;
+    /// Requests the media players in [start, end]. Results are sent through
+    /// |on_browse_items|.
+    fn get_player_list(&mut self, address: String, start: u32, end: u32) -> bool;
+    /// Requests the items of the now playing list in [start, end].
+    fn get_now_playing_list(&mut self, address: String, start: u32, end: u32) -> bool;
+    /// Requests the items of the current folder of the browsed player in [start, end].
+    fn get_folder_list(&mut self, address: String, start: u32, end: u32) -> bool;
+    /// Moves into the folder |uid|, or to the parent folder if |uid| is None.
+    fn change_folder(&mut self, address: String, uid: Option<u64>) -> bool;
+    /// Sets the player to browse.
+    fn set_browsed_player(&mut self, address: String, player_id: u16) -> bool;
+    /// Sets the player that receives the passthrough commands.
+    fn set_addressed_player(&mut self, address: String, player_id: u16) -> bool;
+    /// Plays the item |uid| from the given scope.
+    fn play_item(&mut self, address: String, scope: AvrcpBrowseScope, uid: u64) -> bool;
+}
+
+pub trait IBluetoothAvrcpControllerCallback: RPCProxy {
+    /// Triggered when the AVRCP control channel to a remote target changes.
+    fn on_target_connection_state_changed(&mut self, addr: String, connected: bool);
+    /// Triggered when the metadata of the current track changes.
+    fn on_track_changed(&mut self, addr: String, metadata: PlayerMetadata);
+    /// Triggered when the play status changes.
+    fn on_play_status_changed(&mut self, addr: String, status: AvrcpPlayStatus);
+    /// Triggered when the play position changes. Positions are in milliseconds.
+    fn on_play_position_changed(&mut self, addr: String, length: u32, position: u32);
+    /// Triggered with the result of a browsing request.
+    fn on_browse_items(&mut self, addr: String, success: bool, items: Vec<AvrcpBrowseItem>);
+    /// Triggered when the browsed folder changes.
+    fn on_folder_changed(&mut self, addr: String, num_items: u32);
+    /// Triggered when the browsed player is set.
+    fn on_browsed_player_changed(&mut self, addr: String, num_items: u32, depth: u32);
+    /// Triggered when the remote target changes the addressed player.
+    fn on_addressed_player_changed(&mut self, addr: String, player_id: u16);
+    /// Triggered when the now playing list changes.
+    fn on_now_playing_changed(&mut self, addr: String);
+    /// Triggered when the list of available players changes.
+    fn on_available_players_changed(&mut self, addr: String);
+}
+
 /// Serializable device used in.
 #[derive(Debug, Default, Clone)]
 pub struct BluetoothAudioDevice {
@@ -279,6 +397,15 @@ enum DeviceConnectionStates {
     WaitingConnection,     // Waiting for new connections initiated by peer
 }
 
+/// What is known about the player of a remote AVRCP target.
+#[derive(Debug, Default)]
+struct AvrcpTargetState {
+    metadata: PlayerMetadata,
+    play_status: AvrcpPlayStatus,
+    position_ms: u32,
+    length_ms: u32,
+}
+
 struct UHid {
     pub handle: UHidHfp,
     pub volume: u8,
@@ -292,11 +419,14 @@ pub struct BluetoothMedia {
     initialized: bool,
     callbacks: Arc<Mutex<Callbacks<dyn IBluetoothMediaCallback + Send>>>,
     telephony_callbacks: Arc<Mutex<Callbacks<dyn IBluetoothTelephonyCallback + Send>>>,
+    avrcp_ctrl_callbacks: Arc<Mutex<Callbacks<dyn IBluetoothAvrcpControllerCallback + Send>>>,
     tx: Sender<Message>,
     adapter: Option<Arc<Mutex<Box<Bluetooth>>>>,
     a2dp: Option<A2dp>,
     avrcp: Option<Avrcp>,
     avrcp_direction: BtConnectionDirection,
+    avrcp_ctrl: Option<AvrcpCtrl>,
+    avrcp_targets: HashMap<RawAddress, AvrcpTargetState>,
     a2dp_states: HashMap<RawAddress, BtavConnectionState>,
     a2dp_audio_state: HashMap<RawAddress, BtavAudioState>,
     a2dp_has_interrupted_stream: bool, // Only used for qualification.
@@ -320,6 +450,7 @@ pub struct BluetoothMedia {
     memory_dialing_number: Option<String>,
     last_dialing_number: Option<String>,
     uhid: HashMap<RawAddress, UHid>,
+    voice_recognition_active: HashSet<RawAddress>,
 }
 
 impl BluetoothMedia {
@@ -327,12 +458,13 @@ impl BluetoothMedia {
         tx: Sender<Message>,
         intf: Arc<Mutex<BluetoothInterface>>,
         battery_provider_manager: Arc<Mutex<Box<BatteryProviderManager>>>,
+        key_map_path: String,
     ) -> BluetoothMedia {
         let battery_provider_id = battery_provider_manager
             .lock()
             .unwrap()
             .register_battery_provider(Box::new(BatteryProviderCallback::new()));
-        BluetoothMedia {
+        let mut media = BluetoothMedia {
             intf,
             battery_provider_manager,
             battery_provider_id,
@@ -345,11 +477,17 @@ impl BluetoothMedia {
                 tx.clone(),
                 Message::TelephonyCallbackDisconnected,
             ))),
+            avrcp_ctrl_callbacks: Arc::new(Mutex::new(Callbacks::new(
+                tx.clone(),
+                Message::AvrcpControllerCallbackDisconnected,
+            ))),
             tx,
             adapter: None,
             a2dp: None,
             avrcp: None,
             avrcp_direction: BtConnectionDirection::Unknown,
+            avrcp_ctrl: None,
+            avrcp_targets: HashMap::new(),
             a2dp_states: HashMap::new(),
             a2dp_audio_state: HashMap::new(),
             a2dp_has_interrupted_stream: false,
@@ -373,7 +511,27 @@ impl BluetoothMedia {
             memory_dialing_number: None,
             last_dialing_number: None,
             uhid: HashMap::new(),
+            voice_recognition_active: HashSet::new(),
+        };
+
+        if media.load_key_map_config(&key_map_path).is_err() {
+            info!("No media key map config loaded, using the default key maps");
         }
+        media
+    }
+
+    fn load_key_map_config(&mut self, path: &str) -> std::io::Result<()> {
+        let contents = std::fs::read_to_string(path)?;
+        let json = serde_json::from_str::<Value>(contents.as_str())?;
+        match parse_key_map_config(&json) {
+            Some(key_maps) => {
+                for (class, key_map) in key_maps {
+                    self.uinput.set_key_map(class, key_map);
+                }
+            }
+            None => warn!("Malformed media key map config in {}", path),
+        }
+        Ok(())
     }
 
     fn is_profile_connected(&self, addr: &RawAddress, profile: &uuid::Profile) -> bool {
@@ -437,6 +595,11 @@ impl BluetoothMedia {
                     avrcp.enable();
                 }
             }
+            &Profile::AvrcpController => {
+                if let Some(avrcp_ctrl) = &mut self.avrcp_ctrl {
+                    avrcp_ctrl.enable();
+                }
+            }
             &Profile::Hfp => {
                 if let Some(hfp) = &mut self.hfp {
                     hfp.enable();
@@ -467,6 +630,12 @@ impl BluetoothMedia {
                     avrcp.disable();
                 }
             }
+            &Profile::AvrcpController => {
+                if let Some(avrcp_ctrl) = &mut self.avrcp_ctrl {
+                    avrcp_ctrl.disable();
+                }
+                self.avrcp_targets.clear();
+            }
             &Profile::Hfp => {
                 if let Some(hfp) = &mut self.hfp {
                     hfp.disable();
@@ -489,6 +658,9 @@ impl BluetoothMedia {
             &Profile::AvrcpTarget => {
                 Some(self.avrcp.as_ref().map_or(false, |avrcp| avrcp.is_enabled()))
             }
+            &Profile::AvrcpController => {
+                Some(self.avrcp_ctrl.as_ref().map_or(false, |ctrl| ctrl.is_enabled()))
+            }
             &Profile::Hfp => Some(self.hfp.as_ref().map_or(false, |hfp| hfp.is_enabled())),
             _ => {
                 warn!("Tried to query enablement status of {} in bluetooth_media", profile);
@@ -556,7 +728,11 @@ impl BluetoothMedia {
                     supported
                 );
 
-                match self.uinput.create(self.adapter_get_remote_name(addr), addr.to_string()) {
+                match self.uinput.create(
+                    self.adapter_get_remote_name(addr),
+                    addr.to_string(),
+                    self.adapter_get_remote_class(addr),
+                ) {
                     Ok(()) => info!("uinput device created for: {}", DisplayAddress(&addr)),
                     Err(e) => warn!("{}", e),
                 }
@@ -680,6 +856,96 @@ impl BluetoothMedia {
         }
     }
 
+    pub fn dispatch_avrcp_ctrl_callbacks(&mut self, cb: AvrcpCtrlCallbacks) {
+        match cb {
+            AvrcpCtrlCallbacks::ConnectionState(rc_connected, _bt_connected, addr) => {
+                info!("[{}]: avrcp target connected: {}.", DisplayAddress(&addr), rc_connected);
+                if rc_connected {
+                    self.avrcp_targets.insert(addr, AvrcpTargetState::default());
+                } else if self.avrcp_targets.remove(&addr).is_none() {
+                    return;
+                }
+
+                self.avrcp_ctrl_callbacks.lock().unwrap().for_all_callbacks(|callback| {
+                    callback.on_target_connection_state_changed(addr.to_string(), rc_connected);
+                });
+            }
+            AvrcpCtrlCallbacks::TrackChanged(addr, metadata) => {
+                if let Some(target) = self.avrcp_targets.get_mut(&addr) {
+                    target.metadata = metadata.clone();
+                }
+                self.avrcp_ctrl_callbacks.lock().unwrap().for_all_callbacks(|callback| {
+                    callback.on_track_changed(addr.to_string(), metadata.clone());
+                });
+            }
+            AvrcpCtrlCallbacks::PlayPositionChanged(addr, length, position) => {
+                if let Some(target) = self.avrcp_targets.get_mut(&addr) {
+                    target.length_ms = length;
+                    target.position_ms = position;
+                }
+                self.avrcp_ctrl_callbacks.lock().unwrap().for_all_callbacks(|callback| {
+                    callback.on_play_position_changed(addr.to_string(), length, position);
+                });
+            }
+            AvrcpCtrlCallbacks::PlayStatusChanged(addr, status) => {
+                if let Some(target) = self.avrcp_targets.get_mut(&addr) {
+                    target.play_status = status;
+                }
+                self.avrcp_ctrl_callbacks.lock().unwrap().for_all_callbacks(|callback| {
+                    callback.on_play_status_changed(addr.to_string(), status);
+                });
+            }
+            AvrcpCtrlCallbacks::GetFolderItems(addr, success, items) => {
+                self.avrcp_ctrl_callbacks.lock().unwrap().for_all_callbacks(|callback| {
+                    callback.on_browse_items(addr.to_string(), success, items.clone());
+                });
+            }
+            AvrcpCtrlCallbacks::ChangeFolderPath(addr, num_items) => {
+                self.avrcp_ctrl_callbacks.lock().unwrap().for_all_callbacks(|callback| {
+                    callback.on_folder_changed(addr.to_string(), num_items);
+                });
+            }
+            AvrcpCtrlCallbacks::SetBrowsedPlayer(addr, num_items, depth) => {
+                self.avrcp_ctrl_callbacks.lock().unwrap().for_all_callbacks(|callback| {
+                    callback.on_browsed_player_changed(
+                        addr.to_string(),
+                        num_items.into(),
+                        depth.into(),
+                    );
+                });
+            }
+            AvrcpCtrlCallbacks::AddressedPlayerChanged(addr, player_id) => {
+                self.avrcp_ctrl_callbacks.lock().unwrap().for_all_callbacks(|callback| {
+                    callback.on_addressed_player_changed(addr.to_string(), player_id);
+                });
+            }
+            AvrcpCtrlCallbacks::NowPlayingContentsChanged(addr) => {
+                self.avrcp_ctrl_callbacks.lock().unwrap().for_all_callbacks(|callback| {
+                    callback.on_now_playing_changed(addr.to_string());
+                });
+            }
+            AvrcpCtrlCallbacks::AvailablePlayerChanged(addr) => {
+                self.avrcp_ctrl_callbacks.lock().unwrap().for_all_callbacks(|callback| {
+                    callback.on_available_players_changed(addr.to_string());
+                });
+            }
+            AvrcpCtrlCallbacks::PassthroughRsp(addr, id, key_state) => {
+                debug!(
+                    "[{}]: avrcp passthrough response id={} state={}.",
+                    DisplayAddress(&addr),
+                    id,
+                    key_state
+                );
+            }
+            AvrcpCtrlCallbacks::GetRCFeatures(addr, features) => {
+                debug!("[{}]: avrcp target features {:#x}.", DisplayAddress(&addr), features);
+            }
+            AvrcpCtrlCallbacks::GetCoverArtPsm(addr, psm) => {
+                debug!("[{}]: avrcp target cover art psm {}.", DisplayAddress(&addr), psm);
+            }
+        }
+    }
+
     pub fn dispatch_media_actions(&mut self, action: MediaActions) {
         match action {
             MediaActions::Connect(address) => self.connect(address),
@@ -731,6 +997,7 @@ impl BluetoothMedia {
                     BthfConnectionState::Disconnected => {
                         info!("[{}]: hfp disconnected.", DisplayAddress(&addr));
                         self.uhid_destroy(&addr);
+                        self.voice_recognition_active.remove(&addr);
                         self.hfp_states.remove(&addr);
                         self.hfp_cap.remove(&addr);
                         self.hfp_audio_state.remove(&addr);
@@ -985,6 +1252,23 @@ impl BluetoothMedia {
                     None => warn!("Uninitialized HFP to notify telephony status"),
                 };
             }
+            HfpCallbacks::VoiceRecognition(enabled, addr) => {
+                // The headset button is forwarded as a voice assistant key press. The session
+                // stays active until the headset cancels it or the host assistant ends it
+                // through |stop_voice_recognition|.
+                self.simple_at_response(true, addr);
+                if enabled == self.voice_recognition_active.contains(&addr) {
+                    return;
+                }
+
+                if enabled {
+                    self.voice_recognition_active.insert(addr);
+                } else {
+                    self.voice_recognition_active.remove(&addr);
+                }
+                // The assistant key toggles the host assistant, so the same press ends it.
+                self.send_voice_command(addr);
+            }
             HfpCallbacks::AnswerCall(addr) => {
                 if !self.answer_call_impl() {
                     warn!("[{}]: answer_call triggered by ATA failed", DisplayAddress(&addr));
@@ -1109,6 +1393,22 @@ impl BluetoothMedia {
         self.telephony_callbacks.lock().unwrap().remove_callback(id)
     }
 
+    pub fn remove_avrcp_controller_callback(&mut self, id: u32) -> bool {
+        self.avrcp_ctrl_callbacks.lock().unwrap().remove_callback(id)
+    }
+
+    /// Returns the AVRCP controller and the address if |address| is a connected target.
+    fn get_avrcp_ctrl_for(&mut self, address: &String) -> Option<(&mut AvrcpCtrl, RawAddress)> {
+        let addr = match RawAddress::from_string(address.clone()) {
+            Some(addr) if self.avrcp_targets.contains_key(&addr) => addr,
+            _ => {
+                warn!("Invalid or not connected avrcp target: {}", address);
+                return None;
+            }
+        };
+        self.avrcp_ctrl.as_mut().map(|ctrl| (ctrl, addr))
+    }
+
     fn uhid_create(&mut self, addr: RawAddress) {
         debug!(
             "[{}]: UHID create: PhoneOpsEnabled {}",
@@ -1170,6 +1470,20 @@ impl BluetoothMedia {
         );
     }
 
+    fn send_voice_command(&mut self, addr: RawAddress) {
+        // Prefer the telephony HID device so the press isn't seen twice by the system.
+        let result = match self.uhid.get_mut(&addr) {
+            Some(uhid) => uhid
+                .handle
+                .send_consumer_keys(UHID_CONSUMER_VOICE_COMMAND)
+                .map_err(|e| format!("UHID: {}", e)),
+            None => self.uinput.send_voice_command(addr.to_string()),
+        };
+        if let Err(e) = result {
+            debug!("[{}]: Fail to send voice command: {}", DisplayAddress(&addr), e);
+        }
+    }
+
     fn uhid_destroy(&mut self, addr: &RawAddress) {
         if let Some(uhid) = self.uhid.get_mut(addr) {
             debug!("[{}]: UHID destroy", DisplayAddress(&addr));
@@ -1242,15 +1556,21 @@ impl BluetoothMedia {
             if mute == UHID_OUTPUT_MUTE && !uhid.muted {
                 uhid.muted = true;
                 self.set_hfp_mic_volume(0, addr);
+                // Confirm the new phone mute state to the host.
+                self.uhid_send_input_report(&addr);
             } else if mute != UHID_OUTPUT_MUTE && uhid.muted {
                 uhid.muted = false;
                 let saved_volume = uhid.volume;
                 self.set_hfp_mic_volume(saved_volume, addr);
+                self.uhid_send_input_report(&addr);
             }
 
             let call_state = data & (UHID_OUTPUT_RING | UHID_OUTPUT_OFF_HOOK);
             if call_state == UHID_OUTPUT_NONE {
-                self.hangup_call();
+                if self.hangup_call() {
+                    // Confirm the hook switch is released to the host.
+                    self.uhid_send_input_report(&addr);
+                }
             } else if call_state == UHID_OUTPUT_RING {
                 self.incoming_call("".into());
             } else if call_state == UHID_OUTPUT_OFF_HOOK {
@@ -1649,6 +1969,15 @@ impl BluetoothMedia {
         }
     }
 
+    fn adapter_get_remote_class(&self, addr: RawAddress) -> u32 {
+        let device = BluetoothDevice::new(addr.to_string(), "".to_string());
+        if let Some(adapter) = &self.adapter {
+            adapter.lock().unwrap().get_remote_class(device)
+        } else {
+            0
+        }
+    }
+
     fn adapter_get_audio_profiles(&self, addr: RawAddress) -> HashSet<uuid::Profile> {
         let device = BluetoothDevice::new(addr.to_string(), "".to_string());
         if let Some(adapter) = &self.adapter {
@@ -2202,6 +2531,17 @@ fn get_avrcp_dispatcher(tx: Sender<Message>) -> AvrcpCallbacksDispatcher {
     }
 }
 
+fn get_avrcp_ctrl_dispatcher(tx: Sender<Message>) -> AvrcpCtrlCallbacksDispatcher {
+    AvrcpCtrlCallbacksDispatcher {
+        dispatch: Box::new(move |cb| {
+            let txl = tx.clone();
+            topstack::get_runtime().spawn(async move {
+                let _ = txl.send(Message::AvrcpCtrl(cb)).await;
+            });
+        }),
+    }
+}
+
 fn get_hfp_dispatcher(tx: Sender<Message>) -> HfpCallbacksDispatcher {
     HfpCallbacksDispatcher {
         dispatch: Box::new(move |cb| {
@@ -2235,6 +2575,11 @@ impl IBluetoothMedia for BluetoothMedia {
         self.avrcp = Some(Avrcp::new(&self.intf.lock().unwrap()));
         self.avrcp.as_mut().unwrap().initialize(avrcp_dispatcher);
 
+        // AVRCP controller
+        let avrcp_ctrl_dispatcher = get_avrcp_ctrl_dispatcher(self.tx.clone());
+        self.avrcp_ctrl = Some(AvrcpCtrl::new(&self.intf.lock().unwrap()));
+        self.avrcp_ctrl.as_mut().unwrap().initialize(avrcp_ctrl_dispatcher);
+
         // HFP
         let hfp_dispatcher = get_hfp_dispatcher(self.tx.clone());
         self.hfp = Some(Hfp::new(&self.intf.lock().unwrap()));
@@ -2242,12 +2587,15 @@ impl IBluetoothMedia for BluetoothMedia {
 
         // TODO(b/284811956) A2DP needs to be enabled before AVRCP otherwise AVRCP gets memset'd.
         // Iterate the delay_enable_profiles hashmap directly when this is fixed.
-        let profile_order = vec![Profile::A2dpSource, Profile::AvrcpTarget, Profile::Hfp];
+        // The AVRCP controller is enabled after the target so that it is not reset by it.
+        let profile_order =
+            vec![Profile::A2dpSource, Profile::AvrcpTarget, Profile::AvrcpController, Profile::Hfp];
         for profile in profile_order {
             if self.delay_enable_profiles.contains(&profile) {
                 self.enable_profile(&profile);
             }
         }
+
         true
     }
 
@@ -3096,6 +3444,156 @@ impl IBluetoothTelephony for BluetoothMedia {
     fn audio_disconnect(&mut self, address: String) {
         self.stop_sco_call_impl(address)
     }
+
+    fn stop_voice_recognition(&mut self, address: String) -> bool {
+        let addr = match RawAddress::from_string(address.clone()) {
+            None => {
+                warn!("Can't stop voice recognition: invalid address {}", address);
+                return false;
+            }
+            Some(addr) => addr,
+        };
+        if !self.voice_recognition_active.remove(&addr) {
+            return false;
+        }
+
+        match self.hfp.as_mut() {
+            Some(hfp) => {
+                let status = hfp.stop_voice_recognition(addr);
+                if status != BtStatus::Success {
+                    warn!("[{}]: +BVRA: 0 not sent, status={:?}", DisplayAddress(&addr), status);
+                    return false;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl IBluetoothAvrcpController for BluetoothMedia {
+    fn register_avrcp_controller_callback(
+        &mut self,
+        callback: Box<dyn IBluetoothAvrcpControllerCallback + Send>,
+    ) -> bool {
+        let _id = self.avrcp_ctrl_callbacks.lock().unwrap().add_callback(callback);
+        true
+    }
+
+    fn get_connected_targets(&self) -> Vec<String> {
+        self.avrcp_targets.keys().map(|addr| addr.to_string()).collect()
+    }
+
+    fn send_passthrough_command(
+        &mut self,
+        address: String,
+        command: AvrcpPassthroughCommand,
+    ) -> bool {
+        // Key states as defined by AVRCP.
+        const KEY_PRESSED: u8 = 0;
+        const KEY_RELEASED: u8 = 1;
+
+        let (ctrl, addr) = match self.get_avrcp_ctrl_for(&address) {
+            Some(found) => found,
+            None => return false,
+        };
+        let key = command as u8;
+        ctrl.send_pass_through_cmd(addr, key, KEY_PRESSED) == BtStatus::Success
+            && ctrl.send_pass_through_cmd(addr, key, KEY_RELEASED) == BtStatus::Success
+    }
+
+    fn get_metadata(&self, address: String) -> PlayerMetadata {
+        RawAddress::from_string(address)
+            .and_then(|addr| self.avrcp_targets.get(&addr))
+            .map_or(PlayerMetadata::default(), |target| target.metadata.clone())
+    }
+
+    fn get_play_status(&self, address: String) -> AvrcpPlayStatus {
+        RawAddress::from_string(address)
+            .and_then(|addr| self.avrcp_targets.get(&addr))
+            .map_or(AvrcpPlayStatus::default(), |target| target.play_status)
+    }
+
+    fn get_play_position(&self, address: String) -> u32 {
+        RawAddress::from_string(address)
+            .and_then(|addr| self.avrcp_targets.get(&addr))
+            .map_or(0, |target| target.position_ms)
+    }
+
+    fn get_play_length(&self, address: String) -> u32 {
+        RawAddress::from_string(address)
+            .and_then(|addr| self.avrcp_targets.get(&addr))
+            .map_or(0, |target| target.length_ms)
+    }
+
+    fn refresh_play_status(&mut self, address: String) -> bool {
+        let (ctrl, addr) = match self.get_avrcp_ctrl_for(&address) {
+            Some(found) => found,
+            None => return false,
+        };
+        ctrl.get_current_metadata(addr) == BtStatus::Success
+            && ctrl.get_playback_state(addr) == BtStatus::Success
+    }
+
+    fn get_player_list(&mut self, address: String, start: u32, end: u32) -> bool {
+        match self.get_avrcp_ctrl_for(&address) {
+            Some((ctrl, addr)) => ctrl.get_player_list(addr, start, end) == BtStatus::Success,
+            None => false,
+        }
+    }
+
+    fn get_now_playing_list(&mut self, address: String, start: u32, end: u32) -> bool {
+        match self.get_avrcp_ctrl_for(&address) {
+            Some((ctrl, addr)) => ctrl.get_now_playing_list(addr, start, end) == BtStatus::Success,
+            None => false,
+        }
+    }
+
+    fn get_folder_list(&mut self, address: String, start: u32, end: u32) -> bool {
+        match self.get_avrcp_ctrl_for(&address) {
+            Some((ctrl, addr)) => ctrl.get_folder_list(addr, start, end) == BtStatus::Success,
+            None => false,
+        }
+    }
+
+    fn change_folder(&mut self, address: String, uid: Option<u64>) -> bool {
+        // Folder directions as defined by AVRCP.
+        const FOLDER_UP: u8 = 0;
+        const FOLDER_DOWN: u8 = 1;
+
+        let (direction, uid) = match uid {
+            Some(uid) => (FOLDER_DOWN, uid),
+            None => (FOLDER_UP, 0),
+        };
+        match self.get_avrcp_ctrl_for(&address) {
+            Some((ctrl, addr)) => {
+                ctrl.change_folder_path(addr, direction, uid) == BtStatus::Success
+            }
+            None => false,
+        }
+    }
+
+    fn set_browsed_player(&mut self, address: String, player_id: u16) -> bool {
+        match self.get_avrcp_ctrl_for(&address) {
+            Some((ctrl, addr)) => ctrl.set_browsed_player(addr, player_id) == BtStatus::Success,
+            None => false,
+        }
+    }
+
+    fn set_addressed_player(&mut self, address: String, player_id: u16) -> bool {
+        match self.get_avrcp_ctrl_for(&address) {
+            Some((ctrl, addr)) => ctrl.set_addressed_player(addr, player_id) == BtStatus::Success,
+            None => false,
+        }
+    }
+
+    fn play_item(&mut self, address: String, scope: AvrcpBrowseScope, uid: u64) -> bool {
+        match self.get_avrcp_ctrl_for(&address) {
+            // The UID counter is only meaningful for database aware players, which also accept 0.
+            Some((ctrl, addr)) => ctrl.play_item(addr, scope, uid, 0) == BtStatus::Success,
+            None => false,
+        }
+    }
 }
 
 struct BatteryProviderCallback {}
@@ -3116,3 +3614,45 @@ impl RPCProxy for BatteryProviderCallback {
         "HFP BatteryProvider Callback".to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_map_config() {
+        let json = serde_json::json!({
+            "remote_control": [{"source": "avrcp", "op": 68, "key": 164}],
+            "handsfree": [{"source": "voice_recognition", "key": 582}],
+        });
+        let mut key_maps = parse_key_map_config(&json).unwrap();
+        key_maps.sort_by_key(|(class, _)| format!("{:?}", class));
+
+        assert_eq!(
+            key_maps,
+            vec![
+                (
+                    KeyMapClass::Handsfree,
+                    vec![KeyMap { source: KeySource::HfpVoiceRecognition, uinput: 582 }]
+                ),
+                (
+                    KeyMapClass::RemoteControl,
+                    vec![KeyMap { source: KeySource::Avrcp(68), uinput: 164 }]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_key_map_config_malformed() {
+        let unknown_class = serde_json::json!({"speaker": []});
+        let unknown_source = serde_json::json!({"other": [{"source": "hid", "key": 164}]});
+        let bad_op = serde_json::json!({"other": [{"source": "avrcp", "op": 256, "key": 164}]});
+        let missing_key = serde_json::json!({"other": [{"source": "avrcp", "op": 68}]});
+
+        assert_eq!(parse_key_map_config(&unknown_class), None);
+        assert_eq!(parse_key_map_config(&unknown_source), None);
+        assert_eq!(parse_key_map_config(&bad_op), None);
+        assert_eq!(parse_key_map_config(&missing_key), None);
+    }
+}
@@ -1,42 +1,284 @@
-//! TODO(b/277818879) - Temporary DIS implementation
+//! Anything related to the Device Information Service (DIS).
+//!
+//! The local DIS is exported on the GATT server with values from a config file, and the DIS of
+//! remote LE devices is read on connection into the remote device properties of the adapter.
 
 use log;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Result};
 use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc::Sender;
 
+use crate::bluetooth::{Bluetooth, BluetoothDevice};
 use crate::bluetooth_gatt::{
-    BluetoothGatt, BluetoothGattCharacteristic, BluetoothGattService, GattDbElementType,
-    IBluetoothGatt, IBluetoothGattServerCallback,
+    value_at_offset, BluetoothGatt, BluetoothGattCharacteristic, BluetoothGattService,
+    GattDbElementType, IBluetoothGatt, IBluetoothGattCallback, IBluetoothGattServerCallback,
 };
 use crate::uuid::{Profile, UuidHelper};
 use crate::{Message, RPCProxy};
+use bt_topshim::btif::{BtTransport, BtVendorProductInfo, Uuid128Bit};
 use bt_topshim::profiles::gatt::{GattStatus, LePhy};
 use bt_topshim::sysprop;
 
 /// Random uuid generated for registering against gatt server.
 const DIS_APP_RANDOM_UUID: &str = "1b518948-fd77-4459-906f-4923104bb639";
 
-/// UUID for PNP ID characteristic.
+/// Random uuid generated for registering against gatt client.
+const DIS_CLIENT_APP_RANDOM_UUID: &str = "8d3b2b5e-4a3c-4e0f-a6f2-0f35c8b2d7e4";
+
+/// UUIDs for the DIS characteristics.
+const SYSTEM_ID_CHAR_UUID: &str = "00002A23-0000-1000-8000-00805F9B34FB";
+const MODEL_NUMBER_CHAR_UUID: &str = "00002A24-0000-1000-8000-00805F9B34FB";
+const SERIAL_NUMBER_CHAR_UUID: &str = "00002A25-0000-1000-8000-00805F9B34FB";
+const FIRMWARE_REVISION_CHAR_UUID: &str = "00002A26-0000-1000-8000-00805F9B34FB";
+const HARDWARE_REVISION_CHAR_UUID: &str = "00002A27-0000-1000-8000-00805F9B34FB";
+const SOFTWARE_REVISION_CHAR_UUID: &str = "00002A28-0000-1000-8000-00805F9B34FB";
+const MANUFACTURER_NAME_CHAR_UUID: &str = "00002A29-0000-1000-8000-00805F9B34FB";
 const PNP_ID_CHAR_UUID: &str = "00002A50-0000-1000-8000-00805F9B34FB";
 
-/// Handles exporting the Device Information Service (DIS).
+/// Length of the System ID characteristic value.
+const SYSTEM_ID_LEN: usize = 8;
+
+/// Length of the PNP ID characteristic value.
+const PNP_ID_LEN: usize = 7;
+
+/// The values of the Device Information Service. Fields that are not available are left empty.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeviceInformationValues {
+    pub manufacturer_name: String,
+    pub model_number: String,
+    pub serial_number: String,
+    pub hardware_revision: String,
+    pub firmware_revision: String,
+    pub software_revision: String,
+    /// System ID, 8 octets in little endian if present.
+    pub system_id: Vec<u8>,
+}
+
+impl DeviceInformationValues {
+    fn from_json(json: &Value) -> Option<DeviceInformationValues> {
+        let get_string = |key: &str| -> Option<String> {
+            match json.get(key) {
+                Some(v) => Some(String::from(v.as_str()?)),
+                None => Some(String::new()),
+            }
+        };
+        let system_id = match json.get("system_id") {
+            Some(v) => parse_system_id(v.as_str()?)?,
+            None => vec![],
+        };
+
+        Some(DeviceInformationValues {
+            manufacturer_name: get_string("manufacturer_name")?,
+            model_number: get_string("model_number")?,
+            serial_number: get_string("serial_number")?,
+            hardware_revision: get_string("hardware_revision")?,
+            firmware_revision: get_string("firmware_revision")?,
+            software_revision: get_string("software_revision")?,
+            system_id,
+        })
+    }
+
+    fn get_value(&self, characteristic: DisCharacteristic) -> Vec<u8> {
+        match characteristic {
+            DisCharacteristic::ManufacturerName => self.manufacturer_name.as_bytes().to_vec(),
+            DisCharacteristic::ModelNumber => self.model_number.as_bytes().to_vec(),
+            DisCharacteristic::SerialNumber => self.serial_number.as_bytes().to_vec(),
+            DisCharacteristic::HardwareRevision => self.hardware_revision.as_bytes().to_vec(),
+            DisCharacteristic::FirmwareRevision => self.firmware_revision.as_bytes().to_vec(),
+            DisCharacteristic::SoftwareRevision => self.software_revision.as_bytes().to_vec(),
+            DisCharacteristic::SystemId => self.system_id.clone(),
+            DisCharacteristic::PnpId => get_local_pnp_id(),
+        }
+    }
+
+    fn set_value(&mut self, characteristic: DisCharacteristic, value: &Vec<u8>) {
+        // Strings are UTF-8 but some devices pad them with NULs.
+        let as_string = || String::from_utf8_lossy(value).trim_end_matches('\0').to_string();
+        match characteristic {
+            DisCharacteristic::ManufacturerName => self.manufacturer_name = as_string(),
+            DisCharacteristic::ModelNumber => self.model_number = as_string(),
+            DisCharacteristic::SerialNumber => self.serial_number = as_string(),
+            DisCharacteristic::HardwareRevision => self.hardware_revision = as_string(),
+            DisCharacteristic::FirmwareRevision => self.firmware_revision = as_string(),
+            DisCharacteristic::SoftwareRevision => self.software_revision = as_string(),
+            DisCharacteristic::SystemId => {
+                if value.len() == SYSTEM_ID_LEN {
+                    self.system_id = value.clone();
+                }
+            }
+            // PNP ID is reported to the adapter as the vendor and product info.
+            DisCharacteristic::PnpId => (),
+        }
+    }
+}
+
+/// Characteristics of the Device Information Service.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DisCharacteristic {
+    ManufacturerName,
+    ModelNumber,
+    SerialNumber,
+    HardwareRevision,
+    FirmwareRevision,
+    SoftwareRevision,
+    SystemId,
+    PnpId,
+}
+
+impl DisCharacteristic {
+    const ALL: [DisCharacteristic; 8] = [
+        DisCharacteristic::ManufacturerName,
+        DisCharacteristic::ModelNumber,
+        DisCharacteristic::SerialNumber,
+        DisCharacteristic::HardwareRevision,
+        DisCharacteristic::FirmwareRevision,
+        DisCharacteristic::SoftwareRevision,
+        DisCharacteristic::SystemId,
+        DisCharacteristic::PnpId,
+    ];
+
+    fn uuid(&self) -> Uuid128Bit {
+        let uuid = match self {
+            DisCharacteristic::ManufacturerName => MANUFACTURER_NAME_CHAR_UUID,
+            DisCharacteristic::ModelNumber => MODEL_NUMBER_CHAR_UUID,
+            DisCharacteristic::SerialNumber => SERIAL_NUMBER_CHAR_UUID,
+            DisCharacteristic::HardwareRevision => HARDWARE_REVISION_CHAR_UUID,
+            DisCharacteristic::FirmwareRevision => FIRMWARE_REVISION_CHAR_UUID,
+            DisCharacteristic::SoftwareRevision => SOFTWARE_REVISION_CHAR_UUID,
+            DisCharacteristic::SystemId => SYSTEM_ID_CHAR_UUID,
+            DisCharacteristic::PnpId => PNP_ID_CHAR_UUID,
+        };
+        UuidHelper::from_string(uuid).expect("DIS characteristic uuid is malformed")
+    }
+
+    fn from_uuid(uuid: &Uuid128Bit) -> Option<DisCharacteristic> {
+        DisCharacteristic::ALL.iter().find(|c| &c.uuid() == uuid).cloned()
+    }
+}
+
+/// Parses a System ID given as a 64-bit number in 16 hex digits, most significant first, into
+/// its 8 octet value in little endian.
+fn parse_system_id(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() != SYSTEM_ID_LEN * 2 {
+        return None;
+    }
+    (0..SYSTEM_ID_LEN)
+        .rev()
+        .map(|i| u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok())
+        .collect()
+}
+
+/// Builds the PNP ID value of the local device from sysprops.
+fn get_local_pnp_id() -> Vec<u8> {
+    let vendor_id = sysprop::get_i32(sysprop::PropertyI32::VendorId);
+    let vendor_id_source = sysprop::get_i32(sysprop::PropertyI32::VendorIdSource);
+    let product_id = sysprop::get_i32(sysprop::PropertyI32::ProductId);
+    let product_version = sysprop::get_i32(sysprop::PropertyI32::ProductVersion);
+
+    // PNP ID ordering (all values are in little endian):
+    // - Vendor ID source (1 octet)
+    // - Vendor ID (2 octet)
+    // - Product ID (2 octet)
+    // - Product Version (2 octet)
+    let mut value: Vec<u8> = Vec::new();
+    value.push(vendor_id_source.to_le_bytes()[0]);
+    value.extend_from_slice(&vendor_id.to_le_bytes()[0..2]);
+    value.extend_from_slice(&product_id.to_le_bytes()[0..2]);
+    value.extend_from_slice(&product_version.to_le_bytes()[0..2]);
+    value
+}
+
+/// Parses a remote PNP ID value. See |get_local_pnp_id| for the layout.
+fn parse_pnp_id(value: &Vec<u8>) -> Option<BtVendorProductInfo> {
+    if value.len() < PNP_ID_LEN {
+        return None;
+    }
+    Some(BtVendorProductInfo {
+        vendor_id_src: value[0],
+        vendor_id: u16::from_le_bytes([value[1], value[2]]),
+        product_id: u16::from_le_bytes([value[3], value[4]]),
+        version: u16::from_le_bytes([value[5], value[6]]),
+    })
+}
+
+/// DIS characteristics of a remote device that are still being read.
+struct PendingRead {
+    values: DeviceInformationValues,
+    pnp_id: Option<BtVendorProductInfo>,
+    remaining: HashMap<i32, DisCharacteristic>,
+}
+
+/// Handles exporting the Device Information Service (DIS) and reading it from remote devices.
 pub struct DeviceInformation {
+    /// Path of the config file with the values to export.
+    path: String,
+
+    /// Values exported in the local DIS.
+    values: DeviceInformationValues,
+
+    /// Reference to the adapter to report remote device information to.
+    adapter: Option<Arc<Mutex<Box<Bluetooth>>>>,
+
     /// Reference to Gatt server implementation to export service.
     bluetooth_gatt: Arc<Mutex<Box<BluetoothGatt>>>,
 
     /// Server id (available once we are registered).
     gatt_server_id: Option<i32>,
 
-    /// Handle for the PNP ID characteristic.
-    pnp_id_handle: Option<i32>,
+    /// Handles of the exported characteristics.
+    handles: HashMap<i32, DisCharacteristic>,
+
+    /// Client id (available once we are registered).
+    gatt_client_id: Option<i32>,
+
+    /// Remote devices whose DIS is being read, keyed by address.
+    pending_reads: HashMap<String, PendingRead>,
 
     /// Sender for stack mainloop.
     tx: Sender<Message>,
 }
 
 impl DeviceInformation {
-    pub fn new(bluetooth_gatt: Arc<Mutex<Box<BluetoothGatt>>>, tx: Sender<Message>) -> Self {
-        Self { bluetooth_gatt, gatt_server_id: None, pnp_id_handle: None, tx }
+    pub fn new(
+        path: String,
+        bluetooth_gatt: Arc<Mutex<Box<BluetoothGatt>>>,
+        tx: Sender<Message>,
+    ) -> Self {
+        let mut dis = Self {
+            path,
+            values: DeviceInformationValues::default(),
+            adapter: None,
+            bluetooth_gatt,
+            gatt_server_id: None,
+            handles: HashMap::new(),
+            gatt_client_id: None,
+            pending_reads: HashMap::new(),
+            tx,
+        };
+
+        if dis.load_config().is_err() {
+            log::info!("No DIS config loaded, only exporting PNP ID");
+        }
+        dis
+    }
+
+    pub fn set_adapter(&mut self, adapter: Arc<Mutex<Box<Bluetooth>>>) {
+        self.adapter = Some(adapter);
+    }
+
+    fn load_config(&mut self) -> Result<()> {
+        let mut file = File::open(&self.path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let json = serde_json::from_str::<Value>(contents.as_str())?;
+        match DeviceInformationValues::from_json(&json) {
+            Some(values) => self.values = values,
+            None => log::warn!("Malformed DIS config in {}", &self.path),
+        }
+        Ok(())
     }
 
     pub(crate) fn initialize(&mut self) {
@@ -48,6 +290,13 @@ impl DeviceInformation {
             callback,
             /*eatt_support=*/ true,
         );
+
+        // Register a client to read the DIS of remote devices.
+        self.bluetooth_gatt.lock().unwrap().register_client(
+            DIS_CLIENT_APP_RANDOM_UUID.to_string(),
+            Box::new(DeviceInformationClientCallbacks::new(self.tx.clone())),
+            /*eatt_support=*/ false,
+        );
     }
 
     pub(crate) fn handle_callbacks(&mut self, callback: &ServiceCallbacks) {
@@ -69,12 +318,18 @@ impl DeviceInformation {
                     GattDbElementType::PrimaryService.into(),
                 );
 
-                service.characteristics.push(BluetoothGattCharacteristic::new(
-                    UuidHelper::from_string(PNP_ID_CHAR_UUID).expect("PNP ID uuid is malformed"),
-                    /*instance_id=*/ 0,
-                    BluetoothGattCharacteristic::PROPERTY_READ,
-                    BluetoothGattCharacteristic::PERMISSION_READ,
-                ));
+                // Only export the characteristics we have a value for.
+                for characteristic in DisCharacteristic::ALL.iter() {
+                    if self.values.get_value(*characteristic).is_empty() {
+                        continue;
+                    }
+                    service.characteristics.push(BluetoothGattCharacteristic::new(
+                        characteristic.uuid(),
+                        /*instance_id=*/ 0,
+                        BluetoothGattCharacteristic::PROPERTY_READ,
+                        BluetoothGattCharacteristic::PERMISSION_READ,
+                    ));
+                }
 
                 self.bluetooth_gatt.lock().unwrap().add_service(*server_id, service);
             }
@@ -84,57 +339,167 @@ impl DeviceInformation {
                     return;
                 }
 
-                let pnp_uuid =
-                    UuidHelper::from_string(PNP_ID_CHAR_UUID).expect("PNP ID uuid is malformed");
-
-                // Find the PNP ID characteristic we inserted before and store
-                // the handle for it.
+                // Find the characteristics we inserted before and store the handles for them.
                 for characteristic in &service.characteristics {
-                    if characteristic.uuid == pnp_uuid {
-                        self.pnp_id_handle = Some(characteristic.instance_id);
+                    if let Some(c) = DisCharacteristic::from_uuid(&characteristic.uuid) {
+                        self.handles.insert(characteristic.instance_id, c);
                     }
                 }
             }
+
             ServiceCallbacks::OnCharacteristicReadRequest(
                 addr,
                 trans_id,
                 offset,
                 _is_long,
                 handle,
-            ) => match (self.gatt_server_id, self.pnp_id_handle) {
-                (Some(server_id), Some(pnp_handle)) => {
-                    if &pnp_handle == handle {
-                        let vendor_id = sysprop::get_i32(sysprop::PropertyI32::VendorId);
-                        let vendor_id_source =
-                            sysprop::get_i32(sysprop::PropertyI32::VendorIdSource);
-                        let product_id = sysprop::get_i32(sysprop::PropertyI32::ProductId);
-                        let product_version =
-                            sysprop::get_i32(sysprop::PropertyI32::ProductVersion);
-
-                        // PNP ID ordering (all values are in little endian):
-                        // - Vendor ID source (1 octet)
-                        // - Vendor ID (2 octet)
-                        // - Product ID (2 octet)
-                        // - Product Version (2 octet)
-                        let mut value: Vec<u8> = Vec::new();
-                        value.push(vendor_id_source.to_le_bytes()[0]);
-                        value.extend_from_slice(&vendor_id.to_le_bytes()[0..2]);
-                        value.extend_from_slice(&product_id.to_le_bytes()[0..2]);
-                        value.extend_from_slice(&product_version.to_le_bytes()[0..2]);
-
-                        self.bluetooth_gatt.lock().unwrap().send_response(
-                            server_id,
-                            addr.clone(),
-                            *trans_id,
-                            GattStatus::Success,
-                            *offset,
-                            value,
-                        );
+            ) => {
+                let (server_id, characteristic) =
+                    match (self.gatt_server_id, self.handles.get(handle)) {
+                        (Some(server_id), Some(characteristic)) => (server_id, *characteristic),
+                        _ => return,
+                    };
+
+                // Long reads continue from the offset of the value.
+                let (status, value) =
+                    match value_at_offset(self.values.get_value(characteristic), *offset) {
+                        Ok(value) => (GattStatus::Success, value),
+                        Err(status) => (status, vec![]),
+                    };
+
+                self.bluetooth_gatt.lock().unwrap().send_response(
+                    server_id,
+                    addr.clone(),
+                    *trans_id,
+                    status,
+                    *offset,
+                    value,
+                );
+            }
+
+            ServiceCallbacks::ClientRegistered(status, client_id) => {
+                if status != &GattStatus::Success {
+                    log::error!("DIS failed to register client. Status={:?}", status);
+                    return;
+                }
+                self.gatt_client_id = Some(*client_id);
+            }
+
+            ServiceCallbacks::Connect(device, transport) => {
+                // Only LE devices expose DIS over GATT. Classic devices use the DI SDP record.
+                if transport != &BtTransport::Le {
+                    return;
+                }
+                let client_id = match self.gatt_client_id {
+                    Some(id) => id,
+                    None => return,
+                };
+                self.bluetooth_gatt.lock().unwrap().client_connect(
+                    client_id,
+                    device.address.clone(),
+                    /*is_direct=*/ false,
+                    BtTransport::Le,
+                    /*opportunistic=*/ true,
+                    LePhy::Phy1m,
+                );
+            }
+
+            ServiceCallbacks::Disconnect(device) => {
+                self.pending_reads.remove(&device.address);
+            }
+
+            ServiceCallbacks::ClientConnectionState(status, client_id, connected, addr) => {
+                if !connected || status != &GattStatus::Success {
+                    self.pending_reads.remove(addr);
+                    return;
+                }
+                self.bluetooth_gatt.lock().unwrap().discover_services(*client_id, addr.clone());
+            }
+
+            ServiceCallbacks::SearchComplete(addr, services, status) => {
+                if status != &GattStatus::Success {
+                    log::debug!("DIS discovery for {} failed with status {:?}", addr, status);
+                    return;
+                }
+                let client_id = match self.gatt_client_id {
+                    Some(id) => id,
+                    None => return,
+                };
+                let dis_uuid =
+                    UuidHelper::get_profile_uuid(&Profile::Dis).expect("DIS uuid mapping missing");
+
+                let remaining = services
+                    .iter()
+                    .filter(|service| &service.uuid == dis_uuid)
+                    .flat_map(|service| service.characteristics.iter())
+                    .filter_map(|characteristic| {
+                        DisCharacteristic::from_uuid(&characteristic.uuid)
+                            .map(|c| (characteristic.instance_id, c))
+                    })
+                    .collect::<HashMap<i32, DisCharacteristic>>();
+                if remaining.is_empty() {
+                    self.bluetooth_gatt.lock().unwrap().client_disconnect(client_id, addr.clone());
+                    return;
+                }
+
+                for handle in remaining.keys() {
+                    self.bluetooth_gatt.lock().unwrap().read_characteristic(
+                        client_id,
+                        addr.clone(),
+                        *handle,
+                        0,
+                    );
+                }
+                self.pending_reads.insert(
+                    addr.clone(),
+                    PendingRead {
+                        values: DeviceInformationValues::default(),
+                        pnp_id: None,
+                        remaining,
+                    },
+                );
+            }
+
+            ServiceCallbacks::CharacteristicRead(addr, status, handle, value) => {
+                let pending = match self.pending_reads.get_mut(addr) {
+                    Some(pending) => pending,
+                    None => return,
+                };
+                let characteristic = match pending.remaining.remove(handle) {
+                    Some(c) => c,
+                    None => return,
+                };
+
+                if status == &GattStatus::Success {
+                    match characteristic {
+                        DisCharacteristic::PnpId => pending.pnp_id = parse_pnp_id(value),
+                        c => pending.values.set_value(c, value),
                     }
+                } else {
+                    log::debug!(
+                        "DIS read of {:?} on {} failed: {:?}",
+                        characteristic,
+                        addr,
+                        status
+                    );
                 }
 
-                (_, _) => (),
-            },
+                if !pending.remaining.is_empty() {
+                    return;
+                }
+
+                let pending = self.pending_reads.remove(addr).unwrap();
+                if let Some(adapter) = &self.adapter {
+                    adapter.lock().unwrap().set_remote_device_information(
+                        addr,
+                        pending.values,
+                        pending.pnp_id,
+                    );
+                }
+                if let Some(client_id) = self.gatt_client_id {
+                    self.bluetooth_gatt.lock().unwrap().client_disconnect(client_id, addr.clone());
+                }
+            }
         }
     }
 }
@@ -144,6 +509,19 @@ pub enum ServiceCallbacks {
     Registered(GattStatus, i32),
     ServiceAdded(GattStatus, BluetoothGattService),
     OnCharacteristicReadRequest(String, i32, i32, bool, i32),
+
+    /// Params: status, client_id
+    ClientRegistered(GattStatus, i32),
+    /// Params: status, client_id, connected, addr
+    ClientConnectionState(GattStatus, i32, bool, String),
+    /// Params: addr, services, status
+    SearchComplete(String, Vec<BluetoothGattService>, GattStatus),
+    /// Params: addr, status, handle, value
+    CharacteristicRead(String, GattStatus, i32, Vec<u8>),
+    /// Params: remote_device, transport
+    Connect(BluetoothDevice, BtTransport),
+    /// Params: remote_device
+    Disconnect(BluetoothDevice),
 }
 
 // Handle callbacks for DIS to register
@@ -266,3 +644,180 @@ impl RPCProxy for DeviceInformationServerCallbacks {
         "DIS Gatt Server Callback".to_string()
     }
 }
+
+// Handle GATT client callbacks for reading the DIS of remote devices
+struct DeviceInformationClientCallbacks {
+    // Sender to the main loop
+    tx: Sender<Message>,
+}
+
+impl DeviceInformationClientCallbacks {
+    fn new(tx: Sender<Message>) -> Self {
+        Self { tx }
+    }
+
+    fn send(&self, callback: ServiceCallbacks) {
+        let txl = self.tx.clone();
+        tokio::spawn(async move {
+            let _ = txl.send(Message::Dis(callback)).await;
+        });
+    }
+}
+
+impl IBluetoothGattCallback for DeviceInformationClientCallbacks {
+    fn on_client_registered(&mut self, status: GattStatus, client_id: i32) {
+        self.send(ServiceCallbacks::ClientRegistered(status, client_id));
+    }
+
+    fn on_client_connection_state(
+        &mut self,
+        status: GattStatus,
+        client_id: i32,
+        connected: bool,
+        addr: String,
+    ) {
+        self.send(ServiceCallbacks::ClientConnectionState(status, client_id, connected, addr));
+    }
+
+    fn on_search_complete(
+        &mut self,
+        addr: String,
+        services: Vec<BluetoothGattService>,
+        status: GattStatus,
+    ) {
+        self.send(ServiceCallbacks::SearchComplete(addr, services, status));
+    }
+
+    fn on_characteristic_read(
+        &mut self,
+        addr: String,
+        status: GattStatus,
+        handle: i32,
+        value: Vec<u8>,
+    ) {
+        self.send(ServiceCallbacks::CharacteristicRead(addr, status, handle, value));
+    }
+
+    // Remaining callbacks are unhandled
+
+    fn on_phy_update(
+        &mut self,
+        _addr: String,
+        _tx_phy: LePhy,
+        _rx_phy: LePhy,
+        _status: GattStatus,
+    ) {
+    }
+    fn on_phy_read(&mut self, _addr: String, _tx_phy: LePhy, _rx_phy: LePhy, _status: GattStatus) {}
+    fn on_characteristic_write(&mut self, _addr: String, _status: GattStatus, _handle: i32) {}
+    fn on_execute_write(&mut self, _addr: String, _status: GattStatus) {}
+    fn on_descriptor_read(
+        &mut self,
+        _addr: String,
+        _status: GattStatus,
+        _handle: i32,
+        _value: Vec<u8>,
+    ) {
+    }
+    fn on_descriptor_write(&mut self, _addr: String, _status: GattStatus, _handle: i32) {}
+    fn on_notify(&mut self, _addr: String, _handle: i32, _value: Vec<u8>) {}
+    fn on_read_remote_rssi(&mut self, _addr: String, _rssi: i32, _status: GattStatus) {}
+    fn on_configure_mtu(&mut self, _addr: String, _mtu: i32, _status: GattStatus) {}
+    fn on_connection_updated(
+        &mut self,
+        _addr: String,
+        _interval: i32,
+        _latency: i32,
+        _timeout: i32,
+        _status: GattStatus,
+    ) {
+    }
+    fn on_service_changed(&mut self, _addr: String) {}
+}
+
+impl RPCProxy for DeviceInformationClientCallbacks {
+    fn get_object_id(&self) -> String {
+        "DIS Gatt Client Callback".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_system_id() {
+        assert_eq!(
+            parse_system_id("0123456789abcdef"),
+            Some(vec![0xef, 0xcd, 0xab, 0x89, 0x67, 0x45, 0x23, 0x01])
+        );
+        assert_eq!(parse_system_id("0123456789abcd"), None);
+        assert_eq!(parse_system_id("0123456789abcdefff"), None);
+        assert_eq!(parse_system_id("0123456789abcdeg"), None);
+        // Multi-byte characters must not split a byte in the middle.
+        assert_eq!(parse_system_id("é123456789abcd"), None);
+    }
+
+    #[test]
+    fn test_parse_pnp_id() {
+        let pnp_id = parse_pnp_id(&vec![0x02, 0x6d, 0x04, 0x45, 0xb0, 0x10, 0x01]).unwrap();
+        assert_eq!(pnp_id.vendor_id_src, 0x02);
+        assert_eq!(pnp_id.vendor_id, 0x046d);
+        assert_eq!(pnp_id.product_id, 0xb045);
+        assert_eq!(pnp_id.version, 0x0110);
+
+        assert!(parse_pnp_id(&vec![0x02, 0x6d, 0x04, 0x45, 0xb0, 0x10]).is_none());
+    }
+
+    #[test]
+    fn test_values_from_json() {
+        let values = DeviceInformationValues::from_json(&json!({
+            "manufacturer_name": "Floss",
+            "model_number": "Model 1",
+            "system_id": "0102030405060708",
+        }))
+        .unwrap();
+        assert_eq!(values.manufacturer_name, "Floss");
+        assert_eq!(values.model_number, "Model 1");
+        assert_eq!(values.serial_number, "");
+        // The System ID is sent in little endian.
+        assert_eq!(values.system_id, vec![8, 7, 6, 5, 4, 3, 2, 1]);
+
+        // Every field is optional.
+        assert_eq!(
+            DeviceInformationValues::from_json(&json!({})),
+            Some(DeviceInformationValues::default())
+        );
+
+        // Fields of the wrong type or malformed System IDs make the whole config invalid.
+        assert_eq!(DeviceInformationValues::from_json(&json!({ "model_number": 1 })), None);
+        assert_eq!(DeviceInformationValues::from_json(&json!({ "system_id": "01" })), None);
+    }
+
+    #[test]
+    fn test_long_read() {
+        let values = DeviceInformationValues {
+            manufacturer_name: "A long manufacturer name".to_string(),
+            ..Default::default()
+        };
+        let value = values.get_value(DisCharacteristic::ManufacturerName);
+        assert_eq!(value_at_offset(value.clone(), 0), Ok(value.clone()));
+        assert_eq!(value_at_offset(value.clone(), 7), Ok(b"manufacturer name".to_vec()));
+        assert_eq!(value_at_offset(value.clone(), 24), Ok(vec![]));
+        assert_eq!(value_at_offset(value, 25), Err(GattStatus::InvalidOffset));
+    }
+
+    #[test]
+    fn test_set_value() {
+        let mut values = DeviceInformationValues::default();
+        values.set_value(DisCharacteristic::SerialNumber, &b"1234\0\0".to_vec());
+        assert_eq!(values.serial_number, "1234");
+
+        // System IDs of the wrong length are ignored.
+        values.set_value(DisCharacteristic::SystemId, &vec![1, 2, 3]);
+        assert!(values.system_id.is_empty());
+        values.set_value(DisCharacteristic::SystemId, &vec![1; SYSTEM_ID_LEN]);
+        assert_eq!(values.system_id, vec![1; SYSTEM_ID_LEN]);
+    }
+}
@@ -6,6 +6,7 @@
 pub mod async_helper;
 pub mod battery_manager;
 pub mod battery_provider_manager;
+pub mod battery_server;
 pub mod battery_service;
 pub mod bluetooth;
 pub mod bluetooth_admin;
@@ -29,6 +30,7 @@ use tokio::sync::mpsc::{Receiver, Sender};
 
 use crate::battery_manager::{BatteryManager, BatterySet};
 use crate::battery_provider_manager::BatteryProviderManager;
+use crate::battery_server::{BatteryServer, BatteryServerActions};
 use crate::battery_service::{BatteryService, BatteryServiceActions};
 use crate::bluetooth::{
     dispatch_base_callbacks, dispatch_hid_host_callbacks, dispatch_sdp_callbacks, Bluetooth,
@@ -48,7 +50,7 @@ use bt_topshim::{
     btif::{BaseCallbacks, BtTransport},
     profiles::{
         a2dp::A2dpCallbacks,
-        avrcp::AvrcpCallbacks,
+        avrcp::{AvrcpCallbacks, AvrcpCtrlCallbacks},
         gatt::GattAdvCallbacks,
         gatt::GattAdvInbandCallbacks,
         gatt::GattClientCallbacks,
@@ -73,6 +75,7 @@ pub enum Message {
     // Callbacks from libbluetooth
     A2dp(A2dpCallbacks),
     Avrcp(AvrcpCallbacks),
+    AvrcpCtrl(AvrcpCtrlCallbacks),
     Base(BaseCallbacks),
     GattClient(GattClientCallbacks),
     GattServer(GattServerCallbacks),
@@ -88,6 +91,7 @@ pub enum Message {
     Media(MediaActions),
     MediaCallbackDisconnected(u32),
     TelephonyCallbackDisconnected(u32),
+    AvrcpControllerCallbackDisconnected(u32),
 
     // Client callback disconnections
     AdapterCallbackDisconnected(u32),
@@ -124,6 +128,7 @@ pub enum Message {
     BatteryService(BatteryServiceActions),
     BatteryServiceRefresh,
     BatteryManagerCallbackDisconnected(u32),
+    BatteryServer(BatteryServerActions),
 
     GattActions(GattActions),
     GattClientCallbackDisconnected(u32),
@@ -197,6 +202,7 @@ impl Stack {
         battery_service: Arc<Mutex<Box<BatteryService>>>,
         battery_manager: Arc<Mutex<Box<BatteryManager>>>,
         battery_provider_manager: Arc<Mutex<Box<BatteryProviderManager>>>,
+        battery_server: Arc<Mutex<Box<BatteryServer>>>,
         bluetooth_media: Arc<Mutex<Box<BluetoothMedia>>>,
         suspend: Arc<Mutex<Box<Suspend>>>,
         bluetooth_socketmgr: Arc<Mutex<Box<BluetoothSocketManager>>>,
@@ -227,6 +233,9 @@ impl Stack {
 
                     // Register device information service.
                     bluetooth_dis.lock().unwrap().initialize();
+
+                    // Register battery service for the host battery.
+                    battery_server.lock().unwrap().initialize();
                 }
 
                 Message::A2dp(a) => {
@@ -237,6 +246,10 @@ impl Stack {
                     bluetooth_media.lock().unwrap().dispatch_avrcp_callbacks(av);
                 }
 
+                Message::AvrcpCtrl(av) => {
+                    bluetooth_media.lock().unwrap().dispatch_avrcp_ctrl_callbacks(av);
+                }
+
                 Message::Base(b) => {
                     dispatch_base_callbacks(bluetooth.lock().unwrap().as_mut(), b.clone());
                     dispatch_base_callbacks(suspend.lock().unwrap().as_mut(), b);
@@ -293,6 +306,10 @@ impl Stack {
                     bluetooth_media.lock().unwrap().remove_telephony_callback(cb_id);
                 }
 
+                Message::AvrcpControllerCallbackDisconnected(cb_id) => {
+                    bluetooth_media.lock().unwrap().remove_avrcp_controller_callback(cb_id);
+                }
+
                 Message::AdapterCallbackDisconnected(id) => {
                     bluetooth.lock().unwrap().adapter_callback_disconnected(id);
                 }
@@ -309,6 +326,10 @@ impl Stack {
                 // update method triggered from here rather than needing a
                 // reference to Bluetooth.
                 Message::OnAclConnected(device, transport) => {
+                    bluetooth_dis
+                        .lock()
+                        .unwrap()
+                        .handle_callbacks(&ServiceCallbacks::Connect(device.clone(), transport));
                     battery_service
                         .lock()
                         .unwrap()
@@ -318,6 +339,10 @@ impl Stack {
                 // For battery service, use this to clean up internal handles. GATT connection is
                 // already dropped if ACL disconnect has occurred.
                 Message::OnAclDisconnected(device) => {
+                    bluetooth_dis
+                        .lock()
+                        .unwrap()
+                        .handle_callbacks(&ServiceCallbacks::Disconnect(device.clone()));
                     battery_service
                         .lock()
                         .unwrap()
@@ -376,6 +401,9 @@ impl Stack {
                 Message::BatteryServiceRefresh => {
                     battery_service.lock().unwrap().refresh_all_devices();
                 }
+                Message::BatteryServer(action) => {
+                    battery_server.lock().unwrap().handle_action(&action);
+                }
                 Message::BatteryManagerCallbackDisconnected(id) => {
                     battery_manager.lock().unwrap().remove_callback(id);
                 }
@@ -396,6 +424,7 @@ impl Stack {
                 }
                 Message::AdminPolicyChanged => {
                     bluetooth_socketmgr.lock().unwrap().handle_admin_policy_changed();
+                    bluetooth.lock().unwrap().handle_admin_policy_changed();
                 }
                 Message::Dis(callback) => {
                     bluetooth_dis.lock().unwrap().handle_callbacks(&callback);
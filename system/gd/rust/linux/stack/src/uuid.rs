@@ -110,13 +110,14 @@ pub struct UuidHelper {}
 
 lazy_static! {
     // AVRCP fights with A2DP when initializing, so let's initiate profiles in a known good order.
-    // Specifically, A2DP must be initialized before AVRCP.
+    // Specifically, A2DP must be initialized before AVRCP, and the AVRCP target before the
+    // controller.
     // TODO (b/286991526): remove after issue is resolved
     static ref ORDERED_SUPPORTED_PROFILES: Vec<Profile> = vec![
         Profile::A2dpSink,
         Profile::A2dpSource,
-        Profile::AvrcpController,
         Profile::AvrcpTarget,
+        Profile::AvrcpController,
         Profile::Bas,
         Profile::Hsp,
         Profile::Hfp,
@@ -7,105 +7,269 @@ use bt_topshim::btif::Uuid128Bit;
 
 // Advertising data types.
 const FLAGS: u8 = 0x01;
+const INCOMPLETE_LIST_16_BIT_SERVICE_UUIDS: u8 = 0x02;
 const COMPLETE_LIST_16_BIT_SERVICE_UUIDS: u8 = 0x03;
+const INCOMPLETE_LIST_32_BIT_SERVICE_UUIDS: u8 = 0x04;
 const COMPLETE_LIST_32_BIT_SERVICE_UUIDS: u8 = 0x05;
+const INCOMPLETE_LIST_128_BIT_SERVICE_UUIDS: u8 = 0x06;
 const COMPLETE_LIST_128_BIT_SERVICE_UUIDS: u8 = 0x07;
 const SHORTENED_LOCAL_NAME: u8 = 0x08;
 const COMPLETE_LOCAL_NAME: u8 = 0x09;
+const TX_POWER_LEVEL: u8 = 0x0a;
+const CLASS_OF_DEVICE: u8 = 0x0d;
+const PERIPHERAL_CONNECTION_INTERVAL_RANGE: u8 = 0x12;
+const SOLICITATION_16_BIT_UUIDS: u8 = 0x14;
+const SOLICITATION_128_BIT_UUIDS: u8 = 0x15;
 const SERVICE_DATA_16_BIT_UUID: u8 = 0x16;
+const APPEARANCE: u8 = 0x19;
+const SOLICITATION_32_BIT_UUIDS: u8 = 0x1f;
 const SERVICE_DATA_32_BIT_UUID: u8 = 0x20;
 const SERVICE_DATA_128_BIT_UUID: u8 = 0x21;
+const URI: u8 = 0x24;
+const TRANSPORT_DISCOVERY_DATA: u8 = 0x26;
+const LE_SUPPORTED_FEATURES: u8 = 0x27;
+const BROADCAST_NAME: u8 = 0x30;
+const ENCRYPTED_ADVERTISING_DATA: u8 = 0x31;
 const MANUFACTURER_SPECIFIC_DATA: u8 = 0xff;
 
-struct AdvDataIterator<'a> {
-    data: &'a [u8],
-    data_type: u8,
-    cur: usize, // to keep current position
+/// A decoded AD structure. UUIDs of any size are expanded to 128 bits.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AdStructure {
+    Flags(u8),
+    /// Service UUIDs, and whether the list is complete.
+    ServiceUuids {
+        uuids: Vec<Uuid128Bit>,
+        complete: bool,
+    },
+    /// Local name, and whether it is complete or shortened.
+    LocalName {
+        name: String,
+        complete: bool,
+    },
+    /// TX power level in dBm.
+    TxPowerLevel(i8),
+    ClassOfDevice(u32),
+    /// Preferred connection interval range, in units of 1.25ms. 0xffff means no specific value.
+    PeripheralConnectionIntervalRange {
+        min: u16,
+        max: u16,
+    },
+    SolicitationUuids(Vec<Uuid128Bit>),
+    ServiceData {
+        uuid: Uuid128Bit,
+        data: Vec<u8>,
+    },
+    Appearance(u16),
+    /// URI with its scheme decoded when it is http or https.
+    Uri(String),
+    TransportDiscoveryData(Vec<u8>),
+    /// LE supported features, the least significant octet first.
+    LeSupportedFeatures(Vec<u8>),
+    BroadcastName(String),
+    /// Encrypted data, still encrypted.
+    EncryptedAdvertisingData(Vec<u8>),
+    /// Manufacturer specific data. Unlike |extract_manufacturer_data|, the company ID is decoded
+    /// as little endian, as in the Core Specification Supplement.
+    ManufacturerData {
+        company_id: u16,
+        data: Vec<u8>,
+    },
+    /// AD type not known by this parser.
+    Unknown {
+        ad_type: u8,
+        data: Vec<u8>,
+    },
 }
 
-// Iterates over Advertising Data's elements having the given AD type. `next()`
-// returns the next slice of the advertising data element excluding the length
-// and type.
-impl<'a> Iterator for AdvDataIterator<'a> {
-    type Item = &'a [u8];
-    fn next(&mut self) -> Option<&'a [u8]> {
-        let mut i = self.cur;
-        while i < self.data.len() {
-            let len: usize = self.data[i].into();
-            if (len == 0) || (i + len >= self.data.len()) {
-                break;
-            }
-            if self.data[i + 1] == self.data_type {
-                self.cur = i + len + 1;
-                return Some(&self.data[i + 2..self.cur]);
+/// Error found when parsing an AD structure at |offset| in the advertising data.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AdParseError {
+    /// The length of the AD structure goes past the end of the data. Parsing stops there.
+    Truncated { offset: usize, length: usize },
+    /// The payload length isn't valid for the AD type.
+    InvalidLength { offset: usize, ad_type: u8, length: usize },
+}
+
+fn parse_uuids(payload: &[u8], uuid_len: usize) -> Option<Vec<Uuid128Bit>> {
+    if payload.len() % uuid_len != 0 {
+        return None;
+    }
+    payload
+        .chunks(uuid_len)
+        .map(|chunk| Uuid::try_from_little_endian(chunk).ok().map(|uuid| uuid.uu))
+        .collect()
+}
+
+fn parse_service_data(payload: &[u8], uuid_len: usize) -> Option<AdStructure> {
+    let uuid = Uuid::try_from_little_endian(payload.get(0..uuid_len)?).ok()?.uu;
+    Some(AdStructure::ServiceData { uuid, data: payload[uuid_len..].to_vec() })
+}
+
+fn parse_uri(payload: &[u8]) -> Option<AdStructure> {
+    // The scheme is a code point encoded in UTF-8. 0x01 means no scheme.
+    let uri = std::str::from_utf8(payload).ok()?;
+    let mut chars = uri.chars();
+    let scheme = match chars.next()? {
+        '\u{01}' => "",
+        '\u{16}' => "http:",
+        '\u{17}' => "https:",
+        _ => return Some(AdStructure::Uri(uri.to_string())),
+    };
+    Some(AdStructure::Uri(format!("{}{}", scheme, chars.as_str())))
+}
+
+fn parse_ad_structure(ad_type: u8, payload: &[u8]) -> Option<AdStructure> {
+    let structure = match ad_type {
+        FLAGS => AdStructure::Flags(*payload.first()?),
+        INCOMPLETE_LIST_16_BIT_SERVICE_UUIDS
+        | COMPLETE_LIST_16_BIT_SERVICE_UUIDS
+        | INCOMPLETE_LIST_32_BIT_SERVICE_UUIDS
+        | COMPLETE_LIST_32_BIT_SERVICE_UUIDS
+        | INCOMPLETE_LIST_128_BIT_SERVICE_UUIDS
+        | COMPLETE_LIST_128_BIT_SERVICE_UUIDS => {
+            let uuid_len = match ad_type {
+                INCOMPLETE_LIST_16_BIT_SERVICE_UUIDS | COMPLETE_LIST_16_BIT_SERVICE_UUIDS => 2,
+                INCOMPLETE_LIST_32_BIT_SERVICE_UUIDS | COMPLETE_LIST_32_BIT_SERVICE_UUIDS => 4,
+                _ => 16,
+            };
+            AdStructure::ServiceUuids {
+                uuids: parse_uuids(payload, uuid_len)?,
+                complete: ad_type % 2 == 1,
             }
-            i += len + 1;
         }
-        None
-    }
+        SHORTENED_LOCAL_NAME | COMPLETE_LOCAL_NAME => AdStructure::LocalName {
+            name: String::from_utf8_lossy(payload).to_string(),
+            complete: ad_type == COMPLETE_LOCAL_NAME,
+        },
+        TX_POWER_LEVEL => match payload {
+            [tx_power] => AdStructure::TxPowerLevel(*tx_power as i8),
+            _ => return None,
+        },
+        CLASS_OF_DEVICE => match payload {
+            [b0, b1, b2] => AdStructure::ClassOfDevice(u32::from_le_bytes([*b0, *b1, *b2, 0])),
+            _ => return None,
+        },
+        PERIPHERAL_CONNECTION_INTERVAL_RANGE => match payload {
+            [min0, min1, max0, max1] => AdStructure::PeripheralConnectionIntervalRange {
+                min: u16::from_le_bytes([*min0, *min1]),
+                max: u16::from_le_bytes([*max0, *max1]),
+            },
+            _ => return None,
+        },
+        SOLICITATION_16_BIT_UUIDS => AdStructure::SolicitationUuids(parse_uuids(payload, 2)?),
+        SOLICITATION_32_BIT_UUIDS => AdStructure::SolicitationUuids(parse_uuids(payload, 4)?),
+        SOLICITATION_128_BIT_UUIDS => AdStructure::SolicitationUuids(parse_uuids(payload, 16)?),
+        SERVICE_DATA_16_BIT_UUID => parse_service_data(payload, 2)?,
+        SERVICE_DATA_32_BIT_UUID => parse_service_data(payload, 4)?,
+        SERVICE_DATA_128_BIT_UUID => parse_service_data(payload, 16)?,
+        APPEARANCE => match payload {
+            [b0, b1] => AdStructure::Appearance(u16::from_le_bytes([*b0, *b1])),
+            _ => return None,
+        },
+        URI => parse_uri(payload)?,
+        TRANSPORT_DISCOVERY_DATA => AdStructure::TransportDiscoveryData(payload.to_vec()),
+        LE_SUPPORTED_FEATURES => AdStructure::LeSupportedFeatures(payload.to_vec()),
+        BROADCAST_NAME => match payload.len() {
+            4..=32 => AdStructure::BroadcastName(String::from_utf8_lossy(payload).to_string()),
+            _ => return None,
+        },
+        ENCRYPTED_ADVERTISING_DATA => AdStructure::EncryptedAdvertisingData(payload.to_vec()),
+        MANUFACTURER_SPECIFIC_DATA => {
+            let company_id = u16::from_le_bytes(payload.get(0..2)?.try_into().ok()?);
+            AdStructure::ManufacturerData { company_id, data: payload[2..].to_vec() }
+        }
+        _ => AdStructure::Unknown { ad_type, data: payload.to_vec() },
+    };
+    Some(structure)
 }
 
-fn iterate_adv_data(data: &[u8], data_type: u8) -> AdvDataIterator {
-    AdvDataIterator { data, data_type, cur: 0 }
+/// Parses every AD structure of the advertising data, in order, along with its AD type. Malformed
+/// structures are reported in place. A zero length ends the significant part of the data.
+pub fn parse_ad_structures(bytes: &[u8]) -> Vec<Result<(u8, AdStructure), AdParseError>> {
+    let mut structures = vec![];
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let length: usize = bytes[offset].into();
+        if length == 0 {
+            break;
+        }
+        if offset + length >= bytes.len() {
+            structures.push(Err(AdParseError::Truncated { offset, length }));
+            break;
+        }
+        let ad_type = bytes[offset + 1];
+        let payload = &bytes[offset + 2..offset + length + 1];
+        structures.push(
+            parse_ad_structure(ad_type, payload)
+                .map(|structure| (ad_type, structure))
+                .ok_or(AdParseError::InvalidLength { offset, ad_type, length }),
+        );
+        offset += length + 1;
+    }
+    structures
 }
 
 // Helper function to extract flags from advertising data
 pub fn extract_flags(bytes: &[u8]) -> u8 {
-    iterate_adv_data(bytes, FLAGS).next().map_or(0, |v| v[0])
+    parse_ad_structures(bytes)
+        .into_iter()
+        .find_map(|structure| match structure {
+            Ok((_, AdStructure::Flags(flags))) => Some(flags),
+            _ => None,
+        })
+        .unwrap_or(0)
 }
 
 // Helper function to extract service uuids (128bit) from advertising data
 pub fn extract_service_uuids(bytes: &[u8]) -> Vec<Uuid128Bit> {
-    iterate_adv_data(bytes, COMPLETE_LIST_16_BIT_SERVICE_UUIDS)
-        .flat_map(|slice| slice.chunks(2))
-        .filter_map(|chunk| Uuid::try_from_little_endian(chunk).ok().map(|uuid| uuid.uu))
-        .chain(
-            iterate_adv_data(bytes, COMPLETE_LIST_32_BIT_SERVICE_UUIDS)
-                .flat_map(|slice| slice.chunks(4))
-                .filter_map(|chunk| Uuid::try_from_little_endian(chunk).ok().map(|uuid| uuid.uu)),
-        )
-        .chain(
-            iterate_adv_data(bytes, COMPLETE_LIST_128_BIT_SERVICE_UUIDS)
-                .flat_map(|slice| slice.chunks(16))
-                .filter_map(|chunk| Uuid::try_from_little_endian(chunk).ok().map(|uuid| uuid.uu)),
-        )
+    parse_ad_structures(bytes)
+        .into_iter()
+        .filter_map(|structure| match structure {
+            Ok((_, AdStructure::ServiceUuids { uuids, complete: true })) => Some(uuids),
+            _ => None,
+        })
+        .flatten()
         .collect()
 }
 
 // Helper function to extract name from advertising data
 pub fn extract_name(bytes: &[u8]) -> String {
-    iterate_adv_data(bytes, COMPLETE_LOCAL_NAME)
-        .next()
-        .or(iterate_adv_data(bytes, SHORTENED_LOCAL_NAME).next())
-        .map_or("".to_string(), |v| String::from_utf8_lossy(v).to_string())
+    let mut shortened_name = None;
+    for structure in parse_ad_structures(bytes) {
+        match structure {
+            Ok((_, AdStructure::LocalName { name, complete: true })) => return name,
+            Ok((_, AdStructure::LocalName { name, complete: false })) => {
+                shortened_name.get_or_insert(name);
+            }
+            _ => {}
+        }
+    }
+    shortened_name.unwrap_or_default()
 }
 
 // Helper function to extract service data from advertising data
 pub fn extract_service_data(bytes: &[u8]) -> HashMap<String, Vec<u8>> {
-    iterate_adv_data(bytes, SERVICE_DATA_16_BIT_UUID)
-        .filter_map(|slice| {
-            Uuid::try_from_little_endian(slice.get(0..2)?)
-                .ok()
-                .map(|uuid| (uuid.to_string(), slice[2..].to_vec()))
+    parse_ad_structures(bytes)
+        .into_iter()
+        .filter_map(|structure| match structure {
+            Ok((_, AdStructure::ServiceData { uuid, data })) => {
+                Some((Uuid::from(uuid).to_string(), data))
+            }
+            _ => None,
         })
-        .chain(iterate_adv_data(bytes, SERVICE_DATA_32_BIT_UUID).filter_map(|slice| {
-            Uuid::try_from_little_endian(slice.get(0..4)?)
-                .ok()
-                .map(|uuid| (uuid.to_string(), slice[4..].to_vec()))
-        }))
-        .chain(iterate_adv_data(bytes, SERVICE_DATA_128_BIT_UUID).filter_map(|slice| {
-            Uuid::try_from_little_endian(slice.get(0..16)?)
-                .ok()
-                .map(|uuid| (uuid.to_string(), slice[16..].to_vec()))
-        }))
         .collect()
 }
 
 // Helper function to extract manufacturer data from advertising data
 pub fn extract_manufacturer_data(bytes: &[u8]) -> HashMap<u16, Vec<u8>> {
-    iterate_adv_data(bytes, MANUFACTURER_SPECIFIC_DATA)
-        .filter_map(|slice| {
-            slice.get(0..2)?.try_into().ok().map(|be| (u16::from_be_bytes(be), slice[2..].to_vec()))
+    parse_ad_structures(bytes)
+        .into_iter()
+        .filter_map(|structure| match structure {
+            // Clients of this helper expect the company ID decoded as big endian.
+            Ok((_, AdStructure::ManufacturerData { company_id, data })) => {
+                Some((company_id.swap_bytes(), data))
+            }
+            _ => None,
         })
         .collect()
 }
@@ -312,4 +476,132 @@ mod tests {
         assert_eq!(manufacturer_data.get(&1), Some(&vec![2]));
         assert_eq!(manufacturer_data.get(&258), Some(&vec![]));
     }
+
+    #[test]
+    fn test_parse_ad_structures() {
+        let payload: Vec<u8> = vec![
+            2,
+            FLAGS,
+            6,
+            3,
+            INCOMPLETE_LIST_16_BIT_SERVICE_UUIDS,
+            0x2C,
+            0xFE,
+            5,
+            COMPLETE_LOCAL_NAME,
+            116,
+            101,
+            115,
+            116,
+            2,
+            TX_POWER_LEVEL,
+            0xF6,
+            3,
+            APPEARANCE,
+            0xC1,
+            0x03,
+            4,
+            CLASS_OF_DEVICE,
+            0x04,
+            0x04,
+            0x24,
+            6,
+            URI,
+            0x17,
+            b'a',
+            b'.',
+            b'b',
+            b'c',
+            5,
+            MANUFACTURER_SPECIFIC_DATA,
+            0xE0,
+            0x00,
+            1,
+            2,
+            2,
+            0x42,
+            9,
+        ];
+        let structures = parse_ad_structures(payload.as_slice());
+        assert_eq!(
+            structures,
+            vec![
+                Ok((FLAGS, AdStructure::Flags(6))),
+                Ok((
+                    INCOMPLETE_LIST_16_BIT_SERVICE_UUIDS,
+                    AdStructure::ServiceUuids {
+                        uuids: vec![
+                            Uuid::from([
+                                0x0, 0x0, 0xFE, 0x2C, 0x0, 0x0, 0x10, 0x0, 0x80, 0x0, 0x0, 0x80,
+                                0x5f, 0x9b, 0x34, 0xfb
+                            ])
+                            .uu
+                        ],
+                        complete: false
+                    }
+                )),
+                Ok((
+                    COMPLETE_LOCAL_NAME,
+                    AdStructure::LocalName { name: "test".to_string(), complete: true }
+                )),
+                Ok((TX_POWER_LEVEL, AdStructure::TxPowerLevel(-10))),
+                Ok((APPEARANCE, AdStructure::Appearance(0x03C1))),
+                Ok((CLASS_OF_DEVICE, AdStructure::ClassOfDevice(0x240404))),
+                Ok((URI, AdStructure::Uri("https:a.bc".to_string()))),
+                Ok((
+                    MANUFACTURER_SPECIFIC_DATA,
+                    AdStructure::ManufacturerData { company_id: 0xE0, data: vec![1, 2] }
+                )),
+                Ok((0x42, AdStructure::Unknown { ad_type: 0x42, data: vec![9] })),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_ad_structures_malformed() {
+        // Zero length ends the data.
+        let payload: Vec<u8> = vec![2, FLAGS, 6, 0, 0, 0];
+        assert_eq!(
+            parse_ad_structures(payload.as_slice()),
+            vec![Ok((FLAGS, AdStructure::Flags(6)))]
+        );
+
+        // Invalid payload lengths are reported and parsing continues.
+        let payload: Vec<u8> = vec![
+            4,
+            COMPLETE_LIST_16_BIT_SERVICE_UUIDS,
+            0x2C,
+            0xFE,
+            0x00,
+            3,
+            TX_POWER_LEVEL,
+            1,
+            2,
+            2,
+            FLAGS,
+            6,
+        ];
+        assert_eq!(
+            parse_ad_structures(payload.as_slice()),
+            vec![
+                Err(AdParseError::InvalidLength {
+                    offset: 0,
+                    ad_type: COMPLETE_LIST_16_BIT_SERVICE_UUIDS,
+                    length: 4
+                }),
+                Err(AdParseError::InvalidLength { offset: 5, ad_type: TX_POWER_LEVEL, length: 3 }),
+                Ok((FLAGS, AdStructure::Flags(6))),
+            ]
+        );
+
+        // A length past the end of the data stops parsing.
+        let payload: Vec<u8> = vec![2, FLAGS, 6, 5, COMPLETE_LOCAL_NAME, 116];
+        assert_eq!(
+            parse_ad_structures(payload.as_slice()),
+            vec![
+                Ok((FLAGS, AdStructure::Flags(6))),
+                Err(AdParseError::Truncated { offset: 3, length: 5 })
+            ]
+        );
+    }
 }
@@ -3,6 +3,7 @@
 // whatever reason.
 
 use std::collections::HashMap;
+use std::fmt;
 
 /// The AT command type indicated.
 #[derive(Clone, Debug, PartialEq)]
@@ -175,6 +176,285 @@ pub fn calculate_battery_percent(at_command: AtCommand) -> Result<u32, String> {
     Err("No battery data found".to_string())
 }
 
+/// Action requested by AT+CHLD. Actions on a specific call carry its index.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CallHoldAction {
+    /// 0: Releases all held calls or rejects the waiting call.
+    ReleaseHeld,
+    /// 1 or 1<idx>: Releases the active calls, or the given call, and accepts the other call.
+    ReleaseActiveAcceptHeld(Option<u32>),
+    /// 2 or 2<idx>: Holds the active calls, or all but the given call, and accepts the other call.
+    HoldActiveAcceptHeld(Option<u32>),
+    /// 3: Adds the held call to the conversation.
+    AddHeldToConversation,
+    /// 4: Connects the two calls and disconnects the subscriber.
+    ExplicitCallTransfer,
+}
+
+/// An HFP command sent by the HF, as typed by the AG.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HfpCommand {
+    /// AT+BRSF=<HF supported features>
+    Brsf(u32),
+    /// AT+BAC=<codec ID>,...
+    Bac(Vec<u8>),
+    /// AT+CIND=?
+    CindTest,
+    /// AT+CIND?
+    CindRead,
+    /// AT+CMER=<mode>,<keyp>,<disp>,<ind>. Only <ind> matters to HFP.
+    Cmer { indicators_enabled: bool },
+    /// AT+CHLD=?
+    ChldTest,
+    /// AT+CHLD=<action>
+    Chld(CallHoldAction),
+    /// AT+BIND=<HF indicator>,...
+    Bind(Vec<u16>),
+    /// AT+BIND=?
+    BindTest,
+    /// AT+BIND?
+    BindRead,
+    /// AT+BIEV=<HF indicator>,<value>
+    Biev { indicator: u16, value: u32 },
+    /// AT+BCS=<codec ID>
+    Bcs(u8),
+    /// AT+VGS=<speaker gain>, 0 to 15.
+    Vgs(u8),
+    /// AT+VGM=<microphone gain>, 0 to 15.
+    Vgm(u8),
+    /// AT+CLCC
+    Clcc,
+    /// AT+COPS=3,0 sets the format of the operator name.
+    CopsSetFormat,
+    /// AT+COPS?
+    CopsRead,
+    /// AT+BTRH?
+    BtrhRead,
+    /// AT+BTRH=<n>: 0 puts the incoming call on hold, 1 accepts and 2 rejects the held call.
+    Btrh(u8),
+    /// AT+NREC=0 disables noise reduction and echo canceling in the AG.
+    Nrec(bool),
+    /// AT+BVRA=<vrec>: 0 disables and 1 enables voice recognition. 2 is for enhanced voice
+    /// recognition status.
+    Bvra(u8),
+    /// AT+CNUM
+    Cnum,
+    /// Any other command, unparsed.
+    Other(String),
+}
+
+fn parse_arg<T: std::str::FromStr>(args: &[String], index: usize) -> Result<T, String> {
+    args.get(index)
+        .ok_or(format!("Missing argument {}", index))?
+        .trim()
+        .parse::<T>()
+        .map_err(|_| format!("Invalid argument {}: {}", index, args[index]))
+}
+
+fn parse_chld(arg: &str) -> Result<CallHoldAction, String> {
+    let mut chars = arg.chars();
+    let action = chars.next();
+    let index = match chars.as_str() {
+        "" => None,
+        index => Some(index.parse::<u32>().map_err(|_| format!("Invalid call index {}", index))?),
+    };
+    match (action, index) {
+        (Some('0'), None) => Ok(CallHoldAction::ReleaseHeld),
+        (Some('1'), index) => Ok(CallHoldAction::ReleaseActiveAcceptHeld(index)),
+        (Some('2'), index) => Ok(CallHoldAction::HoldActiveAcceptHeld(index)),
+        (Some('3'), None) => Ok(CallHoldAction::AddHeldToConversation),
+        (Some('4'), None) => Ok(CallHoldAction::ExplicitCallTransfer),
+        _ => Err(format!("Invalid AT+CHLD action {}", arg)),
+    }
+}
+
+/// Parses an HFP command from the HF into its typed form. Commands that aren't part of HFP are
+/// returned as |HfpCommand::Other|, while malformed HFP commands return an error.
+pub fn parse_hfp_command(at_string: &str) -> Result<HfpCommand, String> {
+    let at_command = parse_at_command_data(at_string.trim_end().to_string())?;
+    let args = at_command.raw_args.unwrap_or_default();
+    let command = match (at_command.command.as_str(), at_command.at_type) {
+        ("BRSF", AtCommandType::Set) => HfpCommand::Brsf(parse_arg(&args, 0)?),
+        ("BAC", AtCommandType::Set) => HfpCommand::Bac(
+            (0..args.len()).map(|i| parse_arg(&args, i)).collect::<Result<Vec<u8>, String>>()?,
+        ),
+        ("CIND", AtCommandType::Test) => HfpCommand::CindTest,
+        ("CIND", AtCommandType::Query) => HfpCommand::CindRead,
+        ("CMER", AtCommandType::Set) => {
+            HfpCommand::Cmer { indicators_enabled: parse_arg::<u8>(&args, 3)? == 1 }
+        }
+        ("CHLD", AtCommandType::Test) => HfpCommand::ChldTest,
+        ("CHLD", AtCommandType::Set) => {
+            HfpCommand::Chld(parse_chld(args.get(0).ok_or("Missing AT+CHLD action")?.trim())?)
+        }
+        ("BIND", AtCommandType::Test) => HfpCommand::BindTest,
+        ("BIND", AtCommandType::Query) => HfpCommand::BindRead,
+        ("BIND", AtCommandType::Set) => HfpCommand::Bind(
+            (0..args.len()).map(|i| parse_arg(&args, i)).collect::<Result<Vec<u16>, String>>()?,
+        ),
+        ("BIEV", AtCommandType::Set) => {
+            HfpCommand::Biev { indicator: parse_arg(&args, 0)?, value: parse_arg(&args, 1)? }
+        }
+        ("BCS", AtCommandType::Set) => HfpCommand::Bcs(parse_arg(&args, 0)?),
+        ("VGS", AtCommandType::Set) | ("VGM", AtCommandType::Set) => {
+            let gain: u8 = parse_arg(&args, 0)?;
+            if gain > 15 {
+                return Err(format!("Invalid gain {}, want 0 to 15", gain));
+            }
+            match at_command.command.as_str() {
+                "VGS" => HfpCommand::Vgs(gain),
+                _ => HfpCommand::Vgm(gain),
+            }
+        }
+        ("CLCC", AtCommandType::Execute) => HfpCommand::Clcc,
+        ("COPS", AtCommandType::Set) => {
+            if parse_arg::<u8>(&args, 0)? != 3 || parse_arg::<u8>(&args, 1)? != 0 {
+                return Err("Only AT+COPS=3,0 is supported by HFP".to_string());
+            }
+            HfpCommand::CopsSetFormat
+        }
+        ("COPS", AtCommandType::Query) => HfpCommand::CopsRead,
+        ("BTRH", AtCommandType::Query) => HfpCommand::BtrhRead,
+        ("BTRH", AtCommandType::Set) => HfpCommand::Btrh(parse_arg(&args, 0)?),
+        ("NREC", AtCommandType::Set) => HfpCommand::Nrec(parse_arg::<u8>(&args, 0)? == 1),
+        ("BVRA", AtCommandType::Set) => HfpCommand::Bvra(parse_arg(&args, 0)?),
+        ("CNUM", AtCommandType::Execute) => HfpCommand::Cnum,
+        _ => HfpCommand::Other(at_command.raw),
+    };
+    Ok(command)
+}
+
+/// A call reported in +CLCC.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClccCall {
+    pub index: u32,
+    /// 0 for outgoing, 1 for incoming.
+    pub dir: u8,
+    /// 0 active, 1 held, 2 dialing, 3 alerting, 4 incoming, 5 waiting, 6 held by response and
+    /// hold.
+    pub status: u8,
+    /// 0 voice, 1 data, 2 fax.
+    pub mode: u8,
+    pub multiparty: bool,
+    /// Phone number and its type, e.g. 129 for unknown and 145 for international.
+    pub number: Option<(String, u8)>,
+}
+
+/// A result code sent by the AG, solicited or not.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HfpResult {
+    Ok,
+    Error,
+    /// +BRSF: <AG supported features>
+    Brsf(u32),
+    /// +CIEV: <indicator index>,<value>. Indexes start at 1.
+    Ciev {
+        indicator: u8,
+        value: u8,
+    },
+    /// +CIND: ("<name>",(<min>-<max>)),... in answer to AT+CIND=?
+    CindTest(Vec<(String, u8, u8)>),
+    /// +CIND: <value>,... in answer to AT+CIND?
+    CindRead(Vec<u8>),
+    /// +CLCC: <idx>,<dir>,<status>,<mode>,<mpty>[,<number>,<type>]
+    Clcc(ClccCall),
+    /// +BCS: <codec ID>
+    Bcs(u8),
+    Ring,
+    /// +CLIP: <number>,<type>
+    Clip {
+        number: String,
+        number_type: u8,
+    },
+    /// +CHLD: (<action>,...) in answer to AT+CHLD=?, e.g. "1x" for actions taking an index.
+    ChldTest(Vec<String>),
+    /// +BIND: (<HF indicator>,...) in answer to AT+BIND=?
+    BindTest(Vec<u16>),
+    /// +BIND: <HF indicator>,<state> in answer to AT+BIND? or when the AG toggles an indicator.
+    Bind {
+        indicator: u16,
+        enabled: bool,
+    },
+    /// +CNUM: ,<number>,<type>,,<service>. Service 4 is voice.
+    Cnum {
+        number: String,
+        number_type: u8,
+        service: u8,
+    },
+    /// +COPS: 0,0,<operator name>
+    Cops(String),
+    /// +BTRH: <n>, the response and hold state of the incoming call.
+    Btrh(u8),
+    /// +VGS: <speaker gain>
+    Vgs(u8),
+    /// +VGM: <microphone gain>
+    Vgm(u8),
+}
+
+impl fmt::Display for HfpResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HfpResult::Ok => write!(f, "OK"),
+            HfpResult::Error => write!(f, "ERROR"),
+            HfpResult::Brsf(features) => write!(f, "+BRSF: {}", features),
+            HfpResult::Ciev { indicator, value } => write!(f, "+CIEV: {},{}", indicator, value),
+            HfpResult::CindTest(indicators) => write!(
+                f,
+                "+CIND: {}",
+                indicators
+                    .iter()
+                    .map(|(name, min, max)| format!("(\"{}\",({}-{}))", name, min, max))
+                    .collect::<Vec<String>>()
+                    .join(",")
+            ),
+            HfpResult::CindRead(values) => write!(
+                f,
+                "+CIND: {}",
+                values.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(",")
+            ),
+            HfpResult::Clcc(call) => {
+                write!(
+                    f,
+                    "+CLCC: {},{},{},{},{}",
+                    call.index, call.dir, call.status, call.mode, call.multiparty as u8
+                )?;
+                match &call.number {
+                    Some((number, number_type)) => write!(f, ",\"{}\",{}", number, number_type),
+                    None => Ok(()),
+                }
+            }
+            HfpResult::Bcs(codec_id) => write!(f, "+BCS: {}", codec_id),
+            HfpResult::Ring => write!(f, "RING"),
+            HfpResult::Clip { number, number_type } => {
+                write!(f, "+CLIP: \"{}\",{}", number, number_type)
+            }
+            HfpResult::ChldTest(actions) => write!(f, "+CHLD: ({})", actions.join(",")),
+            HfpResult::BindTest(indicators) => write!(
+                f,
+                "+BIND: ({})",
+                indicators.iter().map(|i| i.to_string()).collect::<Vec<String>>().join(",")
+            ),
+            HfpResult::Bind { indicator, enabled } => {
+                write!(f, "+BIND: {},{}", indicator, *enabled as u8)
+            }
+            HfpResult::Cnum { number, number_type, service } => {
+                write!(f, "+CNUM: ,\"{}\",{},,{}", number, number_type, service)
+            }
+            HfpResult::Cops(name) => write!(f, "+COPS: 0,0,\"{}\"", name),
+            HfpResult::Btrh(state) => write!(f, "+BTRH: {}", state),
+            HfpResult::Vgs(gain) => write!(f, "+VGS: {}", gain),
+            HfpResult::Vgm(gain) => write!(f, "+VGM: {}", gain),
+        }
+    }
+}
+
+impl HfpResult {
+    /// Encodes the result code as sent over RFCOMM, framed by <cr><lf>.
+    pub fn encode(&self) -> String {
+        format!("\r\n{}\r\n", self)
+    }
+}
+
 fn parse_at_command_type(command: String) -> AtCommandType {
     if command.contains(AT_COMMAND_DELIMITER_TEST) {
         return AtCommandType::Test;
@@ -440,4 +720,112 @@ mod tests {
         let battery_level = calculate_battery_percent(at_command.unwrap()).unwrap();
         assert_eq!(battery_level, 50);
     }
+
+    #[test]
+    fn test_parse_hfp_command() {
+        assert_eq!(parse_hfp_command("AT+BRSF=959"), Ok(HfpCommand::Brsf(959)));
+        assert_eq!(parse_hfp_command("AT+BAC=1,2,3\r"), Ok(HfpCommand::Bac(vec![1, 2, 3])));
+        assert_eq!(parse_hfp_command("AT+CIND=?"), Ok(HfpCommand::CindTest));
+        assert_eq!(parse_hfp_command("AT+CIND?"), Ok(HfpCommand::CindRead));
+        assert_eq!(
+            parse_hfp_command("AT+CMER=3,0,0,1"),
+            Ok(HfpCommand::Cmer { indicators_enabled: true })
+        );
+        assert_eq!(parse_hfp_command("AT+CHLD=?"), Ok(HfpCommand::ChldTest));
+        assert_eq!(
+            parse_hfp_command("AT+CHLD=0"),
+            Ok(HfpCommand::Chld(CallHoldAction::ReleaseHeld))
+        );
+        assert_eq!(
+            parse_hfp_command("AT+CHLD=12"),
+            Ok(HfpCommand::Chld(CallHoldAction::ReleaseActiveAcceptHeld(Some(2))))
+        );
+        assert_eq!(
+            parse_hfp_command("AT+CHLD=2"),
+            Ok(HfpCommand::Chld(CallHoldAction::HoldActiveAcceptHeld(None)))
+        );
+        assert_eq!(parse_hfp_command("AT+BIND=1,2"), Ok(HfpCommand::Bind(vec![1, 2])));
+        assert_eq!(parse_hfp_command("AT+BIND=?"), Ok(HfpCommand::BindTest));
+        assert_eq!(parse_hfp_command("AT+BIND?"), Ok(HfpCommand::BindRead));
+        assert_eq!(
+            parse_hfp_command("AT+BIEV=2,80"),
+            Ok(HfpCommand::Biev { indicator: 2, value: 80 })
+        );
+        assert_eq!(parse_hfp_command("AT+BCS=2"), Ok(HfpCommand::Bcs(2)));
+        assert_eq!(parse_hfp_command("AT+VGS=15"), Ok(HfpCommand::Vgs(15)));
+        assert_eq!(parse_hfp_command("AT+VGM=7"), Ok(HfpCommand::Vgm(7)));
+        assert_eq!(parse_hfp_command("AT+CLCC"), Ok(HfpCommand::Clcc));
+        assert_eq!(parse_hfp_command("AT+COPS=3,0"), Ok(HfpCommand::CopsSetFormat));
+        assert_eq!(parse_hfp_command("AT+COPS?"), Ok(HfpCommand::CopsRead));
+        assert_eq!(parse_hfp_command("AT+BTRH?"), Ok(HfpCommand::BtrhRead));
+        assert_eq!(parse_hfp_command("AT+BTRH=1"), Ok(HfpCommand::Btrh(1)));
+        assert_eq!(parse_hfp_command("AT+NREC=0"), Ok(HfpCommand::Nrec(false)));
+        assert_eq!(parse_hfp_command("AT+BVRA=1"), Ok(HfpCommand::Bvra(1)));
+        assert_eq!(parse_hfp_command("AT+CNUM"), Ok(HfpCommand::Cnum));
+        assert_eq!(
+            parse_hfp_command("AT+XAPL=ABCD-1234-0100,10"),
+            Ok(HfpCommand::Other("AT+XAPL=ABCD-1234-0100,10".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_hfp_command_invalid() {
+        assert!(parse_hfp_command("AT+BRSF=").is_err());
+        assert!(parse_hfp_command("AT+BAC=1,x").is_err());
+        assert!(parse_hfp_command("AT+CMER=3,0,0").is_err());
+        assert!(parse_hfp_command("AT+CHLD=5").is_err());
+        assert!(parse_hfp_command("AT+CHLD=0x").is_err());
+        assert!(parse_hfp_command("AT+CHLD=é").is_err());
+        assert!(parse_hfp_command("AT+VGS=16").is_err());
+        assert!(parse_hfp_command("AT+COPS=0").is_err());
+    }
+
+    #[test]
+    fn test_encode_hfp_result() {
+        assert_eq!(HfpResult::Ok.encode(), "\r\nOK\r\n");
+        assert_eq!(HfpResult::Ring.encode(), "\r\nRING\r\n");
+        assert_eq!(HfpResult::Brsf(871).to_string(), "+BRSF: 871");
+        assert_eq!(HfpResult::Ciev { indicator: 2, value: 1 }.to_string(), "+CIEV: 2,1");
+        assert_eq!(
+            HfpResult::CindTest(vec![("service".to_string(), 0, 1), ("call".to_string(), 0, 1)])
+                .to_string(),
+            "+CIND: (\"service\",(0-1)),(\"call\",(0-1))"
+        );
+        assert_eq!(HfpResult::CindRead(vec![1, 0, 0, 3]).to_string(), "+CIND: 1,0,0,3");
+        assert_eq!(
+            HfpResult::Clcc(ClccCall {
+                index: 1,
+                dir: 1,
+                status: 4,
+                mode: 0,
+                multiparty: false,
+                number: Some(("+15551234".to_string(), 145)),
+            })
+            .to_string(),
+            "+CLCC: 1,1,4,0,0,\"+15551234\",145"
+        );
+        assert_eq!(HfpResult::Bcs(2).to_string(), "+BCS: 2");
+        assert_eq!(
+            HfpResult::Clip { number: "5551234".to_string(), number_type: 129 }.to_string(),
+            "+CLIP: \"5551234\",129"
+        );
+        assert_eq!(
+            HfpResult::ChldTest(
+                ["0", "1", "1x", "2", "2x", "3", "4"].iter().map(|a| a.to_string()).collect()
+            )
+            .to_string(),
+            "+CHLD: (0,1,1x,2,2x,3,4)"
+        );
+        assert_eq!(HfpResult::BindTest(vec![1, 2]).to_string(), "+BIND: (1,2)");
+        assert_eq!(HfpResult::Bind { indicator: 2, enabled: true }.to_string(), "+BIND: 2,1");
+        assert_eq!(
+            HfpResult::Cnum { number: "5551234".to_string(), number_type: 129, service: 4 }
+                .to_string(),
+            "+CNUM: ,\"5551234\",129,,4"
+        );
+        assert_eq!(HfpResult::Cops("Carrier".to_string()).to_string(), "+COPS: 0,0,\"Carrier\"");
+        assert_eq!(HfpResult::Btrh(0).to_string(), "+BTRH: 0");
+        assert_eq!(HfpResult::Vgs(9).to_string(), "+VGS: 9");
+        assert_eq!(HfpResult::Vgm(15).to_string(), "+VGM: 15");
+    }
 }
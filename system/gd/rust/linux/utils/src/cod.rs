@@ -21,9 +21,28 @@ pub fn is_cod_hid_combo(cod: u32) -> bool {
     c.major == 0x05 && ((c.minor >> 6) & 0x03) == 0x03
 }
 
+pub fn is_cod_audio_wearable(cod: u32) -> bool {
+    let c = Class::new(cod);
+    // Wearable headset or headphones.
+    c.major == 0x04 && matches!((c.minor >> 2) & 0x3f, 0x01 | 0x06)
+}
+
+pub fn is_cod_handsfree(cod: u32) -> bool {
+    let c = Class::new(cod);
+    c.major == 0x04 && ((c.minor >> 2) & 0x3f) == 0x02
+}
+
+pub fn is_cod_remote_control(cod: u32) -> bool {
+    let c = Class::new(cod);
+    c.major == 0x05 && ((c.minor >> 2) & 0x0f) == 0x03
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::cod::{is_cod_hid_combo, is_cod_hid_keyboard};
+    use crate::cod::{
+        is_cod_audio_wearable, is_cod_handsfree, is_cod_hid_combo, is_cod_hid_keyboard,
+        is_cod_remote_control,
+    };
 
     #[test]
     fn test_cod() {
@@ -38,4 +57,22 @@ mod tests {
         assert_eq!(is_cod_hid_keyboard(mouse_cod), false);
         assert_eq!(is_cod_hid_combo(mouse_cod), false);
     }
+
+    #[test]
+    fn test_cod_audio() {
+        let headset_cod = 0x240404;
+        let headphones_cod = 0x240418;
+        let handsfree_cod = 0x200408;
+        let car_audio_cod = 0x200420;
+        let remote_cod = 0x00050c;
+
+        assert_eq!(is_cod_audio_wearable(headset_cod), true);
+        assert_eq!(is_cod_audio_wearable(headphones_cod), true);
+        assert_eq!(is_cod_audio_wearable(handsfree_cod), false);
+        assert_eq!(is_cod_handsfree(handsfree_cod), true);
+        assert_eq!(is_cod_handsfree(car_audio_cod), false);
+        assert_eq!(is_cod_audio_wearable(car_audio_cod), false);
+        assert_eq!(is_cod_remote_control(remote_cod), true);
+        assert_eq!(is_cod_remote_control(headset_cod), false);
+    }
 }
@@ -76,6 +76,9 @@ impl MgmtPacket {
 #[derive(FromPrimitive, ToPrimitive)]
 pub enum MgmtCommandOpcode {
     ReadIndexList = 0x3,
+    ReadControllerInfo = 0x4,
+    SetPowered = 0x5,
+    ReadExtendedIndexList = 0x3C,
     FlossNotifySuspendState = 0x103,
 }
 
@@ -96,8 +99,15 @@ impl TryFrom<u16> for MgmtCommandOpcode {
     }
 }
 
+/// MGMT commands that can be sent on the control channel. btmanagerd reads the index lists and
+/// the controller information, btadapterd sends |FlossNotifySuspendState|.
 pub enum MgmtCommand {
     ReadIndexList,
+    /// Read the information of the given controller index.
+    ReadControllerInfo(u16),
+    /// Power the given controller index on or off.
+    SetPowered(u16, bool),
+    ReadExtendedIndexList,
     FlossNotifySuspendState(u16, bool),
 }
 
@@ -110,6 +120,24 @@ impl From<MgmtCommand> for MgmtPacket {
                 len: 0,
                 data: Vec::new(),
             },
+            MgmtCommand::ReadControllerInfo(hci_index) => MgmtPacket {
+                opcode: MgmtCommandOpcode::ReadControllerInfo.into(),
+                index: hci_index,
+                len: 0,
+                data: Vec::new(),
+            },
+            MgmtCommand::SetPowered(hci_index, powered) => MgmtPacket {
+                opcode: MgmtCommandOpcode::SetPowered.into(),
+                index: hci_index,
+                len: 1,
+                data: vec![u8::from(powered)],
+            },
+            MgmtCommand::ReadExtendedIndexList => MgmtPacket {
+                opcode: MgmtCommandOpcode::ReadExtendedIndexList.into(),
+                index: HCI_DEV_NONE,
+                len: 0,
+                data: Vec::new(),
+            },
             MgmtCommand::FlossNotifySuspendState(hci_index, suspended) => MgmtPacket {
                 opcode: MgmtCommandOpcode::FlossNotifySuspendState.into(),
                 index: HCI_DEV_NONE,
@@ -123,8 +151,12 @@ impl From<MgmtCommand> for MgmtPacket {
 #[derive(FromPrimitive, ToPrimitive, Debug)]
 pub enum MgmtEventOpcode {
     CommandComplete = 0x1,
+    CommandStatus = 0x2,
+    ControllerError = 0x3,
     IndexAdded = 0x4,
     IndexRemoved = 0x5,
+    ExtendedIndexAdded = 0x20,
+    ExtendedIndexRemoved = 0x21,
 }
 
 impl TryFrom<u16> for MgmtEventOpcode {
@@ -144,7 +176,196 @@ pub enum MgmtCommandResponse {
     // for this response has been dropped.
     DataUnused,
 
-    ReadIndexList { num_intf: u16, interfaces: Vec<u16> },
+    ReadIndexList {
+        num_intf: u16,
+        interfaces: Vec<u16>,
+    },
+
+    ReadControllerInfo(MgmtControllerInfo),
+
+    ReadExtendedIndexList {
+        controllers: Vec<MgmtExtendedIndex>,
+    },
+
+    /// Settings of the controller after the power change.
+    SetPowered {
+        current_settings: u32,
+    },
+}
+
+/// Bits of the supported and current settings of a controller.
+pub const MGMT_SETTING_POWERED: u32 = 1 << 0;
+pub const MGMT_SETTING_CONNECTABLE: u32 = 1 << 1;
+pub const MGMT_SETTING_FAST_CONNECTABLE: u32 = 1 << 2;
+pub const MGMT_SETTING_DISCOVERABLE: u32 = 1 << 3;
+pub const MGMT_SETTING_BONDABLE: u32 = 1 << 4;
+pub const MGMT_SETTING_LINK_SECURITY: u32 = 1 << 5;
+pub const MGMT_SETTING_SSP: u32 = 1 << 6;
+pub const MGMT_SETTING_BREDR: u32 = 1 << 7;
+pub const MGMT_SETTING_HS: u32 = 1 << 8;
+pub const MGMT_SETTING_LE: u32 = 1 << 9;
+pub const MGMT_SETTING_ADVERTISING: u32 = 1 << 10;
+pub const MGMT_SETTING_SECURE_CONN: u32 = 1 << 11;
+pub const MGMT_SETTING_DEBUG_KEYS: u32 = 1 << 12;
+pub const MGMT_SETTING_PRIVACY: u32 = 1 << 13;
+pub const MGMT_SETTING_CONFIGURATION: u32 = 1 << 14;
+pub const MGMT_SETTING_STATIC_ADDRESS: u32 = 1 << 15;
+pub const MGMT_SETTING_PHY_CONFIGURATION: u32 = 1 << 16;
+pub const MGMT_SETTING_WIDEBAND_SPEECH: u32 = 1 << 17;
+
+const MGMT_SETTING_NAMES: [(u32, &str); 18] = [
+    (MGMT_SETTING_POWERED, "powered"),
+    (MGMT_SETTING_CONNECTABLE, "connectable"),
+    (MGMT_SETTING_FAST_CONNECTABLE, "fast-connectable"),
+    (MGMT_SETTING_DISCOVERABLE, "discoverable"),
+    (MGMT_SETTING_BONDABLE, "bondable"),
+    (MGMT_SETTING_LINK_SECURITY, "link-security"),
+    (MGMT_SETTING_SSP, "ssp"),
+    (MGMT_SETTING_BREDR, "br/edr"),
+    (MGMT_SETTING_HS, "hs"),
+    (MGMT_SETTING_LE, "le"),
+    (MGMT_SETTING_ADVERTISING, "advertising"),
+    (MGMT_SETTING_SECURE_CONN, "secure-conn"),
+    (MGMT_SETTING_DEBUG_KEYS, "debug-keys"),
+    (MGMT_SETTING_PRIVACY, "privacy"),
+    (MGMT_SETTING_CONFIGURATION, "configuration"),
+    (MGMT_SETTING_STATIC_ADDRESS, "static-addr"),
+    (MGMT_SETTING_PHY_CONFIGURATION, "phy-configuration"),
+    (MGMT_SETTING_WIDEBAND_SPEECH, "wide-band-speech"),
+];
+
+/// Names of the settings set in a settings bitmask, lowest bit first.
+pub fn mgmt_settings_to_names(settings: u32) -> Vec<&'static str> {
+    MGMT_SETTING_NAMES
+        .iter()
+        .filter(|(bit, _)| settings & bit != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+/// Size of the Read Controller Information response.
+const MGMT_CONTROLLER_INFO_SIZE: usize = 280;
+
+/// Maximum length of the name and short name of a controller, including the terminator.
+const MGMT_MAX_NAME_LENGTH: usize = 249;
+const MGMT_MAX_SHORT_NAME_LENGTH: usize = 11;
+
+/// Response of Read Controller Information.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MgmtControllerInfo {
+    /// Controller index the information belongs to.
+    pub index: u16,
+    /// Public address, in the little-endian order of the wire.
+    pub address: [u8; 6],
+    /// Bluetooth core version of the controller (HCI version).
+    pub version: u8,
+    /// Company identifier of the controller manufacturer.
+    pub manufacturer: u16,
+    pub supported_settings: u32,
+    pub current_settings: u32,
+    pub class_of_device: u32,
+    pub name: String,
+    pub short_name: String,
+}
+
+/// Type of a controller as reported by the extended index list.
+#[derive(FromPrimitive, ToPrimitive, Debug, Clone, Copy, PartialEq)]
+pub enum MgmtControllerType {
+    Primary = 0x0,
+    Unconfigured = 0x1,
+    Amp = 0x2,
+}
+
+/// Bus a controller is attached to.
+#[derive(FromPrimitive, ToPrimitive, Debug, Clone, Copy, PartialEq)]
+pub enum MgmtControllerBus {
+    Virtual = 0x0,
+    Usb = 0x1,
+    Pcmcia = 0x2,
+    Uart = 0x3,
+    Rs232 = 0x4,
+    Pci = 0x5,
+    Sdio = 0x6,
+    Spi = 0x7,
+    I2c = 0x8,
+    Smd = 0x9,
+    Virtio = 0xa,
+}
+
+/// A controller of the extended index list, or of an extended index added/removed event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MgmtExtendedIndex {
+    pub index: u16,
+    /// Raw controller type. See |MgmtControllerType|.
+    pub controller_type: u8,
+    /// Raw bus type. See |MgmtControllerBus|.
+    pub bus: u8,
+}
+
+impl MgmtExtendedIndex {
+    pub fn get_controller_type(&self) -> Option<MgmtControllerType> {
+        MgmtControllerType::from_u8(self.controller_type)
+    }
+
+    pub fn get_bus(&self) -> Option<MgmtControllerBus> {
+        MgmtControllerBus::from_u8(self.bus)
+    }
+}
+
+/// Reads a null-terminated UTF-8 string from a fixed size field.
+fn parse_fixed_string(data: &[u8]) -> String {
+    let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+fn parse_controller_info(index: u16, data: &[u8]) -> Result<MgmtControllerInfo, ()> {
+    if data.len() < MGMT_CONTROLLER_INFO_SIZE {
+        debug!("ReadControllerInfo packet too small: {}", data.len());
+        return Err(());
+    }
+
+    let (name, short_name) = data[MGMT_CONTROLLER_INFO_SIZE
+        - MGMT_MAX_NAME_LENGTH
+        - MGMT_MAX_SHORT_NAME_LENGTH..MGMT_CONTROLLER_INFO_SIZE]
+        .split_at(MGMT_MAX_NAME_LENGTH);
+
+    Ok(MgmtControllerInfo {
+        index,
+        address: data[0..6].try_into().unwrap(),
+        version: data[6],
+        manufacturer: u16::from_le_bytes([data[7], data[8]]),
+        supported_settings: u32::from_le_bytes(data[9..13].try_into().unwrap()),
+        current_settings: u32::from_le_bytes(data[13..17].try_into().unwrap()),
+        class_of_device: u32::from_le_bytes([data[17], data[18], data[19], 0]),
+        name: parse_fixed_string(name),
+        short_name: parse_fixed_string(short_name),
+    })
+}
+
+fn parse_extended_index_list(data: &[u8]) -> Result<Vec<MgmtExtendedIndex>, ()> {
+    if data.len() < 2 {
+        debug!("ReadExtendedIndexList packet too small: {}", data.len());
+        return Err(());
+    }
+
+    let (len_arr, rest) = data.split_at(std::mem::size_of::<u16>());
+    let len = u16::from_le_bytes(len_arr.try_into().unwrap());
+
+    // Each entry is the index (u16), the type (u8) and the bus (u8).
+    let explen = (len as usize) * 4usize;
+    if rest.len() < explen {
+        debug!("ReadExtendedIndexList len malformed: expect = {}, actual = {}", explen, rest.len());
+        return Err(());
+    }
+
+    Ok(rest[..explen]
+        .chunks_exact(4)
+        .map(|entry| MgmtExtendedIndex {
+            index: u16::from_le_bytes([entry[0], entry[1]]),
+            controller_type: entry[2],
+            bus: entry[3],
+        })
+        .collect())
 }
 
 #[derive(Debug)]
@@ -157,6 +378,18 @@ pub enum MgmtEvent {
 
     /// HCI device was removed.
     IndexRemoved(u16),
+
+    /// A command is pending or failed before it could complete.
+    CommandStatus { opcode: u16, status: u8 },
+
+    /// The controller of the given index reported a hardware error.
+    ControllerError { index: u16, error_code: u8 },
+
+    /// HCI device was added, with its type and bus.
+    ExtendedIndexAdded(MgmtExtendedIndex),
+
+    /// HCI device was removed, with its type and bus.
+    ExtendedIndexRemoved(MgmtExtendedIndex),
 }
 
 #[derive(Debug)]
@@ -229,6 +462,30 @@ impl TryFrom<MgmtPacket> for MgmtEvent {
 
                                 MgmtCommandResponse::ReadIndexList { num_intf: len, interfaces }
                             }
+                            // Failed commands may not carry any response.
+                            _ if status != 0 => MgmtCommandResponse::DataUnused,
+                            MgmtCommandOpcode::ReadControllerInfo => {
+                                MgmtCommandResponse::ReadControllerInfo(parse_controller_info(
+                                    item.index, rest,
+                                )?)
+                            }
+                            MgmtCommandOpcode::SetPowered => {
+                                if rest.len() < 4 {
+                                    debug!("SetPowered packet too small: {}", rest.len());
+                                    return Err(());
+                                }
+
+                                MgmtCommandResponse::SetPowered {
+                                    current_settings: u32::from_le_bytes(
+                                        rest[..4].try_into().unwrap(),
+                                    ),
+                                }
+                            }
+                            MgmtCommandOpcode::ReadExtendedIndexList => {
+                                MgmtCommandResponse::ReadExtendedIndexList {
+                                    controllers: parse_extended_index_list(rest)?,
+                                }
+                            }
                             MgmtCommandOpcode::FlossNotifySuspendState => {
                                 MgmtCommandResponse::DataUnused
                             }
@@ -241,6 +498,41 @@ impl TryFrom<MgmtPacket> for MgmtEvent {
                 }
                 MgmtEventOpcode::IndexAdded => MgmtEvent::IndexAdded(item.index),
                 MgmtEventOpcode::IndexRemoved => MgmtEvent::IndexRemoved(item.index),
+                MgmtEventOpcode::CommandStatus => {
+                    if item.data.len() < 3 {
+                        debug!("CommandStatus packet too small: {}", item.data.len());
+                        return Err(());
+                    }
+
+                    MgmtEvent::CommandStatus {
+                        opcode: u16::from_le_bytes([item.data[0], item.data[1]]),
+                        status: item.data[2],
+                    }
+                }
+                MgmtEventOpcode::ControllerError => {
+                    if item.data.is_empty() {
+                        debug!("ControllerError packet too small");
+                        return Err(());
+                    }
+
+                    MgmtEvent::ControllerError { index: item.index, error_code: item.data[0] }
+                }
+                MgmtEventOpcode::ExtendedIndexAdded | MgmtEventOpcode::ExtendedIndexRemoved => {
+                    if item.data.len() < 2 {
+                        debug!("ExtendedIndex event too small: {}", item.data.len());
+                        return Err(());
+                    }
+
+                    let index = MgmtExtendedIndex {
+                        index: item.index,
+                        controller_type: item.data[0],
+                        bus: item.data[1],
+                    };
+                    match ev {
+                        MgmtEventOpcode::ExtendedIndexAdded => MgmtEvent::ExtendedIndexAdded(index),
+                        _ => MgmtEvent::ExtendedIndexRemoved(index),
+                    }
+                }
             })
         })
     }
@@ -454,4 +746,126 @@ mod tests {
             }
         }
     }
+
+    fn command_complete(opcode: u16, status: u8, response: &[u8]) -> MgmtPacket {
+        let mut data = opcode.to_le_bytes().to_vec();
+        data.push(status);
+        data.extend_from_slice(response);
+        MgmtPacket {
+            opcode: MgmtEventOpcode::CommandComplete.to_u16().unwrap(),
+            index: 0,
+            len: data.len().try_into().unwrap_or(0),
+            data,
+        }
+    }
+
+    #[test]
+    fn mgmt_tryfrom_controller_info() {
+        let mut response = vec![
+            /*address*/ 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, /*version*/ 0x0c,
+            /*manufacturer*/ 0x02, 0x00, /*supported*/ 0xff, 0xfe, 0x03, 0x00,
+            /*current*/ 0x81, 0x02, 0x00, 0x00, /*class*/ 0x0c, 0x01, 0x1c,
+        ];
+        let mut name = b"floss".to_vec();
+        name.resize(MGMT_MAX_NAME_LENGTH, 0);
+        response.extend(name);
+        response.extend(vec![0; MGMT_MAX_SHORT_NAME_LENGTH]);
+
+        let event = MgmtEvent::try_from(command_complete(0x4, 0, &response));
+        if let Ok(MgmtEvent::CommandComplete {
+            response: MgmtCommandResponse::ReadControllerInfo(info),
+            ..
+        }) = event
+        {
+            assert_eq!(info.index, 0);
+            assert_eq!(info.address, [0x66, 0x55, 0x44, 0x33, 0x22, 0x11]);
+            assert_eq!(info.version, 0x0c);
+            assert_eq!(info.manufacturer, 0x2);
+            assert_eq!(info.supported_settings, 0x3feff);
+            assert_eq!(info.class_of_device, 0x1c010c);
+            assert_eq!(info.name, "floss");
+            assert_eq!(info.short_name, "");
+            assert_eq!(
+                mgmt_settings_to_names(info.current_settings),
+                vec!["powered", "br/edr", "le"]
+            );
+        } else {
+            panic!("Packet doesn't parse into controller info: {:?}", event);
+        }
+
+        // Truncated responses are rejected, failed commands carry no data.
+        assert!(MgmtEvent::try_from(command_complete(0x4, 0, &response[..100])).is_err());
+        assert!(matches!(
+            MgmtEvent::try_from(command_complete(0x4, 0x11, &[])),
+            Ok(MgmtEvent::CommandComplete { response: MgmtCommandResponse::DataUnused, .. })
+        ));
+    }
+
+    #[test]
+    fn mgmt_tryfrom_extended_index() {
+        let response = [
+            /*num_controllers*/ 0x02, 0x00, /*index*/ 0x00, 0x00, /*type*/ 0x00,
+            /*bus*/ 0x01, /*index*/ 0x01, 0x00, /*type*/ 0x01, /*bus*/ 0x03,
+        ];
+        let event = MgmtEvent::try_from(command_complete(0x3C, 0, &response));
+        if let Ok(MgmtEvent::CommandComplete {
+            response: MgmtCommandResponse::ReadExtendedIndexList { controllers },
+            ..
+        }) = event
+        {
+            assert_eq!(controllers.len(), 2);
+            assert_eq!(controllers[0].get_bus(), Some(MgmtControllerBus::Usb));
+            assert_eq!(controllers[1].index, 1);
+            assert_eq!(
+                controllers[1].get_controller_type(),
+                Some(MgmtControllerType::Unconfigured)
+            );
+            assert_eq!(controllers[1].get_bus(), Some(MgmtControllerBus::Uart));
+        } else {
+            panic!("Packet doesn't parse into extended index list: {:?}", event);
+        }
+
+        let packet = MgmtPacket {
+            opcode: MgmtEventOpcode::ExtendedIndexAdded.to_u16().unwrap(),
+            index: 3,
+            len: 2,
+            data: vec![0x00, 0x06],
+        };
+        assert!(matches!(
+            MgmtEvent::try_from(packet),
+            Ok(MgmtEvent::ExtendedIndexAdded(MgmtExtendedIndex {
+                index: 3,
+                controller_type: 0,
+                bus: 6
+            }))
+        ));
+    }
+
+    #[test]
+    fn mgmt_tryfrom_status_and_error() {
+        let packet = MgmtPacket {
+            opcode: MgmtEventOpcode::CommandStatus.to_u16().unwrap(),
+            index: 0,
+            len: 3,
+            data: vec![0x05, 0x00, 0x0a],
+        };
+        assert!(matches!(
+            MgmtEvent::try_from(packet),
+            Ok(MgmtEvent::CommandStatus { opcode: 0x5, status: 0x0a })
+        ));
+
+        let packet = MgmtPacket {
+            opcode: MgmtEventOpcode::ControllerError.to_u16().unwrap(),
+            index: 1,
+            len: 1,
+            data: vec![0x42],
+        };
+        assert!(matches!(
+            MgmtEvent::try_from(packet),
+            Ok(MgmtEvent::ControllerError { index: 1, error_code: 0x42 })
+        ));
+
+        let packet: MgmtPacket = MgmtCommand::SetPowered(2, true).into();
+        assert_eq!(packet.write_to_wire(), vec![0x05, 0x00, 0x02, 0x00, 0x01, 0x00, 0x01]);
+    }
 }
@@ -11,14 +11,17 @@ pub use uhid_virt::OutputEvent;
 use uhid_virt::{Bus, CreateParams, InputEvent, StreamError, UHID_EVENT_SIZE};
 
 pub const BLUETOOTH_TELEPHONY_UHID_REPORT_ID: u8 = 1;
+pub const BLUETOOTH_CONSUMER_UHID_REPORT_ID: u8 = 2;
 pub const UHID_INPUT_HOOK_SWITCH: u8 = 1 << 0;
 pub const UHID_INPUT_PHONE_MUTE: u8 = 1 << 1;
 pub const UHID_OUTPUT_NONE: u8 = 0;
 pub const UHID_OUTPUT_RING: u8 = 1 << 0;
 pub const UHID_OUTPUT_OFF_HOOK: u8 = 1 << 1;
 pub const UHID_OUTPUT_MUTE: u8 = 1 << 2;
+pub const UHID_CONSUMER_NONE: u8 = 0;
+pub const UHID_CONSUMER_VOICE_COMMAND: u8 = 1 << 0;
 
-const RDESC: [u8; 55] = [
+const RDESC: [u8; 82] = [
     0x05,
     0x0B, // Usage Page (Telephony)
     0x09,
@@ -74,6 +77,33 @@ const RDESC: [u8; 55] = [
     0x91,
     0x01, //   Output
     0xC0, // End Collection
+    0x05,
+    0x0C, // Usage Page (Consumer)
+    0x09,
+    0x01, // Usage (Consumer Control)
+    0xA1,
+    0x01, // Collection (Application)
+    0x85,
+    BLUETOOTH_CONSUMER_UHID_REPORT_ID, //   Report ID (2)
+    0x15,
+    0x00, //   Logical Minimum (0)
+    0x25,
+    0x01, //   Logical Maximum (1)
+    0x09,
+    0xCF, //   Usage (Voice Command)
+    0x75,
+    0x01, //   Report Size (1)
+    0x95,
+    0x01, //   Report Count (1)
+    0x81,
+    0x02, //   Input
+    0x75,
+    0x01, //   Report Size (1)
+    0x95,
+    0x07, //   Report Count (7)
+    0x81,
+    0x01, //   Input
+    0xC0, // End Collection
 ];
 
 pub struct UHidHfp {
@@ -157,7 +187,17 @@ impl UHidHfp {
     }
 
     pub fn send_input(&mut self, report: u8) -> io::Result<()> {
-        let data: [u8; 2] = [BLUETOOTH_TELEPHONY_UHID_REPORT_ID, report];
+        self.send_report(BLUETOOTH_TELEPHONY_UHID_REPORT_ID, report)
+    }
+
+    /// Sends a press of the given consumer keys followed by their release.
+    pub fn send_consumer_keys(&mut self, keys: u8) -> io::Result<()> {
+        self.send_report(BLUETOOTH_CONSUMER_UHID_REPORT_ID, keys)?;
+        self.send_report(BLUETOOTH_CONSUMER_UHID_REPORT_ID, UHID_CONSUMER_NONE)
+    }
+
+    fn send_report(&mut self, id: u8, report: u8) -> io::Result<()> {
+        let data: [u8; 2] = [id, report];
         let input_event: [u8; UHID_EVENT_SIZE] = InputEvent::Input { data: &data }.into();
         self.handle.write_all(&input_event)
     }
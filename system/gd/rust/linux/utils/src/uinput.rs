@@ -1,12 +1,16 @@
 //! This library provides access to Linux uinput.
 
+use crate::cod;
 use libc;
 use nix;
+use std::collections::HashMap;
 use std::ffi::CString;
 use std::mem;
 use std::slice;
 
 // Supported AVRCP Keys
+const AVC_VOLUME_UP: u8 = 0x41;
+const AVC_VOLUME_DOWN: u8 = 0x42;
 const AVC_PLAY: u8 = 0x44;
 const AVC_STOP: u8 = 0x45;
 const AVC_PAUSE: u8 = 0x46;
@@ -16,6 +20,8 @@ const AVC_FORWARD: u8 = 0x4B;
 const AVC_BACKWARD: u8 = 0x4C;
 
 // Supported uinput keys
+const KEY_VOLUMEDOWN: libc::c_uint = 114;
+const KEY_VOLUMEUP: libc::c_uint = 115;
 const KEY_NEXTSONG: libc::c_uint = 163;
 const KEY_PREVIOUSSONG: libc::c_uint = 165;
 const KEY_STOPCD: libc::c_uint = 166;
@@ -23,6 +29,7 @@ const KEY_REWIND: libc::c_uint = 168;
 const KEY_PLAYCD: libc::c_uint = 200;
 const KEY_PAUSECD: libc::c_uint = 201;
 const KEY_FASTFORWAED: libc::c_uint = 208;
+const KEY_VOICECOMMAND: libc::c_uint = 582;
 
 // uinput setup constants
 const UINPUT_MAX_NAME_SIZE: usize = 80;
@@ -48,23 +55,107 @@ const UI_SET_PHYS: libc::c_ulong =
 const UI_SET_KEYBIT: libc::c_ulong =
     nix::request_code_write!(UINPUT_IOCTL_BASE, 101, mem::size_of::<libc::c_int>());
 
-// Conversion key map from AVRCP keys to uinput keys.
-#[allow(dead_code)]
-struct KeyMap {
-    avc: u8,
-    uinput: libc::c_uint,
+/// Origin of a key event forwarded to uinput.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeySource {
+    /// AVRCP passthrough operation ID.
+    Avrcp(u8),
+    /// Voice recognition activation (AT+BVRA) from an HFP headset.
+    HfpVoiceRecognition,
 }
 
-const KEY_MAP: [KeyMap; 7] = [
-    KeyMap { avc: AVC_PLAY, uinput: KEY_PLAYCD },
-    KeyMap { avc: AVC_STOP, uinput: KEY_STOPCD },
-    KeyMap { avc: AVC_PAUSE, uinput: KEY_PAUSECD },
-    KeyMap { avc: AVC_REWIND, uinput: KEY_REWIND },
-    KeyMap { avc: AVC_FAST_FORWAED, uinput: KEY_FASTFORWAED },
-    KeyMap { avc: AVC_FORWARD, uinput: KEY_NEXTSONG },
-    KeyMap { avc: AVC_BACKWARD, uinput: KEY_PREVIOUSSONG },
+/// Conversion key map from a key source to a uinput key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyMap {
+    pub source: KeySource,
+    pub uinput: libc::c_uint,
+}
+
+impl KeyMap {
+    const fn avrcp(avc: u8, uinput: libc::c_uint) -> Self {
+        KeyMap { source: KeySource::Avrcp(avc), uinput }
+    }
+}
+
+/// Media keys every AVRCP device can send.
+const MEDIA_KEY_MAP: [KeyMap; 7] = [
+    KeyMap::avrcp(AVC_PLAY, KEY_PLAYCD),
+    KeyMap::avrcp(AVC_STOP, KEY_STOPCD),
+    KeyMap::avrcp(AVC_PAUSE, KEY_PAUSECD),
+    KeyMap::avrcp(AVC_REWIND, KEY_REWIND),
+    KeyMap::avrcp(AVC_FAST_FORWAED, KEY_FASTFORWAED),
+    KeyMap::avrcp(AVC_FORWARD, KEY_NEXTSONG),
+    KeyMap::avrcp(AVC_BACKWARD, KEY_PREVIOUSSONG),
 ];
 
+/// Keys of devices whose volume buttons control the host, e.g. headsets and remotes.
+const VOLUME_KEY_MAP: [KeyMap; 2] =
+    [KeyMap::avrcp(AVC_VOLUME_UP, KEY_VOLUMEUP), KeyMap::avrcp(AVC_VOLUME_DOWN, KEY_VOLUMEDOWN)];
+
+/// Keys of devices with a voice assistant button.
+const VOICE_KEY_MAP: [KeyMap; 1] =
+    [KeyMap { source: KeySource::HfpVoiceRecognition, uinput: KEY_VOICECOMMAND }];
+
+/// Device classes that get their own key map.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum KeyMapClass {
+    /// Headsets and headphones.
+    AudioWearable,
+    /// Handsfree devices, e.g. car kits.
+    Handsfree,
+    /// Remote controls.
+    RemoteControl,
+    /// Any other device, e.g. speakers and car audio.
+    Other,
+}
+
+impl KeyMapClass {
+    /// Returns the key map class of a device of the given class of device.
+    pub fn from_cod(class_of_device: u32) -> Self {
+        if cod::is_cod_audio_wearable(class_of_device) {
+            KeyMapClass::AudioWearable
+        } else if cod::is_cod_handsfree(class_of_device) {
+            KeyMapClass::Handsfree
+        } else if cod::is_cod_remote_control(class_of_device) {
+            KeyMapClass::RemoteControl
+        } else {
+            KeyMapClass::Other
+        }
+    }
+
+    /// Returns the key map class of the given name, as used in key map configs.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "audio_wearable" => Some(KeyMapClass::AudioWearable),
+            "handsfree" => Some(KeyMapClass::Handsfree),
+            "remote_control" => Some(KeyMapClass::RemoteControl),
+            "other" => Some(KeyMapClass::Other),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the default key map of the given key map class.
+///
+/// Car kits and speakers handle their volume locally, so only headsets, headphones and remote
+/// controls get the volume keys. The voice assistant key is only available to devices
+/// that can initiate HFP voice recognition.
+pub fn default_key_map(class: KeyMapClass) -> Vec<KeyMap> {
+    let mut key_map = MEDIA_KEY_MAP.to_vec();
+    if matches!(class, KeyMapClass::AudioWearable | KeyMapClass::RemoteControl) {
+        key_map.extend_from_slice(&VOLUME_KEY_MAP);
+    }
+    if matches!(class, KeyMapClass::AudioWearable | KeyMapClass::Handsfree) {
+        key_map.extend_from_slice(&VOICE_KEY_MAP);
+    }
+    key_map
+}
+
+/// Returns the default key map of a device of the given class of device.
+pub fn key_map_for_class(class_of_device: u32) -> Vec<KeyMap> {
+    default_key_map(KeyMapClass::from_cod(class_of_device))
+}
+
 #[repr(C, packed)]
 struct UInputId {
     bustype: libc::c_ushort,
@@ -138,6 +229,7 @@ struct UInputDev {
     fd: i32,
     addr: String,
     device: UInputDevInfo,
+    key_map: Vec<KeyMap>,
 }
 
 impl Default for UInputDev {
@@ -146,6 +238,7 @@ impl Default for UInputDev {
             fd: -1,
             addr: String::from("00:00:00:00:00:00"),
             device: UInputDevInfo::default(),
+            key_map: MEDIA_KEY_MAP.to_vec(),
         }
     }
 }
@@ -158,7 +251,7 @@ impl Drop for UInputDev {
 
 impl UInputDev {
     #[allow(temporary_cstring_as_ptr)]
-    fn init(&mut self, mut name: String, addr: String) -> Result<(), String> {
+    fn init(&mut self, mut name: String, addr: String, key_map: Vec<KeyMap>) -> Result<(), String> {
         // Truncate the device name if over the max size allowed.
         name.truncate(UINPUT_MAX_NAME_SIZE - UINPUT_SUFFIX_SIZE);
         name.push_str(UINPUT_SUFFIX);
@@ -204,8 +297,8 @@ impl UInputDev {
             libc::ioctl(fd, UI_SET_EVBIT, EV_SYN);
             libc::ioctl(fd, UI_SET_PHYS, addr.clone());
 
-            for key_map in KEY_MAP {
-                libc::ioctl(fd, UI_SET_KEYBIT, key_map.uinput);
+            for key in key_map.iter() {
+                libc::ioctl(fd, UI_SET_KEYBIT, key.uinput);
             }
 
             if libc::ioctl(fd, UI_DEV_CREATE, 0) < 0 {
@@ -220,6 +313,7 @@ impl UInputDev {
 
         self.fd = fd;
         self.addr = addr;
+        self.key_map = key_map;
         Ok(())
     }
 
@@ -254,18 +348,13 @@ impl UInputDev {
         }
     }
 
-    fn send_key(&mut self, key: u8, value: u8) -> Result<(), String> {
-        let mut uinput_key: libc::c_ushort = 0;
-
-        for key_map in KEY_MAP {
-            if key_map.avc == key {
-                uinput_key = key_map.uinput.try_into().unwrap();
+    fn send_key(&mut self, source: KeySource, value: u8) -> Result<(), String> {
+        let uinput_key: libc::c_ushort = match self.key_map.iter().find(|k| k.source == source) {
+            Some(key) => key.uinput.try_into().unwrap(),
+            None => {
+                return Err(format!("Key: {:?} is not supported for device: {}", source, self.addr))
             }
-        }
-
-        if uinput_key == 0 {
-            return Err(format!("AVRCP key: {} is not supported for device: {}", key, self.addr));
-        }
+        };
 
         if self.send_event(EV_KEY.try_into().unwrap(), uinput_key, value.into()) < 0
             || self.send_event(EV_SYN.try_into().unwrap(), SYN_REPORT.try_into().unwrap(), 0) < 0
@@ -285,6 +374,8 @@ pub struct UInput {
     devices: Vec<UInputDev>,
     /// The address of current active device.
     active_device: String,
+    /// Key maps that replace the default key map of a key map class.
+    key_maps: HashMap<KeyMapClass, Vec<KeyMap>>,
 }
 
 impl Drop for UInput {
@@ -310,17 +401,45 @@ impl UInput {
         UInput {
             devices: Vec::<UInputDev>::new(),
             active_device: String::from("00:00:00:00:00:00"),
+            key_maps: HashMap::new(),
         }
     }
 
-    /// Initialize a uinput device with kernel.
-    pub fn create(&mut self, name: String, addr: String) -> Result<(), String> {
+    /// Replace the key map of devices of |class| created from now on.
+    pub fn set_key_map(&mut self, class: KeyMapClass, key_map: Vec<KeyMap>) {
+        self.key_maps.insert(class, key_map);
+    }
+
+    /// Returns the key map used for devices of the given class of device.
+    pub fn get_key_map(&self, class_of_device: u32) -> Vec<KeyMap> {
+        let class = KeyMapClass::from_cod(class_of_device);
+        self.key_maps.get(&class).cloned().unwrap_or_else(|| default_key_map(class))
+    }
+
+    /// Initialize a uinput device with kernel, with the key map of its class of device.
+    pub fn create(
+        &mut self,
+        name: String,
+        addr: String,
+        class_of_device: u32,
+    ) -> Result<(), String> {
+        let key_map = self.get_key_map(class_of_device);
+        self.create_with_key_map(name, addr, key_map)
+    }
+
+    /// Initialize a uinput device with kernel, forwarding only the keys of |key_map|.
+    pub fn create_with_key_map(
+        &mut self,
+        name: String,
+        addr: String,
+        key_map: Vec<KeyMap>,
+    ) -> Result<(), String> {
         if self.get_device(addr.clone()).is_some() {
             return Ok(());
         }
 
         let mut device = UInputDev::default();
-        match device.init(name, addr) {
+        match device.init(name, addr, key_map) {
             Ok(()) => {
                 self.devices.push(device);
                 Ok(())
@@ -352,9 +471,81 @@ impl UInput {
         match self.active_device.as_str() {
             "00:00:00:00:00:00" => Err(format!("Active device is not specified")),
             _ => match self.get_device(self.active_device.clone()) {
-                Some(device) => device.send_key(key, value),
+                Some(device) => device.send_key(KeySource::Avrcp(key), value),
                 None => Err(format!("uinput: {} is not initialized", self.active_device)),
             },
         }
     }
+
+    /// Send a press and a release of the voice command key of the specified device.
+    pub fn send_voice_command(&mut self, addr: String) -> Result<(), String> {
+        match self.get_device(addr.clone()) {
+            Some(device) => device
+                .send_key(KeySource::HfpVoiceRecognition, 1)
+                .and_then(|_| device.send_key(KeySource::HfpVoiceRecognition, 0)),
+            None => Err(format!("uinput: {} is not initialized", addr)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has_key(key_map: &[KeyMap], uinput: libc::c_uint) -> bool {
+        key_map.iter().any(|k| k.uinput == uinput)
+    }
+
+    #[test]
+    fn test_key_map_headset() {
+        let key_map = key_map_for_class(0x240404);
+        assert!(MEDIA_KEY_MAP.iter().all(|k| key_map.contains(k)));
+        assert!(has_key(&key_map, KEY_VOLUMEUP));
+        assert!(has_key(&key_map, KEY_VOLUMEDOWN));
+        assert!(has_key(&key_map, KEY_VOICECOMMAND));
+    }
+
+    #[test]
+    fn test_key_map_headphones() {
+        let key_map = key_map_for_class(0x240418);
+        assert_eq!(key_map.len(), MEDIA_KEY_MAP.len() + 3);
+        assert!(has_key(&key_map, KEY_VOLUMEUP));
+        assert!(has_key(&key_map, KEY_VOICECOMMAND));
+    }
+
+    #[test]
+    fn test_key_map_handsfree() {
+        let key_map = key_map_for_class(0x200408);
+        assert!(!has_key(&key_map, KEY_VOLUMEUP));
+        assert!(!has_key(&key_map, KEY_VOLUMEDOWN));
+        assert!(has_key(&key_map, KEY_VOICECOMMAND));
+    }
+
+    #[test]
+    fn test_key_map_remote_control() {
+        let key_map = key_map_for_class(0x00050c);
+        assert!(has_key(&key_map, KEY_VOLUMEUP));
+        assert!(has_key(&key_map, KEY_VOLUMEDOWN));
+        assert!(!has_key(&key_map, KEY_VOICECOMMAND));
+    }
+
+    #[test]
+    fn test_key_map_car_audio() {
+        assert_eq!(key_map_for_class(0x200420), MEDIA_KEY_MAP.to_vec());
+    }
+
+    #[test]
+    fn test_key_map_unknown_class() {
+        assert_eq!(key_map_for_class(0), MEDIA_KEY_MAP.to_vec());
+    }
+
+    #[test]
+    fn test_key_map_override() {
+        let mut uinput = UInput::new();
+        let key_map = vec![KeyMap::avrcp(AVC_PLAY, KEY_PLAYCD)];
+        uinput.set_key_map(KeyMapClass::Handsfree, key_map.clone());
+
+        assert_eq!(uinput.get_key_map(0x200408), key_map);
+        assert_eq!(uinput.get_key_map(0x240404), key_map_for_class(0x240404));
+    }
 }
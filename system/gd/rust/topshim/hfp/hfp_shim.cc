@@ -64,6 +64,10 @@ static void current_calls_query_cb(RawAddress* addr) {
   rusty::hfp_current_calls_query_callback(*addr);
 }
 
+static void voice_recognition_cb(bluetooth::headset::bthf_vr_state_t state, RawAddress* addr) {
+  rusty::hfp_voice_recognition_callback(state == bluetooth::headset::BTHF_VR_STATE_STARTED, *addr);
+}
+
 static void answer_call_cb(RawAddress* addr) {
   rusty::hfp_answer_call_callback(*addr);
 }
@@ -157,8 +161,9 @@ class DBusHeadsetCallbacks : public headset::Callbacks {
     topshim::rust::internal::audio_state_cb(state, bd_addr);
   }
 
-  void VoiceRecognitionCallback(
-      [[maybe_unused]] headset::bthf_vr_state_t state, [[maybe_unused]] RawAddress* bd_addr) override {}
+  void VoiceRecognitionCallback(headset::bthf_vr_state_t state, RawAddress* bd_addr) override {
+    topshim::rust::internal::voice_recognition_cb(state, bd_addr);
+  }
 
   void AnswerCallCallback(RawAddress* bd_addr) override {
     topshim::rust::internal::answer_call_cb(bd_addr);
@@ -421,6 +426,10 @@ uint32_t HfpIntf::simple_at_response(bool ok, RawAddress addr) {
       (ok ? headset::BTHF_AT_RESPONSE_OK : headset::BTHF_AT_RESPONSE_ERROR), 0, &addr);
 }
 
+uint32_t HfpIntf::stop_voice_recognition(RawAddress addr) {
+  return intf_->StopVoiceRecognition(&addr);
+}
+
 void HfpIntf::debug_dump() {
   intf_->DebugDump();
 }
@@ -50,6 +50,7 @@ class HfpIntf {
   uint32_t phone_state_change(
       PhoneState phone_state, const ::rust::String& number, RawAddress addr);
   uint32_t simple_at_response(bool ok, RawAddress addr);
+  uint32_t stop_voice_recognition(RawAddress addr);
   void debug_dump();
   void cleanup();
 
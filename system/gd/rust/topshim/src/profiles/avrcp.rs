@@ -9,8 +9,10 @@ use crate::ccall;
 use crate::utils::LTCheckedPtrMut;
 
 use log::warn;
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::cast::FromPrimitive;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct PlayerMetadata {
     pub title: String,
     pub artist: String,
@@ -218,6 +220,7 @@ unsafe impl Send for RawAvrcpCtrlWrapper {}
 pub struct AvrcpCtrl {
     internal: RawAvrcpCtrlWrapper,
     callbacks: Option<Box<bindings::btrc_ctrl_callbacks_t>>,
+    _is_enabled: bool,
 }
 
 cb_variant!(AvrcpCtCb, avrcp_connection_state_cb -> AvrcpCtrlCallbacks::ConnectionState,
@@ -240,12 +243,223 @@ cb_variant!(AvrcpCtCb, avrcp_passthrough_rsp_cb -> AvrcpCtrlCallbacks::Passthrou
     let _0 = unsafe { *_0 };
 });
 
+cb_variant!(AvrcpCtCb, avrcp_track_changed_cb -> AvrcpCtrlCallbacks::TrackChanged,
+*const RawAddress, u8 -> _, *mut bindings::btrc_element_attr_val_t, {
+    let _0 = unsafe { *_0 };
+    let _2 = attrs_to_metadata(_2, _1 as usize);
+});
+
+cb_variant!(AvrcpCtCb, avrcp_play_position_changed_cb -> AvrcpCtrlCallbacks::PlayPositionChanged,
+*const RawAddress, u32, u32, {
+    let _0 = unsafe { *_0 };
+});
+
+cb_variant!(AvrcpCtCb, avrcp_play_status_changed_cb -> AvrcpCtrlCallbacks::PlayStatusChanged,
+*const RawAddress, bindings::btrc_play_status_t, {
+    let _0 = unsafe { *_0 };
+    let _1 = AvrcpPlayStatus::from_u32(_1 as u32).unwrap_or(AvrcpPlayStatus::Error);
+});
+
+cb_variant!(AvrcpCtCb, avrcp_get_folder_items_cb -> AvrcpCtrlCallbacks::GetFolderItems,
+*const RawAddress, bindings::btrc_status_t, *const bindings::btrc_folder_items_t, u8 -> _, {
+    let _0 = unsafe { *_0 };
+    let _1 = _1 as u32 == BTRC_STS_NO_ERROR;
+    let _2 = folder_items_to_vec(_2, _3 as usize);
+});
+
+cb_variant!(AvrcpCtCb, avrcp_change_folder_path_cb -> AvrcpCtrlCallbacks::ChangeFolderPath,
+*const RawAddress, u32, {
+    let _0 = unsafe { *_0 };
+});
+
+cb_variant!(AvrcpCtCb, avrcp_set_browsed_player_cb -> AvrcpCtrlCallbacks::SetBrowsedPlayer,
+*const RawAddress, u8, u8, {
+    let _0 = unsafe { *_0 };
+});
+
+cb_variant!(AvrcpCtCb, avrcp_addressed_player_changed_cb -> AvrcpCtrlCallbacks::AddressedPlayerChanged,
+*const RawAddress, u16, {
+    let _0 = unsafe { *_0 };
+});
+
+cb_variant!(AvrcpCtCb, avrcp_now_playing_contents_changed_cb -> AvrcpCtrlCallbacks::NowPlayingContentsChanged,
+*const RawAddress, {
+    let _0 = unsafe { *_0 };
+});
+
+cb_variant!(AvrcpCtCb, avrcp_available_player_changed_cb -> AvrcpCtrlCallbacks::AvailablePlayerChanged,
+*const RawAddress, {
+    let _0 = unsafe { *_0 };
+});
+
 #[derive(Debug)]
 pub enum AvrcpCtrlCallbacks {
     PassthroughRsp(RawAddress, i32, i32),
     ConnectionState(bool, bool, RawAddress),
     GetRCFeatures(RawAddress, i32),
     GetCoverArtPsm(RawAddress, u16),
+    TrackChanged(RawAddress, PlayerMetadata),
+    /// Params: addr, song length in ms, song position in ms
+    PlayPositionChanged(RawAddress, u32, u32),
+    PlayStatusChanged(RawAddress, AvrcpPlayStatus),
+    /// Params: addr, success, items
+    GetFolderItems(RawAddress, bool, Vec<AvrcpBrowseItem>),
+    /// Params: addr, number of items in the new folder
+    ChangeFolderPath(RawAddress, u32),
+    /// Params: addr, number of items, folder depth
+    SetBrowsedPlayer(RawAddress, u8, u8),
+    AddressedPlayerChanged(RawAddress, u16),
+    NowPlayingContentsChanged(RawAddress),
+    AvailablePlayerChanged(RawAddress),
+}
+
+/// Status code of a successful browsing response.
+const BTRC_STS_NO_ERROR: u32 = 0x04;
+
+/// Item types of browsing responses.
+const BTRC_ITEM_PLAYER: u8 = 0x01;
+const BTRC_ITEM_FOLDER: u8 = 0x02;
+const BTRC_ITEM_MEDIA: u8 = 0x03;
+
+/// Media attribute ids used in |PlayerMetadata|.
+const BTRC_MEDIA_ATTR_ID_TITLE: u32 = 0x01;
+const BTRC_MEDIA_ATTR_ID_ARTIST: u32 = 0x02;
+const BTRC_MEDIA_ATTR_ID_ALBUM: u32 = 0x03;
+const BTRC_MEDIA_ATTR_ID_PLAYING_TIME: u32 = 0x07;
+
+/// Play status of a remote AVRCP target.
+#[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, Clone, Copy)]
+#[repr(u32)]
+pub enum AvrcpPlayStatus {
+    Stopped = 0,
+    Playing = 1,
+    Paused = 2,
+    FwdSeek = 3,
+    RevSeek = 4,
+    Error = 0xFF,
+}
+
+impl Default for AvrcpPlayStatus {
+    fn default() -> Self {
+        AvrcpPlayStatus::Stopped
+    }
+}
+
+/// Kind of item returned when browsing a remote AVRCP target.
+#[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, Clone, Copy)]
+#[repr(u32)]
+pub enum AvrcpBrowseItemType {
+    Player = 1,
+    Folder = 2,
+    Media = 3,
+}
+
+impl Default for AvrcpBrowseItemType {
+    fn default() -> Self {
+        AvrcpBrowseItemType::Media
+    }
+}
+
+/// Scope of the item to play, as defined by AVRCP.
+#[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, Clone, Copy)]
+#[repr(u32)]
+pub enum AvrcpBrowseScope {
+    PlayerList = 0,
+    FileSystem = 1,
+    Search = 2,
+    NowPlaying = 3,
+}
+
+/// Item returned when browsing a remote AVRCP target.
+#[derive(Debug, Default, Clone)]
+pub struct AvrcpBrowseItem {
+    pub item_type: AvrcpBrowseItemType,
+    /// Player id for players, item uid otherwise.
+    pub uid: u64,
+    pub name: String,
+    pub playable: bool,
+    /// Only set for media items.
+    pub metadata: PlayerMetadata,
+}
+
+fn text_to_string(text: &[u8]) -> String {
+    let len = text.iter().position(|&c| c == 0).unwrap_or(text.len());
+    String::from_utf8_lossy(&text[..len]).into_owned()
+}
+
+fn attrs_to_metadata(
+    attrs: *const bindings::btrc_element_attr_val_t,
+    count: usize,
+) -> PlayerMetadata {
+    let mut metadata = PlayerMetadata::default();
+    if attrs.is_null() {
+        return metadata;
+    }
+
+    // The attributes are large, so they are read in place instead of copied.
+    let attrs = unsafe { std::slice::from_raw_parts(attrs, count) };
+    for attr in attrs {
+        let text = text_to_string(&attr.text);
+        match attr.attr_id {
+            BTRC_MEDIA_ATTR_ID_TITLE => metadata.title = text,
+            BTRC_MEDIA_ATTR_ID_ARTIST => metadata.artist = text,
+            BTRC_MEDIA_ATTR_ID_ALBUM => metadata.album = text,
+            BTRC_MEDIA_ATTR_ID_PLAYING_TIME => {
+                metadata.length_us = text.parse::<i64>().unwrap_or(0) * 1000
+            }
+            _ => (),
+        }
+    }
+    metadata
+}
+
+fn folder_items_to_vec(
+    items: *const bindings::btrc_folder_items_t,
+    count: usize,
+) -> Vec<AvrcpBrowseItem> {
+    if items.is_null() {
+        return vec![];
+    }
+
+    let items = unsafe { std::slice::from_raw_parts(items, count) };
+    items
+        .iter()
+        .filter_map(|item| unsafe {
+            match item.item_type {
+                BTRC_ITEM_PLAYER => {
+                    let player = &item.__bindgen_anon_1.player;
+                    Some(AvrcpBrowseItem {
+                        item_type: AvrcpBrowseItemType::Player,
+                        uid: player.player_id.into(),
+                        name: text_to_string(&player.name),
+                        playable: false,
+                        metadata: PlayerMetadata::default(),
+                    })
+                }
+                BTRC_ITEM_FOLDER => {
+                    let folder = &item.__bindgen_anon_1.folder;
+                    Some(AvrcpBrowseItem {
+                        item_type: AvrcpBrowseItemType::Folder,
+                        uid: u64::from_be_bytes(folder.uid),
+                        name: text_to_string(&folder.name),
+                        playable: folder.playable != 0,
+                        metadata: PlayerMetadata::default(),
+                    })
+                }
+                BTRC_ITEM_MEDIA => {
+                    let media = &item.__bindgen_anon_1.media;
+                    Some(AvrcpBrowseItem {
+                        item_type: AvrcpBrowseItemType::Media,
+                        uid: u64::from_be_bytes(media.uid),
+                        name: text_to_string(&media.name),
+                        playable: true,
+                        metadata: attrs_to_metadata(media.p_attrs, media.num_attrs as usize),
+                    })
+                }
+                _ => None,
+            }
+        })
+        .collect()
 }
 
 pub struct AvrcpCtrlCallbacksDispatcher {
@@ -254,15 +468,43 @@ pub struct AvrcpCtrlCallbacksDispatcher {
 
 type AvrcpCtCb = Arc<Mutex<AvrcpCtrlCallbacksDispatcher>>;
 
+impl ToggleableProfile for AvrcpCtrl {
+    fn is_enabled(&self) -> bool {
+        self._is_enabled
+    }
+
+    fn enable(&mut self) -> bool {
+        let cb_ptr = match self.callbacks.as_mut() {
+            Some(callbacks) => LTCheckedPtrMut::from(callbacks),
+            None => return false,
+        };
+        ccall!(self, init, cb_ptr.into());
+        self._is_enabled = true;
+        true
+    }
+
+    #[profile_enabled_or(false)]
+    fn disable(&mut self) -> bool {
+        ccall!(self, cleanup);
+        self._is_enabled = false;
+        true
+    }
+}
+
 impl AvrcpCtrl {
     pub fn new(intf: &BluetoothInterface) -> AvrcpCtrl {
         let r = intf.get_profile_interface(SupportedProfiles::AvrcpCtrl);
         AvrcpCtrl {
             internal: RawAvrcpCtrlWrapper { raw: r as *const bindings::btrc_ctrl_interface_t },
             callbacks: None,
+            _is_enabled: false,
         }
     }
 
+    pub fn is_initialized(&self) -> bool {
+        self.callbacks.is_some()
+    }
+
     pub fn initialize(&mut self, callbacks: AvrcpCtrlCallbacksDispatcher) -> bool {
         // Register dispatcher
         if get_dispatchers().lock().unwrap().set::<AvrcpCtCb>(Arc::new(Mutex::new(callbacks))) {
@@ -280,25 +522,20 @@ impl AvrcpCtrl {
             playerapplicationsetting_changed_cb: None,
             setabsvol_cmd_cb: None,
             registernotification_absvol_cb: None,
-            track_changed_cb: None,
-            play_position_changed_cb: None,
-            play_status_changed_cb: None,
-            get_folder_items_cb: None,
-            change_folder_path_cb: None,
-            set_browsed_player_cb: None,
+            track_changed_cb: Some(avrcp_track_changed_cb),
+            play_position_changed_cb: Some(avrcp_play_position_changed_cb),
+            play_status_changed_cb: Some(avrcp_play_status_changed_cb),
+            get_folder_items_cb: Some(avrcp_get_folder_items_cb),
+            change_folder_path_cb: Some(avrcp_change_folder_path_cb),
+            set_browsed_player_cb: Some(avrcp_set_browsed_player_cb),
             set_addressed_player_cb: None,
-            addressed_player_changed_cb: None,
-            now_playing_contents_changed_cb: None,
-            available_player_changed_cb: None,
+            addressed_player_changed_cb: Some(avrcp_addressed_player_changed_cb),
+            now_playing_contents_changed_cb: Some(avrcp_now_playing_contents_changed_cb),
+            available_player_changed_cb: Some(avrcp_available_player_changed_cb),
             get_cover_art_psm_cb: Some(avrcp_get_cover_art_psm_cb),
         });
 
         self.callbacks = Some(callbacks);
-
-        let cb_ptr = LTCheckedPtrMut::from(self.callbacks.as_mut().unwrap());
-
-        ccall!(self, init, cb_ptr.into());
-
         true
     }
 
@@ -310,4 +547,152 @@ impl AvrcpCtrl {
     ) -> BtStatus {
         ccall!(self, send_pass_through_cmd, &addr, key_code.into(), key_state.into()).into()
     }
+
+    pub fn get_current_metadata(&mut self, addr: RawAddress) -> BtStatus {
+        ccall!(self, get_current_metadata_cmd, &addr).into()
+    }
+
+    pub fn get_playback_state(&mut self, addr: RawAddress) -> BtStatus {
+        ccall!(self, get_playback_state_cmd, &addr).into()
+    }
+
+    pub fn get_now_playing_list(&mut self, addr: RawAddress, start: u32, end: u32) -> BtStatus {
+        ccall!(self, get_now_playing_list_cmd, &addr, start, end).into()
+    }
+
+    pub fn get_folder_list(&mut self, addr: RawAddress, start: u32, end: u32) -> BtStatus {
+        ccall!(self, get_folder_list_cmd, &addr, start, end).into()
+    }
+
+    pub fn get_player_list(&mut self, addr: RawAddress, start: u32, end: u32) -> BtStatus {
+        ccall!(self, get_player_list_cmd, &addr, start, end).into()
+    }
+
+    /// Moves up one folder if |direction| is 0, or down into the folder |uid| if 1.
+    pub fn change_folder_path(&mut self, addr: RawAddress, direction: u8, uid: u64) -> BtStatus {
+        let mut uid = uid.to_be_bytes();
+        ccall!(self, change_folder_path_cmd, &addr, direction, uid.as_mut_ptr()).into()
+    }
+
+    pub fn set_browsed_player(&mut self, addr: RawAddress, player_id: u16) -> BtStatus {
+        ccall!(self, set_browsed_player_cmd, &addr, player_id).into()
+    }
+
+    pub fn set_addressed_player(&mut self, addr: RawAddress, player_id: u16) -> BtStatus {
+        ccall!(self, set_addressed_player_cmd, &addr, player_id).into()
+    }
+
+    pub fn play_item(
+        &mut self,
+        addr: RawAddress,
+        scope: AvrcpBrowseScope,
+        uid: u64,
+        uid_counter: u16,
+    ) -> BtStatus {
+        let mut uid = uid.to_be_bytes();
+        ccall!(self, play_item_cmd, &addr, scope as u8, uid.as_mut_ptr(), uid_counter).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_text(dst: &mut [u8], text: &str) {
+        dst[..text.len()].copy_from_slice(text.as_bytes());
+    }
+
+    fn attr(attr_id: u32, text: &str) -> bindings::btrc_element_attr_val_t {
+        let mut attr = bindings::btrc_element_attr_val_t::default();
+        attr.attr_id = attr_id;
+        set_text(&mut attr.text, text);
+        attr
+    }
+
+    #[test]
+    fn test_attrs_to_metadata() {
+        let attrs = vec![
+            attr(BTRC_MEDIA_ATTR_ID_TITLE, "Title"),
+            attr(BTRC_MEDIA_ATTR_ID_ARTIST, "Artist"),
+            attr(BTRC_MEDIA_ATTR_ID_ALBUM, "Album"),
+            attr(BTRC_MEDIA_ATTR_ID_PLAYING_TIME, "215000"),
+            // Unknown attributes are ignored.
+            attr(0x04, "1"),
+        ];
+        let metadata = attrs_to_metadata(attrs.as_ptr(), attrs.len());
+        assert_eq!(metadata.title, "Title");
+        assert_eq!(metadata.artist, "Artist");
+        assert_eq!(metadata.album, "Album");
+        // The playing time is in milliseconds.
+        assert_eq!(metadata.length_us, 215_000_000);
+
+        let attrs = vec![attr(BTRC_MEDIA_ATTR_ID_PLAYING_TIME, "unknown")];
+        assert_eq!(attrs_to_metadata(attrs.as_ptr(), attrs.len()).length_us, 0);
+    }
+
+    #[test]
+    fn test_attrs_to_metadata_null() {
+        let metadata = attrs_to_metadata(std::ptr::null(), 3);
+        assert!(metadata.title.is_empty() && metadata.artist.is_empty());
+        assert_eq!(metadata.length_us, 0);
+    }
+
+    #[test]
+    fn test_folder_items_to_vec() {
+        let mut attrs = vec![
+            attr(BTRC_MEDIA_ATTR_ID_TITLE, "Song"),
+            attr(BTRC_MEDIA_ATTR_ID_PLAYING_TIME, "1500"),
+        ];
+
+        let mut player = bindings::btrc_folder_items_t::default();
+        player.item_type = BTRC_ITEM_PLAYER;
+        unsafe {
+            player.__bindgen_anon_1.player.player_id = 7;
+            set_text(&mut player.__bindgen_anon_1.player.name, "Player");
+        }
+
+        let mut folder = bindings::btrc_folder_items_t::default();
+        folder.item_type = BTRC_ITEM_FOLDER;
+        unsafe {
+            folder.__bindgen_anon_1.folder.uid = [0, 0, 0, 0, 0, 0, 0x01, 0x02];
+            folder.__bindgen_anon_1.folder.playable = 1;
+            set_text(&mut folder.__bindgen_anon_1.folder.name, "Folder");
+        }
+
+        let mut media = bindings::btrc_folder_items_t::default();
+        media.item_type = BTRC_ITEM_MEDIA;
+        unsafe {
+            media.__bindgen_anon_1.media.uid = [0, 0, 0, 0, 0, 0, 0, 0x03];
+            media.__bindgen_anon_1.media.num_attrs = attrs.len() as i32;
+            media.__bindgen_anon_1.media.p_attrs = attrs.as_mut_ptr();
+            set_text(&mut media.__bindgen_anon_1.media.name, "Media");
+        }
+
+        // Items of unknown type are skipped.
+        let mut unknown = bindings::btrc_folder_items_t::default();
+        unknown.item_type = 0x10;
+
+        let items = vec![player, folder, media, unknown];
+        let items = folder_items_to_vec(items.as_ptr(), items.len());
+        assert_eq!(items.len(), 3);
+
+        assert_eq!(items[0].item_type, AvrcpBrowseItemType::Player);
+        assert_eq!((items[0].uid, items[0].name.as_str(), items[0].playable), (7, "Player", false));
+
+        assert_eq!(items[1].item_type, AvrcpBrowseItemType::Folder);
+        assert_eq!(
+            (items[1].uid, items[1].name.as_str(), items[1].playable),
+            (0x102, "Folder", true)
+        );
+
+        assert_eq!(items[2].item_type, AvrcpBrowseItemType::Media);
+        assert_eq!((items[2].uid, items[2].name.as_str(), items[2].playable), (3, "Media", true));
+        assert_eq!(items[2].metadata.title, "Song");
+        assert_eq!(items[2].metadata.length_us, 1_500_000);
+    }
+
+    #[test]
+    fn test_folder_items_to_vec_null() {
+        assert!(folder_items_to_vec(std::ptr::null(), 2).is_empty());
+    }
 }
@@ -180,6 +180,7 @@ pub mod ffi {
             addr: RawAddress,
         ) -> u32;
         fn simple_at_response(self: Pin<&mut HfpIntf>, ok: bool, addr: RawAddress) -> u32;
+        fn stop_voice_recognition(self: Pin<&mut HfpIntf>, addr: RawAddress) -> u32;
         fn debug_dump(self: Pin<&mut HfpIntf>);
         fn cleanup(self: Pin<&mut HfpIntf>);
 
@@ -195,6 +196,7 @@ pub mod ffi {
         fn hfp_swb_caps_update_callback(swb_supported: bool, addr: RawAddress);
         fn hfp_indicator_query_callback(addr: RawAddress);
         fn hfp_current_calls_query_callback(addr: RawAddress);
+        fn hfp_voice_recognition_callback(enabled: bool, addr: RawAddress);
         fn hfp_answer_call_callback(addr: RawAddress);
         fn hfp_hangup_call_callback(addr: RawAddress);
         fn hfp_dial_call_callback(number: String, addr: RawAddress);
@@ -248,6 +250,7 @@ pub enum HfpCallbacks {
     SwbCapsUpdate(bool, RawAddress),
     IndicatorQuery(RawAddress),
     CurrentCallsQuery(RawAddress),
+    VoiceRecognition(bool, RawAddress),
     AnswerCall(RawAddress),
     HangupCall(RawAddress),
     DialCall(String, RawAddress),
@@ -311,6 +314,11 @@ cb_variant!(
     hfp_current_calls_query_callback -> HfpCallbacks::CurrentCallsQuery,
     RawAddress);
 
+cb_variant!(
+    HfpCb,
+    hfp_voice_recognition_callback -> HfpCallbacks::VoiceRecognition,
+    bool, RawAddress);
+
 cb_variant!(
     HfpCb,
     hfp_answer_call_callback -> HfpCallbacks::AnswerCall,
@@ -473,6 +481,11 @@ impl Hfp {
         BtStatus::from(self.internal.pin_mut().simple_at_response(ok, addr))
     }
 
+    #[profile_enabled_or(BtStatus::NotReady)]
+    pub fn stop_voice_recognition(&mut self, addr: RawAddress) -> BtStatus {
+        BtStatus::from(self.internal.pin_mut().stop_voice_recognition(addr))
+    }
+
     #[profile_enabled_or]
     pub fn debug_dump(&mut self) {
         self.internal.pin_mut().debug_dump();
@@ -17,7 +17,9 @@
 #include "sysprops/sysprops_module.h"
 
 #include <filesystem>
+#include <set>
 
+#include "common/init_flags.h"
 #include "os/handler.h"
 #include "os/log.h"
 #include "os/parameter_provider.h"
@@ -29,6 +31,53 @@ namespace sysprops {
 
 static const size_t kDefaultCapacity = 10000;
 
+static const std::list<std::string> kSupportedSysprops = {
+    // General
+    "bluetooth.btm.sec.delay_auth_ms.value",
+    "bluetooth.device.default_name",
+    "bluetooth.core.gap.le.privacy.enabled",
+    "bluetooth.core.gap.le.conn.only_init_1m_phy.enabled",
+    "bluetooth.device.class_of_device",
+    "bluetooth.device_id.product_id",
+    "bluetooth.device_id.product_version",
+    "bluetooth.device_id.vendor_id",
+    "bluetooth.device_id.vendor_id_source",
+    "persist.bluetooth.inq_by_rssi",
+    // BR/EDR
+    "bluetooth.core.classic.page_scan_type",
+    "bluetooth.core.classic.page_scan_interval",
+    "bluetooth.core.classic.page_scan_window",
+    "bluetooth.core.classic.inq_scan_type",
+    "bluetooth.core.classic.inq_scan_interval",
+    "bluetooth.core.classic.inq_scan_window",
+    "bluetooth.core.acl.link_supervision_timeout",
+    "bluetooth.core.classic.page_timeout",
+    "bluetooth.core.classic.sniff_max_intervals",
+    "bluetooth.core.classic.sniff_min_intervals",
+    "bluetooth.core.classic.sniff_attempts",
+    "bluetooth.core.classic.sniff_timeouts",
+    // LE
+    "bluetooth.core.le.min_connection_interval",
+    "bluetooth.core.le.max_connection_interval",
+    "bluetooth.core.le.connection_latency",
+    "bluetooth.core.le.connection_supervision_timeout",
+    "bluetooth.core.le.direct_connection_timeout",
+    "bluetooth.core.le.connection_scan_interval_fast",
+    "bluetooth.core.le.connection_scan_window_fast",
+    "bluetooth.core.le.connection_scan_window_2m_fast",
+    "bluetooth.core.le.connection_scan_window_coded_fast",
+    "bluetooth.core.le.connection_scan_interval_slow",
+    "bluetooth.core.le.connection_scan_window_slow",
+    "bluetooth.core.le.inquiry_scan_interval",
+    "bluetooth.core.le.inquiry_scan_window",
+    "bluetooth.core.le.vendor_capabilities.enabled",
+    // SCO
+    "bluetooth.sco.disable_enhanced_connection",
+    "bluetooth.sco.swb_supported",
+    // Profile
+    "persist.bluetooth.avrcpcontrolversion",
+};
+
 const ModuleFactory SyspropsModule::Factory = ModuleFactory([]() { return new SyspropsModule(); });
 
 struct SyspropsModule::impl {
@@ -40,14 +89,26 @@ struct SyspropsModule::impl {
 void SyspropsModule::ListDependencies(ModuleList* /* list */) const {}
 
 void SyspropsModule::Start() {
+  // Sysprops overridden on the daemon's command line, e.g. by --ll-privacy, are specific to this
+  // adapter and take precedence over the config files. Values loaded from the config files by an
+  // earlier start aren't preset, so that edits to the files apply on the next start.
+  std::set<std::string> preset;
+  for (const auto& s : kSupportedSysprops) {
+    std::string value = bluetooth::common::InitFlags::GetSyspropOverride(s);
+    if (!value.empty()) {
+      bluetooth::os::SetSystemProperty(s, value);
+      preset.insert(s);
+    }
+  }
+
   std::string file_path = os::ParameterProvider::SyspropsFilePath();
   if (!file_path.empty()) {
-    parse_config(file_path);
+    parse_config(file_path, preset);
     // Merge config fragments
     std::string override_dir = file_path + ".d";
     if (std::filesystem::exists(override_dir)) {
       for (const auto& entry : std::filesystem::directory_iterator(override_dir)) {
-        parse_config(entry.path());
+        parse_config(entry.path(), preset);
       }
     }
   }
@@ -63,60 +124,16 @@ std::string SyspropsModule::ToString() const {
   return "Sysprops Module";
 }
 
-void SyspropsModule::parse_config(std::string file_path) {
-  const std::list<std::string> supported_sysprops = {
-      // General
-      "bluetooth.btm.sec.delay_auth_ms.value",
-      "bluetooth.device.default_name",
-      "bluetooth.core.gap.le.privacy.enabled",
-      "bluetooth.core.gap.le.conn.only_init_1m_phy.enabled",
-      "bluetooth.device.class_of_device",
-      "bluetooth.device_id.product_id",
-      "bluetooth.device_id.product_version",
-      "bluetooth.device_id.vendor_id",
-      "bluetooth.device_id.vendor_id_source",
-      "persist.bluetooth.inq_by_rssi",
-      // BR/EDR
-      "bluetooth.core.classic.page_scan_type",
-      "bluetooth.core.classic.page_scan_interval",
-      "bluetooth.core.classic.page_scan_window",
-      "bluetooth.core.classic.inq_scan_type",
-      "bluetooth.core.classic.inq_scan_interval",
-      "bluetooth.core.classic.inq_scan_window",
-      "bluetooth.core.acl.link_supervision_timeout",
-      "bluetooth.core.classic.page_timeout",
-      "bluetooth.core.classic.sniff_max_intervals",
-      "bluetooth.core.classic.sniff_min_intervals",
-      "bluetooth.core.classic.sniff_attempts",
-      "bluetooth.core.classic.sniff_timeouts",
-      // LE
-      "bluetooth.core.le.min_connection_interval",
-      "bluetooth.core.le.max_connection_interval",
-      "bluetooth.core.le.connection_latency",
-      "bluetooth.core.le.connection_supervision_timeout",
-      "bluetooth.core.le.direct_connection_timeout",
-      "bluetooth.core.le.connection_scan_interval_fast",
-      "bluetooth.core.le.connection_scan_window_fast",
-      "bluetooth.core.le.connection_scan_window_2m_fast",
-      "bluetooth.core.le.connection_scan_window_coded_fast",
-      "bluetooth.core.le.connection_scan_interval_slow",
-      "bluetooth.core.le.connection_scan_window_slow",
-      "bluetooth.core.le.inquiry_scan_interval",
-      "bluetooth.core.le.inquiry_scan_window",
-      "bluetooth.core.le.vendor_capabilities.enabled",
-      // SCO
-      "bluetooth.sco.disable_enhanced_connection",
-      "bluetooth.sco.swb_supported",
-      // Profile
-      "persist.bluetooth.avrcpcontrolversion",
-  };
-
+void SyspropsModule::parse_config(std::string file_path, const std::set<std::string>& preset) {
   auto config = storage::LegacyConfigFile::FromPath(file_path).Read(kDefaultCapacity);
   if (!config) {
     return;
   }
 
-  for (auto s = supported_sysprops.begin(); s != supported_sysprops.end(); s++) {
+  for (auto s = kSupportedSysprops.begin(); s != kSupportedSysprops.end(); s++) {
+    if (preset.count(*s)) {
+      continue;
+    }
     auto str = config->GetProperty("Sysprops", *s);
     if (str) {
       bluetooth::os::SetSystemProperty(*s, *str);
@@ -15,6 +15,9 @@
  */
 #pragma once
 
+#include <set>
+#include <string>
+
 #include "module.h"
 
 namespace bluetooth {
@@ -42,7 +45,7 @@ class SyspropsModule : public bluetooth::Module {
  private:
   struct impl;
   std::unique_ptr<impl> pimpl_;
-  void parse_config(std::string file_path);
+  void parse_config(std::string file_path, const std::set<std::string>& preset);
 };
 
 }  // namespace sysprops